@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use std::num::{NonZeroU64, NonZeroUsize};
 
 #[derive(Parser, Clone, Copy, Debug)]
@@ -25,7 +25,7 @@ pub struct IngestConfig {
     pub http_req_concurrency: u32,
     /// Offset from tip of the chain (to avoid rollbacks)
     #[clap(long)]
-    pub best_block_offset: u32,
+    pub best_block_offset: u64,
     #[clap(long)]
     pub rpc_urls: Vec<url::Url>,
     /// Get transaction receipts. This fills the transaction.status field.
@@ -38,10 +38,49 @@ pub struct IngestConfig {
     /// An rpc endpoint is considered behind and excluded
     /// if max_best_block - rpc_endpoint.best_block > max_rpc_endpoint_best_block_diff
     #[clap(long, default_value_t = 5)]
-    pub max_rpc_endpoint_best_block_diff: u32,
+    pub max_rpc_endpoint_best_block_diff: u64,
     /// The real target rpc endpoint. This is useful when using the rpc_proxy
     #[clap(long)]
     pub target_rpc_endpoint: Option<url::Url>,
+    /// Maximum number of times a round of blocks is re-fetched
+    /// when the parent hash check fails
+    #[clap(long, default_value_t = 5)]
+    pub max_parent_hash_refetches: usize,
+    /// Rpc method to fetch call traces with. Traces aren't fetched if this isn't set.
+    #[clap(long, value_enum)]
+    pub trace_method: Option<TraceMethod>,
+    /// Rpc method to fetch state diffs with. State diffs aren't fetched if this isn't set.
+    #[clap(long, value_enum)]
+    pub state_diff_method: Option<StateDiffMethod>,
+}
+
+/// Rpc method used to get the call traces of a block
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceMethod {
+    /// trace_block
+    TraceBlock,
+    /// debug_traceBlockByNumber with callTracer
+    DebugTraceBlockByNumber,
+}
+
+/// Rpc method used to get the state diffs of a block
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateDiffMethod {
+    /// trace_replayBlockTransactions with stateDiff
+    TraceReplayBlockTransactions,
+    /// debug_traceBlockByNumber with prestateTracer in diff mode
+    DebugTraceBlockByNumber,
+}
+
+/// What to do when a block's parent hash doesn't match the hash of the block before it
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParentHashPolicy {
+    /// Re-fetch the round of blocks from the other healthy rpc endpoints,
+    /// leaving out the one that served the mismatched block. Stops with an error if
+    /// the block before it is in a round that was already written.
+    Refetch,
+    /// Stop with an error
+    Halt,
 }
 
 #[derive(Parser, Clone, Debug)]
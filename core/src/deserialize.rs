@@ -5,10 +5,14 @@ use std::convert::TryInto;
 use std::fmt;
 use std::result::Result as StdResult;
 
-#[derive(Debug, Clone, derive_more::Deref, derive_more::From, PartialEq, Eq, Hash)]
+#[derive(
+    Debug, Clone, derive_more::Deref, derive_more::From, PartialEq, Eq, Hash, PartialOrd, Ord,
+)]
 pub struct Bytes32(pub Box<[u8; 32]>);
 
-#[derive(Debug, Clone, derive_more::Deref, derive_more::From, PartialEq, Eq, Hash)]
+#[derive(
+    Debug, Clone, derive_more::Deref, derive_more::From, PartialEq, Eq, Hash, PartialOrd, Ord,
+)]
 pub struct Address(pub Box<[u8; 20]>);
 
 #[derive(Debug, Clone, derive_more::Deref, derive_more::From, PartialEq, Eq)]
@@ -23,9 +27,41 @@ pub struct BloomFilterBytes(pub Box<[u8; 256]>);
 #[derive(Default, Debug, Clone, Copy, derive_more::Deref, derive_more::From, PartialEq, Eq)]
 pub struct Index(pub u32);
 
+#[derive(
+    Default,
+    Debug,
+    Clone,
+    Copy,
+    derive_more::Deref,
+    derive_more::From,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+)]
+pub struct BlockNumber(pub u64);
+
+#[derive(Default, Debug, Clone, Copy, derive_more::Deref, derive_more::From, PartialEq, Eq)]
+pub struct ChainId(pub u64);
+
 #[derive(Debug, Clone, derive_more::Deref, derive_more::From, PartialEq, Eq)]
 pub struct Bytes(pub Vec<u8>);
 
+/// 256 bit quantity as big endian bytes, so comparing the bytes compares the numbers
+#[derive(
+    Default,
+    Debug,
+    Clone,
+    Copy,
+    derive_more::Deref,
+    derive_more::From,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+)]
+pub struct U256(pub [u8; 32]);
+
 impl Bytes32 {
     pub fn new(bytes: &[u8]) -> Self {
         Self(Box::new(bytes.try_into().unwrap()))
@@ -92,10 +128,96 @@ impl Index {
     }
 }
 
+impl From<u32> for BlockNumber {
+    fn from(val: u32) -> Self {
+        Self(val.into())
+    }
+}
+
+impl From<u32> for ChainId {
+    fn from(val: u32) -> Self {
+        Self(val.into())
+    }
+}
+
 impl Bytes {
     pub fn new(bytes: &[u8]) -> Self {
         Self(bytes.to_owned())
     }
+
+    /// Big endian bytes of the quantity without leading zeros, the same as
+    /// deserializing the hex quantity the eth node returns.
+    pub fn from_quantity(num: u64) -> Self {
+        Self::from_be_quantity(&num.to_be_bytes())
+    }
+
+    /// Same as `from_quantity` but takes big endian bytes of any length
+    pub fn from_be_quantity(bytes: &[u8]) -> Self {
+        let start = bytes
+            .iter()
+            .position(|&b| b != 0)
+            .unwrap_or(bytes.len().saturating_sub(1));
+
+        Self(bytes[start..].to_vec())
+    }
+
+    /// Returns None if the quantity doesn't fit into a u64
+    pub fn to_u64(&self) -> Option<u64> {
+        let bytes = trim_leading_zeros(&self.0);
+        if bytes.len() > 8 {
+            return None;
+        }
+
+        let mut buf = [0; 8];
+        buf[8 - bytes.len()..].copy_from_slice(bytes);
+
+        Some(u64::from_be_bytes(buf))
+    }
+}
+
+impl U256 {
+    pub fn new(bytes: &[u8]) -> Self {
+        Self(bytes.try_into().unwrap())
+    }
+
+    /// Returns None if the big endian quantity doesn't fit into 256 bits
+    pub fn from_quantity(bytes: &[u8]) -> Option<Self> {
+        let bytes = trim_leading_zeros(bytes);
+        if bytes.len() > 32 {
+            return None;
+        }
+
+        let mut buf = [0; 32];
+        buf[32 - bytes.len()..].copy_from_slice(bytes);
+
+        Some(Self(buf))
+    }
+
+    pub fn to_quantity(&self) -> Bytes {
+        Bytes::from_be_quantity(&self.0)
+    }
+
+    pub fn saturating_add(self, other: Self) -> Self {
+        let mut sum = [0; 32];
+        let mut carry = 0u16;
+
+        for ((byte, a), b) in sum.iter_mut().zip(self.0).zip(other.0).rev() {
+            let s = u16::from(a) + u16::from(b) + carry;
+            *byte = s as u8;
+            carry = s >> 8;
+        }
+
+        if carry != 0 {
+            return Self([0xff; 32]);
+        }
+
+        Self(sum)
+    }
+}
+
+fn trim_leading_zeros(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    &bytes[start..]
 }
 
 struct Bytes32Visitor;
@@ -333,6 +455,43 @@ impl Serialize for Bytes {
     }
 }
 
+struct U256Visitor;
+
+impl<'de> Visitor<'de> for U256Visitor {
+    type Value = U256;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("hex quantity that fits into 256 bits")
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        let buf = vec_from_hex(value).map_err(|e| E::custom(e.to_string()))?;
+
+        U256::from_quantity(&buf).ok_or_else(|| E::custom("quantity is wider than 256 bits"))
+    }
+}
+
+impl<'de> Deserialize<'de> for U256 {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(U256Visitor)
+    }
+}
+
+impl Serialize for U256 {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.to_quantity().serialize(serializer)
+    }
+}
+
 struct IndexVisitor;
 
 impl<'de> Visitor<'de> for IndexVisitor {
@@ -389,6 +548,129 @@ impl Serialize for Index {
     }
 }
 
+struct BlockNumberVisitor;
+
+impl<'de> Visitor<'de> for BlockNumberVisitor {
+    type Value = BlockNumber;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("hex string or integer for 8 byte unsigned integer")
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        let without_prefix = value.trim_start_matches("0x");
+        let val = u64::from_str_radix(without_prefix, 16).map_err(|e| E::custom(e.to_string()))?;
+
+        Ok(BlockNumber(val))
+    }
+
+    // block numbers written to the hot database before they were widened are encoded as u32
+    fn visit_u32<E>(self, value: u32) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(BlockNumber(value.into()))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(BlockNumber(value))
+    }
+}
+
+impl<'de> Deserialize<'de> for BlockNumber {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(BlockNumberVisitor)
+    }
+}
+
+impl Serialize for BlockNumber {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u64(self.0)
+    }
+}
+
+struct ChainIdVisitor;
+
+impl<'de> Visitor<'de> for ChainIdVisitor {
+    type Value = ChainId;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("hex string or integer for 8 byte chain id")
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        let without_prefix = value.trim_start_matches("0x");
+        let val = u64::from_str_radix(without_prefix, 16).map_err(|e| E::custom(e.to_string()))?;
+
+        Ok(ChainId(val))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(ChainId(value))
+    }
+}
+
+impl<'de> Deserialize<'de> for ChainId {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ChainIdVisitor)
+    }
+}
+
+impl Serialize for ChainId {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u64(self.0)
+    }
+}
+
+/// Deserializes an optional arbitrary length quantity.
+///
+/// Quantities that used to be stored as `BigUnsigned` were serialized as decimal strings,
+/// those are accepted too so the hot data written by older versions can still be read.
+pub fn deserialize_opt_quantity<'de, D>(deserializer: D) -> Result<Option<Bytes>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = match Option::<String>::deserialize(deserializer)? {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+
+    let bytes = if value.starts_with("0x") {
+        Bytes(vec_from_hex(&value).map_err(|e| de::Error::custom(e.to_string()))?)
+    } else {
+        let num = value
+            .parse::<u64>()
+            .map_err(|e| de::Error::custom(e.to_string()))?;
+        Bytes::from_quantity(num)
+    };
+
+    Ok(Some(bytes))
+}
+
 fn vec_from_hex(value: &str) -> StdResult<Vec<u8>, prefix_hex::Error> {
     let buf: Vec<u8> = if value.len() % 2 != 0 {
         let value = format!("0x0{}", &value[2..]);
@@ -492,4 +774,87 @@ mod tests {
     impl_int_test!(test_index, Index, "0xF64B41", u32);
 
     impl_int_test!(test_big_unsigned, BigUnsigned, "0xF64B41", u64);
+
+    impl_int_test!(test_block_number, BlockNumber, "0x1F64B41F6", u64);
+
+    // chain id of Palm mainnet, doesn't fit 4 bytes
+    impl_int_test!(test_chain_id, ChainId, "0x2A15C308D", u64);
+
+    // v of a legacy transaction on a chain with an id close to u64::MAX
+    impl_bytes_test!(test_bytes_wide_v, Bytes, "0x200000000000000021");
+
+    #[test]
+    fn test_chain_id_from_integer() {
+        let chain_id: ChainId = serde_json::from_str("11297108109").unwrap();
+        assert_eq!(chain_id.0, 11297108109);
+    }
+
+    #[test]
+    fn test_opt_quantity() {
+        #[derive(Deserialize)]
+        struct Tx {
+            #[serde(default, deserialize_with = "deserialize_opt_quantity")]
+            v: Option<Bytes>,
+        }
+
+        let parse = |json: &str| serde_json::from_str::<Tx>(json).unwrap().v;
+
+        // v of an EIP-155 transaction on Palm mainnet
+        assert_eq!(
+            parse(r#"{"v": "0x542B8613D"}"#),
+            Some(Bytes(vec![5, 0x42, 0xB8, 0x61, 0x3D]))
+        );
+        assert_eq!(parse(r#"{"v": "0x0"}"#), Some(Bytes(vec![0])));
+        assert_eq!(parse(r#"{"v": null}"#), None);
+        assert_eq!(parse("{}"), None);
+
+        // decimal strings written by BigUnsigned
+        assert_eq!(
+            parse(r#"{"v": "22594216253"}"#),
+            parse(r#"{"v": "0x542B8613D"}"#)
+        );
+        assert_eq!(parse(r#"{"v": "0"}"#), parse(r#"{"v": "0x0"}"#));
+        assert_eq!(
+            parse(r#"{"v": "18446744073709551615"}"#),
+            Some(Bytes(vec![0xFF; 8]))
+        );
+    }
+
+    #[test]
+    fn test_u256() {
+        // 100 ETH in wei
+        let value: U256 = serde_json::from_str(r#""0x56bc75e2d63100000""#).unwrap();
+        assert_eq!(
+            value.to_quantity(),
+            Bytes(vec_from_hex("0x56bc75e2d63100000").unwrap())
+        );
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            r#""0x056bc75e2d63100000""#
+        );
+
+        let one_eth = U256::from_quantity(&[0x0d, 0xe0, 0xb6, 0xb3, 0xa7, 0x64, 0, 0]).unwrap();
+        assert!(one_eth < value);
+        assert_eq!(U256::from_quantity(&[0]), Some(U256::default()));
+        assert_eq!(U256::default().to_quantity(), Bytes(vec![0]));
+
+        assert!(serde_json::from_str::<U256>(&format!(r#""0x01{}""#, "00".repeat(32))).is_err());
+        assert!(U256::from_quantity(&[0; 40]).is_some());
+
+        assert_eq!(
+            U256::from_quantity(&[0xff])
+                .unwrap()
+                .saturating_add(one_eth),
+            U256::from_quantity(&[0x0d, 0xe0, 0xb6, 0xb3, 0xa7, 0x64, 0, 0xff]).unwrap()
+        );
+        assert_eq!(U256([0xff; 32]).saturating_add(one_eth), U256([0xff; 32]));
+    }
+
+    #[test]
+    fn test_quantity_to_u64() {
+        assert_eq!(Bytes(vec![]).to_u64(), Some(0));
+        assert_eq!(Bytes::from_quantity(u64::MAX).to_u64(), Some(u64::MAX));
+        assert_eq!(Bytes(vec![0, 0, 1, 2]).to_u64(), Some(258));
+        assert_eq!(Bytes(vec![1; 9]).to_u64(), None);
+    }
 }
@@ -7,7 +7,12 @@ use std::path::Path;
 use std::str::FromStr;
 
 const FOLDER_PREFIX: &str = "blk";
+/// Prefix of folders that have block numbers that don't fit the legacy nine digit names.
+/// It sorts after the legacy prefix followed by a digit so all names stay lexicographically sorted.
+const WIDE_FOLDER_PREFIX: &str = "blk_";
 const TEMP_SUFFIX: &str = "temp";
+/// Highest block number that fits in a legacy folder name
+const MAX_LEGACY_BLOCK_NUMBER: u64 = 999_999_999;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct DirName {
@@ -21,7 +26,10 @@ impl FromStr for DirName {
     fn from_str(input: &str) -> Result<DirName> {
         let err = || Error::InvalidBlockRange(input.to_owned());
 
-        let input = input.strip_prefix(FOLDER_PREFIX).ok_or_else(err)?;
+        let input = input
+            .strip_prefix(WIDE_FOLDER_PREFIX)
+            .or_else(|| input.strip_prefix(FOLDER_PREFIX))
+            .ok_or_else(err)?;
 
         let (input, is_temp) = match input.strip_suffix(TEMP_SUFFIX) {
             None => (input, false),
@@ -35,8 +43,8 @@ impl FromStr for DirName {
             return Err(err());
         }
 
-        let from = u32::from_str(from).map_err(|_| err())?;
-        let to = u32::from_str(to).map_err(|_| err())?;
+        let from = u64::from_str(from).map_err(|_| err())?;
+        let to = u64::from_str(to).map_err(|_| err())?;
 
         Ok(Self {
             range: BlockRange { from, to },
@@ -47,11 +55,19 @@ impl FromStr for DirName {
 
 impl fmt::Display for DirName {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{}{:09}-{:09}",
-            FOLDER_PREFIX, self.range.from, self.range.to
-        )?;
+        if self.range.to <= MAX_LEGACY_BLOCK_NUMBER {
+            write!(
+                f,
+                "{}{:09}-{:09}",
+                FOLDER_PREFIX, self.range.from, self.range.to
+            )?;
+        } else {
+            write!(
+                f,
+                "{}{:020}-{:020}",
+                WIDE_FOLDER_PREFIX, self.range.from, self.range.to
+            )?;
+        }
 
         if self.is_temp {
             f.write_str(TEMP_SUFFIX)?;
@@ -133,7 +149,7 @@ impl DirName {
         Ok(sorted_names)
     }
 
-    pub async fn find_sorted(path: &Path, from: u32) -> Result<Vec<DirName>> {
+    pub async fn find_sorted(path: &Path, from: u64) -> Result<Vec<DirName>> {
         let mut dir = tokio::fs::read_dir(&path)
             .await
             .map_err(Error::ReadParquetDir)?;
@@ -177,3 +193,56 @@ impl DirName {
         Ok(dir_names)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dir_name(from: u64, to: u64, is_temp: bool) -> DirName {
+        DirName {
+            range: BlockRange { from, to },
+            is_temp,
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        for name in [
+            dir_name(0, 100, false),
+            dir_name(999_999_000, 999_999_999, false),
+            dir_name(999_999_999, 1_000_000_100, false),
+            dir_name(u64::MAX - 1, u64::MAX, false),
+            dir_name(100, 200, true),
+            dir_name(1_000_000_000, 1_000_000_100, true),
+        ] {
+            assert_eq!(DirName::from_str(&name.to_string()).unwrap(), name);
+        }
+
+        assert_eq!(
+            dir_name(1, 2, true).to_string(),
+            "blk000000001-000000002temp"
+        );
+        assert_eq!(
+            dir_name(999_999_999, 1_000_000_000, false).to_string(),
+            "blk_00000000000999999999-00000000001000000000"
+        );
+    }
+
+    #[test]
+    fn test_names_sort_by_block_range() {
+        let names = [
+            dir_name(0, 100, false),
+            dir_name(900_000_000, 999_999_999, false),
+            dir_name(999_999_999, 1_000_000_100, false),
+            dir_name(1_000_000_100, 10_000_000_000, false),
+        ];
+
+        let mut sorted = names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        sorted.sort();
+
+        assert_eq!(
+            sorted,
+            names.iter().map(|n| n.to_string()).collect::<Vec<_>>()
+        );
+    }
+}
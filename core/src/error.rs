@@ -70,11 +70,13 @@ pub enum Error {
     #[error("failed to read parquet file:\n{0}")]
     ReadParquet(ArrowError),
     #[error("block {0} not found while streaming batches from s3")]
-    BlockNotFoundInS3(u32),
+    BlockNotFoundInS3(u64),
     #[error("unknown format: {0}")]
     UnknownFormat(String),
     #[error("no blocks to ingest from rpc node.")]
     NoBlocksOnNode,
+    #[error("parent hash of block {0} doesn't match the hash of the previous block.")]
+    ParentHashMismatch(u64),
 }
 
 pub type Result<T> = StdResult<T, Error>;
@@ -1,16 +1,20 @@
-use crate::config::IngestConfig;
+use crate::config::{IngestConfig, ParentHashPolicy, StateDiffMethod, TraceMethod};
+use crate::deserialize::Bytes32;
 use crate::error::{Error, Result};
-use crate::eth_request::{EthRequest, GetBestBlock, GetBlockByNumber, GetBlockReceipts, GetLogs};
+use crate::eth_request::{
+    DebugTraceBlockByNumber, DebugTraceBlockPrestateDiff, EthRequest, GetBestBlock,
+    GetBlockByNumber, GetBlockReceipts, GetLogs, TraceBlock, TraceReplayBlockTransactions,
+};
 use crate::ingest_metrics::IngestMetrics;
 use crate::retry::Retry;
-use crate::types::{Block, BlockRange, Log};
+use crate::types::{Block, BlockRange, Log, StateDiff, Trace, Transaction, TransactionReceipt};
 use futures::stream::Stream;
 use rand::seq::SliceRandom;
 use serde_json::Value as JsonValue;
-use std::cmp;
 use std::sync::Arc;
 use std::time::Duration;
 use std::time::Instant;
+use std::{cmp, iter};
 use url::Url;
 
 pub const TARGET_ENDPOINT_HEADER_NAME: &str = "eth_archive_rpc_proxy_target";
@@ -24,13 +28,32 @@ pub struct EthClient {
 
 struct UrlSet {
     inner: Vec<Url>,
-    best_block: u32,
+    best_block: u64,
 }
 
 impl UrlSet {
     fn get_random(&self) -> Url {
         self.inner.choose(&mut rand::thread_rng()).unwrap().clone()
     }
+
+    /// Returns None if there are no other urls in the set
+    fn without(&self, url: &Url) -> Option<Arc<UrlSet>> {
+        let inner = self
+            .inner
+            .iter()
+            .filter(|u| *u != url)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if inner.is_empty() {
+            return None;
+        }
+
+        Some(Arc::new(UrlSet {
+            inner,
+            best_block: self.best_block,
+        }))
+    }
 }
 
 impl EthClient {
@@ -164,11 +187,25 @@ impl EthClient {
         url_set: Arc<UrlSet>,
         req: R,
     ) -> Result<R::Resp> {
+        self.send_with_url(url_set, req).await.map(|(_, resp)| resp)
+    }
+
+    /// Same as `send` but also returns the url that served the response.
+    async fn send_with_url<R: EthRequest + Copy>(
+        self: Arc<Self>,
+        url_set: Arc<UrlSet>,
+        req: R,
+    ) -> Result<(Url, R::Resp)> {
         self.retry
             .retry(|| {
                 let client = self.clone();
                 let url = url_set.get_random();
-                async move { client.send_impl(url, req).await }
+                async move {
+                    client
+                        .send_impl(url.clone(), req)
+                        .await
+                        .map(|resp| (url, resp))
+                }
             })
             .await
             .map_err(Error::Retry)
@@ -259,13 +296,29 @@ impl EthClient {
         url_set: Arc<UrlSet>,
         batches: &[B],
     ) -> Result<Vec<Vec<R::Resp>>> {
+        let batches = self.send_batches_with_urls(url_set, batches).await?;
+
+        Ok(batches.into_iter().map(|(_, batch)| batch).collect())
+    }
+
+    /// Same as `send_batches` but also returns the url that served each batch.
+    async fn send_batches_with_urls<R: EthRequest, B: AsRef<[R]>>(
+        self: Arc<Self>,
+        url_set: Arc<UrlSet>,
+        batches: &[B],
+    ) -> Result<Vec<(Url, Vec<R::Resp>)>> {
         let group = batches.iter().map(|batch| {
             let client = self.clone();
             let url_set = url_set.clone();
             self.retry.retry(move || {
                 let client = client.clone();
                 let url = url_set.get_random();
-                async move { client.send_batch(url, batch.as_ref()).await }
+                async move {
+                    client
+                        .send_batch(url.clone(), batch.as_ref())
+                        .await
+                        .map(|resp| (url, resp))
+                }
             })
         });
         let group = futures::future::join_all(group).await;
@@ -285,17 +338,23 @@ impl EthClient {
         group.into_iter().collect()
     }
 
-    pub async fn get_best_block(self: Arc<Self>) -> Result<u32> {
+    pub async fn get_best_block(self: Arc<Self>) -> Result<u64> {
         let url_set = self.healthy_url_set().await?;
 
         Ok(url_set.best_block)
     }
 
-    async fn get_best_block_url(&self, url: Url) -> Result<u32> {
+    pub async fn get_block(self: Arc<Self>, block_number: u64) -> Result<Block> {
+        let url_set = self.clone().healthy_url_set().await?;
+
+        self.send(url_set, GetBlockByNumber { block_number }).await
+    }
+
+    async fn get_best_block_url(&self, url: Url) -> Result<u64> {
         let offset = self.cfg.best_block_offset;
 
         let num = self.send_impl(url, GetBestBlock {}).await?;
-        let num = get_u32_from_hex(&num);
+        let num = get_u64_from_hex(&num);
 
         self.metrics.record_chain_height(num);
 
@@ -308,26 +367,360 @@ impl EthClient {
         Ok(num)
     }
 
+    /// Also returns the url that served the block.
+    async fn get_block_and_logs(
+        self: Arc<Self>,
+        url_set: Arc<UrlSet>,
+        block_num: u64,
+    ) -> Result<(Block, Vec<Log>, Url)> {
+        let (url, mut block) = self
+            .clone()
+            .send_with_url(
+                url_set.clone(),
+                GetBlockByNumber {
+                    block_number: block_num,
+                },
+            )
+            .await?;
+
+        set_withdrawal_block_numbers(&mut block);
+
+        block.traces = self.clone().get_traces(url_set.clone(), &block).await?;
+        block.state_diffs = self
+            .clone()
+            .get_state_diffs(url_set.clone(), &block)
+            .await?;
+
+        let logs = if self.cfg.get_receipts {
+            let receipts = self
+                .clone()
+                .send(
+                    url_set,
+                    GetBlockReceipts {
+                        block_number: block_num,
+                    },
+                )
+                .await?;
+
+            block
+                .transactions
+                .iter_mut()
+                .zip(receipts.into_iter())
+                .filter_map(|(tx, receipt)| apply_receipt(tx, receipt).map(|logs| logs.into_iter()))
+                .flatten()
+                .collect()
+        } else {
+            self.clone()
+                .send(
+                    url_set,
+                    GetLogs {
+                        from_block: block_num,
+                        to_block: block_num,
+                    },
+                )
+                .await?
+        };
+
+        Ok((block, logs, url))
+    }
+
+    async fn get_traces(
+        self: Arc<Self>,
+        url_set: Arc<UrlSet>,
+        block: &Block,
+    ) -> Result<Vec<Trace>> {
+        let block_number = block.number.0;
+
+        match self.cfg.trace_method {
+            None => Ok(Vec::new()),
+            Some(TraceMethod::TraceBlock) => {
+                let traces = self.send(url_set, TraceBlock { block_number }).await?;
+
+                Ok(Trace::from_rpc_traces(traces))
+            }
+            Some(TraceMethod::DebugTraceBlockByNumber) => {
+                let frames = self
+                    .send(url_set, DebugTraceBlockByNumber { block_number })
+                    .await?;
+
+                Ok(Trace::from_call_frames(block, frames))
+            }
+        }
+    }
+
+    /// Fills in the traces of the given blocks if trace fetching is enabled.
+    async fn get_trace_batches(
+        self: Arc<Self>,
+        url_set: Arc<UrlSet>,
+        block_batches: &mut [Vec<Block>],
+    ) -> Result<()> {
+        match self.cfg.trace_method {
+            None => (),
+            Some(TraceMethod::TraceBlock) => {
+                let trace_batches = block_batches
+                    .iter()
+                    .map(|blocks| {
+                        blocks
+                            .iter()
+                            .map(|block| TraceBlock {
+                                block_number: block.number.0,
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>();
+
+                let trace_batches = self.send_batches(url_set, &trace_batches).await?;
+
+                for (blocks, trace_batch) in block_batches.iter_mut().zip(trace_batches) {
+                    for (block, traces) in blocks.iter_mut().zip(trace_batch) {
+                        block.traces = Trace::from_rpc_traces(traces);
+                    }
+                }
+            }
+            Some(TraceMethod::DebugTraceBlockByNumber) => {
+                let trace_batches = block_batches
+                    .iter()
+                    .map(|blocks| {
+                        blocks
+                            .iter()
+                            .map(|block| DebugTraceBlockByNumber {
+                                block_number: block.number.0,
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>();
+
+                let trace_batches = self.send_batches(url_set, &trace_batches).await?;
+
+                for (blocks, trace_batch) in block_batches.iter_mut().zip(trace_batches) {
+                    for (block, frames) in blocks.iter_mut().zip(trace_batch) {
+                        block.traces = Trace::from_call_frames(block, frames);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get_state_diffs(
+        self: Arc<Self>,
+        url_set: Arc<UrlSet>,
+        block: &Block,
+    ) -> Result<Vec<StateDiff>> {
+        let block_number = block.number.0;
+
+        match self.cfg.state_diff_method {
+            None => Ok(Vec::new()),
+            Some(StateDiffMethod::TraceReplayBlockTransactions) => {
+                let diffs = self
+                    .send(url_set, TraceReplayBlockTransactions { block_number })
+                    .await?;
+
+                Ok(StateDiff::from_rpc_state_diffs(block, diffs))
+            }
+            Some(StateDiffMethod::DebugTraceBlockByNumber) => {
+                let diffs = self
+                    .send(url_set, DebugTraceBlockPrestateDiff { block_number })
+                    .await?;
+
+                Ok(StateDiff::from_prestate_diffs(block, diffs))
+            }
+        }
+    }
+
+    /// Fills in the state diffs of the given blocks if state diff fetching is enabled.
+    async fn get_state_diff_batches(
+        self: Arc<Self>,
+        url_set: Arc<UrlSet>,
+        block_batches: &mut [Vec<Block>],
+    ) -> Result<()> {
+        match self.cfg.state_diff_method {
+            None => (),
+            Some(StateDiffMethod::TraceReplayBlockTransactions) => {
+                let diff_batches = block_batches
+                    .iter()
+                    .map(|blocks| {
+                        blocks
+                            .iter()
+                            .map(|block| TraceReplayBlockTransactions {
+                                block_number: block.number.0,
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>();
+
+                let diff_batches = self.send_batches(url_set, &diff_batches).await?;
+
+                for (blocks, diff_batch) in block_batches.iter_mut().zip(diff_batches) {
+                    for (block, diffs) in blocks.iter_mut().zip(diff_batch) {
+                        block.state_diffs = StateDiff::from_rpc_state_diffs(block, diffs);
+                    }
+                }
+            }
+            Some(StateDiffMethod::DebugTraceBlockByNumber) => {
+                let diff_batches = block_batches
+                    .iter()
+                    .map(|blocks| {
+                        blocks
+                            .iter()
+                            .map(|block| DebugTraceBlockPrestateDiff {
+                                block_number: block.number.0,
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>();
+
+                let diff_batches = self.send_batches(url_set, &diff_batches).await?;
+
+                for (blocks, diff_batch) in block_batches.iter_mut().zip(diff_batches) {
+                    for (block, diffs) in blocks.iter_mut().zip(diff_batch) {
+                        block.state_diffs = StateDiff::from_prestate_diffs(block, diffs);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Also returns the url that served each batch of blocks.
+    async fn get_batches(
+        self: Arc<Self>,
+        url_set: Arc<UrlSet>,
+        block_num: u64,
+        to_block: u64,
+    ) -> Result<(Vec<Vec<Block>>, Vec<Vec<Log>>, Vec<Url>)> {
+        let concurrency = u64::from(self.cfg.http_req_concurrency);
+        let batch_size = u64::from(self.cfg.block_batch_size);
+
+        let block_batches = (0..concurrency)
+            .filter_map(|step_no: u64| {
+                let start = block_num + step_no * batch_size;
+                let end = cmp::min(start + batch_size, to_block);
+
+                let batch = (start..end)
+                    .map(|i| GetBlockByNumber { block_number: i })
+                    .collect::<Vec<_>>();
+
+                if batch.is_empty() {
+                    None
+                } else {
+                    Some(batch)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let receipt_batches = (0..concurrency)
+            .filter_map(|step_no: u64| {
+                let start = block_num + step_no * batch_size;
+                let end = cmp::min(start + batch_size, to_block);
+
+                let batch = (start..end)
+                    .map(|i| GetBlockReceipts { block_number: i })
+                    .collect::<Vec<_>>();
+
+                if batch.is_empty() {
+                    None
+                } else {
+                    Some(batch)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let (block_urls, mut block_batches): (Vec<_>, Vec<_>) = self
+            .clone()
+            .send_batches_with_urls(url_set.clone(), &block_batches)
+            .await?
+            .into_iter()
+            .unzip();
+
+        for block in block_batches.iter_mut().flatten() {
+            set_withdrawal_block_numbers(block);
+        }
+
+        self.clone()
+            .get_trace_batches(url_set.clone(), &mut block_batches)
+            .await?;
+
+        self.clone()
+            .get_state_diff_batches(url_set.clone(), &mut block_batches)
+            .await?;
+
+        let log_batches = if self.cfg.get_receipts {
+            let receipt_batches = self
+                .clone()
+                .send_batches(url_set.clone(), &receipt_batches)
+                .await?;
+
+            block_batches
+                .iter_mut()
+                .zip(receipt_batches.into_iter())
+                .map(|(block_batch, receipt_batch)| {
+                    block_batch
+                        .iter_mut()
+                        .zip(receipt_batch.into_iter())
+                        .flat_map(|(block, receipts)| {
+                            block
+                                .transactions
+                                .iter_mut()
+                                .zip(receipts.into_iter())
+                                .filter_map(|(tx, receipt)| {
+                                    apply_receipt(tx, receipt).map(|logs| logs.into_iter())
+                                })
+                                .flatten()
+                        })
+                        .collect()
+                })
+                .collect()
+        } else {
+            let log_batches = (0..concurrency)
+                .filter_map(|step_no| {
+                    let start = block_num + step_no * batch_size;
+                    let end = cmp::min(start + batch_size, to_block);
+
+                    if start < end {
+                        Some(GetLogs {
+                            from_block: start,
+                            to_block: end - 1,
+                        })
+                    } else {
+                        None
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            self.clone().send_concurrent(url_set, &log_batches).await?
+        };
+
+        Ok((block_batches, log_batches, block_urls))
+    }
+
+    /// Streams blocks and logs starting from the given block.
+    ///
+    /// If a parent hash policy is given, every block is checked to be a child of the block
+    /// before it, including across yielded batches.
     pub fn stream_batches(
         self: Arc<Self>,
-        from: Option<u32>,
-        to: Option<u32>,
+        from: Option<u64>,
+        to: Option<u64>,
+        parent_hash_policy: Option<ParentHashPolicy>,
     ) -> impl Stream<Item = Result<(Vec<BlockRange>, Vec<Vec<Block>>, Vec<Vec<Log>>)>> {
         let from_block = from.unwrap_or(0);
 
-        let step = self.cfg.http_req_concurrency * self.cfg.block_batch_size;
+        let step = u64::from(self.cfg.http_req_concurrency * self.cfg.block_batch_size);
         async_stream::try_stream! {
             let mut block_num = from_block;
+            let mut last_block: Option<(u64, Bytes32)> = None;
             loop {
                 match to {
                     Some(to_block) if block_num >= to_block => break,
                     _ => (),
                 }
 
-                let concurrency = self.cfg.http_req_concurrency;
-                let batch_size = self.cfg.block_batch_size;
+                let batch_size = u64::from(self.cfg.block_batch_size);
 
-                let (url_set, to_block) = loop {
+                let (mut url_set, to_block) = loop {
                     let url_set = self.clone().healthy_url_set().await?;
 
                     let best_block = url_set.best_block;
@@ -343,31 +736,25 @@ impl EthClient {
                     }
                 };
 
+                let mut num_refetches = 0;
+
                 if block_num + batch_size >= to_block {
                     let start_time = Instant::now();
 
-                    let mut block = self.clone().send(url_set.clone(), GetBlockByNumber { block_number: block_num }).await?;
-
-                    let logs = if self.cfg.get_receipts {
-                        let receipts = self.clone().send(url_set, GetBlockReceipts {
-                            block_number: block_num
-                        }).await?;
-
-                        block.transactions.iter_mut().zip(receipts.into_iter()).filter_map(|(tx, receipt)| {
-                            assert_eq!(tx.block_number, receipt.block_number);
-                            assert_eq!(tx.transaction_index, receipt.transaction_index);
+                    let (block, logs) = loop {
+                        let (block, logs, url) = self.clone().get_block_and_logs(url_set.clone(), block_num).await?;
 
-                            tx.status = receipt.status;
-
-                            receipt.logs.map(|logs| logs.into_iter())
-                        }).flatten().collect()
-                    } else {
-                        self.clone().send(url_set, GetLogs {
-                            from_block: block_num,
-                            to_block: block_num,
-                        }).await?
+                        let mismatch = find_parent_hash_mismatch(last_block.as_ref(), iter::once(&block))
+                            .map(|mismatch| (mismatch, &url));
+                        if !handle_parent_hash_mismatch(parent_hash_policy, mismatch, &mut url_set, &mut num_refetches, self.cfg.max_parent_hash_refetches)? {
+                            break (block, logs);
+                        }
                     };
 
+                    if let Some(hash) = block.hash.as_ref() {
+                        last_block = Some((block.number.0, hash.clone()));
+                    }
+
                     self.metrics.record_download_height(block_num);
 
                     let elapsed = start_time.elapsed().as_millis();
@@ -393,86 +780,28 @@ impl EthClient {
 
                 let start_time = Instant::now();
 
-                let block_batches = (0..concurrency)
-                    .filter_map(|step_no: u32| {
-                        let start = block_num + step_no * batch_size;
-                        let end = cmp::min(start + batch_size, to_block);
-
-                        let batch = (start..end)
-                            .map(|i| GetBlockByNumber { block_number: i })
-                            .collect::<Vec<_>>();
-
-                        if batch.is_empty() {
-                            None
-                        } else {
-                            Some(batch)
-                        }
-                    })
-                    .collect::<Vec<_>>();
-
-                let receipt_batches = (0..concurrency)
-                    .filter_map(|step_no: u32| {
-                        let start = block_num + step_no * batch_size;
-                        let end = cmp::min(start + batch_size, to_block);
-
-                        let batch = (start..end)
-                            .map(|i| GetBlockReceipts { block_number: i })
-                            .collect::<Vec<_>>();
-
-                        if batch.is_empty() {
-                            None
-                        } else {
-                            Some(batch)
-                        }
-                    })
-                    .collect::<Vec<_>>();
-
-                let mut block_batches = self
-                    .clone()
-                    .send_batches(url_set.clone(), &block_batches)
-                    .await?;
-
-                let log_batches = if self.cfg.get_receipts {
-                    let receipt_batches = self
-                        .clone()
-                        .send_batches(url_set.clone(), &receipt_batches)
-                        .await?;
-
-                    block_batches.iter_mut().zip(receipt_batches.into_iter()).map(|(block_batch, receipt_batch)| {
-                        block_batch.iter_mut().zip(receipt_batch.into_iter()).flat_map(|(block, receipts)| {
-                            block.transactions.iter_mut().zip(receipts.into_iter()).filter_map(|(tx, receipt)| {
-                                assert_eq!(tx.block_number, receipt.block_number);
-                                assert_eq!(tx.transaction_index, receipt.transaction_index);
-
-                                tx.status = receipt.status;
-
-                                receipt.logs.map(|logs| logs.into_iter())
-                            }).flatten()
-                        }).collect()
-                    }).collect()
-                } else {
-                    let log_batches = (0..concurrency)
-                        .filter_map(|step_no| {
-                            let start = block_num + step_no * batch_size;
-                            let end = cmp::min(start + batch_size, to_block);
-
-                            if start < end {
-                                Some(GetLogs {
-                                    from_block: start,
-                                    to_block: end - 1,
-                                })
-                            } else {
-                                None
-                            }
-                        })
-                        .collect::<Vec<_>>();
-
-                    self
-                        .clone()
-                        .send_concurrent(url_set, &log_batches)
-                        .await?
+                let (block_batches, log_batches) = loop {
+                    let (block_batches, log_batches, urls) = self.clone().get_batches(url_set.clone(), block_num, to_block).await?;
+
+                    let mismatch = find_parent_hash_mismatch(last_block.as_ref(), block_batches.iter().flatten())
+                        .map(|mismatch| {
+                            let batch_idx = block_batches
+                                .iter()
+                                .position(|batch| batch.iter().any(|block| block.number.0 == mismatch.block_num()))
+                                .unwrap();
+                            (mismatch, &urls[batch_idx])
+                        });
+                    if !handle_parent_hash_mismatch(parent_hash_policy, mismatch, &mut url_set, &mut num_refetches, self.cfg.max_parent_hash_refetches)? {
+                        break (block_batches, log_batches);
+                    }
                 };
 
+                if let Some(block) = block_batches.iter().flatten().last() {
+                    if let Some(hash) = block.hash.as_ref() {
+                        last_block = Some((block.number.0, hash.clone()));
+                    }
+                }
+
                 let ended_block = cmp::min(block_num + step, to_block);
 
                 if ended_block > 0 {
@@ -483,7 +812,7 @@ impl EthClient {
                     self.metrics.record_download_speed((ended_block-block_num) as f64 / elapsed as f64 * 1000.);
                 }
 
-                let num_batches = u32::try_from(block_batches.len()).unwrap();
+                let num_batches = u64::try_from(block_batches.len()).unwrap();
 
                 let block_ranges = (0..num_batches).map(|i| {
                     let start = block_num + i * batch_size;
@@ -510,7 +839,361 @@ impl EthClient {
     }
 }
 
-fn get_u32_from_hex(hex: &str) -> u32 {
+/// Block that isn't a child of the block before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mismatch {
+    /// The block before it is the last block of a previous round, which was already yielded
+    Yielded(u64),
+    /// The block before it is in the same round
+    Round(u64),
+}
+
+impl Mismatch {
+    fn block_num(self) -> u64 {
+        match self {
+            Self::Yielded(block_num) | Self::Round(block_num) => block_num,
+        }
+    }
+}
+
+/// Returns the first block that isn't a child of the block before it.
+/// A block without a hash is a mismatch since the next block can't be checked against it.
+fn find_parent_hash_mismatch<'a>(
+    last_block: Option<&(u64, Bytes32)>,
+    blocks: impl Iterator<Item = &'a Block>,
+) -> Option<Mismatch> {
+    let mut prev = last_block.cloned();
+    let mut prev_is_yielded = true;
+
+    for block in blocks {
+        if let Some((prev_num, prev_hash)) = &prev {
+            if prev_num + 1 == block.number.0 && prev_hash != &block.parent_hash {
+                let block_num = block.number.0;
+                return Some(if prev_is_yielded {
+                    Mismatch::Yielded(block_num)
+                } else {
+                    Mismatch::Round(block_num)
+                });
+            }
+        }
+
+        match &block.hash {
+            Some(hash) => prev = Some((block.number.0, hash.clone())),
+            None => return Some(Mismatch::Round(block.number.0)),
+        }
+        prev_is_yielded = false;
+    }
+
+    None
+}
+
+/// Decides if a round of blocks should be re-fetched based on the parent hash check.
+/// Returns an error if the stream should be halted.
+///
+/// The url that served the mismatched block is removed from `url_set` before re-fetching,
+/// so a node that is on a fork isn't asked again. A mismatch with a block that was already
+/// yielded always halts since re-fetching the round can't change that block.
+fn handle_parent_hash_mismatch(
+    policy: Option<ParentHashPolicy>,
+    mismatch: Option<(Mismatch, &Url)>,
+    url_set: &mut Arc<UrlSet>,
+    num_refetches: &mut usize,
+    max_refetches: usize,
+) -> Result<bool> {
+    let (policy, (mismatch, url)) = match (policy, mismatch) {
+        (Some(policy), Some(mismatch)) => (policy, mismatch),
+        _ => return Ok(false),
+    };
+
+    let block_num = mismatch.block_num();
+
+    if let Mismatch::Yielded(_) = mismatch {
+        log::error!(
+            "parent hash of block {} doesn't match the block before it, which was already yielded",
+            block_num
+        );
+        return Err(Error::ParentHashMismatch(block_num));
+    }
+
+    if policy == ParentHashPolicy::Halt || *num_refetches >= max_refetches {
+        return Err(Error::ParentHashMismatch(block_num));
+    }
+
+    *url_set = match url_set.without(url) {
+        Some(url_set) => url_set,
+        None => {
+            log::error!(
+                "parent hash of block {} doesn't match the previous block and there are no other rpc urls to re-fetch it from",
+                block_num
+            );
+            return Err(Error::ParentHashMismatch(block_num));
+        }
+    };
+
+    log::warn!(
+        "parent hash of block {} doesn't match the previous block. re-fetching without rpc url {}...",
+        block_num,
+        url
+    );
+    *num_refetches += 1;
+
+    Ok(true)
+}
+
+fn get_u64_from_hex(hex: &str) -> u64 {
     let without_prefix = hex.trim_start_matches("0x");
-    u32::from_str_radix(without_prefix, 16).unwrap()
+    u64::from_str_radix(without_prefix, 16).unwrap()
+}
+
+/// Copies the fields that only the receipt has into the transaction and returns the logs
+/// of the receipt.
+fn apply_receipt(tx: &mut Transaction, receipt: TransactionReceipt) -> Option<Vec<Log>> {
+    assert_eq!(tx.block_number, receipt.block_number);
+    assert_eq!(tx.transaction_index, receipt.transaction_index);
+
+    tx.status = receipt.status;
+    tx.contract_address = receipt.contract_address;
+    tx.gas_used = receipt.gas_used;
+    tx.cumulative_gas_used = receipt.cumulative_gas_used;
+    tx.effective_gas_price = receipt.effective_gas_price;
+    tx.logs_bloom = receipt.logs_bloom;
+
+    receipt.logs
+}
+
+/// Withdrawals in the rpc response don't have the number of the block they are in.
+fn set_withdrawal_block_numbers(block: &mut Block) {
+    for withdrawal in block.withdrawals.iter_mut() {
+        withdrawal.block_number = block.number;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deserialize::{Address, BlockNumber, BloomFilterBytes, Bytes, Index};
+    use serde_json::json;
+
+    fn block(number: u64, hash: Option<u8>, parent_hash: u8) -> Block {
+        Block {
+            parent_hash: Bytes32::new(&[parent_hash; 32]),
+            sha3_uncles: Bytes32::new(&[0; 32]),
+            miner: Address::new(&[0; 20]),
+            state_root: Bytes32::new(&[0; 32]),
+            transactions_root: Bytes32::new(&[0; 32]),
+            receipts_root: Bytes32::new(&[0; 32]),
+            logs_bloom: BloomFilterBytes::new(&[0; 256]),
+            difficulty: None,
+            number: BlockNumber(number),
+            gas_limit: Bytes::new(&[]),
+            gas_used: Bytes::new(&[]),
+            timestamp: Bytes::new(&[]),
+            extra_data: Bytes::new(&[]),
+            mix_hash: None,
+            nonce: None,
+            total_difficulty: None,
+            base_fee_per_gas: None,
+            size: Bytes::new(&[]),
+            hash: hash.map(|hash| Bytes32::new(&[hash; 32])),
+            transactions: Vec::new(),
+            traces: Vec::new(),
+            state_diffs: Vec::new(),
+            blob_gas_used: None,
+            excess_blob_gas: None,
+            parent_beacon_block_root: None,
+            withdrawals_root: None,
+            withdrawals: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_find_parent_hash_mismatch() {
+        let blocks = [block(10, Some(10), 9), block(11, Some(11), 10)];
+        assert_eq!(find_parent_hash_mismatch(None, blocks.iter()), None);
+
+        let blocks = [block(10, Some(10), 9), block(11, Some(11), 7)];
+        assert_eq!(
+            find_parent_hash_mismatch(None, blocks.iter()),
+            Some(Mismatch::Round(11))
+        );
+    }
+
+    #[test]
+    fn test_find_parent_hash_mismatch_across_batches() {
+        let last_block = (9, Bytes32::new(&[9; 32]));
+        let batches = vec![
+            vec![block(10, Some(10), 9), block(11, Some(11), 10)],
+            vec![block(12, Some(12), 11), block(13, Some(13), 12)],
+        ];
+        assert_eq!(
+            find_parent_hash_mismatch(Some(&last_block), batches.iter().flatten()),
+            None
+        );
+
+        let batches = vec![
+            vec![block(10, Some(10), 9), block(11, Some(11), 10)],
+            vec![block(12, Some(12), 8), block(13, Some(13), 12)],
+        ];
+        assert_eq!(
+            find_parent_hash_mismatch(Some(&last_block), batches.iter().flatten()),
+            Some(Mismatch::Round(12))
+        );
+
+        let batches = vec![vec![block(10, Some(10), 8)]];
+        assert_eq!(
+            find_parent_hash_mismatch(Some(&last_block), batches.iter().flatten()),
+            Some(Mismatch::Yielded(10))
+        );
+    }
+
+    #[test]
+    fn test_find_parent_hash_mismatch_missing_hash() {
+        let blocks = [
+            block(10, Some(10), 9),
+            block(11, None, 10),
+            block(12, Some(12), 3),
+        ];
+        assert_eq!(
+            find_parent_hash_mismatch(None, blocks.iter()),
+            Some(Mismatch::Round(11))
+        );
+    }
+
+    #[test]
+    fn test_handle_parent_hash_mismatch() {
+        let a = Url::parse("http://a.com").unwrap();
+        let b = Url::parse("http://b.com").unwrap();
+        let mut url_set = Arc::new(UrlSet {
+            inner: vec![a.clone(), b.clone()],
+            best_block: 5,
+        });
+        let mut num_refetches = 0;
+        let policy = Some(ParentHashPolicy::Refetch);
+
+        assert!(
+            !handle_parent_hash_mismatch(policy, None, &mut url_set, &mut num_refetches, 5)
+                .unwrap()
+        );
+
+        // the parent was yielded by the previous round so re-fetching can't fix it
+        let res = handle_parent_hash_mismatch(
+            policy,
+            Some((Mismatch::Yielded(10), &a)),
+            &mut url_set,
+            &mut num_refetches,
+            5,
+        );
+        assert!(matches!(res, Err(Error::ParentHashMismatch(10))));
+        assert_eq!(url_set.inner, vec![a.clone(), b.clone()]);
+        assert_eq!(num_refetches, 0);
+
+        let res = handle_parent_hash_mismatch(
+            policy,
+            Some((Mismatch::Round(12), &a)),
+            &mut url_set,
+            &mut num_refetches,
+            5,
+        );
+        assert!(res.unwrap());
+        assert_eq!(url_set.inner, vec![b.clone()]);
+        assert_eq!(num_refetches, 1);
+
+        let res = handle_parent_hash_mismatch(
+            Some(ParentHashPolicy::Halt),
+            Some((Mismatch::Round(12), &b)),
+            &mut url_set,
+            &mut num_refetches,
+            5,
+        );
+        assert!(matches!(res, Err(Error::ParentHashMismatch(12))));
+    }
+
+    #[test]
+    fn test_url_set_without() {
+        let a = Url::parse("http://a.com").unwrap();
+        let b = Url::parse("http://b.com").unwrap();
+        let url_set = UrlSet {
+            inner: vec![a.clone(), b.clone()],
+            best_block: 5,
+        };
+
+        let url_set = url_set.without(&a).unwrap();
+        assert_eq!(url_set.inner, vec![b.clone()]);
+        assert_eq!(url_set.best_block, 5);
+        assert!(url_set.without(&b).is_none());
+    }
+
+    fn hex(byte: u8, len: usize) -> String {
+        prefix_hex::encode(vec![byte; len])
+    }
+
+    /// Contract creation without a `to` address, as returned by eth_getBlockByNumber
+    fn contract_creation() -> Transaction {
+        serde_json::from_value(json!({
+            "blockHash": hex(100, 32),
+            "blockNumber": "0x64",
+            "from": hex(1, 20),
+            "gas": "0x30d40",
+            "gasPrice": "0x3b9aca00",
+            "hash": hex(0xaa, 32),
+            "input": "0x6080",
+            "nonce": "0x0",
+            "to": null,
+            "transactionIndex": "0x0",
+            "value": "0x0",
+        }))
+        .unwrap()
+    }
+
+    /// The address of the created contract is taken from the receipt.
+    #[test]
+    fn test_apply_receipt_contract_address() {
+        let mut tx = contract_creation();
+        assert_eq!(tx.contract_address, None);
+
+        let receipt: TransactionReceipt = serde_json::from_value(json!({
+            "blockNumber": "0x64",
+            "transactionIndex": "0x0",
+            "logs": [],
+            "status": "0x1",
+            "contractAddress": hex(3, 20),
+        }))
+        .unwrap();
+
+        let logs = apply_receipt(&mut tx, receipt);
+
+        assert_eq!(logs.unwrap().len(), 0);
+        assert_eq!(tx.status, Some(Index(1)));
+        assert_eq!(tx.contract_address, Some(Address::new(&[3; 20])));
+        assert_eq!(tx.dest, None);
+    }
+
+    /// Gas and fee fields of the receipt are copied into the transaction.
+    #[test]
+    fn test_apply_receipt_fee_fields() {
+        let mut tx = contract_creation();
+
+        let receipt: TransactionReceipt = serde_json::from_value(json!({
+            "blockNumber": "0x64",
+            "transactionIndex": "0x0",
+            "logs": [],
+            "status": "0x1",
+            "contractAddress": hex(3, 20),
+            "gasUsed": "0x5208",
+            "cumulativeGasUsed": "0xa410",
+            "effectiveGasPrice": "0x3b9aca00",
+            "logsBloom": hex(1, 256),
+        }))
+        .unwrap();
+
+        apply_receipt(&mut tx, receipt);
+
+        assert_eq!(tx.gas_used, Some(Bytes::from_quantity(21_000)));
+        assert_eq!(tx.cumulative_gas_used, Some(Bytes::from_quantity(42_000)));
+        assert_eq!(
+            tx.effective_gas_price,
+            Some(Bytes::from_quantity(1_000_000_000))
+        );
+        assert_eq!(tx.logs_bloom, Some(BloomFilterBytes::new(&[1; 256])));
+    }
 }
@@ -1,4 +1,6 @@
-use crate::types::{Block, Log, TransactionReceipt};
+use crate::types::{
+    Block, Log, RpcTrace, RpcTxStateDiff, TransactionReceipt, TxCallFrame, TxPrestateDiff,
+};
 use serde::de::DeserializeOwned;
 use serde_json::Value as JsonValue;
 
@@ -10,7 +12,7 @@ pub trait EthRequest {
 
 #[derive(Debug, Clone, Copy)]
 pub struct GetBlockByNumber {
-    pub block_number: u32,
+    pub block_number: u64,
 }
 
 impl EthRequest for GetBlockByNumber {
@@ -47,7 +49,7 @@ impl EthRequest for GetBestBlock {
 
 #[derive(Debug, Clone, Copy)]
 pub struct GetBlockReceipts {
-    pub block_number: u32,
+    pub block_number: u64,
 }
 
 impl EthRequest for GetBlockReceipts {
@@ -67,8 +69,8 @@ impl EthRequest for GetBlockReceipts {
 
 #[derive(Debug, Clone, Copy)]
 pub struct GetLogs {
-    pub from_block: u32,
-    pub to_block: u32,
+    pub from_block: u64,
+    pub to_block: u64,
 }
 
 impl EthRequest for GetLogs {
@@ -89,6 +91,96 @@ impl EthRequest for GetLogs {
     }
 }
 
-fn block_number_to_hex(block_number: u32) -> String {
+#[derive(Debug, Clone, Copy)]
+pub struct TraceBlock {
+    pub block_number: u64,
+}
+
+impl EthRequest for TraceBlock {
+    type Resp = Vec<RpcTrace>;
+
+    fn to_body(&self, id: usize) -> JsonValue {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "trace_block",
+            "params": [
+                block_number_to_hex(self.block_number),
+            ],
+            "id": id,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DebugTraceBlockByNumber {
+    pub block_number: u64,
+}
+
+impl EthRequest for DebugTraceBlockByNumber {
+    type Resp = Vec<TxCallFrame>;
+
+    fn to_body(&self, id: usize) -> JsonValue {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "debug_traceBlockByNumber",
+            "params": [
+                block_number_to_hex(self.block_number),
+                {
+                    "tracer": "callTracer",
+                },
+            ],
+            "id": id,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TraceReplayBlockTransactions {
+    pub block_number: u64,
+}
+
+impl EthRequest for TraceReplayBlockTransactions {
+    type Resp = Vec<RpcTxStateDiff>;
+
+    fn to_body(&self, id: usize) -> JsonValue {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "trace_replayBlockTransactions",
+            "params": [
+                block_number_to_hex(self.block_number),
+                ["stateDiff"],
+            ],
+            "id": id,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DebugTraceBlockPrestateDiff {
+    pub block_number: u64,
+}
+
+impl EthRequest for DebugTraceBlockPrestateDiff {
+    type Resp = Vec<TxPrestateDiff>;
+
+    fn to_body(&self, id: usize) -> JsonValue {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "debug_traceBlockByNumber",
+            "params": [
+                block_number_to_hex(self.block_number),
+                {
+                    "tracer": "prestateTracer",
+                    "tracerConfig": {
+                        "diffMode": true,
+                    },
+                },
+            ],
+            "id": id,
+        })
+    }
+}
+
+fn block_number_to_hex(block_number: u64) -> String {
     format!("0x{block_number:x}")
 }
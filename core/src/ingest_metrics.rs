@@ -68,28 +68,28 @@ impl IngestMetrics {
             .set(blocks_per_second);
     }
 
-    pub fn record_download_height(&self, height: u32) {
+    pub fn record_download_height(&self, height: u64) {
         self.height
             .get_or_create(&Label {
                 kind: LabelKind::Download,
             })
-            .set(i64::from(height));
+            .set(i64::try_from(height).unwrap());
     }
 
-    pub fn record_write_height(&self, height: u32) {
+    pub fn record_write_height(&self, height: u64) {
         self.height
             .get_or_create(&Label {
                 kind: LabelKind::Write,
             })
-            .set(i64::from(height));
+            .set(i64::try_from(height).unwrap());
     }
 
-    pub fn record_chain_height(&self, height: u32) {
+    pub fn record_chain_height(&self, height: u64) {
         self.height
             .get_or_create(&Label {
                 kind: LabelKind::Chain,
             })
-            .set(i64::from(height));
+            .set(i64::try_from(height).unwrap());
     }
 
     pub fn encode(&self) -> Result<String> {
@@ -6,6 +6,7 @@ use crate::types::{Block, BlockRange, FormatVersion, Log};
 use crate::{Error, Result};
 use arrow2::datatypes::Field;
 use futures::{Stream, TryFutureExt};
+use std::io;
 use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -13,7 +14,7 @@ use std::time::Instant;
 
 pub async fn stream_batches(
     ingest_metrics: Arc<IngestMetrics>,
-    start_block: u32,
+    start_block: u64,
     local_source_path: &Path,
     local_format_version: &str,
 ) -> Result<BatchStream> {
@@ -56,11 +57,39 @@ fn stream_batches_impl(
                 }
             };
 
+            // optional files are only written if the folder has any items of that kind
+            let read_opt_fut = |kind, fields: Option<Vec<Field>>| {
+                let mut path = local_source_path.clone();
+                path.push(&dir_name.to_string());
+                path.push(&format!("{kind}.parquet"));
+
+                async move {
+                    let fields = match fields {
+                        Some(fields) => fields,
+                        None => return Ok(None),
+                    };
+
+                    let file = match tokio::fs::read(&path).await {
+                        Ok(file) => file,
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+                        Err(e) => return Err(Error::ReadFile(e)),
+                    };
+
+                    read_parquet_buf(&file, fields).map(Some)
+                }
+            };
+
             let block_fut = read_fut("block", source.block_fields()).map_ok(|columns| source.read_blocks(columns));
             let tx_fut = read_fut("tx", source.tx_fields()).map_ok(|columns| source.read_txs(columns));
             let log_fut = read_fut("log", source.log_fields()).map_ok(|columns| source.read_logs(columns));
+            let trace_fut = read_opt_fut("trace", source.trace_fields())
+                .map_ok(|columns| columns.map(|columns| source.read_traces(columns)).unwrap_or_default());
+            let state_diff_fut = read_opt_fut("state_diff", source.state_diff_fields())
+                .map_ok(|columns| columns.map(|columns| source.read_state_diffs(columns)).unwrap_or_default());
+            let withdrawal_fut = read_opt_fut("withdrawal", source.withdrawal_fields())
+                .map_ok(|columns| columns.map(|columns| source.read_withdrawals(columns)).unwrap_or_default());
 
-            let (mut blocks, txs, logs) = futures::future::try_join3(block_fut, tx_fut, log_fut).await?;
+            let (mut blocks, txs, logs, traces, state_diffs, withdrawals) = futures::try_join!(block_fut, tx_fut, log_fut, trace_fut, state_diff_fut, withdrawal_fut)?;
 
             let block_range = BlockRange {
                 from: *blocks.first_key_value().unwrap().0,
@@ -71,6 +100,18 @@ fn stream_batches_impl(
                 blocks.get_mut(&tx.block_number.0).unwrap().transactions.push(tx);
             }
 
+            for trace in traces {
+                blocks.get_mut(&trace.block_number.0).unwrap().traces.push(trace);
+            }
+
+            for state_diff in state_diffs {
+                blocks.get_mut(&state_diff.block_number.0).unwrap().state_diffs.push(state_diff);
+            }
+
+            for withdrawal in withdrawals {
+                blocks.get_mut(&withdrawal.block_number.0).unwrap().withdrawals.push(withdrawal);
+            }
+
             let blocks = blocks.into_values().collect::<Vec<_>>();
 
             let block_num = dir_name.range.to;
@@ -1,4 +1,4 @@
-use crate::types::{Block, FormatVersion, Log, Transaction};
+use crate::types::{Block, FormatVersion, Log, StateDiff, Trace, Transaction, Withdrawal};
 use crate::{Error, Result};
 use arrow2::datatypes::Field;
 use arrow2::io::parquet::read::ArrayIter;
@@ -9,11 +9,17 @@ use std::io::Cursor;
 pub mod util;
 mod ver0_0_39;
 mod ver0_1_0;
+mod ver0_2_0;
+mod ver0_3_0;
+mod ver0_4_0;
 
 pub fn get(ver: FormatVersion) -> Box<dyn ParquetSource> {
     match ver {
         FormatVersion::Ver0_0_39 => Box::new(ver0_0_39::Ver0_0_39),
         FormatVersion::Ver0_1_0 => Box::new(ver0_1_0::Ver0_1_0),
+        FormatVersion::Ver0_2_0 => Box::new(ver0_2_0::Ver0_2_0),
+        FormatVersion::Ver0_3_0 => Box::new(ver0_3_0::Ver0_3_0),
+        FormatVersion::Ver0_4_0 => Box::new(ver0_4_0::Ver0_4_0),
     }
 }
 
@@ -42,15 +48,43 @@ pub fn read_parquet_buf(buf: &[u8], fields: Vec<Field>) -> Result<Columns> {
 }
 
 pub trait ParquetSource {
-    fn read_blocks(&self, columns: Columns) -> BTreeMap<u32, Block>;
+    fn read_blocks(&self, columns: Columns) -> BTreeMap<u64, Block>;
 
     fn read_txs(&self, columns: Columns) -> Vec<Transaction>;
 
     fn read_logs(&self, columns: Columns) -> Vec<Log>;
 
+    fn read_traces(&self, _columns: Columns) -> Vec<Trace> {
+        Vec::new()
+    }
+
+    fn read_state_diffs(&self, _columns: Columns) -> Vec<StateDiff> {
+        Vec::new()
+    }
+
+    fn read_withdrawals(&self, _columns: Columns) -> Vec<Withdrawal> {
+        Vec::new()
+    }
+
     fn block_fields(&self) -> Vec<Field>;
 
     fn tx_fields(&self) -> Vec<Field>;
 
     fn log_fields(&self) -> Vec<Field>;
+
+    /// Fields of trace.parquet, None if the format doesn't have traces.
+    /// The file is optional even if the format has them.
+    fn trace_fields(&self) -> Option<Vec<Field>> {
+        None
+    }
+
+    /// Fields of state_diff.parquet, None if the format doesn't have state diffs.
+    fn state_diff_fields(&self) -> Option<Vec<Field>> {
+        None
+    }
+
+    /// Fields of withdrawal.parquet, None if the format doesn't have withdrawals.
+    fn withdrawal_fields(&self) -> Option<Vec<Field>> {
+        None
+    }
 }
@@ -1,4 +1,13 @@
-use crate::deserialize::{BigUnsigned, Bytes};
+use crate::deserialize::{Address, Bytes, Bytes32, Index, U256};
+use crate::types::{AccessListItem, Authorization};
+use arrow2::array::{self, Array, FixedSizeBinaryArray, StructArray, UInt32Array, UInt64Array};
+use arrow2::compute::cast::{cast, CastOptions};
+use arrow2::datatypes::{DataType, Field, Schema};
+use arrow2::error::Error as ArrowError;
+use arrow2::io::parquet::read::ArrayIter;
+
+type BinaryArray = array::BinaryArray<i32>;
+type ListArray = array::ListArray<i32>;
 
 // defines columns using the result frame and a list of column names
 #[macro_export]
@@ -33,6 +42,99 @@ pub(crate) use define_cols;
 pub(crate) use map_from_arrow;
 pub(crate) use map_from_arrow_opt;
 
+/// Replaces the types of the given fields with the types of the columns in the file.
+/// Files written before format version 0.3.0 store block numbers and chain ids as UInt32 and
+/// files written before 0.4.0 store gas amounts, timestamps, withdrawal amounts, values and fees
+/// as Binary so the columns read with these fields should be passed to `cast_columns`.
+pub fn file_fields(schema: &Schema, fields: &[Field]) -> Vec<Field> {
+    fields
+        .iter()
+        .map(|field| {
+            schema
+                .fields
+                .iter()
+                .find(|f| f.name == field.name)
+                .unwrap_or(field)
+                .clone()
+        })
+        .collect()
+}
+
+/// Casts the columns that were read using `file_fields` to the types of the given fields.
+pub fn cast_columns<'a>(columns: Vec<ArrayIter<'a>>, fields: &[Field]) -> Vec<ArrayIter<'a>> {
+    columns
+        .into_iter()
+        .zip(fields.iter())
+        .map(|(column, field)| {
+            let data_type = field.data_type.clone();
+
+            Box::new(column.map(move |arr| {
+                let arr = arr?;
+                if arr.data_type() == &data_type {
+                    return Ok(arr);
+                }
+                cast_array(arr.as_ref(), &data_type)
+            })) as ArrayIter<'a>
+        })
+        .collect()
+}
+
+fn cast_array(arr: &dyn Array, data_type: &DataType) -> arrow2::error::Result<Box<dyn Array>> {
+    match (arr.data_type(), data_type) {
+        // quantities that were widened to arbitrary length binary, like `v` before 0.3.0
+        (DataType::UInt64 | DataType::Int64, DataType::Binary) => {
+            let arr = cast(arr, &DataType::UInt64, CastOptions::default())?;
+            let arr = arr.as_any().downcast_ref::<UInt64Array>().unwrap();
+
+            Ok(Box::new(
+                arr.iter()
+                    .map(|num| num.map(|num| Bytes::from_quantity(*num).0))
+                    .collect::<BinaryArray>(),
+            ))
+        }
+        // quantities that were stored as arbitrary length binary before 0.4.0
+        (DataType::Binary, DataType::UInt64) => {
+            let arr = arr.as_any().downcast_ref::<BinaryArray>().unwrap();
+
+            let arr = arr
+                .iter()
+                .map(|quantity| {
+                    quantity
+                        .map(|quantity| {
+                            Bytes::new(quantity).to_u64().ok_or_else(|| {
+                                ArrowError::InvalidArgumentError(
+                                    "quantity doesn't fit into a u64".to_owned(),
+                                )
+                            })
+                        })
+                        .transpose()
+                })
+                .collect::<arrow2::error::Result<Vec<_>>>()?;
+
+            Ok(Box::new(UInt64Array::from(arr)))
+        }
+        (DataType::Binary, DataType::FixedSizeBinary(32)) => {
+            let arr = arr.as_any().downcast_ref::<BinaryArray>().unwrap();
+
+            let mut values = Vec::with_capacity(arr.len() * 32);
+            for quantity in arr.iter() {
+                let quantity = quantity.unwrap_or_default();
+                let quantity = U256::from_quantity(quantity).ok_or_else(|| {
+                    ArrowError::InvalidArgumentError("quantity is wider than 256 bits".to_owned())
+                })?;
+                values.extend_from_slice(&quantity.0);
+            }
+
+            Ok(Box::new(FixedSizeBinaryArray::new(
+                data_type.clone(),
+                values.into(),
+                arr.validity().cloned(),
+            )))
+        }
+        _ => cast(arr, data_type, CastOptions::default()),
+    }
+}
+
 pub fn i64_to_bytes(num: i64) -> Bytes {
     let bytes = num.to_be_bytes();
     let idx = bytes
@@ -45,6 +147,67 @@ pub fn i64_to_bytes(num: i64) -> Bytes {
     Bytes::new(bytes)
 }
 
-pub fn i64_to_big_unsigned(num: i64) -> BigUnsigned {
-    BigUnsigned(num.try_into().unwrap())
+pub fn i64_to_quantity(num: i64) -> Bytes {
+    Bytes::from_quantity(num.try_into().unwrap())
+}
+
+/// Reads the access list of the transaction at `idx` from an `access_list` column
+pub fn access_list_from_arrow(arr: &ListArray, idx: usize) -> Option<Vec<AccessListItem>> {
+    if !arr.is_valid(idx) {
+        return None;
+    }
+
+    let items = arr.value(idx);
+    let items = items.as_any().downcast_ref::<StructArray>().unwrap();
+    let address = downcast_field::<BinaryArray>(items, 0);
+    let storage_keys = downcast_field::<ListArray>(items, 1);
+
+    let access_list = (0..items.len())
+        .map(|i| {
+            let keys = storage_keys.value(i);
+            let keys = keys.as_any().downcast_ref::<BinaryArray>().unwrap();
+
+            AccessListItem {
+                address: Address::new(address.value(i)),
+                storage_keys: keys.values_iter().map(Bytes32::new).collect(),
+            }
+        })
+        .collect();
+
+    Some(access_list)
+}
+
+/// Reads the authorization list of the transaction at `idx` from an `authorization_list` column
+pub fn authorization_list_from_arrow(arr: &ListArray, idx: usize) -> Option<Vec<Authorization>> {
+    if !arr.is_valid(idx) {
+        return None;
+    }
+
+    let items = arr.value(idx);
+    let items = items.as_any().downcast_ref::<StructArray>().unwrap();
+    let chain_id = downcast_field::<BinaryArray>(items, 0);
+    let address = downcast_field::<BinaryArray>(items, 1);
+    let nonce = downcast_field::<BinaryArray>(items, 2);
+    let y_parity = downcast_field::<UInt32Array>(items, 3);
+    let r = downcast_field::<BinaryArray>(items, 4);
+    let s = downcast_field::<BinaryArray>(items, 5);
+    let authority = downcast_field::<BinaryArray>(items, 6);
+
+    let authorization_list = (0..items.len())
+        .map(|i| Authorization {
+            chain_id: Bytes::new(chain_id.value(i)),
+            address: Address::new(address.value(i)),
+            nonce: Bytes::new(nonce.value(i)),
+            y_parity: Index(y_parity.value(i)),
+            r: Bytes::new(r.value(i)),
+            s: Bytes::new(s.value(i)),
+            authority: authority.get(i).map(Address::new),
+        })
+        .collect();
+
+    Some(authorization_list)
+}
+
+fn downcast_field<T: 'static>(arr: &StructArray, idx: usize) -> &T {
+    arr.values()[idx].as_any().downcast_ref::<T>().unwrap()
 }
@@ -1,8 +1,8 @@
-use super::util::{
-    define_cols, i64_to_big_unsigned, i64_to_bytes, map_from_arrow, map_from_arrow_opt,
-};
+use super::util::{define_cols, i64_to_bytes, i64_to_quantity, map_from_arrow, map_from_arrow_opt};
 use super::{Columns, ParquetSource};
-use crate::deserialize::{Address, BigUnsigned, BloomFilterBytes, Bytes, Bytes32, Index};
+use crate::deserialize::{
+    Address, BigUnsigned, BlockNumber, BloomFilterBytes, Bytes, Bytes32, ChainId, Index,
+};
 use crate::types::{Block, Log, Transaction};
 use arrayvec::ArrayVec;
 use arrow2::array::{self, BooleanArray, Int64Array, UInt32Array, UInt64Array};
@@ -15,7 +15,7 @@ type BinaryArray = array::BinaryArray<i32>;
 pub struct Ver0_0_39;
 
 impl ParquetSource for Ver0_0_39 {
-    fn read_blocks(&self, columns: Columns) -> BTreeMap<u32, Block> {
+    fn read_blocks(&self, columns: Columns) -> BTreeMap<u64, Block> {
         let mut blocks = BTreeMap::new();
 
         for columns in columns {
@@ -46,7 +46,7 @@ impl ParquetSource for Ver0_0_39 {
             let len = block_number.len();
 
             for i in 0..len {
-                let number = map_from_arrow!(block_number, Index, i);
+                let number = map_from_arrow!(block_number, BlockNumber::from, i);
 
                 blocks.insert(
                     number.0,
@@ -83,6 +83,13 @@ impl ParquetSource for Ver0_0_39 {
                         size: map_from_arrow!(block_size, i64_to_bytes, i),
                         hash: map_from_arrow_opt!(block_hash, Bytes32::new, i),
                         transactions: Vec::new(),
+                        traces: Vec::new(),
+                        state_diffs: Vec::new(),
+                        blob_gas_used: None,
+                        excess_blob_gas: None,
+                        parent_beacon_block_root: None,
+                        withdrawals_root: None,
+                        withdrawals: Vec::new(),
                     },
                 );
             }
@@ -136,17 +143,26 @@ impl ParquetSource for Ver0_0_39 {
                     ),
                     max_fee_per_gas: map_from_arrow_opt!(tx_max_fee_per_gas, i64_to_bytes, i),
                     y_parity: map_from_arrow_opt!(tx_y_parity, Index, i),
-                    chain_id: map_from_arrow_opt!(tx_chain_id, Index, i),
-                    v: map_from_arrow_opt!(tx_v, i64_to_big_unsigned, i),
+                    chain_id: map_from_arrow_opt!(tx_chain_id, ChainId::from, i),
+                    v: map_from_arrow_opt!(tx_v, i64_to_quantity, i),
                     r: map_from_arrow_opt!(tx_r, Bytes::new, i),
                     s: map_from_arrow_opt!(tx_s, Bytes::new, i),
                     source: map_from_arrow_opt!(tx_source, Address::new, i),
                     block_hash: map_from_arrow!(tx_block_hash, Bytes32::new, i),
-                    block_number: map_from_arrow!(tx_block_number, Index, i),
+                    block_number: map_from_arrow!(tx_block_number, BlockNumber::from, i),
                     transaction_index: map_from_arrow!(tx_transaction_index, Index, i),
                     gas_price: Some(map_from_arrow!(tx_gas_price, i64_to_bytes, i)),
                     hash: map_from_arrow!(tx_hash, Bytes32::new, i),
                     status: None,
+                    contract_address: None,
+                    gas_used: None,
+                    cumulative_gas_used: None,
+                    effective_gas_price: None,
+                    logs_bloom: None,
+                    max_fee_per_blob_gas: None,
+                    blob_versioned_hashes: None,
+                    access_list: None,
+                    authorization_list: None,
                 });
             }
         }
@@ -181,7 +197,7 @@ impl ParquetSource for Ver0_0_39 {
                 logs.push(Log {
                     address: map_from_arrow!(log_address, Address::new, i),
                     block_hash: map_from_arrow!(log_block_hash, Bytes32::new, i),
-                    block_number: map_from_arrow!(log_block_number, Index, i),
+                    block_number: map_from_arrow!(log_block_number, BlockNumber::from, i),
                     data: map_from_arrow!(log_data, Bytes::new, i),
                     log_index: map_from_arrow!(log_log_index, Index, i),
                     removed: log_removed.get(i),
@@ -1,7 +1,9 @@
 use super::util::{define_cols, map_from_arrow, map_from_arrow_opt};
 use super::{Columns, ParquetSource};
-use crate::deserialize::{Address, BigUnsigned, BloomFilterBytes, Bytes, Bytes32, Index};
-use crate::types::{Block, Log, Transaction};
+use crate::deserialize::{
+    Address, BigUnsigned, BlockNumber, BloomFilterBytes, Bytes, Bytes32, ChainId, Index,
+};
+use crate::types::{Block, Log, StateDiff, Trace, Transaction};
 use arrayvec::ArrayVec;
 use arrow2::array::{self, BooleanArray, UInt32Array, UInt64Array};
 use arrow2::compute::concatenate::concatenate;
@@ -9,11 +11,15 @@ use arrow2::datatypes::{DataType, Field};
 use std::collections::BTreeMap;
 
 type BinaryArray = array::BinaryArray<i32>;
+type ListArray = array::ListArray<i32>;
+type Utf8Array = array::Utf8Array<i32>;
 
+/// Folders written with this label can have a contract_address column, it is ignored
+/// since receipt fields are part of 0.2.0.
 pub struct Ver0_1_0;
 
 impl ParquetSource for Ver0_1_0 {
-    fn read_blocks(&self, columns: Columns) -> BTreeMap<u32, Block> {
+    fn read_blocks(&self, columns: Columns) -> BTreeMap<u64, Block> {
         let mut blocks = BTreeMap::new();
 
         for columns in columns {
@@ -44,7 +50,7 @@ impl ParquetSource for Ver0_1_0 {
             let len = block_number.len();
 
             for i in 0..len {
-                let number = map_from_arrow!(block_number, Index, i);
+                let number = map_from_arrow!(block_number, BlockNumber::from, i);
 
                 blocks.insert(
                     number.0,
@@ -81,6 +87,13 @@ impl ParquetSource for Ver0_1_0 {
                         size: map_from_arrow!(block_size, Bytes::new, i),
                         hash: map_from_arrow_opt!(block_hash, Bytes32::new, i),
                         transactions: Vec::new(),
+                        traces: Vec::new(),
+                        state_diffs: Vec::new(),
+                        blob_gas_used: None,
+                        excess_blob_gas: None,
+                        parent_beacon_block_root: None,
+                        withdrawals_root: None,
+                        withdrawals: Vec::new(),
                     },
                 );
             }
@@ -135,17 +148,26 @@ impl ParquetSource for Ver0_1_0 {
                     ),
                     max_fee_per_gas: map_from_arrow_opt!(tx_max_fee_per_gas, Bytes::new, i),
                     y_parity: map_from_arrow_opt!(tx_y_parity, Index, i),
-                    chain_id: map_from_arrow_opt!(tx_chain_id, Index, i),
-                    v: map_from_arrow_opt!(tx_v, BigUnsigned, i),
+                    chain_id: map_from_arrow_opt!(tx_chain_id, ChainId::from, i),
+                    v: map_from_arrow_opt!(tx_v, Bytes::from_quantity, i),
                     r: map_from_arrow_opt!(tx_r, Bytes::new, i),
                     s: map_from_arrow_opt!(tx_s, Bytes::new, i),
                     source: map_from_arrow_opt!(tx_source, Address::new, i),
                     block_hash: map_from_arrow!(tx_block_hash, Bytes32::new, i),
-                    block_number: map_from_arrow!(tx_block_number, Index, i),
+                    block_number: map_from_arrow!(tx_block_number, BlockNumber::from, i),
                     transaction_index: map_from_arrow!(tx_transaction_index, Index, i),
                     gas_price: map_from_arrow_opt!(tx_gas_price, Bytes::new, i),
                     hash: map_from_arrow!(tx_hash, Bytes32::new, i),
                     status: map_from_arrow_opt!(tx_status, Index, i),
+                    contract_address: None,
+                    gas_used: None,
+                    cumulative_gas_used: None,
+                    effective_gas_price: None,
+                    logs_bloom: None,
+                    max_fee_per_blob_gas: None,
+                    blob_versioned_hashes: None,
+                    access_list: None,
+                    authorization_list: None,
                 });
             }
         }
@@ -180,7 +202,7 @@ impl ParquetSource for Ver0_1_0 {
                 logs.push(Log {
                     address: map_from_arrow!(log_address, Address::new, i),
                     block_hash: map_from_arrow!(log_block_hash, Bytes32::new, i),
-                    block_number: map_from_arrow!(log_block_number, Index, i),
+                    block_number: map_from_arrow!(log_block_number, BlockNumber::from, i),
                     data: map_from_arrow!(log_data, Bytes::new, i),
                     log_index: map_from_arrow!(log_log_index, Index, i),
                     removed: log_removed.get(i),
@@ -214,6 +236,101 @@ impl ParquetSource for Ver0_1_0 {
         logs
     }
 
+    fn read_traces(&self, columns: Columns) -> Vec<Trace> {
+        let mut traces = Vec::new();
+
+        for columns in columns {
+            #[rustfmt::skip]
+            define_cols!(
+                columns,
+                trace_block_number, UInt32Array,
+                trace_transaction_index, UInt32Array,
+                trace_transaction_hash, BinaryArray,
+                trace_trace_index, UInt32Array,
+                trace_trace_address, ListArray,
+                trace_subtraces, UInt32Array,
+                trace_call_type, Utf8Array,
+                trace_source, BinaryArray,
+                trace_dest, BinaryArray,
+                trace_value, BinaryArray,
+                trace_gas, BinaryArray,
+                trace_gas_used, BinaryArray,
+                trace_input, BinaryArray,
+                trace_output, BinaryArray,
+                trace_error, Utf8Array
+            );
+
+            let len = trace_block_number.len();
+
+            for i in 0..len {
+                traces.push(Trace {
+                    block_number: map_from_arrow!(trace_block_number, BlockNumber::from, i),
+                    transaction_index: map_from_arrow_opt!(trace_transaction_index, Index, i),
+                    transaction_hash: map_from_arrow_opt!(trace_transaction_hash, Bytes32::new, i),
+                    trace_index: map_from_arrow!(trace_trace_index, Index, i),
+                    trace_address: {
+                        let trace_address = trace_trace_address.value(i);
+                        let trace_address = trace_address
+                            .as_any()
+                            .downcast_ref::<UInt32Array>()
+                            .unwrap();
+                        trace_address.values().to_vec()
+                    },
+                    subtraces: map_from_arrow!(trace_subtraces, Index, i),
+                    call_type: map_from_arrow!(trace_call_type, str::to_owned, i),
+                    source: map_from_arrow_opt!(trace_source, Address::new, i),
+                    dest: map_from_arrow_opt!(trace_dest, Address::new, i),
+                    value: map_from_arrow_opt!(trace_value, Bytes::new, i),
+                    gas: map_from_arrow_opt!(trace_gas, Bytes::new, i),
+                    gas_used: map_from_arrow_opt!(trace_gas_used, Bytes::new, i),
+                    input: map_from_arrow_opt!(trace_input, Bytes::new, i),
+                    output: map_from_arrow_opt!(trace_output, Bytes::new, i),
+                    error: map_from_arrow_opt!(trace_error, str::to_owned, i),
+                });
+            }
+        }
+
+        traces
+    }
+
+    fn read_state_diffs(&self, columns: Columns) -> Vec<StateDiff> {
+        let mut state_diffs = Vec::new();
+
+        for columns in columns {
+            #[rustfmt::skip]
+            define_cols!(
+                columns,
+                diff_block_number, UInt32Array,
+                diff_transaction_index, UInt32Array,
+                diff_transaction_hash, BinaryArray,
+                diff_diff_index, UInt32Array,
+                diff_address, BinaryArray,
+                diff_kind, Utf8Array,
+                diff_key, BinaryArray,
+                diff_prev, BinaryArray,
+                diff_next, BinaryArray
+            );
+
+            let len = diff_block_number.len();
+
+            for i in 0..len {
+                state_diffs.push(StateDiff {
+                    block_number: map_from_arrow!(diff_block_number, BlockNumber::from, i),
+                    transaction_index: map_from_arrow!(diff_transaction_index, Index, i),
+                    transaction_hash: map_from_arrow!(diff_transaction_hash, Bytes32::new, i),
+                    diff_index: map_from_arrow!(diff_diff_index, Index, i),
+                    address: map_from_arrow!(diff_address, Address::new, i),
+                    kind: map_from_arrow!(diff_kind, str::to_owned, i),
+                    key: map_from_arrow_opt!(diff_key, Bytes32::new, i),
+                    prev: map_from_arrow_opt!(diff_prev, Bytes::new, i),
+                    next: map_from_arrow_opt!(diff_next, Bytes::new, i),
+                });
+            }
+        }
+
+        state_diffs
+    }
+
     fn block_fields(&self) -> Vec<Field> {
         vec![
             Field::new("parent_hash", DataType::Binary, false),
@@ -279,4 +396,42 @@ impl ParquetSource for Ver0_1_0 {
             Field::new("transaction_index", DataType::UInt32, false),
         ]
     }
+
+    fn trace_fields(&self) -> Option<Vec<Field>> {
+        Some(vec![
+            Field::new("block_number", DataType::UInt32, false),
+            Field::new("transaction_index", DataType::UInt32, true),
+            Field::new("transaction_hash", DataType::Binary, true),
+            Field::new("trace_index", DataType::UInt32, false),
+            Field::new(
+                "trace_address",
+                DataType::List(Box::new(Field::new("item", DataType::UInt32, true))),
+                false,
+            ),
+            Field::new("subtraces", DataType::UInt32, false),
+            Field::new("call_type", DataType::Utf8, false),
+            Field::new("source", DataType::Binary, true),
+            Field::new("dest", DataType::Binary, true),
+            Field::new("value", DataType::Binary, true),
+            Field::new("gas", DataType::Binary, true),
+            Field::new("gas_used", DataType::Binary, true),
+            Field::new("input", DataType::Binary, true),
+            Field::new("output", DataType::Binary, true),
+            Field::new("error", DataType::Utf8, true),
+        ])
+    }
+
+    fn state_diff_fields(&self) -> Option<Vec<Field>> {
+        Some(vec![
+            Field::new("block_number", DataType::UInt32, false),
+            Field::new("transaction_index", DataType::UInt32, false),
+            Field::new("transaction_hash", DataType::Binary, false),
+            Field::new("diff_index", DataType::UInt32, false),
+            Field::new("address", DataType::Binary, false),
+            Field::new("kind", DataType::Utf8, false),
+            Field::new("key", DataType::Binary, true),
+            Field::new("prev", DataType::Binary, true),
+            Field::new("next", DataType::Binary, true),
+        ])
+    }
 }
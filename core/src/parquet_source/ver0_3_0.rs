@@ -0,0 +1,557 @@
+use super::util::{
+    access_list_from_arrow, authorization_list_from_arrow, define_cols, map_from_arrow,
+    map_from_arrow_opt,
+};
+use super::{Columns, ParquetSource};
+use crate::deserialize::{
+    Address, BigUnsigned, BlockNumber, BloomFilterBytes, Bytes, Bytes32, ChainId, Index,
+};
+use crate::types::{Block, Log, StateDiff, Trace, Transaction, Withdrawal};
+use arrayvec::ArrayVec;
+use arrow2::array::{self, Array, BooleanArray, UInt32Array, UInt64Array};
+use arrow2::compute::concatenate::concatenate;
+use arrow2::datatypes::{DataType, Field};
+use std::collections::BTreeMap;
+
+type BinaryArray = array::BinaryArray<i32>;
+type ListArray = array::ListArray<i32>;
+type Utf8Array = array::Utf8Array<i32>;
+
+/// Format that stores block numbers and chain ids as UInt64 and `v` as a binary quantity.
+/// It is otherwise the same as 0.2.0.
+pub struct Ver0_3_0;
+
+impl ParquetSource for Ver0_3_0 {
+    fn read_blocks(&self, columns: Columns) -> BTreeMap<u64, Block> {
+        let mut blocks = BTreeMap::new();
+
+        for columns in columns {
+            #[rustfmt::skip]
+            define_cols!(
+                columns,
+                block_parent_hash, BinaryArray,
+                block_sha3_uncles, BinaryArray,
+                block_miner, BinaryArray,
+                block_state_root, BinaryArray,
+                block_transactions_root, BinaryArray,
+                block_receipts_root, BinaryArray,
+                block_logs_bloom, BinaryArray,
+                block_difficulty, BinaryArray,
+                block_number, UInt64Array,
+                block_gas_limit, BinaryArray,
+                block_gas_used, BinaryArray,
+                block_timestamp, BinaryArray,
+                block_extra_data, BinaryArray,
+                block_mix_hash, BinaryArray,
+                block_nonce, UInt64Array,
+                block_total_difficulty, BinaryArray,
+                block_base_fee_per_gas, BinaryArray,
+                block_size, BinaryArray,
+                block_hash, BinaryArray,
+                block_blob_gas_used, BinaryArray,
+                block_excess_blob_gas, BinaryArray,
+                block_parent_beacon_block_root, BinaryArray,
+                block_withdrawals_root, BinaryArray
+            );
+
+            let len = block_number.len();
+
+            for i in 0..len {
+                let number = map_from_arrow!(block_number, BlockNumber, i);
+
+                blocks.insert(
+                    number.0,
+                    Block {
+                        parent_hash: map_from_arrow!(block_parent_hash, Bytes32::new, i),
+                        sha3_uncles: map_from_arrow!(block_sha3_uncles, Bytes32::new, i),
+                        miner: map_from_arrow!(block_miner, Address::new, i),
+                        state_root: map_from_arrow!(block_state_root, Bytes32::new, i),
+                        transactions_root: map_from_arrow!(
+                            block_transactions_root,
+                            Bytes32::new,
+                            i
+                        ),
+                        receipts_root: map_from_arrow!(block_receipts_root, Bytes32::new, i),
+                        logs_bloom: map_from_arrow!(block_logs_bloom, BloomFilterBytes::new, i),
+                        difficulty: map_from_arrow_opt!(block_difficulty, Bytes::new, i),
+                        number,
+                        gas_limit: map_from_arrow!(block_gas_limit, Bytes::new, i),
+                        gas_used: map_from_arrow!(block_gas_used, Bytes::new, i),
+                        timestamp: map_from_arrow!(block_timestamp, Bytes::new, i),
+                        extra_data: map_from_arrow!(block_extra_data, Bytes::new, i),
+                        mix_hash: map_from_arrow_opt!(block_mix_hash, Bytes32::new, i),
+                        nonce: map_from_arrow_opt!(block_nonce, BigUnsigned, i),
+                        total_difficulty: map_from_arrow_opt!(
+                            block_total_difficulty,
+                            Bytes::new,
+                            i
+                        ),
+                        base_fee_per_gas: map_from_arrow_opt!(
+                            block_base_fee_per_gas,
+                            Bytes::new,
+                            i
+                        ),
+                        size: map_from_arrow!(block_size, Bytes::new, i),
+                        hash: map_from_arrow_opt!(block_hash, Bytes32::new, i),
+                        transactions: Vec::new(),
+                        traces: Vec::new(),
+                        state_diffs: Vec::new(),
+                        blob_gas_used: map_from_arrow_opt!(block_blob_gas_used, Bytes::new, i),
+                        excess_blob_gas: map_from_arrow_opt!(block_excess_blob_gas, Bytes::new, i),
+                        parent_beacon_block_root: map_from_arrow_opt!(
+                            block_parent_beacon_block_root,
+                            Bytes32::new,
+                            i
+                        ),
+                        withdrawals_root: map_from_arrow_opt!(
+                            block_withdrawals_root,
+                            Bytes32::new,
+                            i
+                        ),
+                        withdrawals: Vec::new(),
+                    },
+                );
+            }
+        }
+
+        blocks
+    }
+
+    fn read_txs(&self, columns: Columns) -> Vec<Transaction> {
+        let mut txs = Vec::new();
+
+        for columns in columns {
+            #[rustfmt::skip]
+            define_cols!(
+                columns,
+                tx_kind, UInt32Array,
+                tx_nonce, UInt64Array,
+                tx_dest, BinaryArray,
+                tx_gas, BinaryArray,
+                tx_value, BinaryArray,
+                tx_input, BinaryArray,
+                tx_max_priority_fee_per_gas, BinaryArray,
+                tx_max_fee_per_gas, BinaryArray,
+                tx_y_parity, UInt32Array,
+                tx_chain_id, UInt64Array,
+                tx_v, BinaryArray,
+                tx_r, BinaryArray,
+                tx_s, BinaryArray,
+                tx_source, BinaryArray,
+                tx_block_hash, BinaryArray,
+                tx_block_number, UInt64Array,
+                tx_transaction_index, UInt32Array,
+                tx_gas_price, BinaryArray,
+                tx_hash, BinaryArray,
+                tx_status, UInt32Array,
+                tx_contract_address, BinaryArray,
+                tx_gas_used, BinaryArray,
+                tx_cumulative_gas_used, BinaryArray,
+                tx_effective_gas_price, BinaryArray,
+                tx_logs_bloom, BinaryArray,
+                tx_max_fee_per_blob_gas, BinaryArray,
+                tx_blob_versioned_hashes, ListArray,
+                tx_access_list, ListArray,
+                tx_authorization_list, ListArray
+            );
+
+            let len = tx_block_number.len();
+
+            for i in 0..len {
+                txs.push(Transaction {
+                    kind: map_from_arrow_opt!(tx_kind, Index, i),
+                    nonce: map_from_arrow!(tx_nonce, BigUnsigned, i),
+                    dest: map_from_arrow_opt!(tx_dest, Address::new, i),
+                    gas: map_from_arrow!(tx_gas, Bytes::new, i),
+                    value: map_from_arrow!(tx_value, Bytes::new, i),
+                    input: map_from_arrow!(tx_input, Bytes::new, i),
+                    max_priority_fee_per_gas: map_from_arrow_opt!(
+                        tx_max_priority_fee_per_gas,
+                        Bytes::new,
+                        i
+                    ),
+                    max_fee_per_gas: map_from_arrow_opt!(tx_max_fee_per_gas, Bytes::new, i),
+                    y_parity: map_from_arrow_opt!(tx_y_parity, Index, i),
+                    chain_id: map_from_arrow_opt!(tx_chain_id, ChainId, i),
+                    v: map_from_arrow_opt!(tx_v, Bytes::new, i),
+                    r: map_from_arrow_opt!(tx_r, Bytes::new, i),
+                    s: map_from_arrow_opt!(tx_s, Bytes::new, i),
+                    source: map_from_arrow_opt!(tx_source, Address::new, i),
+                    block_hash: map_from_arrow!(tx_block_hash, Bytes32::new, i),
+                    block_number: map_from_arrow!(tx_block_number, BlockNumber, i),
+                    transaction_index: map_from_arrow!(tx_transaction_index, Index, i),
+                    gas_price: map_from_arrow_opt!(tx_gas_price, Bytes::new, i),
+                    hash: map_from_arrow!(tx_hash, Bytes32::new, i),
+                    status: map_from_arrow_opt!(tx_status, Index, i),
+                    contract_address: map_from_arrow_opt!(tx_contract_address, Address::new, i),
+                    gas_used: map_from_arrow_opt!(tx_gas_used, Bytes::new, i),
+                    cumulative_gas_used: map_from_arrow_opt!(tx_cumulative_gas_used, Bytes::new, i),
+                    effective_gas_price: map_from_arrow_opt!(tx_effective_gas_price, Bytes::new, i),
+                    logs_bloom: map_from_arrow_opt!(tx_logs_bloom, BloomFilterBytes::new, i),
+                    max_fee_per_blob_gas: map_from_arrow_opt!(
+                        tx_max_fee_per_blob_gas,
+                        Bytes::new,
+                        i
+                    ),
+                    blob_versioned_hashes: tx_blob_versioned_hashes.is_valid(i).then(|| {
+                        let hashes = tx_blob_versioned_hashes.value(i);
+                        let hashes = hashes.as_any().downcast_ref::<BinaryArray>().unwrap();
+                        hashes.values_iter().map(Bytes32::new).collect()
+                    }),
+                    access_list: access_list_from_arrow(tx_access_list, i),
+                    authorization_list: authorization_list_from_arrow(tx_authorization_list, i),
+                });
+            }
+        }
+
+        txs
+    }
+
+    fn read_logs(&self, columns: Columns) -> Vec<Log> {
+        let mut logs = Vec::new();
+
+        for columns in columns {
+            #[rustfmt::skip]
+            define_cols!(
+                columns,
+                log_address, BinaryArray,
+                log_block_hash, BinaryArray,
+                log_block_number, UInt64Array,
+                log_data, BinaryArray,
+                log_log_index, UInt32Array,
+                log_removed, BooleanArray,
+                log_topic0, BinaryArray,
+                log_topic1, BinaryArray,
+                log_topic2, BinaryArray,
+                log_topic3, BinaryArray,
+                log_transaction_hash, BinaryArray,
+                log_transaction_index, UInt32Array
+            );
+
+            let len = log_block_number.len();
+
+            for i in 0..len {
+                logs.push(Log {
+                    address: map_from_arrow!(log_address, Address::new, i),
+                    block_hash: map_from_arrow!(log_block_hash, Bytes32::new, i),
+                    block_number: map_from_arrow!(log_block_number, BlockNumber, i),
+                    data: map_from_arrow!(log_data, Bytes::new, i),
+                    log_index: map_from_arrow!(log_log_index, Index, i),
+                    removed: log_removed.get(i),
+                    topics: {
+                        let mut topics = ArrayVec::new();
+
+                        if let Some(topic) = log_topic0.get(i) {
+                            topics.push(Bytes32::new(topic));
+                        }
+
+                        if let Some(topic) = log_topic1.get(i) {
+                            topics.push(Bytes32::new(topic));
+                        }
+
+                        if let Some(topic) = log_topic2.get(i) {
+                            topics.push(Bytes32::new(topic));
+                        }
+
+                        if let Some(topic) = log_topic3.get(i) {
+                            topics.push(Bytes32::new(topic));
+                        }
+
+                        topics
+                    },
+                    transaction_hash: map_from_arrow!(log_transaction_hash, Bytes32::new, i),
+                    transaction_index: map_from_arrow!(log_transaction_index, Index, i),
+                });
+            }
+        }
+
+        logs
+    }
+
+    fn read_traces(&self, columns: Columns) -> Vec<Trace> {
+        let mut traces = Vec::new();
+
+        for columns in columns {
+            #[rustfmt::skip]
+            define_cols!(
+                columns,
+                trace_block_number, UInt64Array,
+                trace_transaction_index, UInt32Array,
+                trace_transaction_hash, BinaryArray,
+                trace_trace_index, UInt32Array,
+                trace_trace_address, ListArray,
+                trace_subtraces, UInt32Array,
+                trace_call_type, Utf8Array,
+                trace_source, BinaryArray,
+                trace_dest, BinaryArray,
+                trace_value, BinaryArray,
+                trace_gas, BinaryArray,
+                trace_gas_used, BinaryArray,
+                trace_input, BinaryArray,
+                trace_output, BinaryArray,
+                trace_error, Utf8Array
+            );
+
+            let len = trace_block_number.len();
+
+            for i in 0..len {
+                traces.push(Trace {
+                    block_number: map_from_arrow!(trace_block_number, BlockNumber, i),
+                    transaction_index: map_from_arrow_opt!(trace_transaction_index, Index, i),
+                    transaction_hash: map_from_arrow_opt!(trace_transaction_hash, Bytes32::new, i),
+                    trace_index: map_from_arrow!(trace_trace_index, Index, i),
+                    trace_address: {
+                        let trace_address = trace_trace_address.value(i);
+                        let trace_address = trace_address
+                            .as_any()
+                            .downcast_ref::<UInt32Array>()
+                            .unwrap();
+                        trace_address.values().to_vec()
+                    },
+                    subtraces: map_from_arrow!(trace_subtraces, Index, i),
+                    call_type: map_from_arrow!(trace_call_type, str::to_owned, i),
+                    source: map_from_arrow_opt!(trace_source, Address::new, i),
+                    dest: map_from_arrow_opt!(trace_dest, Address::new, i),
+                    value: map_from_arrow_opt!(trace_value, Bytes::new, i),
+                    gas: map_from_arrow_opt!(trace_gas, Bytes::new, i),
+                    gas_used: map_from_arrow_opt!(trace_gas_used, Bytes::new, i),
+                    input: map_from_arrow_opt!(trace_input, Bytes::new, i),
+                    output: map_from_arrow_opt!(trace_output, Bytes::new, i),
+                    error: map_from_arrow_opt!(trace_error, str::to_owned, i),
+                });
+            }
+        }
+
+        traces
+    }
+
+    fn read_state_diffs(&self, columns: Columns) -> Vec<StateDiff> {
+        let mut state_diffs = Vec::new();
+
+        for columns in columns {
+            #[rustfmt::skip]
+            define_cols!(
+                columns,
+                diff_block_number, UInt64Array,
+                diff_transaction_index, UInt32Array,
+                diff_transaction_hash, BinaryArray,
+                diff_diff_index, UInt32Array,
+                diff_address, BinaryArray,
+                diff_kind, Utf8Array,
+                diff_key, BinaryArray,
+                diff_prev, BinaryArray,
+                diff_next, BinaryArray
+            );
+
+            let len = diff_block_number.len();
+
+            for i in 0..len {
+                state_diffs.push(StateDiff {
+                    block_number: map_from_arrow!(diff_block_number, BlockNumber, i),
+                    transaction_index: map_from_arrow!(diff_transaction_index, Index, i),
+                    transaction_hash: map_from_arrow!(diff_transaction_hash, Bytes32::new, i),
+                    diff_index: map_from_arrow!(diff_diff_index, Index, i),
+                    address: map_from_arrow!(diff_address, Address::new, i),
+                    kind: map_from_arrow!(diff_kind, str::to_owned, i),
+                    key: map_from_arrow_opt!(diff_key, Bytes32::new, i),
+                    prev: map_from_arrow_opt!(diff_prev, Bytes::new, i),
+                    next: map_from_arrow_opt!(diff_next, Bytes::new, i),
+                });
+            }
+        }
+
+        state_diffs
+    }
+
+    fn read_withdrawals(&self, columns: Columns) -> Vec<Withdrawal> {
+        let mut withdrawals = Vec::new();
+
+        for columns in columns {
+            #[rustfmt::skip]
+            define_cols!(
+                columns,
+                withdrawal_block_number, UInt64Array,
+                withdrawal_index, UInt32Array,
+                withdrawal_validator_index, UInt32Array,
+                withdrawal_address, BinaryArray,
+                withdrawal_amount, BinaryArray
+            );
+
+            let len = withdrawal_block_number.len();
+
+            for i in 0..len {
+                withdrawals.push(Withdrawal {
+                    block_number: map_from_arrow!(withdrawal_block_number, BlockNumber, i),
+                    index: map_from_arrow!(withdrawal_index, Index, i),
+                    validator_index: map_from_arrow!(withdrawal_validator_index, Index, i),
+                    address: map_from_arrow!(withdrawal_address, Address::new, i),
+                    amount: map_from_arrow!(withdrawal_amount, Bytes::new, i),
+                });
+            }
+        }
+
+        withdrawals
+    }
+
+    fn block_fields(&self) -> Vec<Field> {
+        vec![
+            Field::new("parent_hash", DataType::Binary, false),
+            Field::new("sha3_uncles", DataType::Binary, false),
+            Field::new("miner", DataType::Binary, false),
+            Field::new("state_root", DataType::Binary, false),
+            Field::new("transactions_root", DataType::Binary, false),
+            Field::new("receipts_root", DataType::Binary, false),
+            Field::new("logs_bloom", DataType::Binary, false),
+            Field::new("difficulty", DataType::Binary, true),
+            Field::new("number", DataType::UInt64, false),
+            Field::new("gas_limit", DataType::Binary, false),
+            Field::new("gas_used", DataType::Binary, false),
+            Field::new("timestamp", DataType::Binary, false),
+            Field::new("extra_data", DataType::Binary, false),
+            Field::new("mix_hash", DataType::Binary, true),
+            Field::new("nonce", DataType::UInt64, true),
+            Field::new("total_difficulty", DataType::Binary, true),
+            Field::new("base_fee_per_gas", DataType::Binary, true),
+            Field::new("size", DataType::Binary, false),
+            Field::new("hash", DataType::Binary, true),
+            Field::new("blob_gas_used", DataType::Binary, true),
+            Field::new("excess_blob_gas", DataType::Binary, true),
+            Field::new("parent_beacon_block_root", DataType::Binary, true),
+            Field::new("withdrawals_root", DataType::Binary, true),
+        ]
+    }
+
+    fn tx_fields(&self) -> Vec<Field> {
+        vec![
+            Field::new("kind", DataType::UInt32, true),
+            Field::new("nonce", DataType::UInt64, false),
+            Field::new("dest", DataType::Binary, true),
+            Field::new("gas", DataType::Binary, false),
+            Field::new("value", DataType::Binary, false),
+            Field::new("input", DataType::Binary, false),
+            Field::new("max_priority_fee_per_gas", DataType::Binary, true),
+            Field::new("max_fee_per_gas", DataType::Binary, true),
+            Field::new("y_parity", DataType::UInt32, true),
+            Field::new("chain_id", DataType::UInt64, true),
+            Field::new("v", DataType::Binary, true),
+            Field::new("r", DataType::Binary, false),
+            Field::new("s", DataType::Binary, false),
+            Field::new("source", DataType::Binary, true),
+            Field::new("block_hash", DataType::Binary, false),
+            Field::new("block_number", DataType::UInt64, false),
+            Field::new("transaction_index", DataType::UInt32, false),
+            Field::new("gas_price", DataType::Binary, true),
+            Field::new("hash", DataType::Binary, false),
+            Field::new("status", DataType::UInt32, true),
+            Field::new("contract_address", DataType::Binary, true),
+            Field::new("gas_used", DataType::Binary, true),
+            Field::new("cumulative_gas_used", DataType::Binary, true),
+            Field::new("effective_gas_price", DataType::Binary, true),
+            Field::new("logs_bloom", DataType::Binary, true),
+            Field::new("max_fee_per_blob_gas", DataType::Binary, true),
+            Field::new(
+                "blob_versioned_hashes",
+                DataType::List(Box::new(Field::new("item", DataType::Binary, true))),
+                true,
+            ),
+            Field::new(
+                "access_list",
+                DataType::List(Box::new(Field::new(
+                    "item",
+                    DataType::Struct(vec![
+                        Field::new("address", DataType::Binary, false),
+                        Field::new(
+                            "storage_keys",
+                            DataType::List(Box::new(Field::new("item", DataType::Binary, true))),
+                            false,
+                        ),
+                    ]),
+                    true,
+                ))),
+                true,
+            ),
+            Field::new(
+                "authorization_list",
+                DataType::List(Box::new(Field::new(
+                    "item",
+                    DataType::Struct(vec![
+                        Field::new("chain_id", DataType::Binary, false),
+                        Field::new("address", DataType::Binary, false),
+                        Field::new("nonce", DataType::Binary, false),
+                        Field::new("y_parity", DataType::UInt32, false),
+                        Field::new("r", DataType::Binary, false),
+                        Field::new("s", DataType::Binary, false),
+                        Field::new("authority", DataType::Binary, true),
+                    ]),
+                    true,
+                ))),
+                true,
+            ),
+        ]
+    }
+
+    fn log_fields(&self) -> Vec<Field> {
+        vec![
+            Field::new("address", DataType::Binary, false),
+            Field::new("block_hash", DataType::Binary, false),
+            Field::new("block_number", DataType::UInt64, false),
+            Field::new("data", DataType::Binary, false),
+            Field::new("log_index", DataType::UInt32, false),
+            Field::new("removed", DataType::Boolean, false),
+            Field::new("topic0", DataType::Binary, true),
+            Field::new("topic1", DataType::Binary, true),
+            Field::new("topic2", DataType::Binary, true),
+            Field::new("topic3", DataType::Binary, true),
+            Field::new("transaction_hash", DataType::Binary, false),
+            Field::new("transaction_index", DataType::UInt32, false),
+        ]
+    }
+
+    fn trace_fields(&self) -> Option<Vec<Field>> {
+        Some(vec![
+            Field::new("block_number", DataType::UInt64, false),
+            Field::new("transaction_index", DataType::UInt32, true),
+            Field::new("transaction_hash", DataType::Binary, true),
+            Field::new("trace_index", DataType::UInt32, false),
+            Field::new(
+                "trace_address",
+                DataType::List(Box::new(Field::new("item", DataType::UInt32, true))),
+                false,
+            ),
+            Field::new("subtraces", DataType::UInt32, false),
+            Field::new("call_type", DataType::Utf8, false),
+            Field::new("source", DataType::Binary, true),
+            Field::new("dest", DataType::Binary, true),
+            Field::new("value", DataType::Binary, true),
+            Field::new("gas", DataType::Binary, true),
+            Field::new("gas_used", DataType::Binary, true),
+            Field::new("input", DataType::Binary, true),
+            Field::new("output", DataType::Binary, true),
+            Field::new("error", DataType::Utf8, true),
+        ])
+    }
+
+    fn state_diff_fields(&self) -> Option<Vec<Field>> {
+        Some(vec![
+            Field::new("block_number", DataType::UInt64, false),
+            Field::new("transaction_index", DataType::UInt32, false),
+            Field::new("transaction_hash", DataType::Binary, false),
+            Field::new("diff_index", DataType::UInt32, false),
+            Field::new("address", DataType::Binary, false),
+            Field::new("kind", DataType::Utf8, false),
+            Field::new("key", DataType::Binary, true),
+            Field::new("prev", DataType::Binary, true),
+            Field::new("next", DataType::Binary, true),
+        ])
+    }
+
+    fn withdrawal_fields(&self) -> Option<Vec<Field>> {
+        Some(vec![
+            Field::new("block_number", DataType::UInt64, false),
+            Field::new("index", DataType::UInt32, false),
+            Field::new("validator_index", DataType::UInt32, false),
+            Field::new("address", DataType::Binary, false),
+            Field::new("amount", DataType::Binary, false),
+        ])
+    }
+}
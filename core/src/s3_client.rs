@@ -73,15 +73,13 @@ impl S3Client {
     pub async fn stream_batches(
         self: Arc<Self>,
         ingest_metrics: Arc<IngestMetrics>,
-        start_block: u32,
+        start_block: u64,
         s3_src_bucket: &str,
         format_version: &str,
     ) -> Result<BatchStream> {
         let source = parquet_source::get(FormatVersion::from_str(format_version)?);
-        let dir_names = Self::get_dir_names_from_list(
-            start_block,
-            &self.clone().get_list(s3_src_bucket.into()).await?,
-        );
+        let s3_names = self.clone().get_list(s3_src_bucket.into()).await?;
+        let dir_names = Self::get_dir_names_from_list(start_block, &s3_names);
 
         let batch_stream = self.stream_batches_impl(
             ingest_metrics,
@@ -89,6 +87,7 @@ impl S3Client {
             s3_src_bucket,
             source,
             dir_names,
+            s3_names,
         );
 
         Ok(Box::pin(batch_stream))
@@ -97,10 +96,11 @@ impl S3Client {
     fn stream_batches_impl(
         self: Arc<Self>,
         ingest_metrics: Arc<IngestMetrics>,
-        start_block: u32,
+        start_block: u64,
         s3_src_bucket: &str,
         source: Box<dyn ParquetSource>,
         dir_names: Vec<DirName>,
+        s3_names: BTreeSet<String>,
     ) -> impl Stream<Item = Result<(Vec<BlockRange>, Vec<Vec<Block>>, Vec<Vec<Log>>)>> {
         let num_files = dir_names.len();
 
@@ -115,7 +115,7 @@ impl S3Client {
             for (i, dir_name) in dir_names.into_iter().enumerate() {
                 // s3 files have a gap in them
                 if dir_name.range.from > block_num {
-                    fn block_not_found_err(block_num: u32) -> Result<()> {
+                    fn block_not_found_err(block_num: u64) -> Result<()> {
                         Err(Error::BlockNotFoundInS3(block_num))
                     }
                     // This is a function call to make the macro work
@@ -135,11 +135,30 @@ impl S3Client {
                     }
                 };
 
+                // optional files are only written if the folder has any items of that kind
+                let read_opt_fut = |kind, fields: Option<Vec<Field>>| {
+                    let fields = fields.filter(|_| s3_names.contains(&format!("{dir_name}/{kind}.parquet")));
+                    let fut = fields.map(|fields| read_fut(kind, fields));
+
+                    async move {
+                        match fut {
+                            Some(fut) => fut.await.map(Some),
+                            None => Ok(None),
+                        }
+                    }
+                };
+
                 let block_fut = read_fut("block", source.block_fields()).map_ok(|columns| source.read_blocks(columns));
                 let tx_fut = read_fut("tx", source.tx_fields()).map_ok(|columns| source.read_txs(columns));
                 let log_fut = read_fut("log", source.log_fields()).map_ok(|columns| source.read_logs(columns));
+                let trace_fut = read_opt_fut("trace", source.trace_fields())
+                    .map_ok(|columns| columns.map(|columns| source.read_traces(columns)).unwrap_or_default());
+                let state_diff_fut = read_opt_fut("state_diff", source.state_diff_fields())
+                    .map_ok(|columns| columns.map(|columns| source.read_state_diffs(columns)).unwrap_or_default());
+                let withdrawal_fut = read_opt_fut("withdrawal", source.withdrawal_fields())
+                    .map_ok(|columns| columns.map(|columns| source.read_withdrawals(columns)).unwrap_or_default());
 
-                let (mut blocks, txs, logs) = futures::future::try_join3(block_fut, tx_fut, log_fut).await?;
+                let (mut blocks, txs, logs, traces, state_diffs, withdrawals) = futures::try_join!(block_fut, tx_fut, log_fut, trace_fut, state_diff_fut, withdrawal_fut)?;
 
                 let block_range = BlockRange {
                     from: *blocks.first_key_value().unwrap().0,
@@ -150,6 +169,18 @@ impl S3Client {
                     blocks.get_mut(&tx.block_number.0).unwrap().transactions.push(tx);
                 }
 
+                for trace in traces {
+                    blocks.get_mut(&trace.block_number.0).unwrap().traces.push(trace);
+                }
+
+                for state_diff in state_diffs {
+                    blocks.get_mut(&state_diff.block_number.0).unwrap().state_diffs.push(state_diff);
+                }
+
+                for withdrawal in withdrawals {
+                    blocks.get_mut(&withdrawal.block_number.0).unwrap().withdrawals.push(withdrawal);
+                }
+
                 let blocks = blocks.into_values().collect::<Vec<_>>();
 
                 block_num = dir_name.range.to;
@@ -315,7 +346,7 @@ impl S3Client {
                 continue;
             }
 
-            for kind in ["block", "tx", "log"] {
+            for kind in ["block", "tx", "log", "trace", "state_diff", "withdrawal"] {
                 let s3_path = format!("{dir_name}/{kind}.parquet");
                 if s3_names.contains(s3_path.as_str()) {
                     continue;
@@ -325,6 +356,12 @@ impl S3Client {
                 path.push(dir_name.to_string());
                 path.push(format!("{kind}.parquet"));
 
+                // folders written before trace, state diff and withdrawal ingestion
+                // was added don't have these files
+                if matches!(kind, "trace" | "state_diff" | "withdrawal") && !path.exists() {
+                    continue;
+                }
+
                 let s3_client = self.clone();
 
                 let fut = async move { s3_client.put_file(path.into(), s3_path.into()).await };
@@ -336,11 +373,14 @@ impl S3Client {
         Ok(futs)
     }
 
-    fn get_dir_names_from_list(start_block: u32, list: &BTreeSet<String>) -> Vec<DirName> {
-        let mut dir_names: BTreeMap<u32, (u8, DirName)> = BTreeMap::new();
+    fn get_dir_names_from_list(start_block: u64, list: &BTreeSet<String>) -> Vec<DirName> {
+        let mut dir_names: BTreeMap<u64, (u8, DirName)> = BTreeMap::new();
 
         for s3_name in list.iter() {
-            let (dir_name, _) = parse_s3_name(s3_name);
+            let (dir_name, file_name) = parse_s3_name(s3_name);
+            if !["block.parquet", "tx.parquet", "log.parquet"].contains(&file_name.as_str()) {
+                continue;
+            }
             dir_names
                 .entry(dir_name.range.from)
                 .or_insert((0, dir_name))
@@ -1,9 +1,13 @@
-use crate::deserialize::{Address, BigUnsigned, BloomFilterBytes, Bytes, Bytes32, Index};
+use crate::deserialize::{
+    deserialize_opt_quantity, Address, BigUnsigned, BlockNumber, BloomFilterBytes, Bytes, Bytes32,
+    ChainId, Index,
+};
 use crate::{Error, Result};
 use arrayvec::ArrayVec;
 use core::str::FromStr;
 use serde::{Deserialize, Serialize};
 use std::cmp;
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -16,7 +20,7 @@ pub struct Block {
     pub receipts_root: Bytes32,
     pub logs_bloom: BloomFilterBytes,
     pub difficulty: Option<Bytes>,
-    pub number: Index,
+    pub number: BlockNumber,
     pub gas_limit: Bytes,
     pub gas_used: Bytes,
     pub timestamp: Bytes,
@@ -28,6 +32,24 @@ pub struct Block {
     pub size: Bytes,
     pub hash: Option<Bytes32>,
     pub transactions: Vec<Transaction>,
+    /// Filled in by the eth client if trace fetching is enabled
+    #[serde(default)]
+    pub traces: Vec<Trace>,
+    /// Filled in by the eth client if state diff fetching is enabled
+    #[serde(default)]
+    pub state_diffs: Vec<StateDiff>,
+    // the fields below only exist after the Dencun upgrade
+    #[serde(default)]
+    pub blob_gas_used: Option<Bytes>,
+    #[serde(default)]
+    pub excess_blob_gas: Option<Bytes>,
+    #[serde(default)]
+    pub parent_beacon_block_root: Option<Bytes32>,
+    // the fields below only exist after the Shanghai upgrade
+    #[serde(default)]
+    pub withdrawals_root: Option<Bytes32>,
+    #[serde(default)]
+    pub withdrawals: Vec<Withdrawal>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -44,27 +66,78 @@ pub struct Transaction {
     pub max_priority_fee_per_gas: Option<Bytes>,
     pub max_fee_per_gas: Option<Bytes>,
     pub y_parity: Option<Index>,
-    pub chain_id: Option<Index>,
-    pub v: Option<BigUnsigned>,
+    pub chain_id: Option<ChainId>,
+    #[serde(default, deserialize_with = "deserialize_opt_quantity")]
+    pub v: Option<Bytes>,
     pub r: Option<Bytes>,
     pub s: Option<Bytes>,
     #[serde(rename = "from")]
     pub source: Option<Address>,
     pub block_hash: Bytes32,
-    pub block_number: Index,
+    pub block_number: BlockNumber,
     pub transaction_index: Index,
     pub gas_price: Option<Bytes>,
     pub hash: Bytes32,
     pub status: Option<Index>,
+    // the fields below are filled in from the receipt if receipts are fetched
+    /// Address of the created contract if this is a contract creation. Comes from the receipt.
+    #[serde(default)]
+    pub contract_address: Option<Address>,
+    #[serde(default)]
+    pub gas_used: Option<Bytes>,
+    #[serde(default)]
+    pub cumulative_gas_used: Option<Bytes>,
+    #[serde(default)]
+    pub effective_gas_price: Option<Bytes>,
+    #[serde(default)]
+    pub logs_bloom: Option<BloomFilterBytes>,
+    // the fields below only exist for blob transactions
+    #[serde(default)]
+    pub max_fee_per_blob_gas: Option<Bytes>,
+    #[serde(default)]
+    pub blob_versioned_hashes: Option<Vec<Bytes32>>,
+    // the fields below only exist for access list, dynamic fee, blob and set code transactions
+    #[serde(default)]
+    pub access_list: Option<Vec<AccessListItem>>,
+    #[serde(default)]
+    pub authorization_list: Option<Vec<Authorization>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessListItem {
+    pub address: Address,
+    pub storage_keys: Vec<Bytes32>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Authorization {
+    pub chain_id: Bytes,
+    /// Address of the contract the authority delegates to
+    pub address: Address,
+    pub nonce: Bytes,
+    pub y_parity: Index,
+    pub r: Bytes,
+    pub s: Bytes,
+    /// Signer of the authorization, only present if the node returns it
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authority: Option<Address>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TransactionReceipt {
-    pub block_number: Index,
+    pub block_number: BlockNumber,
     pub transaction_index: Index,
     pub logs: Option<Vec<Log>>,
     pub status: Option<Index>,
+    pub contract_address: Option<Address>,
+    pub gas_used: Option<Bytes>,
+    pub cumulative_gas_used: Option<Bytes>,
+    pub effective_gas_price: Option<Bytes>,
+    pub logs_bloom: Option<BloomFilterBytes>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -72,7 +145,7 @@ pub struct TransactionReceipt {
 pub struct Log {
     pub address: Address,
     pub block_hash: Bytes32,
-    pub block_number: Index,
+    pub block_number: BlockNumber,
     pub data: Bytes,
     pub log_index: Index,
     pub removed: Option<bool>,
@@ -81,6 +154,468 @@ pub struct Log {
     pub transaction_index: Index,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Trace {
+    pub block_number: BlockNumber,
+    pub transaction_index: Option<Index>,
+    pub transaction_hash: Option<Bytes32>,
+    /// Position of the trace in the block
+    pub trace_index: Index,
+    pub trace_address: Vec<u32>,
+    pub subtraces: Index,
+    /// Lowercase type of the call e.g. call, delegatecall, create2, selfdestruct, reward
+    pub call_type: String,
+    #[serde(rename = "from")]
+    pub source: Option<Address>,
+    #[serde(rename = "to")]
+    pub dest: Option<Address>,
+    pub value: Option<Bytes>,
+    pub gas: Option<Bytes>,
+    pub gas_used: Option<Bytes>,
+    pub input: Option<Bytes>,
+    pub output: Option<Bytes>,
+    pub error: Option<String>,
+}
+
+impl Trace {
+    /// Converts the flat list of traces returned by trace_block
+    pub fn from_rpc_traces(traces: Vec<RpcTrace>) -> Vec<Trace> {
+        traces
+            .into_iter()
+            .enumerate()
+            .map(|(i, trace)| {
+                let RpcTrace {
+                    action,
+                    result,
+                    error,
+                    subtraces,
+                    trace_address,
+                    transaction_hash,
+                    transaction_position,
+                    block_number,
+                    kind,
+                } = trace;
+
+                let result = result.unwrap_or_default();
+
+                let (call_type, source, dest, value, input, output) = match kind.as_str() {
+                    "call" => (
+                        action.call_type.unwrap_or(kind),
+                        action.from,
+                        action.to,
+                        action.value,
+                        action.input,
+                        result.output,
+                    ),
+                    "create" => (
+                        action.creation_method.unwrap_or(kind),
+                        action.from,
+                        result.address,
+                        action.value,
+                        action.init,
+                        result.code,
+                    ),
+                    "suicide" => (
+                        "selfdestruct".to_owned(),
+                        action.address,
+                        action.refund_address,
+                        action.balance,
+                        None,
+                        None,
+                    ),
+                    _ => (kind, None, action.author, action.value, None, None),
+                };
+
+                Trace {
+                    block_number,
+                    transaction_index: transaction_position,
+                    transaction_hash,
+                    trace_index: Index(u32::try_from(i).unwrap()),
+                    trace_address,
+                    subtraces,
+                    call_type: call_type.to_lowercase(),
+                    source,
+                    dest,
+                    value,
+                    gas: action.gas,
+                    gas_used: result.gas_used,
+                    input,
+                    output,
+                    error,
+                }
+            })
+            .collect()
+    }
+
+    /// Converts the call frames returned by debug_traceBlockByNumber with callTracer.
+    /// Frames are flattened depth first so the order matches trace_block.
+    pub fn from_call_frames(block: &Block, frames: Vec<TxCallFrame>) -> Vec<Trace> {
+        let mut traces = Vec::new();
+
+        for (tx, frame) in block.transactions.iter().zip(frames.into_iter()) {
+            flatten_call_frame(frame.result, tx, Vec::new(), &mut traces);
+        }
+
+        traces
+    }
+}
+
+fn flatten_call_frame(
+    frame: CallFrame,
+    tx: &Transaction,
+    trace_address: Vec<u32>,
+    traces: &mut Vec<Trace>,
+) {
+    let CallFrame {
+        kind,
+        source,
+        dest,
+        value,
+        gas,
+        gas_used,
+        input,
+        output,
+        error,
+        calls,
+    } = frame;
+
+    traces.push(Trace {
+        block_number: tx.block_number,
+        transaction_index: Some(tx.transaction_index),
+        transaction_hash: Some(tx.hash.clone()),
+        trace_index: Index(u32::try_from(traces.len()).unwrap()),
+        trace_address: trace_address.clone(),
+        subtraces: Index(u32::try_from(calls.len()).unwrap()),
+        call_type: kind.to_lowercase(),
+        source: Some(source),
+        dest,
+        value,
+        gas,
+        gas_used,
+        input,
+        output,
+        error,
+    });
+
+    for (i, call) in calls.into_iter().enumerate() {
+        let mut trace_address = trace_address.clone();
+        trace_address.push(u32::try_from(i).unwrap());
+
+        flatten_call_frame(call, tx, trace_address, traces);
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcTrace {
+    pub action: RpcTraceAction,
+    pub result: Option<RpcTraceResult>,
+    pub error: Option<String>,
+    pub subtraces: Index,
+    pub trace_address: Vec<u32>,
+    pub transaction_hash: Option<Bytes32>,
+    pub transaction_position: Option<Index>,
+    pub block_number: BlockNumber,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcTraceAction {
+    pub call_type: Option<String>,
+    pub creation_method: Option<String>,
+    pub from: Option<Address>,
+    pub to: Option<Address>,
+    pub gas: Option<Bytes>,
+    pub input: Option<Bytes>,
+    pub init: Option<Bytes>,
+    pub value: Option<Bytes>,
+    pub address: Option<Address>,
+    pub refund_address: Option<Address>,
+    pub balance: Option<Bytes>,
+    pub author: Option<Address>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcTraceResult {
+    pub gas_used: Option<Bytes>,
+    pub output: Option<Bytes>,
+    pub address: Option<Address>,
+    pub code: Option<Bytes>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TxCallFrame {
+    pub tx_hash: Option<Bytes32>,
+    pub result: CallFrame,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallFrame {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(rename = "from")]
+    pub source: Address,
+    #[serde(rename = "to")]
+    pub dest: Option<Address>,
+    pub value: Option<Bytes>,
+    pub gas: Option<Bytes>,
+    pub gas_used: Option<Bytes>,
+    pub input: Option<Bytes>,
+    pub output: Option<Bytes>,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub calls: Vec<CallFrame>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StateDiff {
+    pub block_number: BlockNumber,
+    pub transaction_index: Index,
+    pub transaction_hash: Bytes32,
+    /// Position of the diff in the block
+    pub diff_index: Index,
+    pub address: Address,
+    /// One of balance, nonce, code, storage
+    pub kind: String,
+    /// Storage slot, only set for storage diffs
+    pub key: Option<Bytes32>,
+    /// None if the value didn't exist before the transaction
+    pub prev: Option<Bytes>,
+    /// None if the value was deleted by the transaction
+    pub next: Option<Bytes>,
+}
+
+impl StateDiff {
+    /// Converts the state diffs returned by trace_replayBlockTransactions
+    pub fn from_rpc_state_diffs(block: &Block, diffs: Vec<RpcTxStateDiff>) -> Vec<StateDiff> {
+        let mut state_diffs = Vec::new();
+
+        for (tx, diff) in block.transactions.iter().zip(diffs.into_iter()) {
+            for (address, account) in diff.state_diff.unwrap_or_default() {
+                let RpcAccountDiff {
+                    balance,
+                    nonce,
+                    code,
+                    storage,
+                } = account;
+
+                let fields = [
+                    ("balance", None, balance),
+                    ("nonce", None, nonce),
+                    ("code", None, code),
+                ]
+                .into_iter()
+                .chain(
+                    storage
+                        .into_iter()
+                        .map(|(key, diff)| ("storage", Some(key), diff)),
+                );
+
+                for (kind, key, diff) in fields {
+                    let (prev, next) = match diff {
+                        RpcDiff::Same => continue,
+                        RpcDiff::Born(next) => (None, Some(next)),
+                        RpcDiff::Died(prev) => (Some(prev), None),
+                        RpcDiff::Changed(RpcChangedDiff { from, to }) => (Some(from), Some(to)),
+                    };
+
+                    push_state_diff(&mut state_diffs, tx, &address, kind, key, prev, next);
+                }
+            }
+        }
+
+        state_diffs
+    }
+
+    /// Converts the pre and post states returned by debug_traceBlockByNumber
+    /// with prestateTracer in diff mode.
+    pub fn from_prestate_diffs(block: &Block, diffs: Vec<TxPrestateDiff>) -> Vec<StateDiff> {
+        let mut state_diffs = Vec::new();
+
+        for (tx, diff) in block.transactions.iter().zip(diffs.into_iter()) {
+            let PrestateDiff { mut pre, post } = diff.result;
+
+            // accounts that only appear in pre were deleted
+            let deleted_accounts = pre
+                .iter()
+                .filter(|(address, _)| !post.contains_key(*address))
+                .map(|(address, _)| (address.clone(), PrestateAccount::default()))
+                .collect::<Vec<_>>();
+
+            let mut accounts = post;
+            accounts.extend(deleted_accounts);
+
+            for (address, post) in accounts {
+                let pre = pre.remove(&address).unwrap_or_default();
+                let deleted = post.is_empty();
+
+                let fields = [
+                    ("balance", pre.balance, post.balance),
+                    (
+                        "nonce",
+                        pre.nonce.map(nonce_to_bytes),
+                        post.nonce.map(nonce_to_bytes),
+                    ),
+                    ("code", pre.code, post.code),
+                ];
+
+                for (kind, prev, next) in fields {
+                    // post only has the fields that changed unless the account was deleted
+                    if next.is_none() && (!deleted || prev.is_none()) {
+                        continue;
+                    }
+
+                    push_state_diff(&mut state_diffs, tx, &address, kind, None, prev, next);
+                }
+
+                let mut storage = pre
+                    .storage
+                    .into_iter()
+                    .map(|(key, prev)| (key, (Some(prev), None)))
+                    .collect::<BTreeMap<_, _>>();
+                for (key, next) in post.storage {
+                    storage.entry(key).or_insert((None, None)).1 = Some(next);
+                }
+
+                // slots that only appear in pre were cleared
+                for (key, (prev, next)) in storage {
+                    push_state_diff(
+                        &mut state_diffs,
+                        tx,
+                        &address,
+                        "storage",
+                        Some(key),
+                        prev.map(|v| Bytes::new(v.as_ref())),
+                        next.map(|v| Bytes::new(v.as_ref())),
+                    );
+                }
+            }
+        }
+
+        state_diffs
+    }
+}
+
+fn push_state_diff(
+    state_diffs: &mut Vec<StateDiff>,
+    tx: &Transaction,
+    address: &Address,
+    kind: &str,
+    key: Option<Bytes32>,
+    prev: Option<Bytes>,
+    next: Option<Bytes>,
+) {
+    state_diffs.push(StateDiff {
+        block_number: tx.block_number,
+        transaction_index: tx.transaction_index,
+        transaction_hash: tx.hash.clone(),
+        diff_index: Index(u32::try_from(state_diffs.len()).unwrap()),
+        address: address.clone(),
+        kind: kind.to_owned(),
+        key,
+        prev,
+        next,
+    });
+}
+
+fn nonce_to_bytes(nonce: u64) -> Bytes {
+    let bytes = nonce.to_be_bytes();
+    let start = bytes
+        .iter()
+        .position(|&b| b != 0)
+        .unwrap_or(bytes.len() - 1);
+
+    Bytes::new(&bytes[start..])
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcTxStateDiff {
+    pub transaction_hash: Option<Bytes32>,
+    pub state_diff: Option<BTreeMap<Address, RpcAccountDiff>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcAccountDiff {
+    pub balance: RpcDiff,
+    pub nonce: RpcDiff,
+    pub code: RpcDiff,
+    #[serde(default)]
+    pub storage: BTreeMap<Bytes32, RpcDiff>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum RpcDiff {
+    #[serde(rename = "=")]
+    Same,
+    #[serde(rename = "+")]
+    Born(Bytes),
+    #[serde(rename = "-")]
+    Died(Bytes),
+    #[serde(rename = "*")]
+    Changed(RpcChangedDiff),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RpcChangedDiff {
+    pub from: Bytes,
+    pub to: Bytes,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TxPrestateDiff {
+    pub tx_hash: Option<Bytes32>,
+    pub result: PrestateDiff,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PrestateDiff {
+    #[serde(default)]
+    pub pre: BTreeMap<Address, PrestateAccount>,
+    #[serde(default)]
+    pub post: BTreeMap<Address, PrestateAccount>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct PrestateAccount {
+    pub balance: Option<Bytes>,
+    pub nonce: Option<u64>,
+    pub code: Option<Bytes>,
+    #[serde(default)]
+    pub storage: BTreeMap<Bytes32, Bytes32>,
+}
+
+impl PrestateAccount {
+    fn is_empty(&self) -> bool {
+        self.balance.is_none()
+            && self.nonce.is_none()
+            && self.code.is_none()
+            && self.storage.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Withdrawal {
+    /// Not part of the rpc response, filled in by the eth client
+    #[serde(default)]
+    pub block_number: BlockNumber,
+    pub index: Index,
+    pub validator_index: Index,
+    pub address: Address,
+    /// Amount in gwei
+    pub amount: Bytes,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct ResponseBlock {
@@ -101,7 +636,7 @@ pub struct ResponseBlock {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub difficulty: Option<Bytes>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub number: Option<Index>,
+    pub number: Option<BlockNumber>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub gas_limit: Option<Bytes>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -122,6 +657,14 @@ pub struct ResponseBlock {
     pub size: Option<Bytes>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub hash: Option<Bytes32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blob_gas_used: Option<Bytes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub excess_blob_gas: Option<Bytes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_beacon_block_root: Option<Bytes32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub withdrawals_root: Option<Bytes32>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
@@ -148,9 +691,9 @@ pub struct ResponseTransaction {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub y_parity: Option<Index>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub chain_id: Option<Index>,
+    pub chain_id: Option<ChainId>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub v: Option<BigUnsigned>,
+    pub v: Option<Bytes>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub r: Option<Bytes>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -161,7 +704,7 @@ pub struct ResponseTransaction {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub block_hash: Option<Bytes32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub block_number: Option<Index>,
+    pub block_number: Option<BlockNumber>,
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "index")]
     pub transaction_index: Option<Index>,
@@ -171,6 +714,24 @@ pub struct ResponseTransaction {
     pub hash: Option<Bytes32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub status: Option<Index>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contract_address: Option<Address>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gas_used: Option<Bytes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cumulative_gas_used: Option<Bytes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effective_gas_price: Option<Bytes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logs_bloom: Option<BloomFilterBytes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_fee_per_blob_gas: Option<Bytes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blob_versioned_hashes: Option<Vec<Bytes32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_list: Option<Vec<AccessListItem>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authorization_list: Option<Vec<Authorization>>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
@@ -181,7 +742,7 @@ pub struct ResponseLog {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub block_hash: Option<Bytes32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub block_number: Option<Index>,
+    pub block_number: Option<BlockNumber>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<Bytes>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -197,10 +758,87 @@ pub struct ResponseLog {
     pub transaction_index: Option<Index>,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponseTrace {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_number: Option<BlockNumber>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_index: Option<Index>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_hash: Option<Bytes32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "index")]
+    pub trace_index: Option<Index>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_address: Option<Vec<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subtraces: Option<Index>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub call_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "from")]
+    pub source: Option<Address>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "to")]
+    pub dest: Option<Address>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Bytes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gas: Option<Bytes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gas_used: Option<Bytes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input: Option<Bytes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<Bytes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponseStateDiff {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_number: Option<BlockNumber>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_index: Option<Index>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_hash: Option<Bytes32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "index")]
+    pub diff_index: Option<Index>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<Address>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<Bytes32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prev: Option<Bytes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<Bytes>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponseWithdrawal {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_number: Option<BlockNumber>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<Index>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub validator_index: Option<Index>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<Address>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount: Option<Bytes>,
+}
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct BlockRange {
-    pub from: u32,
-    pub to: u32,
+    pub from: u64,
+    pub to: u64,
 }
 
 impl std::ops::Add for BlockRange {
@@ -225,6 +863,9 @@ impl std::ops::AddAssign for BlockRange {
 pub enum FormatVersion {
     Ver0_0_39,
     Ver0_1_0,
+    Ver0_2_0,
+    Ver0_3_0,
+    Ver0_4_0,
 }
 
 impl FromStr for FormatVersion {
@@ -234,7 +875,502 @@ impl FromStr for FormatVersion {
         match s {
             "0.0.39" => Ok(FormatVersion::Ver0_0_39),
             "0.1.0" => Ok(FormatVersion::Ver0_1_0),
+            "0.2.0" => Ok(FormatVersion::Ver0_2_0),
+            "0.3.0" => Ok(FormatVersion::Ver0_3_0),
+            "0.4.0" => Ok(FormatVersion::Ver0_4_0),
             _ => Err(Error::UnknownFormat(s.to_owned())),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn hex(byte: u8, len: usize) -> String {
+        prefix_hex::encode(vec![byte; len])
+    }
+
+    fn addr(n: u8) -> Address {
+        Address::new(&[n; 20])
+    }
+
+    /// Block 100 with one transaction per given hash byte
+    fn block(tx_hashes: &[u8]) -> Block {
+        let transactions = tx_hashes
+            .iter()
+            .enumerate()
+            .map(|(i, &hash)| {
+                json!({
+                    "blockHash": hex(100, 32),
+                    "blockNumber": "0x64",
+                    "from": hex(1, 20),
+                    "gas": "0x5208",
+                    "gasPrice": "0x1",
+                    "hash": hex(hash, 32),
+                    "input": "0x",
+                    "nonce": "0x0",
+                    "to": hex(2, 20),
+                    "transactionIndex": format!("0x{:x}", i),
+                    "value": "0x0",
+                })
+            })
+            .collect::<Vec<_>>();
+
+        serde_json::from_value(json!({
+            "parentHash": hex(99, 32),
+            "sha3Uncles": hex(0, 32),
+            "miner": hex(4, 20),
+            "stateRoot": hex(0, 32),
+            "transactionsRoot": hex(0, 32),
+            "receiptsRoot": hex(0, 32),
+            "logsBloom": hex(0, 256),
+            "number": "0x64",
+            "gasLimit": "0x1c9c380",
+            "gasUsed": "0x5208",
+            "timestamp": "0x63e3a8e3",
+            "extraData": "0x",
+            "size": "0x220",
+            "hash": hex(100, 32),
+            "transactions": transactions,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_from_rpc_traces() {
+        // trace_block output with a call that creates a contract, a selfdestruct and a block reward
+        let traces: Vec<RpcTrace> = serde_json::from_value(json!([
+            {
+                "action": {
+                    "callType": "call",
+                    "from": hex(1, 20),
+                    "gas": "0x5208",
+                    "input": "0xa9059cbb",
+                    "to": hex(2, 20),
+                    "value": "0x0"
+                },
+                "blockHash": hex(100, 32),
+                "blockNumber": 100,
+                "result": { "gasUsed": "0x1234", "output": "0x01" },
+                "subtraces": 1,
+                "traceAddress": [],
+                "transactionHash": hex(0xaa, 32),
+                "transactionPosition": 0,
+                "type": "call"
+            },
+            {
+                "action": {
+                    "creationMethod": "create2",
+                    "from": hex(2, 20),
+                    "gas": "0x100",
+                    "init": "0x6080",
+                    "value": "0x10"
+                },
+                "blockHash": hex(100, 32),
+                "blockNumber": 100,
+                "result": { "address": hex(3, 20), "code": "0x6060", "gasUsed": "0x50" },
+                "subtraces": 0,
+                "traceAddress": [0],
+                "transactionHash": hex(0xaa, 32),
+                "transactionPosition": 0,
+                "type": "create"
+            },
+            {
+                "action": {
+                    "address": hex(3, 20),
+                    "balance": "0x10",
+                    "refundAddress": hex(1, 20)
+                },
+                "blockHash": hex(100, 32),
+                "blockNumber": 100,
+                "error": "Reverted",
+                "subtraces": 0,
+                "traceAddress": [],
+                "transactionHash": hex(0xbb, 32),
+                "transactionPosition": 1,
+                "type": "suicide"
+            },
+            {
+                "action": {
+                    "author": hex(4, 20),
+                    "rewardType": "block",
+                    "value": "0x1bc16d674ec80000"
+                },
+                "blockHash": hex(100, 32),
+                "blockNumber": 100,
+                "result": null,
+                "subtraces": 0,
+                "traceAddress": [],
+                "transactionHash": null,
+                "transactionPosition": null,
+                "type": "reward"
+            }
+        ]))
+        .unwrap();
+
+        let traces = Trace::from_rpc_traces(traces);
+
+        assert_eq!(traces.len(), 4);
+
+        let call = &traces[0];
+        assert_eq!(call.block_number.0, 100);
+        assert_eq!(call.transaction_index, Some(Index(0)));
+        assert_eq!(call.trace_index, Index(0));
+        assert_eq!(call.subtraces, Index(1));
+        assert_eq!(call.call_type, "call");
+        assert_eq!(call.source, Some(addr(1)));
+        assert_eq!(call.dest, Some(addr(2)));
+        assert_eq!(call.input, Some(Bytes::new(&[0xa9, 0x05, 0x9c, 0xbb])));
+        assert_eq!(call.output, Some(Bytes::new(&[1])));
+        assert_eq!(call.gas_used, Some(Bytes::new(&[0x12, 0x34])));
+
+        let create = &traces[1];
+        assert_eq!(create.trace_index, Index(1));
+        assert_eq!(create.trace_address, vec![0]);
+        assert_eq!(create.call_type, "create2");
+        assert_eq!(create.source, Some(addr(2)));
+        assert_eq!(create.dest, Some(addr(3)));
+        assert_eq!(create.input, Some(Bytes::new(&[0x60, 0x80])));
+        assert_eq!(create.output, Some(Bytes::new(&[0x60, 0x60])));
+
+        let selfdestruct = &traces[2];
+        assert_eq!(selfdestruct.call_type, "selfdestruct");
+        assert_eq!(selfdestruct.transaction_index, Some(Index(1)));
+        assert_eq!(selfdestruct.source, Some(addr(3)));
+        assert_eq!(selfdestruct.dest, Some(addr(1)));
+        assert_eq!(selfdestruct.value, Some(Bytes::new(&[0x10])));
+        assert_eq!(selfdestruct.error.as_deref(), Some("Reverted"));
+        assert_eq!(selfdestruct.gas_used, None);
+
+        let reward = &traces[3];
+        assert_eq!(reward.call_type, "reward");
+        assert_eq!(reward.transaction_index, None);
+        assert_eq!(reward.transaction_hash, None);
+        assert_eq!(reward.source, None);
+        assert_eq!(reward.dest, Some(addr(4)));
+    }
+
+    #[test]
+    fn test_from_call_frames() {
+        let block = block(&[0xaa, 0xbb]);
+
+        // debug_traceBlockByNumber output with callTracer
+        let frames: Vec<TxCallFrame> = serde_json::from_value(json!([
+            {
+                "txHash": hex(0xaa, 32),
+                "result": {
+                    "type": "CALL",
+                    "from": hex(1, 20),
+                    "to": hex(2, 20),
+                    "value": "0x0",
+                    "gas": "0x5208",
+                    "gasUsed": "0x1234",
+                    "input": "0xa9059cbb",
+                    "output": "0x01",
+                    "calls": [
+                        {
+                            "type": "DELEGATECALL",
+                            "from": hex(2, 20),
+                            "to": hex(3, 20),
+                            "gas": "0x100",
+                            "gasUsed": "0x50",
+                            "input": "0x",
+                            "calls": [
+                                {
+                                    "type": "CREATE2",
+                                    "from": hex(3, 20),
+                                    "to": hex(5, 20),
+                                    "value": "0x10",
+                                    "gas": "0x80",
+                                    "gasUsed": "0x40",
+                                    "input": "0x6080",
+                                    "output": "0x6060"
+                                }
+                            ]
+                        },
+                        {
+                            "type": "STATICCALL",
+                            "from": hex(2, 20),
+                            "to": hex(6, 20),
+                            "gas": "0x100",
+                            "gasUsed": "0x10",
+                            "input": "0x70a08231",
+                            "error": "execution reverted"
+                        }
+                    ]
+                }
+            },
+            {
+                "txHash": hex(0xbb, 32),
+                "result": {
+                    "type": "CALL",
+                    "from": hex(1, 20),
+                    "to": hex(7, 20),
+                    "value": "0xde0b6b3a7640000",
+                    "gas": "0x5208",
+                    "gasUsed": "0x5208",
+                    "input": "0x"
+                }
+            }
+        ]))
+        .unwrap();
+
+        let traces = Trace::from_call_frames(&block, frames);
+
+        let flat = traces
+            .iter()
+            .map(|t| {
+                (
+                    t.transaction_index.unwrap().0,
+                    t.trace_index.0,
+                    t.trace_address.clone(),
+                    t.subtraces.0,
+                    t.call_type.as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            flat,
+            vec![
+                (0, 0, vec![], 2, "call"),
+                (0, 1, vec![0], 1, "delegatecall"),
+                (0, 2, vec![0, 0], 0, "create2"),
+                (0, 3, vec![1], 0, "staticcall"),
+                (1, 4, vec![], 0, "call"),
+            ]
+        );
+
+        assert!(traces.iter().all(|t| t.block_number.0 == 100));
+        assert_eq!(traces[0].transaction_hash, Some(Bytes32::new(&[0xaa; 32])));
+        assert_eq!(traces[4].transaction_hash, Some(Bytes32::new(&[0xbb; 32])));
+        assert_eq!(traces[2].source, Some(addr(3)));
+        assert_eq!(traces[2].dest, Some(addr(5)));
+        assert_eq!(traces[2].output, Some(Bytes::new(&[0x60, 0x60])));
+        assert_eq!(traces[3].error.as_deref(), Some("execution reverted"));
+        assert_eq!(
+            traces[4].value,
+            Some(Bytes::from_quantity(1_000_000_000_000_000_000))
+        );
+    }
+
+    type FlatDiff = (
+        u32,
+        u32,
+        Address,
+        String,
+        Option<Bytes32>,
+        Option<Bytes>,
+        Option<Bytes>,
+    );
+
+    fn flat_diffs(state_diffs: Vec<StateDiff>) -> Vec<FlatDiff> {
+        state_diffs
+            .into_iter()
+            .map(|d| {
+                assert_eq!(d.block_number.0, 100);
+                (
+                    d.transaction_index.0,
+                    d.diff_index.0,
+                    d.address,
+                    d.kind,
+                    d.key,
+                    d.prev,
+                    d.next,
+                )
+            })
+            .collect()
+    }
+
+    fn diff(
+        tx: u32,
+        idx: u32,
+        address: u8,
+        kind: &str,
+        key: Option<u8>,
+        prev: Option<&[u8]>,
+        next: Option<&[u8]>,
+    ) -> FlatDiff {
+        (
+            tx,
+            idx,
+            addr(address),
+            kind.to_owned(),
+            key.map(|k| Bytes32::new(&[k; 32])),
+            prev.map(Bytes::new),
+            next.map(Bytes::new),
+        )
+    }
+
+    #[test]
+    fn test_from_rpc_state_diffs() {
+        let block = block(&[0xaa, 0xbb, 0xcc]);
+
+        // trace_replayBlockTransactions output with the stateDiff trace type
+        let diffs: Vec<RpcTxStateDiff> = serde_json::from_value(json!([
+            {
+                "output": "0x",
+                "stateDiff": {
+                    hex(1, 20): {
+                        "balance": { "*": { "from": "0x10", "to": "0x08" } },
+                        "nonce": { "*": { "from": "0x1", "to": "0x2" } },
+                        "code": "=",
+                        "storage": {
+                            hex(0x51, 32): { "*": { "from": hex(0x61, 32), "to": hex(0x63, 32) } },
+                            hex(0x52, 32): { "-": hex(0x62, 32) }
+                        }
+                    },
+                    hex(2, 20): {
+                        "balance": { "+": "0x5" },
+                        "nonce": { "+": "0x1" },
+                        "code": { "+": "0x60" },
+                        "storage": {
+                            hex(0x53, 32): { "+": hex(0x64, 32) }
+                        }
+                    }
+                },
+                "trace": [],
+                "transactionHash": hex(0xaa, 32),
+                "vmTrace": null
+            },
+            {
+                "output": "0x",
+                "stateDiff": null,
+                "trace": [],
+                "transactionHash": hex(0xbb, 32),
+                "vmTrace": null
+            },
+            {
+                "output": "0x",
+                "stateDiff": {
+                    hex(3, 20): {
+                        "balance": { "-": "0x1" },
+                        "nonce": { "-": "0x0" },
+                        "code": { "-": "0x6060" },
+                        "storage": {}
+                    }
+                },
+                "trace": [],
+                "transactionHash": hex(0xcc, 32),
+                "vmTrace": null
+            }
+        ]))
+        .unwrap();
+
+        let state_diffs = StateDiff::from_rpc_state_diffs(&block, diffs);
+
+        assert_eq!(state_diffs[0].transaction_hash, Bytes32::new(&[0xaa; 32]));
+        assert_eq!(state_diffs[8].transaction_hash, Bytes32::new(&[0xcc; 32]));
+
+        assert_eq!(
+            flat_diffs(state_diffs),
+            vec![
+                diff(0, 0, 1, "balance", None, Some(&[0x10]), Some(&[0x08])),
+                diff(0, 1, 1, "nonce", None, Some(&[1]), Some(&[2])),
+                diff(
+                    0,
+                    2,
+                    1,
+                    "storage",
+                    Some(0x51),
+                    Some(&[0x61; 32]),
+                    Some(&[0x63; 32])
+                ),
+                diff(0, 3, 1, "storage", Some(0x52), Some(&[0x62; 32]), None),
+                diff(0, 4, 2, "balance", None, None, Some(&[5])),
+                diff(0, 5, 2, "nonce", None, None, Some(&[1])),
+                diff(0, 6, 2, "code", None, None, Some(&[0x60])),
+                diff(0, 7, 2, "storage", Some(0x53), None, Some(&[0x64; 32])),
+                diff(2, 8, 3, "balance", None, Some(&[1]), None),
+                diff(2, 9, 3, "nonce", None, Some(&[0]), None),
+                diff(2, 10, 3, "code", None, Some(&[0x60, 0x60]), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_prestate_diffs() {
+        let block = block(&[0xaa, 0xbb, 0xcc]);
+
+        // debug_traceBlockByNumber output with prestateTracer in diff mode
+        let diffs: Vec<TxPrestateDiff> = serde_json::from_value(json!([
+            {
+                "txHash": hex(0xaa, 32),
+                "result": {
+                    "pre": {
+                        hex(1, 20): {
+                            "balance": "0x10",
+                            "nonce": 1,
+                            "code": "0x6000",
+                            "storage": {
+                                hex(0x51, 32): hex(0x61, 32),
+                                hex(0x52, 32): hex(0x62, 32)
+                            }
+                        }
+                    },
+                    "post": {
+                        hex(1, 20): {
+                            "balance": "0x08",
+                            "nonce": 2,
+                            "storage": {
+                                hex(0x51, 32): hex(0x63, 32)
+                            }
+                        },
+                        hex(2, 20): {
+                            "balance": "0x5",
+                            "nonce": 1,
+                            "code": "0x60",
+                            "storage": {
+                                hex(0x53, 32): hex(0x64, 32)
+                            }
+                        }
+                    }
+                }
+            },
+            {
+                "txHash": hex(0xbb, 32),
+                "result": { "pre": {}, "post": {} }
+            },
+            {
+                "txHash": hex(0xcc, 32),
+                "result": {
+                    "pre": {
+                        hex(3, 20): {
+                            "balance": "0x1",
+                            "code": "0x6060"
+                        }
+                    },
+                    "post": {}
+                }
+            }
+        ]))
+        .unwrap();
+
+        let state_diffs = StateDiff::from_prestate_diffs(&block, diffs);
+
+        assert_eq!(
+            flat_diffs(state_diffs),
+            vec![
+                diff(0, 0, 1, "balance", None, Some(&[0x10]), Some(&[0x08])),
+                diff(0, 1, 1, "nonce", None, Some(&[1]), Some(&[2])),
+                diff(
+                    0,
+                    2,
+                    1,
+                    "storage",
+                    Some(0x51),
+                    Some(&[0x61; 32]),
+                    Some(&[0x63; 32])
+                ),
+                diff(0, 3, 1, "storage", Some(0x52), Some(&[0x62; 32]), None),
+                diff(0, 4, 2, "balance", None, None, Some(&[5])),
+                diff(0, 5, 2, "nonce", None, None, Some(&[1])),
+                diff(0, 6, 2, "code", None, None, Some(&[0x60])),
+                diff(0, 7, 2, "storage", Some(0x53), None, Some(&[0x64; 32])),
+                diff(2, 8, 3, "balance", None, Some(&[1]), None),
+                diff(2, 9, 3, "code", None, Some(&[0x60, 0x60]), None),
+            ]
+        );
+    }
+}
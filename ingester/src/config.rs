@@ -1,5 +1,5 @@
 use clap::Parser;
-use eth_archive_core::config::{IngestConfig, RetryConfig, S3Config};
+use eth_archive_core::config::{IngestConfig, ParentHashPolicy, RetryConfig, S3Config};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::PathBuf;
 
@@ -13,6 +13,9 @@ pub struct Config {
     pub ingest: IngestConfig,
     #[command(flatten)]
     pub retry: RetryConfig,
+    /// What to do when a downloaded block isn't a child of the block before it
+    #[clap(long, value_enum, default_value_t = ParentHashPolicy::Refetch)]
+    pub parent_hash_policy: ParentHashPolicy,
     /// Maximum number of blocks per parquet file
     #[clap(long)]
     pub max_blocks_per_file: usize,
@@ -22,6 +25,15 @@ pub struct Config {
     /// Maximum number of logs per parquet file
     #[clap(long)]
     pub max_logs_per_file: usize,
+    /// Maximum number of traces per parquet file
+    #[clap(long, default_value_t = 1_000_000)]
+    pub max_traces_per_file: usize,
+    /// Maximum number of state diffs per parquet file
+    #[clap(long, default_value_t = 1_000_000)]
+    pub max_state_diffs_per_file: usize,
+    /// Maximum number of withdrawals per parquet file
+    #[clap(long, default_value_t = 1_000_000)]
+    pub max_withdrawals_per_file: usize,
     /// Maximum number of row groups per parquet file
     #[clap(long)]
     pub max_row_groups_per_file: usize,
@@ -40,13 +52,13 @@ pub struct Config {
     /// S3 bucket name to initial sync from
     #[clap(long)]
     pub s3_src_bucket: Option<String>,
-    /// Source data format version
+    /// Source data format version (0.0.39, 0.1.0, 0.2.0, 0.3.0 or 0.4.0). This version of the ingester writes 0.4.0
     #[clap(long)]
     pub s3_src_format_ver: Option<String>,
     /// Local file system path to sync from
     #[clap(long)]
     pub local_src_path: Option<PathBuf>,
-    /// Local source data format version
+    /// Local source data format version (0.0.39, 0.1.0, 0.2.0, 0.3.0 or 0.4.0)
     #[clap(long)]
     pub local_src_format_ver: Option<String>,
     #[command(flatten)]
@@ -28,7 +28,7 @@ pub enum Error {
     #[error("failed to create missing directories:\n{0}")]
     CreateMissingDirectories(io::Error),
     #[error("folder range mismatch {0} => {1}")]
-    FolderRangeMismatch(u32, u32),
+    FolderRangeMismatch(u64, u64),
     #[error("failed to run writer thread:\n{0}")]
     RunWriterThread(tokio::task::JoinError),
     #[error("failed to create directory:\n{0}")]
@@ -57,6 +57,10 @@ pub enum Error {
     StartS3BatchStream(eth_archive_core::Error),
     #[error("failed to start streaming data from local file system:\n{0}")]
     StartLocalBatchStream(eth_archive_core::Error),
+    #[error("{0} doesn't fit into a u64")]
+    QuantityTooWide(&'static str),
+    #[error("{0} is wider than 256 bits")]
+    QuantityWiderThanU256(&'static str),
 }
 
 pub type Result<T> = StdResult<T, Error>;
@@ -1,7 +1,7 @@
 use crate::config::Config;
 use crate::schema::{
-    block_schema, log_schema, parquet_write_options, tx_schema, Blocks, IntoChunks, Logs,
-    Transactions,
+    block_schema, log_schema, parquet_write_options, state_diff_schema, trace_schema, tx_schema,
+    withdrawal_schema, Blocks, IntoChunks, Logs, StateDiffs, Traces, Transactions, Withdrawals,
 };
 use crate::server::Server;
 use crate::{Error, Result};
@@ -167,7 +167,7 @@ impl Ingester {
         let batches = self
             .eth_client
             .clone()
-            .stream_batches(Some(block_num), None)
+            .stream_batches(Some(block_num), None, Some(self.cfg.parent_hash_policy))
             .map_err(Error::GetBatch);
 
         self.ingest_batches(&mut sender, batches).await?;
@@ -184,7 +184,7 @@ impl Ingester {
         &self,
         sender: &mut mpsc::Sender<Data>,
         batches: impl Stream<Item = Result<(Vec<BlockRange>, Vec<Vec<Block>>, Vec<Vec<Log>>)>>,
-    ) -> Result<u32> {
+    ) -> Result<u64> {
         pin_mut!(batches);
 
         let mut data = Data::default();
@@ -211,9 +211,18 @@ impl Ingester {
 
                 for mut block in block_batch.into_iter() {
                     for tx in mem::take(&mut block.transactions).into_iter() {
-                        data.txs.push(tx);
+                        data.txs.push(tx)?;
                     }
-                    data.blocks.push(block);
+                    for trace in mem::take(&mut block.traces).into_iter() {
+                        data.traces.push(trace);
+                    }
+                    for state_diff in mem::take(&mut block.state_diffs).into_iter() {
+                        data.state_diffs.push(state_diff);
+                    }
+                    for withdrawal in mem::take(&mut block.withdrawals).into_iter() {
+                        data.withdrawals.push(withdrawal)?;
+                    }
+                    data.blocks.push(block)?;
                 }
                 for log in log_batch.into_iter() {
                     data.logs.push(log);
@@ -223,6 +232,9 @@ impl Ingester {
                 if data.blocks.len >= self.cfg.max_blocks_per_file
                     || data.txs.len >= self.cfg.max_txs_per_file
                     || data.logs.len >= self.cfg.max_logs_per_file
+                    || data.traces.len >= self.cfg.max_traces_per_file
+                    || data.state_diffs.len >= self.cfg.max_state_diffs_per_file
+                    || data.withdrawals.len >= self.cfg.max_withdrawals_per_file
                 {
                     if sender.send(mem::take(&mut data)).await.is_err() {
                         log::info!("writer thread crashed. exiting ingest loop...");
@@ -235,7 +247,7 @@ impl Ingester {
         Ok(max_block_num)
     }
 
-    fn get_start_block(dir_names: &[DirName]) -> Result<u32> {
+    fn get_start_block(dir_names: &[DirName]) -> Result<u64> {
         if dir_names.is_empty() {
             return Ok(0);
         }
@@ -263,6 +275,9 @@ pub struct Data {
     blocks: Blocks,
     txs: Transactions,
     logs: Logs,
+    traces: Traces,
+    state_diffs: StateDiffs,
+    withdrawals: Withdrawals,
     range: Option<BlockRange>,
 }
 
@@ -323,7 +338,90 @@ impl Data {
             )
         };
 
-        futures::future::try_join3(block_fut, tx_fut, log_fut).await?;
+        // trace.parquet is only written if traces are fetched, the worker treats a missing file
+        // as a folder without traces
+        let trace_fut = {
+            let mut temp_path = temp_path.clone();
+            temp_path.push("trace.parquet");
+
+            let traces = self.traces;
+            let items_per_chunk = cfg.max_traces_per_file / cfg.max_row_groups_per_file;
+            let page_size = cfg.parquet_page_size;
+
+            async move {
+                if traces.len == 0 {
+                    return Ok(());
+                }
+
+                write_file(
+                    temp_path,
+                    Box::new(traces),
+                    trace_schema(),
+                    items_per_chunk,
+                    page_size,
+                )
+                .await
+            }
+        };
+
+        // same as trace.parquet, only written if state diffs are fetched
+        let state_diff_fut = {
+            let mut temp_path = temp_path.clone();
+            temp_path.push("state_diff.parquet");
+
+            let state_diffs = self.state_diffs;
+            let items_per_chunk = cfg.max_state_diffs_per_file / cfg.max_row_groups_per_file;
+            let page_size = cfg.parquet_page_size;
+
+            async move {
+                if state_diffs.len == 0 {
+                    return Ok(());
+                }
+
+                write_file(
+                    temp_path,
+                    Box::new(state_diffs),
+                    state_diff_schema(),
+                    items_per_chunk,
+                    page_size,
+                )
+                .await
+            }
+        };
+
+        // same as trace.parquet, only written if the folder has any withdrawals
+        let withdrawal_fut = {
+            let mut temp_path = temp_path.clone();
+            temp_path.push("withdrawal.parquet");
+
+            let withdrawals = self.withdrawals;
+            let items_per_chunk = cfg.max_withdrawals_per_file / cfg.max_row_groups_per_file;
+            let page_size = cfg.parquet_page_size;
+
+            async move {
+                if withdrawals.len == 0 {
+                    return Ok(());
+                }
+
+                write_file(
+                    temp_path,
+                    Box::new(withdrawals),
+                    withdrawal_schema(),
+                    items_per_chunk,
+                    page_size,
+                )
+                .await
+            }
+        };
+
+        futures::try_join!(
+            block_fut,
+            tx_fut,
+            log_fut,
+            trace_fut,
+            state_diff_fut,
+            withdrawal_fut
+        )?;
 
         let mut final_path = cfg.data_path.to_owned();
         final_path.push(
@@ -396,3 +494,366 @@ async fn write_file<T: IntoChunks + Send + 'static>(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use eth_archive_core::deserialize::{Address, BlockNumber, Bytes, Bytes32, Index};
+    use eth_archive_core::types::{StateDiff, Trace, Transaction, Withdrawal};
+    use serde_json::{json, Value};
+    use std::path::Path;
+
+    fn hex(byte: u8, len: usize) -> String {
+        format!("0x{}", format!("{byte:02x}").repeat(len))
+    }
+
+    fn block(num: u64) -> Block {
+        serde_json::from_value(json!({
+            "parentHash": hex(num as u8, 32),
+            "sha3Uncles": hex(0, 32),
+            "miner": hex(4, 20),
+            "stateRoot": hex(0, 32),
+            "transactionsRoot": hex(0, 32),
+            "receiptsRoot": hex(0, 32),
+            "logsBloom": hex(0, 256),
+            "number": format!("{num:#x}"),
+            "gasLimit": "0x1c9c380",
+            "gasUsed": "0x5208",
+            "timestamp": "0x63e3a8e3",
+            "extraData": "0x",
+            "size": "0x220",
+            "hash": hex(num as u8 + 1, 32),
+            "transactions": [],
+        }))
+        .unwrap()
+    }
+
+    fn blob_tx(block_num: u64, index: u32) -> Transaction {
+        serde_json::from_value(json!({
+            "type": "0x3",
+            "nonce": "0x1",
+            "to": hex(2, 20),
+            "gas": "0x5208",
+            "value": "0x64",
+            "input": "0x",
+            "maxPriorityFeePerGas": "0x3b9aca00",
+            "maxFeePerGas": "0x3b9aca00",
+            "yParity": "0x1",
+            "chainId": "0x1",
+            "r": hex(5, 32),
+            "s": hex(6, 32),
+            "from": hex(1, 20),
+            "blockHash": hex(block_num as u8 + 1, 32),
+            "blockNumber": format!("{block_num:#x}"),
+            "transactionIndex": format!("{index:#x}"),
+            "hash": hex(0x10 + index as u8, 32),
+            "maxFeePerBlobGas": "0x3b9aca00",
+            "blobVersionedHashes": [hex(7, 32), hex(8, 32)],
+        }))
+        .unwrap()
+    }
+
+    fn trace(block_num: u64, trace_index: u32, trace_address: Vec<u32>) -> Trace {
+        Trace {
+            block_number: BlockNumber(block_num),
+            transaction_index: Some(Index(0)),
+            transaction_hash: Some(Bytes32::new(&[0xaa; 32])),
+            trace_index: Index(trace_index),
+            subtraces: Index(0),
+            trace_address,
+            call_type: "call".to_owned(),
+            source: Some(Address::new(&[1; 20])),
+            dest: Some(Address::new(&[2; 20])),
+            value: Some(Bytes::from_quantity(1000)),
+            gas: Some(Bytes::from_quantity(21_000)),
+            gas_used: Some(Bytes::from_quantity(0)),
+            input: Some(Bytes(vec![0xa9, 0x05, 0x9c, 0xbb])),
+            output: None,
+            error: Some("Reverted".to_owned()),
+        }
+    }
+
+    fn state_diff(block_num: u64, diff_index: u32, key: Option<u8>) -> StateDiff {
+        StateDiff {
+            block_number: BlockNumber(block_num),
+            transaction_index: Index(0),
+            transaction_hash: Bytes32::new(&[0xaa; 32]),
+            diff_index: Index(diff_index),
+            address: Address::new(&[3; 20]),
+            kind: if key.is_some() { "storage" } else { "balance" }.to_owned(),
+            key: key.map(|key| Bytes32::new(&[key; 32])),
+            prev: None,
+            next: Some(Bytes::from_quantity(5)),
+        }
+    }
+
+    fn withdrawal(block_num: u64, index: u32) -> Withdrawal {
+        Withdrawal {
+            block_number: BlockNumber(block_num),
+            index: Index(index),
+            validator_index: Index(1000 + index),
+            address: Address::new(&[5 + index as u8; 20]),
+            amount: Bytes::from_quantity(0x0100_0000),
+        }
+    }
+
+    fn data(blocks: Vec<Block>) -> Data {
+        let mut data = Data {
+            range: Some(BlockRange {
+                from: blocks.first().unwrap().number.0,
+                to: blocks.last().unwrap().number.0 + 1,
+            }),
+            ..Default::default()
+        };
+
+        for mut block in blocks {
+            for tx in mem::take(&mut block.transactions) {
+                data.txs.push(tx).unwrap();
+            }
+            for trace in mem::take(&mut block.traces) {
+                data.traces.push(trace);
+            }
+            for state_diff in mem::take(&mut block.state_diffs) {
+                data.state_diffs.push(state_diff);
+            }
+            for withdrawal in mem::take(&mut block.withdrawals) {
+                data.withdrawals.push(withdrawal).unwrap();
+            }
+            data.blocks.push(block).unwrap();
+        }
+
+        data
+    }
+
+    fn config(data_path: &Path) -> Config {
+        Config::try_parse_from([
+            "eth-archive-ingester",
+            "--data-path",
+            data_path.to_str().unwrap(),
+            "--request-timeout-secs",
+            "1",
+            "--connect-timeout-ms",
+            "1",
+            "--block-batch-size",
+            "1",
+            "--http-req-concurrency",
+            "1",
+            "--best-block-offset",
+            "0",
+            "--max-blocks-per-file",
+            "100",
+            "--max-txs-per-file",
+            "100",
+            "--max-logs-per-file",
+            "100",
+            "--max-row-groups-per-file",
+            "1",
+        ])
+        .unwrap()
+    }
+
+    /// Writes the folder and reads it back the same way re-ingesting from a local path does.
+    /// Also returns the sorted names of the files in the folder.
+    async fn round_trip(data: Data, name: &str) -> (Vec<Block>, Vec<String>) {
+        let mut path = std::env::temp_dir();
+        path.push(format!("eth-archive-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+
+        data.write_parquet_folder(&config(&path), &IngestMetrics::new())
+            .await
+            .unwrap();
+
+        let mut files = Vec::new();
+        for folder in std::fs::read_dir(&path).unwrap() {
+            for file in std::fs::read_dir(folder.unwrap().path()).unwrap() {
+                files.push(file.unwrap().file_name().into_string().unwrap());
+            }
+        }
+        files.sort();
+
+        let mut batches =
+            local_sync::stream_batches(Arc::new(IngestMetrics::new()), 0, &path, "0.4.0")
+                .await
+                .unwrap();
+        let (_, blocks, _) = batches.next().await.unwrap().unwrap();
+        assert!(batches.next().await.is_none());
+
+        std::fs::remove_dir_all(&path).unwrap();
+
+        (blocks.into_iter().flatten().collect(), files)
+    }
+
+    fn to_json<T: serde::Serialize>(items: &[T]) -> Vec<Value> {
+        items
+            .iter()
+            .map(|item| serde_json::to_value(item).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let mut blocks = vec![block(0), block(1), block(2)];
+        blocks[0].traces = vec![trace(0, 0, Vec::new()), trace(0, 1, vec![0])];
+        blocks[2].traces = vec![trace(2, 0, vec![1, 2])];
+        blocks[1].state_diffs = vec![state_diff(1, 0, None), state_diff(1, 1, Some(7))];
+        blocks[1].withdrawals_root = Some(Bytes32::new(&[6; 32]));
+        blocks[1].withdrawals = vec![withdrawal(1, 0), withdrawal(1, 1)];
+        let expected = blocks.clone();
+
+        let (blocks, files) = round_trip(data(blocks), "round-trip").await;
+
+        assert_eq!(
+            files,
+            [
+                "block.parquet",
+                "log.parquet",
+                "state_diff.parquet",
+                "trace.parquet",
+                "tx.parquet",
+                "withdrawal.parquet"
+            ]
+        );
+        assert_eq!(blocks.len(), expected.len());
+        for (block, expected) in blocks.iter().zip(expected.iter()) {
+            assert_eq!(to_json(&block.traces), to_json(&expected.traces));
+            assert_eq!(to_json(&block.state_diffs), to_json(&expected.state_diffs));
+            assert_eq!(to_json(&block.withdrawals), to_json(&expected.withdrawals));
+            assert_eq!(block.withdrawals_root, expected.withdrawals_root);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_without_optional_files() {
+        let (blocks, files) = round_trip(
+            data(vec![block(0), block(1)]),
+            "round-trip-without-optional-files",
+        )
+        .await;
+
+        assert_eq!(files, ["block.parquet", "log.parquet", "tx.parquet"]);
+        assert_eq!(blocks.len(), 2);
+        assert!(blocks.iter().all(|block| block.traces.is_empty()
+            && block.state_diffs.is_empty()
+            && block.withdrawals.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_blob_fields() {
+        let mut blocks = vec![block(0), block(1)];
+        blocks[1].blob_gas_used = Some(Bytes::from_quantity(0x20000));
+        blocks[1].excess_blob_gas = Some(Bytes::from_quantity(0x40000));
+        blocks[1].parent_beacon_block_root = Some(Bytes32::new(&[9; 32]));
+        blocks[1].transactions = vec![blob_tx(1, 0)];
+        let expected = blocks.clone();
+
+        let (blocks, _) = round_trip(data(blocks), "round-trip-blob-fields").await;
+
+        assert_eq!(blocks.len(), expected.len());
+        for (block, expected) in blocks.iter().zip(expected.iter()) {
+            assert_eq!(block.blob_gas_used, expected.blob_gas_used);
+            assert_eq!(block.excess_blob_gas, expected.excess_blob_gas);
+            assert_eq!(
+                block.parent_beacon_block_root,
+                expected.parent_beacon_block_root
+            );
+            assert_eq!(block.transactions.len(), expected.transactions.len());
+        }
+
+        let tx = &blocks[1].transactions[0];
+        assert_eq!(tx.kind, Some(Index(3)));
+        assert_eq!(
+            tx.max_fee_per_blob_gas,
+            Some(Bytes::from_quantity(1_000_000_000))
+        );
+        assert_eq!(
+            tx.blob_versioned_hashes,
+            Some(vec![Bytes32::new(&[7; 32]), Bytes32::new(&[8; 32])])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_access_and_authorization_lists() {
+        let mut tx = blob_tx(1, 0);
+        tx.access_list = Some(
+            serde_json::from_value(json!([
+                { "address": hex(2, 20), "storageKeys": [hex(3, 32), hex(4, 32)] },
+                { "address": hex(5, 20), "storageKeys": [] },
+            ]))
+            .unwrap(),
+        );
+        tx.authorization_list = Some(
+            serde_json::from_value(json!([
+                {
+                    "chainId": "0x01",
+                    "address": hex(6, 20),
+                    "nonce": "0x02",
+                    "yParity": "0x1",
+                    "r": hex(7, 32),
+                    "s": hex(8, 32),
+                    "authority": hex(9, 20),
+                },
+                {
+                    "chainId": "0x01",
+                    "address": hex(10, 20),
+                    "nonce": "0x03",
+                    "yParity": "0x0",
+                    "r": hex(11, 32),
+                    "s": hex(12, 32),
+                },
+            ]))
+            .unwrap(),
+        );
+
+        let mut blocks = vec![block(0), block(1)];
+        blocks[0].transactions = vec![blob_tx(0, 0)];
+        blocks[1].transactions = vec![tx];
+        let expected = blocks.clone();
+
+        let (blocks, _) = round_trip(data(blocks), "round-trip-lists").await;
+
+        assert_eq!(blocks.len(), expected.len());
+        for (block, expected) in blocks.iter().zip(expected.iter()) {
+            let (tx, expected) = (&block.transactions[0], &expected.transactions[0]);
+            assert_eq!(
+                serde_json::to_value(&tx.access_list).unwrap(),
+                serde_json::to_value(&expected.access_list).unwrap()
+            );
+            assert_eq!(
+                serde_json::to_value(&tx.authorization_list).unwrap(),
+                serde_json::to_value(&expected.authorization_list).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_push_rejects_wide_quantities() {
+        let mut blocks = Blocks::default();
+        let mut wide_block = block(0);
+        wide_block.gas_limit = Bytes(vec![1; 9]);
+        assert!(matches!(
+            blocks.push(wide_block),
+            Err(Error::QuantityTooWide("gas_limit"))
+        ));
+        assert_eq!(blocks.len, 0);
+
+        let mut txs = Transactions::default();
+        let mut wide_tx = blob_tx(0, 0);
+        wide_tx.value = Bytes(vec![1; 33]);
+        assert!(matches!(
+            txs.push(wide_tx),
+            Err(Error::QuantityWiderThanU256("value"))
+        ));
+        assert_eq!(txs.len, 0);
+
+        let mut withdrawals = Withdrawals::default();
+        let mut wide_withdrawal = withdrawal(0, 0);
+        wide_withdrawal.amount = Bytes(vec![1; 9]);
+        assert!(matches!(
+            withdrawals.push(wide_withdrawal),
+            Err(Error::QuantityTooWide("amount"))
+        ));
+        assert_eq!(withdrawals.len, 0);
+    }
+}
@@ -1,19 +1,28 @@
-use crate::Error;
+use crate::{Error, Result};
 use arrow2::array::{
-    Array, MutableArray, MutableBinaryArray as ArrowMutableBinaryArray, MutableBooleanArray,
-    UInt32Vec, UInt64Vec,
+    Array, FixedSizeBinaryArray, ListArray, MutableArray,
+    MutableBinaryArray as ArrowMutableBinaryArray, MutableBooleanArray, MutableListArray,
+    MutableUtf8Array as ArrowMutableUtf8Array, PrimitiveArray, StructArray, TryPush, UInt32Vec,
+    UInt64Vec,
 };
+use arrow2::bitmap::MutableBitmap;
 use arrow2::chunk::Chunk as ArrowChunk;
 use arrow2::compute::sort::{lexsort_to_indices, sort_to_indices, SortColumn, SortOptions};
 use arrow2::compute::take::take as arrow_take;
 use arrow2::datatypes::{DataType, Field, Schema};
 use arrow2::error::Result as ArrowResult;
 use arrow2::io::parquet::write::{CompressionOptions, Version, WriteOptions};
-use eth_archive_core::types::{Block, Log, Transaction};
-use std::cmp;
+use arrow2::offset::Offsets;
+use arrow2::types::Index as ArrowIndex;
+use eth_archive_core::deserialize::{Bytes, U256};
+use eth_archive_core::types::{
+    AccessListItem, Authorization, Block, Log, StateDiff, Trace, Transaction, Withdrawal,
+};
+use std::{cmp, mem};
 
 type Chunk = ArrowChunk<Box<dyn Array>>;
 type MutableBinaryArray = ArrowMutableBinaryArray<i32>;
+type MutableUtf8Array = ArrowMutableUtf8Array<i32>;
 
 pub fn block_schema() -> Schema {
     Schema::from(vec![
@@ -25,17 +34,21 @@ pub fn block_schema() -> Schema {
         Field::new("receipts_root", DataType::Binary, false),
         Field::new("logs_bloom", DataType::Binary, false),
         Field::new("difficulty", DataType::Binary, true),
-        Field::new("number", DataType::UInt32, false),
-        Field::new("gas_limit", DataType::Binary, false),
-        Field::new("gas_used", DataType::Binary, false),
-        Field::new("timestamp", DataType::Binary, false),
+        Field::new("number", DataType::UInt64, false),
+        Field::new("gas_limit", DataType::UInt64, false),
+        Field::new("gas_used", DataType::UInt64, false),
+        Field::new("timestamp", DataType::UInt64, false),
         Field::new("extra_data", DataType::Binary, false),
         Field::new("mix_hash", DataType::Binary, true),
         Field::new("nonce", DataType::UInt64, true),
         Field::new("total_difficulty", DataType::Binary, true),
-        Field::new("base_fee_per_gas", DataType::Binary, true),
+        Field::new("base_fee_per_gas", DataType::FixedSizeBinary(32), true),
         Field::new("size", DataType::Binary, false),
         Field::new("hash", DataType::Binary, true),
+        Field::new("blob_gas_used", DataType::UInt64, true),
+        Field::new("excess_blob_gas", DataType::UInt64, true),
+        Field::new("parent_beacon_block_root", DataType::Binary, true),
+        Field::new("withdrawals_root", DataType::Binary, true),
     ])
 }
 
@@ -44,32 +57,77 @@ pub fn tx_schema() -> Schema {
         Field::new("kind", DataType::UInt32, true),
         Field::new("nonce", DataType::UInt64, false),
         Field::new("dest", DataType::Binary, true),
-        Field::new("gas", DataType::Binary, false),
-        Field::new("value", DataType::Binary, false),
+        Field::new("gas", DataType::UInt64, false),
+        Field::new("value", DataType::FixedSizeBinary(32), false),
         Field::new("input", DataType::Binary, false),
-        Field::new("max_priority_fee_per_gas", DataType::Binary, true),
-        Field::new("max_fee_per_gas", DataType::Binary, true),
+        Field::new(
+            "max_priority_fee_per_gas",
+            DataType::FixedSizeBinary(32),
+            true,
+        ),
+        Field::new("max_fee_per_gas", DataType::FixedSizeBinary(32), true),
         Field::new("y_parity", DataType::UInt32, true),
-        Field::new("chain_id", DataType::UInt32, true),
-        Field::new("v", DataType::UInt64, true),
+        Field::new("chain_id", DataType::UInt64, true),
+        Field::new("v", DataType::Binary, true),
         Field::new("r", DataType::Binary, false),
         Field::new("s", DataType::Binary, false),
         Field::new("source", DataType::Binary, true),
         Field::new("block_hash", DataType::Binary, false),
-        Field::new("block_number", DataType::UInt32, false),
+        Field::new("block_number", DataType::UInt64, false),
         Field::new("transaction_index", DataType::UInt32, false),
-        Field::new("gas_price", DataType::Binary, true),
+        Field::new("gas_price", DataType::FixedSizeBinary(32), true),
         Field::new("hash", DataType::Binary, false),
         Field::new("status", DataType::UInt32, true),
         Field::new("sighash", DataType::Binary, true),
+        // receipt fields, part of format version 0.2.0
+        Field::new("contract_address", DataType::Binary, true),
+        Field::new("gas_used", DataType::UInt64, true),
+        Field::new("cumulative_gas_used", DataType::UInt64, true),
+        Field::new("effective_gas_price", DataType::FixedSizeBinary(32), true),
+        Field::new("logs_bloom", DataType::Binary, true),
+        Field::new("max_fee_per_blob_gas", DataType::FixedSizeBinary(32), true),
+        Field::new(
+            "blob_versioned_hashes",
+            DataType::List(Box::new(Field::new("item", DataType::Binary, true))),
+            true,
+        ),
+        Field::new("access_list", access_list_type(), true),
+        Field::new("authorization_list", authorization_list_type(), true),
     ])
 }
 
+pub fn access_list_type() -> DataType {
+    let item = DataType::Struct(vec![
+        Field::new("address", DataType::Binary, false),
+        Field::new(
+            "storage_keys",
+            DataType::List(Box::new(Field::new("item", DataType::Binary, true))),
+            false,
+        ),
+    ]);
+
+    DataType::List(Box::new(Field::new("item", item, true)))
+}
+
+pub fn authorization_list_type() -> DataType {
+    let item = DataType::Struct(vec![
+        Field::new("chain_id", DataType::Binary, false),
+        Field::new("address", DataType::Binary, false),
+        Field::new("nonce", DataType::Binary, false),
+        Field::new("y_parity", DataType::UInt32, false),
+        Field::new("r", DataType::Binary, false),
+        Field::new("s", DataType::Binary, false),
+        Field::new("authority", DataType::Binary, true),
+    ]);
+
+    DataType::List(Box::new(Field::new("item", item, true)))
+}
+
 pub fn log_schema() -> Schema {
     Schema::from(vec![
         Field::new("address", DataType::Binary, false),
         Field::new("block_hash", DataType::Binary, false),
-        Field::new("block_number", DataType::UInt32, false),
+        Field::new("block_number", DataType::UInt64, false),
         Field::new("data", DataType::Binary, false),
         Field::new("log_index", DataType::UInt32, false),
         Field::new("removed", DataType::Boolean, true),
@@ -82,6 +140,55 @@ pub fn log_schema() -> Schema {
     ])
 }
 
+pub fn trace_schema() -> Schema {
+    Schema::from(vec![
+        Field::new("block_number", DataType::UInt64, false),
+        Field::new("transaction_index", DataType::UInt32, true),
+        Field::new("transaction_hash", DataType::Binary, true),
+        Field::new("trace_index", DataType::UInt32, false),
+        Field::new(
+            "trace_address",
+            DataType::List(Box::new(Field::new("item", DataType::UInt32, true))),
+            false,
+        ),
+        Field::new("subtraces", DataType::UInt32, false),
+        Field::new("call_type", DataType::Utf8, false),
+        Field::new("source", DataType::Binary, true),
+        Field::new("dest", DataType::Binary, true),
+        Field::new("value", DataType::Binary, true),
+        Field::new("gas", DataType::Binary, true),
+        Field::new("gas_used", DataType::Binary, true),
+        Field::new("input", DataType::Binary, true),
+        Field::new("output", DataType::Binary, true),
+        Field::new("error", DataType::Utf8, true),
+        Field::new("sighash", DataType::Binary, true),
+    ])
+}
+
+pub fn state_diff_schema() -> Schema {
+    Schema::from(vec![
+        Field::new("block_number", DataType::UInt64, false),
+        Field::new("transaction_index", DataType::UInt32, false),
+        Field::new("transaction_hash", DataType::Binary, false),
+        Field::new("diff_index", DataType::UInt32, false),
+        Field::new("address", DataType::Binary, false),
+        Field::new("kind", DataType::Utf8, false),
+        Field::new("key", DataType::Binary, true),
+        Field::new("prev", DataType::Binary, true),
+        Field::new("next", DataType::Binary, true),
+    ])
+}
+
+pub fn withdrawal_schema() -> Schema {
+    Schema::from(vec![
+        Field::new("block_number", DataType::UInt64, false),
+        Field::new("index", DataType::UInt32, false),
+        Field::new("validator_index", DataType::UInt32, false),
+        Field::new("address", DataType::Binary, false),
+        Field::new("amount", DataType::UInt64, false),
+    ])
+}
+
 #[derive(Debug, Default)]
 pub struct Blocks {
     pub parent_hash: MutableBinaryArray,
@@ -92,17 +199,21 @@ pub struct Blocks {
     pub receipts_root: MutableBinaryArray,
     pub logs_bloom: MutableBinaryArray,
     pub difficulty: MutableBinaryArray,
-    pub number: UInt32Vec,
-    pub gas_limit: MutableBinaryArray,
-    pub gas_used: MutableBinaryArray,
-    pub timestamp: MutableBinaryArray,
+    pub number: UInt64Vec,
+    pub gas_limit: UInt64Vec,
+    pub gas_used: UInt64Vec,
+    pub timestamp: UInt64Vec,
     pub extra_data: MutableBinaryArray,
     pub mix_hash: MutableBinaryArray,
     pub nonce: UInt64Vec,
     pub total_difficulty: MutableBinaryArray,
-    pub base_fee_per_gas: MutableBinaryArray,
+    pub base_fee_per_gas: MutableU256Array,
     pub size: MutableBinaryArray,
     pub hash: MutableBinaryArray,
+    pub blob_gas_used: UInt64Vec,
+    pub excess_blob_gas: UInt64Vec,
+    pub parent_beacon_block_root: MutableBinaryArray,
+    pub withdrawals_root: MutableBinaryArray,
     pub len: usize,
 }
 
@@ -138,9 +249,13 @@ impl IntoChunks for Blocks {
             arrow_take(self.mix_hash.as_box().as_ref(), &indices).unwrap(),
             arrow_take(self.nonce.as_box().as_ref(), &indices).unwrap(),
             arrow_take(self.total_difficulty.as_box().as_ref(), &indices).unwrap(),
-            arrow_take(self.base_fee_per_gas.as_box().as_ref(), &indices).unwrap(),
+            self.base_fee_per_gas.take(&indices),
             arrow_take(self.size.as_box().as_ref(), &indices).unwrap(),
             arrow_take(self.hash.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.blob_gas_used.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.excess_blob_gas.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.parent_beacon_block_root.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.withdrawals_root.as_box().as_ref(), &indices).unwrap(),
         ]);
 
         (0..self.len)
@@ -157,7 +272,17 @@ impl IntoChunks for Blocks {
 }
 
 impl Blocks {
-    pub fn push(&mut self, elem: Block) {
+    /// Fails without pushing anything if a quantity doesn't fit into its column
+    pub fn push(&mut self, elem: Block) -> Result<()> {
+        let gas_limit = quantity_to_u64(&elem.gas_limit, "gas_limit")?;
+        let gas_used = quantity_to_u64(&elem.gas_used, "gas_used")?;
+        let timestamp = quantity_to_u64(&elem.timestamp, "timestamp")?;
+        let base_fee_per_gas =
+            opt_quantity_to_u256(elem.base_fee_per_gas.as_ref(), "base_fee_per_gas")?;
+        let blob_gas_used = opt_quantity_to_u64(elem.blob_gas_used.as_ref(), "blob_gas_used")?;
+        let excess_blob_gas =
+            opt_quantity_to_u64(elem.excess_blob_gas.as_ref(), "excess_blob_gas")?;
+
         self.parent_hash.push(Some(elem.parent_hash.to_vec()));
         self.sha3_uncles.push(Some(elem.sha3_uncles.to_vec()));
         self.miner.push(Some(elem.miner.to_vec()));
@@ -168,20 +293,27 @@ impl Blocks {
         self.logs_bloom.push(Some(elem.logs_bloom.to_vec()));
         self.difficulty.push(elem.difficulty.map(|n| n.0));
         self.number.push(Some(elem.number.0));
-        self.gas_limit.push(Some(elem.gas_limit.0));
-        self.gas_used.push(Some(elem.gas_used.0));
-        self.timestamp.push(Some(elem.timestamp.0));
+        self.gas_limit.push(Some(gas_limit));
+        self.gas_used.push(Some(gas_used));
+        self.timestamp.push(Some(timestamp));
         self.extra_data.push(Some(elem.extra_data.0));
         self.mix_hash.push(elem.mix_hash.map(|n| n.to_vec()));
         self.nonce.push(elem.nonce.map(|n| n.0));
         self.total_difficulty
             .push(elem.total_difficulty.map(|n| n.0));
-        self.base_fee_per_gas
-            .push(elem.base_fee_per_gas.map(|n| n.0));
+        self.base_fee_per_gas.push(base_fee_per_gas);
         self.size.push(Some(elem.size.0));
         self.hash.push(elem.hash.map(|n| n.to_vec()));
+        self.blob_gas_used.push(blob_gas_used);
+        self.excess_blob_gas.push(excess_blob_gas);
+        self.parent_beacon_block_root
+            .push(elem.parent_beacon_block_root.map(|n| n.to_vec()));
+        self.withdrawals_root
+            .push(elem.withdrawals_root.map(|n| n.to_vec()));
 
         self.len += 1;
+
+        Ok(())
     }
 }
 
@@ -190,24 +322,33 @@ pub struct Transactions {
     pub kind: UInt32Vec,
     pub nonce: UInt64Vec,
     pub dest: MutableBinaryArray,
-    pub gas: MutableBinaryArray,
-    pub value: MutableBinaryArray,
+    pub gas: UInt64Vec,
+    pub value: MutableU256Array,
     pub input: MutableBinaryArray,
-    pub max_priority_fee_per_gas: MutableBinaryArray,
-    pub max_fee_per_gas: MutableBinaryArray,
+    pub max_priority_fee_per_gas: MutableU256Array,
+    pub max_fee_per_gas: MutableU256Array,
     pub y_parity: UInt32Vec,
-    pub chain_id: UInt32Vec,
-    pub v: UInt64Vec,
+    pub chain_id: UInt64Vec,
+    pub v: MutableBinaryArray,
     pub r: MutableBinaryArray,
     pub s: MutableBinaryArray,
     pub source: MutableBinaryArray,
     pub block_hash: MutableBinaryArray,
-    pub block_number: UInt32Vec,
+    pub block_number: UInt64Vec,
     pub transaction_index: UInt32Vec,
-    pub gas_price: MutableBinaryArray,
+    pub gas_price: MutableU256Array,
     pub hash: MutableBinaryArray,
     pub status: UInt32Vec,
     pub sighash: MutableBinaryArray,
+    pub contract_address: MutableBinaryArray,
+    pub gas_used: UInt64Vec,
+    pub cumulative_gas_used: UInt64Vec,
+    pub effective_gas_price: MutableU256Array,
+    pub logs_bloom: MutableBinaryArray,
+    pub max_fee_per_blob_gas: MutableU256Array,
+    pub blob_versioned_hashes: MutableListArray<i32, MutableBinaryArray>,
+    pub access_list: MutableAccessListArray,
+    pub authorization_list: MutableAuthorizationListArray,
     pub len: usize,
 }
 
@@ -244,10 +385,10 @@ impl IntoChunks for Transactions {
             arrow_take(self.nonce.as_box().as_ref(), &indices).unwrap(),
             arrow_take(self.dest.as_box().as_ref(), &indices).unwrap(),
             arrow_take(self.gas.as_box().as_ref(), &indices).unwrap(),
-            arrow_take(self.value.as_box().as_ref(), &indices).unwrap(),
+            self.value.take(&indices),
             arrow_take(self.input.as_box().as_ref(), &indices).unwrap(),
-            arrow_take(self.max_priority_fee_per_gas.as_box().as_ref(), &indices).unwrap(),
-            arrow_take(self.max_fee_per_gas.as_box().as_ref(), &indices).unwrap(),
+            self.max_priority_fee_per_gas.take(&indices),
+            self.max_fee_per_gas.take(&indices),
             arrow_take(self.y_parity.as_box().as_ref(), &indices).unwrap(),
             arrow_take(self.chain_id.as_box().as_ref(), &indices).unwrap(),
             arrow_take(self.v.as_box().as_ref(), &indices).unwrap(),
@@ -257,10 +398,19 @@ impl IntoChunks for Transactions {
             arrow_take(self.block_hash.as_box().as_ref(), &indices).unwrap(),
             arrow_take(block_number.as_ref(), &indices).unwrap(),
             arrow_take(transaction_index.as_ref(), &indices).unwrap(),
-            arrow_take(self.gas_price.as_box().as_ref(), &indices).unwrap(),
+            self.gas_price.take(&indices),
             arrow_take(self.hash.as_box().as_ref(), &indices).unwrap(),
             arrow_take(self.status.as_box().as_ref(), &indices).unwrap(),
             arrow_take(self.sighash.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.contract_address.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.gas_used.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.cumulative_gas_used.as_box().as_ref(), &indices).unwrap(),
+            self.effective_gas_price.take(&indices),
+            arrow_take(self.logs_bloom.as_box().as_ref(), &indices).unwrap(),
+            self.max_fee_per_blob_gas.take(&indices),
+            arrow_take(self.blob_versioned_hashes.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.access_list.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.authorization_list.as_box().as_ref(), &indices).unwrap(),
         ]);
 
         (0..self.len)
@@ -277,7 +427,25 @@ impl IntoChunks for Transactions {
 }
 
 impl Transactions {
-    pub fn push(&mut self, elem: Transaction) {
+    /// Fails without pushing anything if a quantity doesn't fit into its column
+    pub fn push(&mut self, elem: Transaction) -> Result<()> {
+        let gas = quantity_to_u64(&elem.gas, "gas")?;
+        let value = quantity_to_u256(&elem.value, "value")?;
+        let max_priority_fee_per_gas = opt_quantity_to_u256(
+            elem.max_priority_fee_per_gas.as_ref(),
+            "max_priority_fee_per_gas",
+        )?;
+        let max_fee_per_gas =
+            opt_quantity_to_u256(elem.max_fee_per_gas.as_ref(), "max_fee_per_gas")?;
+        let gas_price = opt_quantity_to_u256(elem.gas_price.as_ref(), "gas_price")?;
+        let gas_used = opt_quantity_to_u64(elem.gas_used.as_ref(), "gas_used")?;
+        let cumulative_gas_used =
+            opt_quantity_to_u64(elem.cumulative_gas_used.as_ref(), "cumulative_gas_used")?;
+        let effective_gas_price =
+            opt_quantity_to_u256(elem.effective_gas_price.as_ref(), "effective_gas_price")?;
+        let max_fee_per_blob_gas =
+            opt_quantity_to_u256(elem.max_fee_per_blob_gas.as_ref(), "max_fee_per_blob_gas")?;
+
         self.sighash.push(elem.input.get(..4));
         self.kind.push(elem.kind.map(|n| n.0));
         self.nonce.push(Some(elem.nonce.0));
@@ -285,12 +453,11 @@ impl Transactions {
             Some(dest) => self.dest.push(Some(dest.to_vec())),
             None => self.dest.push::<&[u8]>(None),
         }
-        self.gas.push(Some(elem.gas.0));
-        self.value.push(Some(elem.value.0));
+        self.gas.push(Some(gas));
+        self.value.push(Some(value));
         self.input.push(Some(elem.input.0));
-        self.max_priority_fee_per_gas
-            .push(elem.max_priority_fee_per_gas.map(|n| n.0));
-        self.max_fee_per_gas.push(elem.max_fee_per_gas.map(|n| n.0));
+        self.max_priority_fee_per_gas.push(max_priority_fee_per_gas);
+        self.max_fee_per_gas.push(max_fee_per_gas);
         self.y_parity.push(elem.y_parity.map(|n| n.0));
         self.chain_id.push(elem.chain_id.map(|n| n.0));
         self.v.push(elem.v.map(|n| n.0));
@@ -300,11 +467,205 @@ impl Transactions {
         self.block_hash.push(Some(elem.block_hash.to_vec()));
         self.block_number.push(Some(elem.block_number.0));
         self.transaction_index.push(Some(elem.transaction_index.0));
-        self.gas_price.push(elem.gas_price.map(|n| n.0));
+        self.gas_price.push(gas_price);
         self.hash.push(Some(elem.hash.to_vec()));
         self.status.push(elem.status.map(|n| n.0));
+        self.contract_address
+            .push(elem.contract_address.map(|n| n.to_vec()));
+        self.gas_used.push(gas_used);
+        self.cumulative_gas_used.push(cumulative_gas_used);
+        self.effective_gas_price.push(effective_gas_price);
+        self.logs_bloom.push(elem.logs_bloom.map(|n| n.to_vec()));
+        self.max_fee_per_blob_gas.push(max_fee_per_blob_gas);
+        self.blob_versioned_hashes
+            .try_push(
+                elem.blob_versioned_hashes
+                    .map(|hashes| hashes.into_iter().map(|hash| Some(hash.to_vec()))),
+            )
+            .unwrap();
+        self.access_list.push(elem.access_list.as_deref());
+        self.authorization_list
+            .push(elem.authorization_list.as_deref());
 
         self.len += 1;
+
+        Ok(())
+    }
+}
+
+/// Builds `FixedSizeBinary(32)` columns of big endian u256 quantities.
+/// Sorted with `take` instead of `arrow_take` since the values are kept as `U256`s until the
+/// column is built.
+#[derive(Debug, Default)]
+pub struct MutableU256Array {
+    values: Vec<Option<U256>>,
+}
+
+impl MutableU256Array {
+    pub fn push(&mut self, quantity: Option<U256>) {
+        self.values.push(quantity);
+    }
+
+    pub fn take<I: ArrowIndex>(&self, indices: &PrimitiveArray<I>) -> Box<dyn Array> {
+        Self::build(indices.values_iter().map(|idx| self.values[idx.to_usize()]))
+    }
+
+    pub fn as_box(&mut self) -> Box<dyn Array> {
+        Self::build(mem::take(&mut self.values).into_iter())
+    }
+
+    fn build<V: ExactSizeIterator<Item = Option<U256>>>(vals: V) -> Box<dyn Array> {
+        let mut values = Vec::with_capacity(vals.len() * 32);
+        let mut validity = MutableBitmap::with_capacity(vals.len());
+
+        for val in vals {
+            values.extend_from_slice(&val.unwrap_or_default().0);
+            validity.push(val.is_some());
+        }
+
+        FixedSizeBinaryArray::new(
+            DataType::FixedSizeBinary(32),
+            values.into(),
+            validity.into(),
+        )
+        .boxed()
+    }
+}
+
+fn quantity_to_u64(quantity: &Bytes, name: &'static str) -> Result<u64> {
+    quantity.to_u64().ok_or(Error::QuantityTooWide(name))
+}
+
+fn opt_quantity_to_u64(quantity: Option<&Bytes>, name: &'static str) -> Result<Option<u64>> {
+    quantity.map(|q| quantity_to_u64(q, name)).transpose()
+}
+
+fn quantity_to_u256(quantity: &Bytes, name: &'static str) -> Result<U256> {
+    U256::from_quantity(quantity).ok_or(Error::QuantityWiderThanU256(name))
+}
+
+fn opt_quantity_to_u256(quantity: Option<&Bytes>, name: &'static str) -> Result<Option<U256>> {
+    quantity.map(|q| quantity_to_u256(q, name)).transpose()
+}
+
+/// Builds the nested `access_list` column of the transaction table
+#[derive(Debug, Default)]
+pub struct MutableAccessListArray {
+    address: MutableBinaryArray,
+    storage_keys: MutableListArray<i32, MutableBinaryArray>,
+    offsets: Offsets<i32>,
+    validity: MutableBitmap,
+}
+
+impl MutableAccessListArray {
+    pub fn push(&mut self, access_list: Option<&[AccessListItem]>) {
+        match access_list {
+            Some(access_list) => {
+                for item in access_list {
+                    self.address.push(Some(item.address.to_vec()));
+                    self.storage_keys
+                        .try_push(Some(item.storage_keys.iter().map(|key| Some(key.to_vec()))))
+                        .unwrap();
+                }
+                self.offsets.try_push_usize(access_list.len()).unwrap();
+                self.validity.push(true);
+            }
+            None => {
+                self.offsets.extend_constant(1);
+                self.validity.push(false);
+            }
+        }
+    }
+
+    pub fn as_box(&mut self) -> Box<dyn Array> {
+        let item_type = match access_list_type() {
+            DataType::List(field) => field.data_type,
+            _ => unreachable!(),
+        };
+
+        let values = StructArray::new(
+            item_type,
+            vec![self.address.as_box(), self.storage_keys.as_box()],
+            None,
+        );
+
+        ListArray::<i32>::new(
+            access_list_type(),
+            mem::take(&mut self.offsets).into(),
+            values.boxed(),
+            mem::take(&mut self.validity).into(),
+        )
+        .boxed()
+    }
+}
+
+/// Builds the nested `authorization_list` column of the transaction table
+#[derive(Debug, Default)]
+pub struct MutableAuthorizationListArray {
+    chain_id: MutableBinaryArray,
+    address: MutableBinaryArray,
+    nonce: MutableBinaryArray,
+    y_parity: UInt32Vec,
+    r: MutableBinaryArray,
+    s: MutableBinaryArray,
+    authority: MutableBinaryArray,
+    offsets: Offsets<i32>,
+    validity: MutableBitmap,
+}
+
+impl MutableAuthorizationListArray {
+    pub fn push(&mut self, authorization_list: Option<&[Authorization]>) {
+        match authorization_list {
+            Some(authorization_list) => {
+                for auth in authorization_list {
+                    self.chain_id.push(Some(auth.chain_id.0.as_slice()));
+                    self.address.push(Some(auth.address.to_vec()));
+                    self.nonce.push(Some(auth.nonce.0.as_slice()));
+                    self.y_parity.push(Some(auth.y_parity.0));
+                    self.r.push(Some(auth.r.0.as_slice()));
+                    self.s.push(Some(auth.s.0.as_slice()));
+                    self.authority
+                        .push(auth.authority.as_ref().map(|addr| addr.to_vec()));
+                }
+                self.offsets
+                    .try_push_usize(authorization_list.len())
+                    .unwrap();
+                self.validity.push(true);
+            }
+            None => {
+                self.offsets.extend_constant(1);
+                self.validity.push(false);
+            }
+        }
+    }
+
+    pub fn as_box(&mut self) -> Box<dyn Array> {
+        let item_type = match authorization_list_type() {
+            DataType::List(field) => field.data_type,
+            _ => unreachable!(),
+        };
+
+        let values = StructArray::new(
+            item_type,
+            vec![
+                self.chain_id.as_box(),
+                self.address.as_box(),
+                self.nonce.as_box(),
+                self.y_parity.as_box(),
+                self.r.as_box(),
+                self.s.as_box(),
+                self.authority.as_box(),
+            ],
+            None,
+        );
+
+        ListArray::<i32>::new(
+            authorization_list_type(),
+            mem::take(&mut self.offsets).into(),
+            values.boxed(),
+            mem::take(&mut self.validity).into(),
+        )
+        .boxed()
     }
 }
 
@@ -312,7 +673,7 @@ impl Transactions {
 pub struct Logs {
     pub address: MutableBinaryArray,
     pub block_hash: MutableBinaryArray,
-    pub block_number: UInt32Vec,
+    pub block_number: UInt64Vec,
     pub data: MutableBinaryArray,
     pub log_index: UInt32Vec,
     pub removed: MutableBooleanArray,
@@ -400,9 +761,275 @@ impl Logs {
     }
 }
 
+#[derive(Debug, Default)]
+pub struct Traces {
+    pub block_number: UInt64Vec,
+    pub transaction_index: UInt32Vec,
+    pub transaction_hash: MutableBinaryArray,
+    pub trace_index: UInt32Vec,
+    pub trace_address: MutableListArray<i32, UInt32Vec>,
+    pub subtraces: UInt32Vec,
+    pub call_type: MutableUtf8Array,
+    pub source: MutableBinaryArray,
+    pub dest: MutableBinaryArray,
+    pub value: MutableBinaryArray,
+    pub gas: MutableBinaryArray,
+    pub gas_used: MutableBinaryArray,
+    pub input: MutableBinaryArray,
+    pub output: MutableBinaryArray,
+    pub error: MutableUtf8Array,
+    pub sighash: MutableBinaryArray,
+    pub len: usize,
+}
+
+impl IntoChunks for Traces {
+    fn into_chunks(mut self, items_per_chunk: usize) -> Vec<ArrowResult<Chunk>> {
+        let block_number = self.block_number.as_box();
+        let trace_index = self.trace_index.as_box();
+
+        let indices = lexsort_to_indices::<i64>(
+            &[
+                SortColumn {
+                    values: block_number.as_ref(),
+                    options: Some(SortOptions {
+                        descending: false,
+                        nulls_first: false,
+                    }),
+                },
+                SortColumn {
+                    values: trace_index.as_ref(),
+                    options: Some(SortOptions {
+                        descending: false,
+                        nulls_first: false,
+                    }),
+                },
+            ],
+            None,
+        )
+        .map_err(Error::SortRowGroup)
+        .unwrap();
+
+        let chunk = Chunk::new(vec![
+            arrow_take(block_number.as_ref(), &indices).unwrap(),
+            arrow_take(self.transaction_index.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.transaction_hash.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(trace_index.as_ref(), &indices).unwrap(),
+            arrow_take(self.trace_address.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.subtraces.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.call_type.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.source.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.dest.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.value.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.gas.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.gas_used.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.input.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.output.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.error.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.sighash.as_box().as_ref(), &indices).unwrap(),
+        ]);
+
+        (0..self.len)
+            .step_by(items_per_chunk)
+            .map(|start| {
+                let end = cmp::min(self.len, start + items_per_chunk);
+                let length = end - start;
+                Ok(Chunk::new(
+                    chunk.iter().map(|arr| arr.sliced(start, length)).collect(),
+                ))
+            })
+            .collect()
+    }
+}
+
+impl Traces {
+    pub fn push(&mut self, elem: Trace) {
+        self.sighash
+            .push(elem.input.as_ref().and_then(|input| input.get(..4)));
+        self.block_number.push(Some(elem.block_number.0));
+        self.transaction_index
+            .push(elem.transaction_index.map(|n| n.0));
+        self.transaction_hash
+            .push(elem.transaction_hash.map(|n| n.to_vec()));
+        self.trace_index.push(Some(elem.trace_index.0));
+        self.trace_address
+            .try_push(Some(elem.trace_address.into_iter().map(Some)))
+            .unwrap();
+        self.subtraces.push(Some(elem.subtraces.0));
+        self.call_type.push(Some(elem.call_type));
+        self.source.push(elem.source.map(|n| n.to_vec()));
+        self.dest.push(elem.dest.map(|n| n.to_vec()));
+        self.value.push(elem.value.map(|n| n.0));
+        self.gas.push(elem.gas.map(|n| n.0));
+        self.gas_used.push(elem.gas_used.map(|n| n.0));
+        self.input.push(elem.input.map(|n| n.0));
+        self.output.push(elem.output.map(|n| n.0));
+        self.error.push(elem.error);
+
+        self.len += 1;
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct StateDiffs {
+    pub block_number: UInt64Vec,
+    pub transaction_index: UInt32Vec,
+    pub transaction_hash: MutableBinaryArray,
+    pub diff_index: UInt32Vec,
+    pub address: MutableBinaryArray,
+    pub kind: MutableUtf8Array,
+    pub key: MutableBinaryArray,
+    pub prev: MutableBinaryArray,
+    pub next: MutableBinaryArray,
+    pub len: usize,
+}
+
+impl IntoChunks for StateDiffs {
+    fn into_chunks(mut self, items_per_chunk: usize) -> Vec<ArrowResult<Chunk>> {
+        let address = self.address.as_box();
+        let block_number = self.block_number.as_box();
+
+        let indices = lexsort_to_indices::<i64>(
+            &[
+                SortColumn {
+                    values: address.as_ref(),
+                    options: Some(SortOptions {
+                        descending: false,
+                        nulls_first: false,
+                    }),
+                },
+                SortColumn {
+                    values: block_number.as_ref(),
+                    options: Some(SortOptions {
+                        descending: false,
+                        nulls_first: false,
+                    }),
+                },
+            ],
+            None,
+        )
+        .map_err(Error::SortRowGroup)
+        .unwrap();
+
+        let chunk = Chunk::new(vec![
+            arrow_take(block_number.as_ref(), &indices).unwrap(),
+            arrow_take(self.transaction_index.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.transaction_hash.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.diff_index.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(address.as_ref(), &indices).unwrap(),
+            arrow_take(self.kind.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.key.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.prev.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.next.as_box().as_ref(), &indices).unwrap(),
+        ]);
+
+        (0..self.len)
+            .step_by(items_per_chunk)
+            .map(|start| {
+                let end = cmp::min(self.len, start + items_per_chunk);
+                let length = end - start;
+                Ok(Chunk::new(
+                    chunk.iter().map(|arr| arr.sliced(start, length)).collect(),
+                ))
+            })
+            .collect()
+    }
+}
+
+impl StateDiffs {
+    pub fn push(&mut self, elem: StateDiff) {
+        self.block_number.push(Some(elem.block_number.0));
+        self.transaction_index.push(Some(elem.transaction_index.0));
+        self.transaction_hash
+            .push(Some(elem.transaction_hash.to_vec()));
+        self.diff_index.push(Some(elem.diff_index.0));
+        self.address.push(Some(elem.address.to_vec()));
+        self.kind.push(Some(elem.kind));
+        self.key.push(elem.key.map(|n| n.to_vec()));
+        self.prev.push(elem.prev.map(|n| n.0));
+        self.next.push(elem.next.map(|n| n.0));
+
+        self.len += 1;
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Withdrawals {
+    pub block_number: UInt64Vec,
+    pub index: UInt32Vec,
+    pub validator_index: UInt32Vec,
+    pub address: MutableBinaryArray,
+    pub amount: UInt64Vec,
+    pub len: usize,
+}
+
+impl IntoChunks for Withdrawals {
+    fn into_chunks(mut self, items_per_chunk: usize) -> Vec<ArrowResult<Chunk>> {
+        let address = self.address.as_box();
+        let block_number = self.block_number.as_box();
+
+        let indices = lexsort_to_indices::<i64>(
+            &[
+                SortColumn {
+                    values: address.as_ref(),
+                    options: Some(SortOptions {
+                        descending: false,
+                        nulls_first: false,
+                    }),
+                },
+                SortColumn {
+                    values: block_number.as_ref(),
+                    options: Some(SortOptions {
+                        descending: false,
+                        nulls_first: false,
+                    }),
+                },
+            ],
+            None,
+        )
+        .map_err(Error::SortRowGroup)
+        .unwrap();
+
+        let chunk = Chunk::new(vec![
+            arrow_take(block_number.as_ref(), &indices).unwrap(),
+            arrow_take(self.index.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(self.validator_index.as_box().as_ref(), &indices).unwrap(),
+            arrow_take(address.as_ref(), &indices).unwrap(),
+            arrow_take(self.amount.as_box().as_ref(), &indices).unwrap(),
+        ]);
+
+        (0..self.len)
+            .step_by(items_per_chunk)
+            .map(|start| {
+                let end = cmp::min(self.len, start + items_per_chunk);
+                let length = end - start;
+                Ok(Chunk::new(
+                    chunk.iter().map(|arr| arr.sliced(start, length)).collect(),
+                ))
+            })
+            .collect()
+    }
+}
+
+impl Withdrawals {
+    /// Fails without pushing anything if the amount doesn't fit into a u64
+    pub fn push(&mut self, elem: Withdrawal) -> Result<()> {
+        let amount = quantity_to_u64(&elem.amount, "amount")?;
+
+        self.block_number.push(Some(elem.block_number.0));
+        self.index.push(Some(elem.index.0));
+        self.validator_index.push(Some(elem.validator_index.0));
+        self.address.push(Some(elem.address.to_vec()));
+        self.amount.push(Some(amount));
+
+        self.len += 1;
+
+        Ok(())
+    }
+}
+
 pub fn parquet_write_options(page_size: Option<usize>) -> WriteOptions {
     WriteOptions {
-        write_statistics: false,
+        write_statistics: true,
         compression: CompressionOptions::Lz4Raw,
         version: Version::V2,
         data_pagesize_limit: page_size,
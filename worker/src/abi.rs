@@ -0,0 +1,772 @@
+use eth_archive_core::deserialize::{Bytes32, Sighash};
+use eth_archive_core::hash::HashMap;
+use eth_archive_core::types::{ResponseLog, ResponseTransaction};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Value};
+use std::iter;
+use thiserror::Error as ThisError;
+use tiny_keccak::{Hasher, Keccak};
+
+#[derive(Debug, ThisError)]
+pub enum AbiError {
+    #[error("invalid signature \"{0}\"")]
+    InvalidSignature(String),
+    #[error("invalid type \"{0}\"")]
+    InvalidType(String),
+    #[error("function parameters can't be indexed")]
+    IndexedFunctionParam,
+    #[error("expected {expected} topics but got {got}")]
+    TopicCount { expected: usize, got: usize },
+    #[error("sighash of the input doesn't match the function")]
+    SighashMismatch,
+    #[error("data is too short")]
+    DataTooShort,
+    #[error("invalid offset or length in data")]
+    InvalidOffset,
+    #[error("invalid {0} value in data")]
+    InvalidValue(&'static str),
+}
+
+pub type AbiResult<T> = Result<T, AbiError>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamType {
+    Address,
+    Bool,
+    Uint(usize),
+    Int(usize),
+    FixedBytes(usize),
+    Bytes,
+    String,
+    Array(Box<ParamType>),
+    FixedArray(Box<ParamType>, usize),
+    Tuple(Vec<ParamType>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: Option<String>,
+    pub kind: ParamType,
+    pub indexed: bool,
+}
+
+/// Event signature like `Transfer(address indexed from,address indexed to,uint256 value)`.
+/// Parameter names are optional.
+#[derive(Debug, Clone)]
+pub struct Event {
+    signature: String,
+    name: String,
+    inputs: Vec<Param>,
+}
+
+/// Function signature like `transfer(address to,uint256 amount)`.
+/// Parameter names are optional.
+#[derive(Debug, Clone)]
+pub struct Function {
+    signature: String,
+    name: String,
+    inputs: Vec<Param>,
+}
+
+/// Events and functions given in a query, keyed by their topic0 and sighash.
+#[derive(Debug, Default)]
+pub struct Abi {
+    events: HashMap<Bytes32, Vec<Event>>,
+    functions: HashMap<[u8; 4], Vec<Function>>,
+}
+
+impl Abi {
+    pub fn new<'a, E, F>(events: E, functions: F) -> Self
+    where
+        E: Iterator<Item = &'a Event>,
+        F: Iterator<Item = &'a Function>,
+    {
+        let mut abi = Self::default();
+
+        for event in events {
+            let entry = abi.events.entry(event.topic0()).or_default();
+            if !entry.iter().any(|e| e.signature == event.signature) {
+                entry.push(event.clone());
+            }
+        }
+
+        for function in functions {
+            let entry = abi.functions.entry(*function.sighash().0).or_default();
+            if !entry.iter().any(|f| f.signature == function.signature) {
+                entry.push(function.clone());
+            }
+        }
+
+        abi
+    }
+
+    pub fn has_events(&self) -> bool {
+        !self.events.is_empty()
+    }
+
+    pub fn has_functions(&self) -> bool {
+        !self.functions.is_empty()
+    }
+
+    /// Returns None if topic0 of the log doesn't belong to any of the events.
+    ///
+    /// Events that share topic0 but differ in which parameters are indexed (like ERC20
+    /// and ERC721 transfers) are tried in order and the first one that decodes is used.
+    pub fn decode_log(&self, log: &ResponseLog) -> Option<Value> {
+        let topics = log.topics.as_ref()?;
+        let events = self.events.get(topics.first()?)?;
+        let data = log.data.as_ref().map_or(&[][..], |data| data.as_slice());
+
+        first_decoded(events.iter().map(|event| event.decode(topics, data)))
+    }
+
+    /// Returns None if the sighash of the transaction doesn't belong to any of the functions.
+    pub fn decode_tx(&self, tx: &ResponseTransaction) -> Option<Value> {
+        let input = tx.input.as_ref()?;
+        let sighash: [u8; 4] = input.get(..4)?.try_into().unwrap();
+        let functions = self.functions.get(&sighash)?;
+
+        first_decoded(functions.iter().map(|function| function.decode(input)))
+    }
+}
+
+fn first_decoded<I: Iterator<Item = AbiResult<Value>>>(results: I) -> Option<Value> {
+    let mut error = None;
+
+    for res in results {
+        match res {
+            Ok(decoded) => return Some(decoded),
+            Err(e) => error = Some(e),
+        }
+    }
+
+    error.map(|e| json!({ "error": e.to_string() }))
+}
+
+impl Event {
+    pub fn parse(signature: &str) -> AbiResult<Self> {
+        let (name, inputs) = parse_signature(signature)?;
+
+        Ok(Self {
+            signature: signature.to_owned(),
+            name,
+            inputs,
+        })
+    }
+
+    pub fn topic0(&self) -> Bytes32 {
+        Bytes32::new(&keccak256(canonical(&self.name, &self.inputs).as_bytes()))
+    }
+
+    pub fn decode(&self, topics: &[Bytes32], data: &[u8]) -> AbiResult<Value> {
+        let num_indexed = self.inputs.iter().filter(|param| param.indexed).count();
+        if topics.len() != num_indexed + 1 {
+            return Err(AbiError::TopicCount {
+                expected: num_indexed + 1,
+                got: topics.len(),
+            });
+        }
+
+        let mut topics = topics[1..].iter();
+        let mut values = decode_seq(
+            self.inputs
+                .iter()
+                .filter(|param| !param.indexed)
+                .map(|param| &param.kind),
+            data,
+        )?
+        .into_iter();
+
+        let params = self
+            .inputs
+            .iter()
+            .map(|param| {
+                let value = if !param.indexed {
+                    values.next().unwrap()
+                } else {
+                    let topic = topics.next().unwrap();
+                    // indexed reference types are stored as the hash of their encoding
+                    if param.kind.is_value_type() {
+                        decode_value(&param.kind, topic.as_slice())?
+                    } else {
+                        Value::String(prefix_hex::encode(topic.as_slice()))
+                    }
+                };
+
+                Ok(param.to_json(value))
+            })
+            .collect::<AbiResult<Vec<_>>>()?;
+
+        Ok(json!({ "name": self.name, "params": params }))
+    }
+}
+
+impl Function {
+    pub fn parse(signature: &str) -> AbiResult<Self> {
+        let (name, inputs) = parse_signature(signature)?;
+
+        if inputs.iter().any(|param| param.indexed) {
+            return Err(AbiError::IndexedFunctionParam);
+        }
+
+        Ok(Self {
+            signature: signature.to_owned(),
+            name,
+            inputs,
+        })
+    }
+
+    pub fn sighash(&self) -> Sighash {
+        Sighash::new(&keccak256(canonical(&self.name, &self.inputs).as_bytes())[..4])
+    }
+
+    pub fn decode(&self, input: &[u8]) -> AbiResult<Value> {
+        if input.get(..4) != Some(self.sighash().as_slice()) {
+            return Err(AbiError::SighashMismatch);
+        }
+
+        let values = decode_seq(self.inputs.iter().map(|param| &param.kind), &input[4..])?;
+
+        let params = self
+            .inputs
+            .iter()
+            .zip(values)
+            .map(|(param, value)| param.to_json(value))
+            .collect::<Vec<_>>();
+
+        Ok(json!({ "name": self.name, "params": params }))
+    }
+}
+
+impl Param {
+    fn to_json(&self, value: Value) -> Value {
+        json!({
+            "name": self.name,
+            "type": self.kind.canonical(),
+            "value": value,
+        })
+    }
+}
+
+impl ParamType {
+    fn parse(s: &str) -> AbiResult<Self> {
+        let invalid = || AbiError::InvalidType(s.to_owned());
+
+        if let Some(inner) = s.strip_suffix(']') {
+            let open = inner.rfind('[').ok_or_else(invalid)?;
+            let elem = Box::new(Self::parse(&inner[..open])?);
+            let size = &inner[open + 1..];
+
+            // zero sized types are rejected so the length of an array is bounded by the data size
+            return if size.is_empty() {
+                Ok(Self::Array(elem))
+            } else {
+                match size.parse() {
+                    Ok(size) if size > 0 => Ok(Self::FixedArray(elem, size)),
+                    _ => Err(invalid()),
+                }
+            };
+        }
+
+        if let Some(inner) = s.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+            let elems = split_params(inner)?
+                .into_iter()
+                .map(|param| parse_param(param).map(|param| param.kind))
+                .collect::<AbiResult<Vec<_>>>()?;
+
+            if elems.is_empty() {
+                return Err(invalid());
+            }
+
+            return Ok(Self::Tuple(elems));
+        }
+
+        let parse_bits = |bits: &str| match bits.parse::<usize>() {
+            Ok(bits) if (1..=256).contains(&bits) && bits % 8 == 0 => Ok(bits),
+            _ => Err(invalid()),
+        };
+
+        match s {
+            "address" => Ok(Self::Address),
+            "bool" => Ok(Self::Bool),
+            "string" => Ok(Self::String),
+            "bytes" => Ok(Self::Bytes),
+            "function" => Ok(Self::FixedBytes(24)),
+            "uint" => Ok(Self::Uint(256)),
+            "int" => Ok(Self::Int(256)),
+            _ => {
+                if let Some(bits) = s.strip_prefix("uint") {
+                    parse_bits(bits).map(Self::Uint)
+                } else if let Some(bits) = s.strip_prefix("int") {
+                    parse_bits(bits).map(Self::Int)
+                } else if let Some(size) = s.strip_prefix("bytes") {
+                    match size.parse::<usize>() {
+                        Ok(size) if (1..=32).contains(&size) => Ok(Self::FixedBytes(size)),
+                        _ => Err(invalid()),
+                    }
+                } else {
+                    Err(invalid())
+                }
+            }
+        }
+    }
+
+    fn canonical(&self) -> String {
+        match self {
+            Self::Address => "address".to_owned(),
+            Self::Bool => "bool".to_owned(),
+            Self::Uint(bits) => format!("uint{}", bits),
+            Self::Int(bits) => format!("int{}", bits),
+            Self::FixedBytes(size) => format!("bytes{}", size),
+            Self::Bytes => "bytes".to_owned(),
+            Self::String => "string".to_owned(),
+            Self::Array(elem) => format!("{}[]", elem.canonical()),
+            Self::FixedArray(elem, size) => format!("{}[{}]", elem.canonical(), size),
+            Self::Tuple(elems) => {
+                let elems = elems.iter().map(Self::canonical).collect::<Vec<_>>();
+                format!("({})", elems.join(","))
+            }
+        }
+    }
+
+    fn is_value_type(&self) -> bool {
+        matches!(
+            self,
+            Self::Address | Self::Bool | Self::Uint(_) | Self::Int(_) | Self::FixedBytes(_)
+        )
+    }
+
+    fn is_dynamic(&self) -> bool {
+        match self {
+            Self::Bytes | Self::String | Self::Array(_) => true,
+            Self::FixedArray(elem, _) => elem.is_dynamic(),
+            Self::Tuple(elems) => elems.iter().any(Self::is_dynamic),
+            _ => false,
+        }
+    }
+
+    /// Size of the type in the head of the enclosing tuple.
+    fn head_size(&self) -> usize {
+        if self.is_dynamic() {
+            return 32;
+        }
+
+        match self {
+            Self::FixedArray(elem, size) => elem.head_size().saturating_mul(*size),
+            Self::Tuple(elems) => elems.iter().map(Self::head_size).sum(),
+            _ => 32,
+        }
+    }
+}
+
+fn parse_signature(signature: &str) -> AbiResult<(String, Vec<Param>)> {
+    let invalid = || AbiError::InvalidSignature(signature.to_owned());
+
+    let signature = signature.trim();
+    let open = signature.find('(').ok_or_else(invalid)?;
+    let params = signature[open + 1..]
+        .strip_suffix(')')
+        .ok_or_else(invalid)?;
+    let name = signature[..open].trim();
+
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(invalid());
+    }
+
+    let inputs = split_params(params)?
+        .into_iter()
+        .map(parse_param)
+        .collect::<AbiResult<Vec<_>>>()?;
+
+    Ok((name.to_owned(), inputs))
+}
+
+/// Splits a parameter list at the commas that aren't inside a tuple.
+fn split_params(params: &str) -> AbiResult<Vec<&str>> {
+    let invalid = || AbiError::InvalidSignature(params.to_owned());
+
+    if params.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut split = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+
+    for (i, c) in params.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.checked_sub(1).ok_or_else(invalid)?,
+            ',' if depth == 0 => {
+                split.push(&params[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+
+    if depth != 0 {
+        return Err(invalid());
+    }
+
+    split.push(&params[start..]);
+
+    Ok(split)
+}
+
+/// Parses a parameter like `address indexed from`.
+fn parse_param(param: &str) -> AbiResult<Param> {
+    let invalid = || AbiError::InvalidSignature(param.to_owned());
+
+    let param = param.trim();
+    let param = param.strip_prefix("tuple(").map_or(param, |_| &param[5..]);
+
+    // the type of a tuple can contain whitespace so it ends at its closing parenthesis
+    let type_end = if param.starts_with('(') {
+        let mut depth = 0usize;
+        let close = param
+            .char_indices()
+            .find(|&(_, c)| {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => (),
+                }
+                depth == 0
+            })
+            .ok_or_else(invalid)?
+            .0;
+        param[close..]
+            .find(char::is_whitespace)
+            .map_or(param.len(), |end| close + end)
+    } else {
+        param.find(char::is_whitespace).unwrap_or(param.len())
+    };
+
+    let kind = ParamType::parse(&param[..type_end])?;
+
+    let words = param[type_end..].split_whitespace().collect::<Vec<_>>();
+    let (indexed, name) = match words.as_slice() {
+        [] => (false, None),
+        ["indexed"] => (true, None),
+        ["indexed", name] => (true, Some(name)),
+        [name] => (false, Some(name)),
+        _ => return Err(invalid()),
+    };
+
+    Ok(Param {
+        name: name.map(|name| name.to_string()),
+        kind,
+        indexed,
+    })
+}
+
+fn canonical(name: &str, inputs: &[Param]) -> String {
+    let inputs = inputs
+        .iter()
+        .map(|param| param.kind.canonical())
+        .collect::<Vec<_>>();
+
+    format!("{}({})", name, inputs.join(","))
+}
+
+fn keccak256(input: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    hasher.update(input);
+    let mut output = [0; 32];
+    hasher.finalize(&mut output);
+    output
+}
+
+/// Decodes a sequence of values that is encoded like a tuple.
+fn decode_seq<'a, I>(kinds: I, data: &[u8]) -> AbiResult<Vec<Value>>
+where
+    I: Iterator<Item = &'a ParamType>,
+{
+    let mut head = 0;
+
+    kinds
+        .map(|kind| {
+            let value = if kind.is_dynamic() {
+                let offset = read_usize(data, head)?;
+                let tail = data.get(offset..).ok_or(AbiError::InvalidOffset)?;
+                decode_value(kind, tail)?
+            } else {
+                let data = data.get(head..).ok_or(AbiError::DataTooShort)?;
+                decode_value(kind, data)?
+            };
+
+            head += kind.head_size();
+
+            Ok(value)
+        })
+        .collect()
+}
+
+fn decode_value(kind: &ParamType, data: &[u8]) -> AbiResult<Value> {
+    match kind {
+        ParamType::Address => {
+            let word = read_word(data, 0)?;
+            if word[..12].iter().any(|&b| b != 0) {
+                return Err(AbiError::InvalidValue("address"));
+            }
+            Ok(Value::String(prefix_hex::encode(&word[12..])))
+        }
+        ParamType::Bool => {
+            let word = read_word(data, 0)?;
+            match (word[..31].iter().all(|&b| b == 0), word[31]) {
+                (true, 0) => Ok(Value::Bool(false)),
+                (true, 1) => Ok(Value::Bool(true)),
+                _ => Err(AbiError::InvalidValue("bool")),
+            }
+        }
+        // integers are returned as decimal strings since they don't fit in json numbers
+        ParamType::Uint(_) => Ok(Value::String(to_decimal(*read_word(data, 0)?))),
+        ParamType::Int(_) => {
+            let mut word = *read_word(data, 0)?;
+            if word[0] & 0x80 == 0 {
+                return Ok(Value::String(to_decimal(word)));
+            }
+
+            // negate the two's complement value
+            for byte in word.iter_mut() {
+                *byte = !*byte;
+            }
+            for byte in word.iter_mut().rev() {
+                let (sum, overflow) = byte.overflowing_add(1);
+                *byte = sum;
+                if !overflow {
+                    break;
+                }
+            }
+
+            Ok(Value::String(format!("-{}", to_decimal(word))))
+        }
+        ParamType::FixedBytes(size) => {
+            let word = read_word(data, 0)?;
+            Ok(Value::String(prefix_hex::encode(&word[..*size])))
+        }
+        ParamType::Bytes => Ok(Value::String(prefix_hex::encode(read_bytes(data)?))),
+        ParamType::String => {
+            let string = std::str::from_utf8(read_bytes(data)?)
+                .map_err(|_| AbiError::InvalidValue("string"))?;
+            Ok(Value::String(string.to_owned()))
+        }
+        ParamType::Array(elem) => {
+            let len = read_usize(data, 0)?;
+            let data = &data[32..];
+            // check the length before decoding so a bogus length can't allocate a huge vec
+            if len.saturating_mul(elem.head_size()) > data.len() {
+                return Err(AbiError::DataTooShort);
+            }
+            decode_seq(iter::repeat(elem.as_ref()).take(len), data).map(Value::Array)
+        }
+        ParamType::FixedArray(elem, size) => {
+            if size.saturating_mul(elem.head_size()) > data.len() {
+                return Err(AbiError::DataTooShort);
+            }
+            decode_seq(iter::repeat(elem.as_ref()).take(*size), data).map(Value::Array)
+        }
+        ParamType::Tuple(elems) => decode_seq(elems.iter(), data).map(Value::Array),
+    }
+}
+
+fn read_word(data: &[u8], offset: usize) -> AbiResult<&[u8; 32]> {
+    data.get(offset..offset + 32)
+        .map(|word| word.try_into().unwrap())
+        .ok_or(AbiError::DataTooShort)
+}
+
+fn read_usize(data: &[u8], offset: usize) -> AbiResult<usize> {
+    let word = read_word(data, offset)?;
+    if word[..24].iter().any(|&b| b != 0) {
+        return Err(AbiError::InvalidOffset);
+    }
+    usize::try_from(u64::from_be_bytes(word[24..].try_into().unwrap()))
+        .map_err(|_| AbiError::InvalidOffset)
+}
+
+fn read_bytes(data: &[u8]) -> AbiResult<&[u8]> {
+    let len = read_usize(data, 0)?;
+    data.get(32..)
+        .and_then(|data| data.get(..len))
+        .ok_or(AbiError::DataTooShort)
+}
+
+fn to_decimal(mut word: [u8; 32]) -> String {
+    let mut digits = Vec::new();
+
+    while word.iter().any(|&b| b != 0) {
+        let mut rem = 0u32;
+        for byte in word.iter_mut() {
+            let cur = (rem << 8) | u32::from(*byte);
+            *byte = (cur / 10) as u8;
+            rem = cur % 10;
+        }
+        digits.push(b'0' + rem as u8);
+    }
+
+    if digits.is_empty() {
+        digits.push(b'0');
+    }
+
+    digits.reverse();
+
+    String::from_utf8(digits).unwrap()
+}
+
+impl<'de> Deserialize<'de> for Event {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let signature = String::deserialize(deserializer)?;
+
+        Self::parse(&signature).map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for Event {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.signature)
+    }
+}
+
+impl<'de> Deserialize<'de> for Function {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let signature = String::deserialize(deserializer)?;
+
+        Self::parse(&signature).map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for Function {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(val: u8) -> [u8; 32] {
+        let mut word = [0; 32];
+        word[31] = val;
+        word
+    }
+
+    fn topic(bytes: &[u8]) -> Bytes32 {
+        let mut topic = [0; 32];
+        topic[32 - bytes.len()..].copy_from_slice(bytes);
+        Bytes32::new(&topic)
+    }
+
+    #[test]
+    fn test_decode_event() {
+        let erc20 = Event::parse("Transfer(address indexed from,address indexed to,uint256 value)")
+            .unwrap();
+        let erc721 =
+            Event::parse("Transfer(address indexed,address indexed,uint256 indexed)").unwrap();
+
+        assert_eq!(
+            prefix_hex::encode(erc20.topic0().as_slice()),
+            "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"
+        );
+        assert_eq!(erc20.topic0(), erc721.topic0());
+
+        let abi = Abi::new([&erc20, &erc721].into_iter(), iter::empty());
+
+        let from = topic(&[1; 20]);
+        let to = topic(&[2; 20]);
+
+        let log = ResponseLog {
+            topics: Some(
+                [erc20.topic0(), from.clone(), to.clone()]
+                    .into_iter()
+                    .collect(),
+            ),
+            data: Some(word(232).to_vec().into()),
+            ..Default::default()
+        };
+        let decoded = abi.decode_log(&log).unwrap();
+        assert_eq!(decoded["name"], "Transfer");
+        assert_eq!(decoded["params"][0]["name"], "from");
+        assert_eq!(
+            decoded["params"][1]["value"],
+            "0x0202020202020202020202020202020202020202"
+        );
+        assert_eq!(decoded["params"][2]["value"], "232");
+
+        let log = ResponseLog {
+            topics: Some(
+                [erc20.topic0(), from, to, topic(&[7])]
+                    .into_iter()
+                    .collect(),
+            ),
+            data: Some(Vec::new().into()),
+            ..Default::default()
+        };
+        let decoded = abi.decode_log(&log).unwrap();
+        assert_eq!(decoded["params"][2]["value"], "7");
+        assert_eq!(decoded["params"][2]["name"], Value::Null);
+
+        let log = ResponseLog {
+            topics: Some([erc20.topic0()].into_iter().collect()),
+            data: Some(Vec::new().into()),
+            ..Default::default()
+        };
+        assert!(abi.decode_log(&log).unwrap()["error"].is_string());
+    }
+
+    #[test]
+    fn test_decode_function() {
+        let function = Function::parse("f(int256 a,string b,(bool,uint8[]) c)").unwrap();
+        assert!(Function::parse("f(uint256 indexed a)").is_err());
+        assert!(Function::parse("f(uint7)").is_err());
+        assert!(Function::parse("f(()[])").is_err());
+        assert!(Function::parse("f(uint256[0][])").is_err());
+
+        let mut input = function.sighash().to_vec();
+        input.extend_from_slice(&[0xff; 32]);
+        input.extend_from_slice(&word(0x60));
+        input.extend_from_slice(&word(0xa0));
+        input.extend_from_slice(&word(5));
+        input.extend_from_slice(b"hello");
+        input.extend_from_slice(&[0; 27]);
+        input.extend_from_slice(&word(1));
+        input.extend_from_slice(&word(0x40));
+        input.extend_from_slice(&word(2));
+        input.extend_from_slice(&word(3));
+        input.extend_from_slice(&word(4));
+
+        let abi = Abi::new(iter::empty(), [&function].into_iter());
+
+        let tx = ResponseTransaction {
+            input: Some(input.clone().into()),
+            ..Default::default()
+        };
+        let decoded = abi.decode_tx(&tx).unwrap();
+        assert_eq!(decoded["params"][0]["value"], "-1");
+        assert_eq!(decoded["params"][1]["value"], "hello");
+        assert_eq!(decoded["params"][2]["type"], "(bool,uint8[])");
+        assert_eq!(decoded["params"][2]["value"][0], true);
+        assert_eq!(decoded["params"][2]["value"][1][1], "4");
+
+        input.truncate(input.len() - 32);
+        let tx = ResponseTransaction {
+            input: Some(input.into()),
+            ..Default::default()
+        };
+        assert_eq!(abi.decode_tx(&tx).unwrap()["error"], "data is too short");
+    }
+}
@@ -0,0 +1,513 @@
+use crate::field_selection::FieldSelection;
+use crate::types::{timestamp_to_u64, MiniQuery, Order, Query, QueryResult};
+use crate::{Error, Result};
+use eth_archive_core::deserialize::U256;
+use eth_archive_core::hash::HashSet;
+use eth_archive_core::types::{ResponseBlock, ResponseLog, ResponseTransaction};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::num::NonZeroU64;
+
+/// Body of an `/aggregate` request, a query with an aggregation instead of a response format.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AggregateQuery {
+    #[serde(flatten)]
+    pub query: Query,
+    pub aggregate: Aggregation,
+}
+
+impl AggregateQuery {
+    /// Rejects the options of `/query` that don't apply to aggregations.
+    pub fn check_options(&self) -> Result<()> {
+        let err = if self.query.format.is_some() {
+            "format can't be set for aggregations"
+        } else if self.query.limit.is_some() {
+            "limit can't be set for aggregations"
+        } else if self.query.block_offset != 0 {
+            "blockOffset can't be set for aggregations"
+        } else if self.query.order != Order::Asc {
+            "aggregations only run in ascending order"
+        } else {
+            return Ok(());
+        };
+
+        Err(Error::InvalidAggregation(err))
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Aggregation {
+    /// All matched items are in a single group if this isn't given
+    #[serde(default)]
+    pub group_by: Option<AggregateKey>,
+    /// Number of distinct values of this key is counted in each group
+    #[serde(default)]
+    pub distinct: Option<AggregateKey>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum AggregateKey {
+    Block,
+    /// Block timestamp rounded down to a multiple of the given number of seconds
+    TimeBucket(NonZeroU64),
+    /// Address of a log
+    Address,
+    /// Topic of a log at the given position
+    Topic(usize),
+    #[serde(rename = "from")]
+    Source,
+    #[serde(rename = "to")]
+    Dest,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Target {
+    Logs,
+    Transactions,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum KeyValue {
+    Number(u64),
+    Bytes(Vec<u8>),
+}
+
+#[derive(Default)]
+struct Group {
+    count: u64,
+    sum: U256,
+    distinct: HashSet<Option<KeyValue>>,
+}
+
+#[derive(Serialize)]
+struct GroupEntry {
+    key: Option<KeyValue>,
+    count: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sum: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    distinct: Option<usize>,
+}
+
+/// Accumulates the query results into groups so only the groups are sent to the client.
+///
+/// Logs are aggregated if the query has log selections and transactions are aggregated
+/// if it has transaction selections. `value` of the transactions is summed in each group.
+pub struct Aggregator {
+    aggregation: Aggregation,
+    target: Target,
+    groups: BTreeMap<Option<KeyValue>, Group>,
+}
+
+impl Aggregator {
+    pub fn new(aggregation: Aggregation, query: &MiniQuery) -> Result<Self> {
+        if !query.traces.is_empty()
+            || !query.state_diffs.is_empty()
+            || !query.withdrawals.is_empty()
+            || !query.blocks.is_empty()
+            || query.include_all_blocks
+        {
+            return Err(Error::InvalidAggregation(
+                "only log and transaction selections can be aggregated",
+            ));
+        }
+
+        let target = match (query.logs.is_empty(), query.transactions.is_empty()) {
+            (false, true) => Target::Logs,
+            (true, false) => Target::Transactions,
+            (false, false) => {
+                return Err(Error::InvalidAggregation(
+                    "logs and transactions can't be aggregated in the same query",
+                ))
+            }
+            (true, true) => return Err(Error::EmptyQuery),
+        };
+
+        for key in [aggregation.group_by, aggregation.distinct]
+            .iter()
+            .flatten()
+        {
+            match (key, target) {
+                (AggregateKey::Address, Target::Transactions) => {
+                    return Err(Error::InvalidAggregation(
+                        "transactions can't be grouped by address, use from or to",
+                    ))
+                }
+                (AggregateKey::Topic(_), Target::Transactions) => {
+                    return Err(Error::InvalidAggregation(
+                        "transactions can't be grouped by topic",
+                    ))
+                }
+                (AggregateKey::Source | AggregateKey::Dest, Target::Logs) => {
+                    return Err(Error::InvalidAggregation(
+                        "logs can't be grouped by from or to",
+                    ))
+                }
+                (AggregateKey::Topic(idx), _) if *idx >= 4 => {
+                    return Err(Error::TooManyTopics(idx + 1))
+                }
+                _ => (),
+            }
+        }
+
+        Ok(Self {
+            aggregation,
+            target,
+            groups: BTreeMap::new(),
+        })
+    }
+
+    /// Field selection with the columns the aggregation reads,
+    /// the columns needed to join and filter the query are added on top of this.
+    pub fn field_selection(&self) -> FieldSelection {
+        let mut field_selection = FieldSelection::default();
+
+        match self.target {
+            Target::Logs => {
+                field_selection.log.address = true;
+                field_selection.log.topics = true;
+            }
+            Target::Transactions => {
+                field_selection.transaction.source = true;
+                field_selection.transaction.dest = true;
+                field_selection.transaction.value = true;
+            }
+        }
+
+        if [self.aggregation.group_by, self.aggregation.distinct]
+            .iter()
+            .any(|key| matches!(key, Some(AggregateKey::TimeBucket(_))))
+        {
+            field_selection.block.timestamp = true;
+        }
+
+        field_selection
+    }
+
+    pub fn push(&mut self, res: QueryResult) {
+        match self.target {
+            Target::Logs => {
+                for ((block_num, _), log) in res.logs.iter() {
+                    let block = res.blocks.get(block_num);
+                    let key = |key: Option<AggregateKey>| {
+                        key.and_then(|key| key.log_value(*block_num, block, log))
+                    };
+
+                    let group = self
+                        .groups
+                        .entry(key(self.aggregation.group_by))
+                        .or_default();
+                    group.count += 1;
+                    if self.aggregation.distinct.is_some() {
+                        group.distinct.insert(key(self.aggregation.distinct));
+                    }
+                }
+            }
+            Target::Transactions => {
+                for ((block_num, _), tx) in res.transactions.iter() {
+                    let block = res.blocks.get(block_num);
+                    let key = |key: Option<AggregateKey>| {
+                        key.and_then(|key| key.tx_value(*block_num, block, tx))
+                    };
+
+                    let group = self
+                        .groups
+                        .entry(key(self.aggregation.group_by))
+                        .or_default();
+                    group.count += 1;
+                    if let Some(value) = tx.value.as_ref().and_then(|v| U256::from_quantity(v)) {
+                        group.sum = group.sum.saturating_add(value);
+                    }
+                    if self.aggregation.distinct.is_some() {
+                        group.distinct.insert(key(self.aggregation.distinct));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Serializes the groups along with the status of the query.
+    ///
+    /// The groups only cover the blocks up to `next_block`. If the time limit stopped the
+    /// aggregation, groups that continue after it are partial and the client has to merge
+    /// them with the groups of the request that continues from `next_block`.
+    pub fn finish(self, archive_height: Option<u64>, next_block: u64, total_time: u128) -> Vec<u8> {
+        let groups = self
+            .groups
+            .into_iter()
+            .map(|(key, group)| GroupEntry {
+                key,
+                count: group.count,
+                sum: match self.target {
+                    Target::Transactions => Some(group.sum),
+                    Target::Logs => None,
+                },
+                distinct: self.aggregation.distinct.map(|_| group.distinct.len()),
+            })
+            .collect::<Vec<_>>();
+
+        let json = serde_json::json!({
+            "groups": groups,
+            "archiveHeight": archive_height,
+            "nextBlock": next_block,
+            "totalTime": total_time,
+        });
+
+        serde_json::to_vec(&json).unwrap()
+    }
+}
+
+impl AggregateKey {
+    fn block_value(self, block_num: u64, block: Option<&ResponseBlock>) -> Option<KeyValue> {
+        match self {
+            Self::Block => Some(KeyValue::Number(block_num)),
+            Self::TimeBucket(size) => {
+                let timestamp = timestamp_to_u64(block?.timestamp.as_ref()?);
+                Some(KeyValue::Number(timestamp - timestamp % size.get()))
+            }
+            _ => None,
+        }
+    }
+
+    fn log_value(
+        self,
+        block_num: u64,
+        block: Option<&ResponseBlock>,
+        log: &ResponseLog,
+    ) -> Option<KeyValue> {
+        match self {
+            Self::Address => Some(KeyValue::Bytes(log.address.as_ref()?.to_vec())),
+            Self::Topic(idx) => Some(KeyValue::Bytes(log.topics.as_ref()?.get(idx)?.to_vec())),
+            _ => self.block_value(block_num, block),
+        }
+    }
+
+    fn tx_value(
+        self,
+        block_num: u64,
+        block: Option<&ResponseBlock>,
+        tx: &ResponseTransaction,
+    ) -> Option<KeyValue> {
+        match self {
+            Self::Source => Some(KeyValue::Bytes(tx.source.as_ref()?.to_vec())),
+            Self::Dest => Some(KeyValue::Bytes(tx.dest.as_ref()?.to_vec())),
+            _ => self.block_value(block_num, block),
+        }
+    }
+}
+
+impl Serialize for KeyValue {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::Number(num) => serializer.serialize_u64(*num),
+            Self::Bytes(bytes) => serializer.serialize_str(&prefix_hex::encode(bytes.as_slice())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eth_archive_core::deserialize::{Address, Bytes, Bytes32};
+    use serde_json::{json, Value};
+
+    fn addr(n: u8) -> Address {
+        Address::new(&[n; 20])
+    }
+
+    fn topic(n: u8) -> Bytes32 {
+        Bytes32::new(&[n; 32])
+    }
+
+    fn hex(n: u8, len: usize) -> String {
+        prefix_hex::encode(vec![n; len])
+    }
+
+    fn quantity(n: u64) -> Value {
+        serde_json::to_value(U256::from_quantity(&Bytes::from_quantity(n)).unwrap()).unwrap()
+    }
+
+    fn aggregator(
+        target: Target,
+        group_by: Option<AggregateKey>,
+        distinct: Option<AggregateKey>,
+    ) -> Aggregator {
+        Aggregator {
+            aggregation: Aggregation { group_by, distinct },
+            target,
+            groups: BTreeMap::new(),
+        }
+    }
+
+    fn groups(aggregator: Aggregator) -> Value {
+        let json: Value = serde_json::from_slice(&aggregator.finish(Some(10), 4, 0)).unwrap();
+        json["groups"].clone()
+    }
+
+    /// Blocks with the given timestamps
+    fn blocks(res: &mut QueryResult, blocks: &[(u64, u64)]) {
+        for &(num, timestamp) in blocks {
+            res.blocks.insert(
+                num,
+                ResponseBlock {
+                    timestamp: Some(Bytes::from_quantity(timestamp)),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    /// The logs of blocks 1 and 2 and the logs of block 3, as two separate query results
+    fn log_results() -> [QueryResult; 2] {
+        let log = |address: u8, topics: &[u8]| ResponseLog {
+            address: Some(addr(address)),
+            topics: Some(topics.iter().map(|&n| topic(n)).collect()),
+            ..Default::default()
+        };
+
+        let mut first = QueryResult::default();
+        blocks(&mut first, &[(1, 100), (2, 150)]);
+        first.logs.insert((1, 0), log(1, &[1, 2]));
+        first.logs.insert((1, 1), log(2, &[1, 3]));
+        first.logs.insert((2, 0), log(1, &[1, 2]));
+
+        let mut second = QueryResult::default();
+        blocks(&mut second, &[(3, 260)]);
+        second.logs.insert((3, 0), log(1, &[4]));
+
+        [first, second]
+    }
+
+    fn aggregate_logs(group_by: Option<AggregateKey>, distinct: Option<AggregateKey>) -> Value {
+        let mut aggregator = aggregator(Target::Logs, group_by, distinct);
+        for res in log_results() {
+            aggregator.push(res);
+        }
+        groups(aggregator)
+    }
+
+    #[test]
+    fn test_aggregate_logs() {
+        assert_eq!(
+            aggregate_logs(None, None),
+            json!([{ "key": null, "count": 4 }])
+        );
+
+        assert_eq!(
+            aggregate_logs(Some(AggregateKey::Block), Some(AggregateKey::Address)),
+            json!([
+                { "key": 1, "count": 2, "distinct": 2 },
+                { "key": 2, "count": 1, "distinct": 1 },
+                { "key": 3, "count": 1, "distinct": 1 },
+            ])
+        );
+
+        let bucket = AggregateKey::TimeBucket(NonZeroU64::new(100).unwrap());
+        assert_eq!(
+            aggregate_logs(Some(bucket), None),
+            json!([
+                { "key": 100, "count": 3 },
+                { "key": 200, "count": 1 },
+            ])
+        );
+
+        assert_eq!(
+            aggregate_logs(Some(AggregateKey::Address), Some(AggregateKey::Block)),
+            json!([
+                { "key": hex(1, 20), "count": 3, "distinct": 3 },
+                { "key": hex(2, 20), "count": 1, "distinct": 1 },
+            ])
+        );
+
+        // the log of block 3 has no second topic
+        assert_eq!(
+            aggregate_logs(Some(AggregateKey::Topic(1)), None),
+            json!([
+                { "key": null, "count": 1 },
+                { "key": hex(2, 32), "count": 2 },
+                { "key": hex(3, 32), "count": 1 },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_aggregate_transactions() {
+        let tx = |source: u8, dest: Option<u8>, value: u64| ResponseTransaction {
+            source: Some(addr(source)),
+            dest: dest.map(addr),
+            value: Some(Bytes::from_quantity(value)),
+            ..Default::default()
+        };
+
+        let aggregate = |group_by, distinct| {
+            let mut res = QueryResult::default();
+            blocks(&mut res, &[(1, 100), (2, 150)]);
+            res.transactions.insert((1, 0), tx(1, Some(2), 100));
+            // contract creation
+            res.transactions.insert((1, 1), tx(1, None, 50));
+            res.transactions.insert((2, 0), tx(3, Some(2), 200));
+
+            let mut aggregator = aggregator(Target::Transactions, group_by, distinct);
+            aggregator.push(res);
+            groups(aggregator)
+        };
+
+        assert_eq!(
+            aggregate(None, None),
+            json!([{ "key": null, "count": 3, "sum": quantity(350) }])
+        );
+
+        assert_eq!(
+            aggregate(Some(AggregateKey::Dest), Some(AggregateKey::Source)),
+            json!([
+                { "key": null, "count": 1, "sum": quantity(50), "distinct": 1 },
+                { "key": hex(2, 20), "count": 2, "sum": quantity(300), "distinct": 2 },
+            ])
+        );
+
+        assert_eq!(
+            aggregate(Some(AggregateKey::Block), None),
+            json!([
+                { "key": 1, "count": 2, "sum": quantity(150) },
+                { "key": 2, "count": 1, "sum": quantity(200) },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_check_options() {
+        let query = |options: Value| {
+            let mut query = json!({ "fromBlock": 0, "aggregate": {} });
+            query
+                .as_object_mut()
+                .unwrap()
+                .extend(options.as_object().unwrap().clone());
+            serde_json::from_value::<AggregateQuery>(query).unwrap()
+        };
+
+        assert!(query(json!({})).check_options().is_ok());
+        assert!(query(json!({ "order": "asc" })).check_options().is_ok());
+
+        for options in [
+            json!({ "format": "parquet" }),
+            json!({ "limit": 10 }),
+            json!({ "order": "desc" }),
+            json!({ "blockOffset": 2 }),
+        ] {
+            assert!(
+                matches!(
+                    query(options.clone()).check_options(),
+                    Err(Error::InvalidAggregation(_))
+                ),
+                "{}",
+                options
+            );
+        }
+    }
+}
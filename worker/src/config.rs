@@ -21,7 +21,7 @@ pub struct Config {
     pub server_addr: SocketAddr,
     /// Initial hot block range. If None, hot blocks will start from 0
     #[clap(long)]
-    pub initial_hot_block_range: Option<u32>,
+    pub initial_hot_block_range: Option<u64>,
     /// Query stops as soon as the response body size in megabytes reaches this number.
     /// Response body might be bigger than this amount of MBs.
     #[clap(long)]
@@ -29,6 +29,13 @@ pub struct Config {
     /// Maximum number of concurrent queries
     #[clap(long, default_value_t = NonZeroUsize::new(32).unwrap())]
     pub max_concurrent_queries: NonZeroUsize,
+    /// Maximum number of concurrent live subscriptions
+    #[clap(long, default_value_t = NonZeroUsize::new(128).unwrap())]
+    pub max_concurrent_subscriptions: NonZeroUsize,
+    /// Interval in milliseconds of the keep-alive comments sent to idle subscriptions.
+    /// Disconnected clients are noticed when the comment can't be sent.
+    #[clap(long, default_value_t = 15000)]
+    pub subscription_keep_alive_ms: u64,
     /// Maximum number of threads per query to use to query parquet folders
     #[clap(long, default_value_t = NonZeroUsize::new(4).unwrap())]
     pub max_parquet_query_concurrency: NonZeroUsize,
@@ -41,6 +48,10 @@ pub struct Config {
     /// Database queries are batched because we don't want to query the entire db at once.
     #[clap(long, default_value_t = 200)]
     pub db_query_batch_size: u32,
+    /// Maximum number of blocks to walk back when searching for the common ancestor
+    /// after a chain reorganization is detected. The worker stops if it isn't found.
+    #[clap(long, default_value_t = 200)]
+    pub max_reorg_depth: usize,
     #[command(flatten)]
     pub s3: S3Config,
 }
@@ -1,31 +1,44 @@
+use crate::aggregate::{AggregateQuery, Aggregator};
 use crate::config::Config;
 use crate::db::DbHandle;
-use crate::db_writer::DbWriter;
+use crate::db_writer::{DbEvent, DbWriter};
 use crate::downloader::Downloader;
-use crate::field_selection::FieldSelection;
-use crate::parquet_query::ParquetQuery;
+use crate::field_selection::{BlockFieldSelection, FieldSelection, TransactionFieldSelection};
+use crate::parquet_query::{find_block_by_timestamp, ParquetQuery};
 use crate::parquet_watcher::ParquetWatcher;
 use crate::serialize_task::SerializeTask;
-use crate::types::{MiniQuery, Query, QueryResult};
+use crate::types::{MiniQuery, Order, Query, QueryResult};
 use crate::{Error, Result};
+use eth_archive_core::deserialize::Bytes32;
 use eth_archive_core::ingest_metrics::IngestMetrics;
 use eth_archive_core::rayon_async;
 use eth_archive_core::retry::Retry;
 use eth_archive_core::s3_client::{Direction, S3Client};
-use eth_archive_core::types::BlockRange;
+use eth_archive_core::types::{BlockRange, ResponseBlock, ResponseTransaction};
+use hyper::Body;
 use std::cmp;
 use std::collections::VecDeque;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
 
 pub struct DataCtx {
     config: Config,
     db: Arc<DbHandle>,
+    db_writer: Arc<DbWriter>,
     current_num_queries: AtomicUsize,
+    current_num_subscriptions: AtomicUsize,
 }
 
 impl DataCtx {
-    pub async fn new(config: Config, ingest_metrics: Arc<IngestMetrics>) -> Result<Self> {
+    /// Returns the context along with the handle of the download task,
+    /// which only finishes if the hot database can't follow the chain anymore.
+    pub async fn new(
+        config: Config,
+        ingest_metrics: Arc<IngestMetrics>,
+    ) -> Result<(Self, JoinHandle<Result<()>>)> {
         let db = DbHandle::new(&config.db_path, ingest_metrics.clone()).await?;
         let db = Arc::new(db);
 
@@ -34,7 +47,7 @@ impl DataCtx {
 
         let retry = Retry::new(config.retry);
 
-        Downloader {
+        let downloader = Downloader {
             config: config.clone(),
             ingest_metrics: ingest_metrics.clone(),
             db: db.clone(),
@@ -67,21 +80,39 @@ impl DataCtx {
             }
         }
 
-        Ok(Self {
+        let data_ctx = Self {
             config,
             db,
+            db_writer,
             current_num_queries: AtomicUsize::new(0),
-        })
+            current_num_subscriptions: AtomicUsize::new(0),
+        };
+
+        Ok((data_ctx, downloader))
     }
 
-    pub fn inclusive_height(&self) -> Option<u32> {
+    pub fn inclusive_height(&self) -> Option<u64> {
         match self.db.height() {
             0 => None,
             num => Some(num - 1),
         }
     }
 
-    pub async fn query(self: Arc<Self>, query: Query) -> Result<Vec<u8>> {
+    pub async fn query(self: Arc<Self>, query: Query) -> Result<Body> {
+        self.acquire_query_slot()?;
+
+        let res = self.clone().query_impl(query).await;
+
+        // the query slot is released by the spawned query task if the query started
+        if res.is_err() {
+            self.current_num_queries.fetch_sub(1, Ordering::SeqCst);
+        }
+
+        res
+    }
+
+    /// Takes a query slot, aggregations count towards the same limit as queries.
+    fn acquire_query_slot(&self) -> Result<()> {
         let max_concurrent_queries = self.config.max_concurrent_queries.get();
         if self
             .current_num_queries
@@ -101,59 +132,517 @@ impl DataCtx {
             return Err(Error::MaxNumberOfQueriesReached);
         }
 
-        let res = self.clone().query_impl(query).await;
+        Ok(())
+    }
 
-        self.current_num_queries.fetch_sub(1, Ordering::SeqCst);
+    pub async fn aggregate(self: Arc<Self>, query: AggregateQuery) -> Result<Body> {
+        self.acquire_query_slot()?;
+
+        let res = self.clone().aggregate_impl(query).await;
+
+        // the query slot is released by the spawned query task if the aggregation started
+        if res.is_err() {
+            self.current_num_queries.fetch_sub(1, Ordering::SeqCst);
+        }
 
         res
     }
 
-    async fn query_impl(self: Arc<Self>, query: Query) -> Result<Vec<u8>> {
+    /// Runs the query over the same parquet and hot data paths as `query`,
+    /// but the results are folded into groups instead of being serialized.
+    async fn aggregate_impl(self: Arc<Self>, query: AggregateQuery) -> Result<Body> {
+        query.check_options()?;
+
+        let AggregateQuery {
+            mut query,
+            aggregate,
+        } = query;
+
+        self.resolve_timestamps(&mut query).await?;
+
         if let Some(to_block) = query.to_block {
             if query.from_block > to_block {
                 return Err(Error::InvalidBlockRange);
             }
         }
 
+        let archive_height = self.db.height();
+        let mut query = rayon_async::spawn(move || query.optimize(archive_height)).await;
+
+        let aggregator = Aggregator::new(aggregate, &query)?;
+
+        query.field_selection = aggregator.field_selection();
+        // decoding isn't needed so the log data and transaction input aren't read
+        query.abi = Default::default();
+
+        let (serialize_task, body) = SerializeTask::new_aggregation(
+            query.from_block,
+            self.config.resp_time_limit,
+            self.inclusive_height(),
+            aggregator,
+        );
+
+        tokio::spawn(async move {
+            let res = self.run_query(&serialize_task, &query, Order::Asc).await;
+
+            let res = match res {
+                Ok(()) => serialize_task.join().await,
+                Err(e) => serialize_task.fail(e).await,
+            };
+
+            if let Err(e) = res {
+                log::error!("failed to join serialize task:\n{}", e);
+            }
+
+            self.current_num_queries.fetch_sub(1, Ordering::SeqCst);
+        });
+
+        Ok(body)
+    }
+
+    async fn query_impl(self: Arc<Self>, mut query: Query) -> Result<Body> {
+        self.resolve_timestamps(&mut query).await?;
+
+        if let Some(to_block) = query.to_block {
+            if query.from_block > to_block {
+                return Err(Error::InvalidBlockRange);
+            }
+        }
+
+        let format = query.format.unwrap_or_default();
+        let limit = query.limit;
+        let block_offset = query.block_offset;
+        let order = query.order;
+
         let archive_height = self.db.height();
         let query = rayon_async::spawn(move || query.optimize(archive_height)).await;
 
-        if query.logs.is_empty() && query.transactions.is_empty() {
+        if query.logs.is_empty()
+            && query.transactions.is_empty()
+            && query.traces.is_empty()
+            && query.state_diffs.is_empty()
+            && query.withdrawals.is_empty()
+            && query.blocks.is_empty()
+        {
             return Err(Error::EmptyQuery);
         }
 
-        let field_selection = query.field_selection;
+        let next_block = match order {
+            Order::Asc => query.from_block,
+            Order::Desc => query.to_block,
+        };
 
-        let serialize_task = SerializeTask::new(
-            query.from_block,
+        let (serialize_task, body) = SerializeTask::new(
+            next_block,
             self.config.max_resp_body_size,
             self.config.resp_time_limit,
             self.inclusive_height(),
-            field_selection,
+            query.field_selection,
+            query.abi.clone(),
+            format,
+            limit,
+            block_offset,
+            order,
         );
 
-        if query.from_block >= self.db.height() {
-            return serialize_task.join().await;
-        }
+        tokio::spawn(async move {
+            let res = self.run_query(&serialize_task, &query, order).await;
 
-        let field_selection = field_selection.with_join_columns();
+            let res = match res {
+                Ok(()) => serialize_task.join().await,
+                Err(e) => serialize_task.fail(e).await,
+            };
 
-        let parquet_height = self.db.parquet_height();
+            if let Err(e) = res {
+                log::error!("failed to join serialize task:\n{}", e);
+            }
 
-        if query.from_block < parquet_height {
-            self.parquet_query(&serialize_task, &query, field_selection)
-                .await?;
+            self.current_num_queries.fetch_sub(1, Ordering::SeqCst);
+        });
+
+        Ok(body)
+    }
+
+    /// Returns the first block that has a timestamp greater than or equal to the given timestamp.
+    /// None if there is no such block in the archive yet.
+    pub async fn block_by_timestamp(&self, timestamp: u64) -> Result<Option<u64>> {
+        if let Some(data_path) = &self.config.data_path {
+            if let Some(dir_name) = self
+                .db
+                .clone()
+                .parquet_folder_by_timestamp(timestamp)
+                .await?
+            {
+                let metadata = self
+                    .db
+                    .clone()
+                    .get_parquet_metadata(dir_name)
+                    .await?
+                    .ok_or(Error::ParquetMetadataNotFound(dir_name))?;
+
+                let block_number =
+                    find_block_by_timestamp(data_path, dir_name, &metadata, timestamp).await?;
+
+                if block_number.is_some() {
+                    return Ok(block_number);
+                }
+            }
         }
 
-        if serialize_task.is_closed() {
-            return serialize_task.join().await;
+        self.db.clone().hot_block_by_timestamp(timestamp).await
+    }
+
+    /// Returns the transaction with the given hash. None if it isn't in the archive.
+    pub async fn transaction_by_hash(
+        &self,
+        hash: Bytes32,
+        field_selection: TransactionFieldSelection,
+    ) -> Result<Option<ResponseTransaction>> {
+        let (block_number, transaction_index) =
+            match self.db.clone().tx_id_by_hash(hash.clone()).await? {
+                Some(tx_id) => tx_id,
+                None => return Ok(None),
+            };
+
+        if let Some(tx) = self
+            .db
+            .clone()
+            .get_transaction(block_number, transaction_index)
+            .await?
+        {
+            if tx.hash == hash {
+                return Ok(Some(field_selection.prune(tx)));
+            }
         }
 
-        let from_block = cmp::max(query.from_block, parquet_height);
-        self.hot_data_query(from_block, &serialize_task, &query, field_selection)
+        let field_selection = FieldSelection {
+            transaction: field_selection,
+            ..Default::default()
+        };
+
+        let parquet_query = match self
+            .parquet_query_for_block(block_number, field_selection)
+            .await?
+        {
+            Some(parquet_query) => parquet_query,
+            None => return Ok(None),
+        };
+
+        let tx = parquet_query
+            .get_transaction(block_number, transaction_index)
             .await?;
 
-        serialize_task.join().await
+        Ok(tx
+            .filter(|tx| tx.hash.as_ref() == Some(&hash))
+            .map(|tx| field_selection.transaction.prune_opt(tx)))
+    }
+
+    /// Returns the block with the given hash. None if it isn't in the archive.
+    pub async fn block_by_hash(
+        &self,
+        hash: Bytes32,
+        field_selection: BlockFieldSelection,
+    ) -> Result<Option<ResponseBlock>> {
+        let block_number = match self.db.clone().block_number_by_hash(hash.clone()).await? {
+            Some(block_number) => block_number,
+            None => return Ok(None),
+        };
+
+        if let Some(block) = self.db.clone().get_block(block_number).await? {
+            if block.hash.as_ref() == Some(&hash) {
+                return Ok(Some(field_selection.prune(block)));
+            }
+        }
+
+        // the hash is needed to check the block
+        let parquet_field_selection = FieldSelection {
+            block: BlockFieldSelection {
+                hash: true,
+                ..field_selection
+            },
+            ..Default::default()
+        };
+
+        let parquet_query = match self
+            .parquet_query_for_block(block_number, parquet_field_selection)
+            .await?
+        {
+            Some(parquet_query) => parquet_query,
+            None => return Ok(None),
+        };
+
+        let block = parquet_query.get_block(block_number).await?;
+
+        Ok(block
+            .filter(|block| block.hash.as_ref() == Some(&hash))
+            .map(|block| field_selection.prune_opt(block)))
+    }
+
+    /// Creates an empty query for the parquet folder that contains the given block.
+    async fn parquet_query_for_block(
+        &self,
+        block_number: u64,
+        field_selection: FieldSelection,
+    ) -> Result<Option<ParquetQuery>> {
+        let data_path = match &self.config.data_path {
+            Some(data_path) => data_path.to_owned(),
+            None => return Ok(None),
+        };
+
+        let mut parquet_idxs = self
+            .db
+            .clone()
+            .iter_parquet_idxs(block_number, Some(block_number + 1), Order::Asc)
+            .await;
+
+        let dir_name = match parquet_idxs.recv().await {
+            Some(res) => res?.0,
+            None => return Ok(None),
+        };
+
+        if !(dir_name.range.from..dir_name.range.to).contains(&block_number) {
+            return Ok(None);
+        }
+
+        let metadata = self
+            .db
+            .clone()
+            .get_parquet_metadata(dir_name)
+            .await?
+            .ok_or(Error::ParquetMetadataNotFound(dir_name))?;
+
+        Ok(Some(ParquetQuery {
+            data_path,
+            dir_name,
+            metadata,
+            mini_query: MiniQuery {
+                from_block: block_number,
+                to_block: block_number + 1,
+                logs: Vec::new(),
+                transactions: Vec::new(),
+                traces: Vec::new(),
+                state_diffs: Vec::new(),
+                withdrawals: Vec::new(),
+                blocks: Vec::new(),
+                field_selection: field_selection.with_join_columns(),
+                include_all_blocks: false,
+                abi: Default::default(),
+            },
+        }))
+    }
+
+    /// Narrows the block range of the query using its timestamp range.
+    async fn resolve_timestamps(&self, query: &mut Query) -> Result<()> {
+        if let Some(from_timestamp) = query.from_timestamp {
+            // all blocks in the archive are older if the timestamp can't be found
+            let from_block = match self.block_by_timestamp(from_timestamp).await? {
+                Some(from_block) => from_block,
+                None => self.db.height(),
+            };
+
+            query.from_block = cmp::max(query.from_block, from_block);
+        }
+
+        if let Some(to_timestamp) = query.to_timestamp {
+            if let Some(to_block) = self.block_by_timestamp(to_timestamp).await? {
+                query.to_block = Some(match query.to_block {
+                    Some(query_to_block) => cmp::min(query_to_block, to_block),
+                    None => to_block,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn subscribe(self: Arc<Self>, query: Query) -> Result<Body> {
+        let max_concurrent_subscriptions = self.config.max_concurrent_subscriptions.get();
+        if self
+            .current_num_subscriptions
+            .fetch_update(
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+                |current_num_subscriptions: usize| {
+                    if current_num_subscriptions < max_concurrent_subscriptions {
+                        Some(current_num_subscriptions + 1)
+                    } else {
+                        None
+                    }
+                },
+            )
+            .is_err()
+        {
+            return Err(Error::MaxNumberOfSubscriptionsReached);
+        }
+
+        let res = self.clone().subscribe_impl(query).await;
+
+        // the subscription slot is released by the spawned task if the subscription started
+        if res.is_err() {
+            self.current_num_subscriptions
+                .fetch_sub(1, Ordering::SeqCst);
+        }
+
+        res
+    }
+
+    async fn subscribe_impl(self: Arc<Self>, mut query: Query) -> Result<Body> {
+        self.resolve_timestamps(&mut query).await?;
+
+        if let Some(to_block) = query.to_block {
+            if query.from_block > to_block {
+                return Err(Error::InvalidBlockRange);
+            }
+        }
+
+        // subscribe before reading the height so no commit is missed
+        let events = self.db_writer.subscribe();
+
+        // the block range is bounded by the height of the db on each round of the subscription
+        let query = rayon_async::spawn(move || query.optimize(u64::MAX)).await;
+
+        if query.logs.is_empty()
+            && query.transactions.is_empty()
+            && query.traces.is_empty()
+            && query.state_diffs.is_empty()
+            && query.withdrawals.is_empty()
+            && query.blocks.is_empty()
+        {
+            return Err(Error::EmptyQuery);
+        }
+
+        let keep_alive = Duration::from_millis(self.config.subscription_keep_alive_ms);
+        let (serialize_task, body) =
+            SerializeTask::new_subscription(query.field_selection, query.abi.clone(), keep_alive);
+
+        tokio::spawn(async move {
+            let res = self.run_subscription(&serialize_task, &query, events).await;
+
+            let res = match res {
+                Ok(()) => serialize_task.join().await,
+                Err(e) => serialize_task.fail(e).await,
+            };
+
+            if let Err(e) = res {
+                log::error!("failed to join serialize task:\n{}", e);
+            }
+
+            self.current_num_subscriptions
+                .fetch_sub(1, Ordering::SeqCst);
+        });
+
+        Ok(body)
+    }
+
+    /// Sends the data up to the current height and then sends newly committed blocks
+    /// until the client disconnects or `to_block` is reached.
+    async fn run_subscription(
+        &self,
+        serialize_task: &SerializeTask,
+        query: &MiniQuery,
+        mut events: broadcast::Receiver<DbEvent>,
+    ) -> Result<()> {
+        let mut next_block = query.from_block;
+
+        loop {
+            let to_block = cmp::min(query.to_block, self.db.height());
+
+            if next_block < to_block {
+                let mini_query = MiniQuery {
+                    from_block: next_block,
+                    to_block,
+                    ..query.clone()
+                };
+
+                self.run_query(serialize_task, &mini_query, Order::Asc)
+                    .await?;
+
+                next_block = to_block;
+            }
+
+            if next_block >= query.to_block {
+                return Ok(());
+            }
+
+            let event = tokio::select! {
+                event = events.recv() => event,
+                _ = serialize_task.closed() => return Ok(()),
+            };
+
+            match event {
+                Ok(DbEvent::NewBlocks) => (),
+                Ok(DbEvent::Rollback(from)) => {
+                    if from < next_block {
+                        if !serialize_task.send_rollback(from).await {
+                            return Ok(());
+                        }
+
+                        next_block = from;
+                    }
+                }
+                // a missed rollback would leave the client with stale data
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    return Err(Error::SubscriptionLagged)
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+
+    async fn run_query(
+        &self,
+        serialize_task: &SerializeTask,
+        query: &MiniQuery,
+        order: Order,
+    ) -> Result<()> {
+        if query.from_block >= self.db.height() {
+            return Ok(());
+        }
+
+        let field_selection = query.join_field_selection();
+
+        let parquet_height = self.db.parquet_height();
+
+        let hot_from_block = cmp::max(query.from_block, parquet_height);
+
+        match order {
+            Order::Asc => {
+                if query.from_block < parquet_height {
+                    self.parquet_query(serialize_task, query, field_selection, order)
+                        .await?;
+                }
+
+                if serialize_task.is_closed() {
+                    return Ok(());
+                }
+
+                self.hot_data_query(
+                    hot_from_block,
+                    serialize_task,
+                    query,
+                    field_selection,
+                    order,
+                )
+                .await
+            }
+            Order::Desc => {
+                self.hot_data_query(
+                    hot_from_block,
+                    serialize_task,
+                    query,
+                    field_selection,
+                    order,
+                )
+                .await?;
+
+                if serialize_task.is_closed() || query.from_block >= parquet_height {
+                    return Ok(());
+                }
+
+                self.parquet_query(serialize_task, query, field_selection, order)
+                    .await
+            }
+        }
     }
 
     async fn parquet_query(
@@ -161,11 +650,12 @@ impl DataCtx {
         serialize_task: &SerializeTask,
         query: &MiniQuery,
         field_selection: FieldSelection,
+        order: Order,
     ) -> Result<()> {
         let mut parquet_idxs = self
             .db
             .clone()
-            .iter_parquet_idxs(query.from_block, Some(query.to_block))
+            .iter_parquet_idxs(query.from_block, Some(query.to_block), order)
             .await;
 
         let concurrency = self.config.max_parquet_query_concurrency.get();
@@ -195,12 +685,15 @@ impl DataCtx {
                 to: to_block,
             };
 
-            let (logs, transactions) = rayon_async::spawn({
+            let (logs, transactions, traces, state_diffs, withdrawals) = rayon_async::spawn({
                 let query = query.clone();
                 move || {
                     (
                         query.pruned_log_selection(&parquet_idx),
-                        query.pruned_tx_selection(&parquet_idx),
+                        query.pruned_tx_selection(&parquet_idx.address),
+                        query.pruned_trace_selection(&parquet_idx.address),
+                        query.pruned_state_diff_selection(&parquet_idx.address),
+                        query.pruned_withdrawal_selection(&parquet_idx.address),
                     )
                 }
             })
@@ -211,8 +704,13 @@ impl DataCtx {
                 to_block,
                 logs,
                 transactions,
+                traces,
+                state_diffs,
+                withdrawals,
+                blocks: query.blocks.clone(),
                 field_selection,
                 include_all_blocks: query.include_all_blocks,
+                abi: query.abi.clone(),
             };
 
             let (tx, rx) = futures::channel::oneshot::channel();
@@ -221,6 +719,10 @@ impl DataCtx {
             if !mini_query.include_all_blocks
                 && mini_query.logs.is_empty()
                 && mini_query.transactions.is_empty()
+                && mini_query.traces.is_empty()
+                && mini_query.state_diffs.is_empty()
+                && mini_query.withdrawals.is_empty()
+                && mini_query.blocks.is_empty()
             {
                 tx.send((Ok(QueryResult::default()), block_range)).ok();
             } else {
@@ -228,7 +730,12 @@ impl DataCtx {
                 let data_path = self.config.data_path.as_ref().unwrap().to_owned();
                 tokio::spawn(async move {
                     let metadata = match db.get_parquet_metadata(dir_name).await {
-                        Ok(metadata) => metadata.unwrap(),
+                        Ok(Some(metadata)) => metadata,
+                        Ok(None) => {
+                            let e = Error::ParquetMetadataNotFound(dir_name);
+                            tx.send((Err(e), block_range)).ok();
+                            return;
+                        }
                         Err(e) => {
                             tx.send((Err(e), block_range)).ok();
                             return;
@@ -265,10 +772,11 @@ impl DataCtx {
 
     async fn hot_data_query(
         &self,
-        from_block: u32,
+        from_block: u64,
         serialize_task: &SerializeTask,
         query: &MiniQuery,
         field_selection: FieldSelection,
+        order: Order,
     ) -> Result<()> {
         let archive_height = self.db.clone().height();
 
@@ -279,16 +787,33 @@ impl DataCtx {
         let to_block = cmp::min(archive_height, query.to_block);
 
         let step = usize::try_from(self.config.db_query_batch_size).unwrap();
-        for start in (from_block..to_block).step_by(step) {
-            let end = cmp::min(to_block, start + self.config.db_query_batch_size);
+        let mut ranges = (from_block..to_block)
+            .step_by(step)
+            .map(|start| {
+                (
+                    start,
+                    cmp::min(to_block, start + u64::from(self.config.db_query_batch_size)),
+                )
+            })
+            .collect::<Vec<_>>();
 
+        if order == Order::Desc {
+            ranges.reverse();
+        }
+
+        for (start, end) in ranges {
             let mini_query = MiniQuery {
                 from_block: start,
                 to_block: end,
                 logs: query.logs.clone(),
                 transactions: query.transactions.clone(),
+                traces: query.traces.clone(),
+                state_diffs: query.state_diffs.clone(),
+                withdrawals: query.withdrawals.clone(),
+                blocks: query.blocks.clone(),
                 field_selection,
                 include_all_blocks: query.include_all_blocks,
+                abi: query.abi.clone(),
             };
 
             if serialize_task.is_closed() {
@@ -310,3 +835,434 @@ impl DataCtx {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow2::datatypes::Schema;
+    use arrow2::io::parquet::write::{transverse, Encoding, FileWriter, RowGroupIterator};
+    use clap::Parser;
+    use eth_archive_core::deserialize::{Address, BloomFilterBytes, Bytes, Index};
+    use eth_archive_core::dir_name::DirName;
+    use eth_archive_core::types::Block;
+    use eth_archive_ingester::schema::{
+        block_schema, log_schema, parquet_write_options, tx_schema, Blocks, IntoChunks, Logs,
+        Transactions,
+    };
+    use hyper::body::HttpBody;
+    use serde_json::{json, Value};
+    use std::path::{Path, PathBuf};
+
+    fn hex(byte: u8, len: usize) -> String {
+        prefix_hex::encode(vec![byte; len])
+    }
+
+    /// Block with a single transaction, the hashes are filled with the given bytes
+    fn block(number: u64, hash: u8, tx_hash: u8) -> Block {
+        serde_json::from_value(json!({
+            "parentHash": hex(0, 32),
+            "sha3Uncles": hex(0, 32),
+            "miner": hex(0, 20),
+            "stateRoot": hex(0, 32),
+            "transactionsRoot": hex(0, 32),
+            "receiptsRoot": hex(0, 32),
+            "logsBloom": hex(0, 256),
+            "number": format!("0x{:x}", number),
+            "gasLimit": "0x1c9c380",
+            "gasUsed": "0x5208",
+            "timestamp": format!("0x{:x}", number),
+            "extraData": "0x",
+            "size": "0x220",
+            "hash": hex(hash, 32),
+            "transactions": [{
+                "blockHash": hex(hash, 32),
+                "blockNumber": format!("0x{:x}", number),
+                "from": hex(1, 20),
+                "to": hex(2, 20),
+                "gas": "0x5208",
+                "hash": hex(tx_hash, 32),
+                "input": "0x",
+                "nonce": "0x0",
+                "transactionIndex": "0x0",
+                "value": "0x64",
+            }],
+        }))
+        .unwrap()
+    }
+
+    /// Same as `block` but the transaction creates a contract at the address filled with
+    /// `contract`
+    fn contract_creation_block(number: u64, hash: u8, tx_hash: u8, contract: u8) -> Block {
+        let mut block = block(number, hash, tx_hash);
+        block.transactions[0].dest = None;
+        block.transactions[0].contract_address = Some(Address::new(&[contract; 20]));
+        block
+    }
+
+    /// Context over an empty database in a temporary directory, without the rpc downloader
+    async fn temp_ctx(name: &str) -> (Arc<DataCtx>, PathBuf) {
+        let mut path = std::env::temp_dir();
+        path.push(format!("eth-archive-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+
+        let config = Config::try_parse_from([
+            "eth-archive-worker",
+            "--db-path",
+            path.join("db").to_str().unwrap(),
+            "--data-path",
+            path.join("data").to_str().unwrap(),
+            "--request-timeout-secs",
+            "1",
+            "--connect-timeout-ms",
+            "1",
+            "--block-batch-size",
+            "1",
+            "--http-req-concurrency",
+            "1",
+            "--best-block-offset",
+            "0",
+            "--max-resp-body-size",
+            "30",
+            "--resp-time-limit",
+            "5000",
+            "--subscription-keep-alive-ms",
+            "10",
+        ])
+        .unwrap();
+
+        let db = DbHandle::new(&config.db_path, Arc::new(IngestMetrics::new()))
+            .await
+            .unwrap();
+        let db = Arc::new(db);
+        let db_writer = Arc::new(DbWriter::new(db.clone(), &config.data_path));
+
+        let ctx = DataCtx {
+            config,
+            db,
+            db_writer,
+            current_num_queries: AtomicUsize::new(0),
+            current_num_subscriptions: AtomicUsize::new(0),
+        };
+
+        (Arc::new(ctx), path)
+    }
+
+    /// Writes the block to the hot database and waits until it is committed
+    async fn write_block(ctx: &DataCtx, block: Block) {
+        let range = BlockRange {
+            from: block.number.0,
+            to: block.number.0 + 1,
+        };
+
+        let mut events = ctx.db_writer.subscribe();
+        ctx.db_writer
+            .write_batches((vec![range], vec![vec![block]], vec![Vec::new()]))
+            .await;
+        events.recv().await.unwrap();
+    }
+
+    /// Writes the blocks into a parquet folder like the ingester does and waits until
+    /// the folder is registered
+    async fn write_parquet_folder(ctx: &DataCtx, range: BlockRange, blocks: Vec<Block>) {
+        let dir_name = DirName {
+            range,
+            is_temp: false,
+        };
+        let mut path = ctx.config.data_path.clone().unwrap();
+        path.push(dir_name.to_string());
+        std::fs::create_dir_all(&path).unwrap();
+
+        let mut block_rows = Blocks::default();
+        let mut tx_rows = Transactions::default();
+        for mut block in blocks {
+            for tx in std::mem::take(&mut block.transactions) {
+                tx_rows.push(tx).unwrap();
+            }
+            block_rows.push(block).unwrap();
+        }
+
+        write_parquet(&path.join("block.parquet"), block_rows, block_schema());
+        write_parquet(&path.join("tx.parquet"), tx_rows, tx_schema());
+        write_parquet(&path.join("log.parquet"), Logs::default(), log_schema());
+
+        ctx.db_writer.register_parquet_folders(vec![dir_name]).await;
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while ctx.db.parquet_height() < range.to {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    fn write_parquet<T: IntoChunks>(path: &Path, rows: T, schema: Schema) {
+        let encodings = schema
+            .fields
+            .iter()
+            .map(|f| transverse(&f.data_type, |_| Encoding::Plain))
+            .collect();
+        let row_groups = RowGroupIterator::try_new(
+            rows.into_chunks(100).into_iter(),
+            &schema,
+            parquet_write_options(None),
+            encodings,
+        )
+        .unwrap();
+
+        let file = std::fs::File::create(path).unwrap();
+        let mut writer = FileWriter::try_new(file, schema, parquet_write_options(None)).unwrap();
+        for group in row_groups {
+            writer.write(group.unwrap()).unwrap();
+        }
+        writer.end(None).unwrap();
+    }
+
+    /// Returns the block entries of all chunks of a query response
+    async fn query_data(ctx: &Arc<DataCtx>, query: Value) -> Vec<Value> {
+        let query: Query = serde_json::from_value(query).unwrap();
+        let body = ctx.clone().query(query).await.unwrap();
+        let body = hyper::body::to_bytes(body).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        body["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(|chunk| chunk.as_array().unwrap().clone())
+            .collect()
+    }
+
+    /// Returns the event name and data of the next server-sent event, skipping keep-alives
+    async fn next_event(body: &mut Body) -> (String, Value) {
+        loop {
+            let chunk = tokio::time::timeout(Duration::from_secs(5), body.data())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            let chunk = std::str::from_utf8(&chunk).unwrap();
+
+            if chunk == ":\n\n" {
+                continue;
+            }
+
+            let (event, data) = chunk
+                .strip_prefix("event: ")
+                .unwrap()
+                .split_once("\ndata: ")
+                .unwrap();
+
+            return (event.to_owned(), serde_json::from_str(data).unwrap());
+        }
+    }
+
+    /// New blocks and rollbacks reach the subscription and the subscription slot is
+    /// released once the client disconnects while it waits for new blocks.
+    #[tokio::test]
+    async fn test_subscription() {
+        let (ctx, path) = temp_ctx("subscription").await;
+
+        let query: Query = serde_json::from_value(json!({
+            "fromBlock": 0,
+            "blocks": [{
+                "fieldSelection": {"block": {"number": true, "hash": true}},
+            }],
+        }))
+        .unwrap();
+        let mut body = ctx.clone().subscribe(query).await.unwrap();
+
+        write_block(&ctx, block(0, 10, 20)).await;
+        let (event, data) = next_event(&mut body).await;
+        assert_eq!(event, "data");
+        assert_eq!(data["data"][0]["block"]["number"], 0);
+        assert_eq!(data["nextBlock"], 1);
+
+        write_block(&ctx, block(1, 11, 21)).await;
+        let (event, data) = next_event(&mut body).await;
+        assert_eq!(event, "data");
+        assert_eq!(data["data"][0]["block"]["hash"], hex(11, 32));
+        assert_eq!(data["nextBlock"], 2);
+
+        ctx.db_writer.rollback(1).await;
+        let (event, data) = next_event(&mut body).await;
+        assert_eq!(event, "rollback");
+        assert_eq!(data["fromBlock"], 1);
+
+        write_block(&ctx, block(1, 12, 22)).await;
+        let (event, data) = next_event(&mut body).await;
+        assert_eq!(event, "data");
+        assert_eq!(data["data"][0]["block"]["hash"], hex(12, 32));
+        assert_eq!(data["nextBlock"], 2);
+
+        drop(body);
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while ctx.current_num_subscriptions.load(Ordering::SeqCst) > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    /// Transactions and blocks are found by hash in the hot database and in parquet folders,
+    /// and only the selected fields are returned.
+    #[tokio::test]
+    async fn test_lookup_by_hash() {
+        let (ctx, path) = temp_ctx("lookup-by-hash").await;
+
+        write_parquet_folder(
+            &ctx,
+            BlockRange { from: 0, to: 2 },
+            vec![block(0, 10, 20), block(1, 11, 21)],
+        )
+        .await;
+        write_block(&ctx, block(2, 12, 22)).await;
+
+        let tx_fields = TransactionFieldSelection {
+            hash: true,
+            block_number: true,
+            ..Default::default()
+        };
+        let block_fields = BlockFieldSelection {
+            number: true,
+            ..Default::default()
+        };
+
+        // block 1 is in the parquet folder and block 2 is in the hot database
+        for (number, hash, tx_hash) in [(1, 11, 21), (2, 12, 22)] {
+            let tx = ctx
+                .transaction_by_hash(Bytes32::new(&[tx_hash; 32]), tx_fields)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(tx.hash, Some(Bytes32::new(&[tx_hash; 32])));
+            assert_eq!(tx.block_number.unwrap().0, number);
+            assert!(tx.value.is_none());
+
+            let block = ctx
+                .block_by_hash(Bytes32::new(&[hash; 32]), block_fields)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(block.number.unwrap().0, number);
+            assert!(block.hash.is_none());
+        }
+
+        assert!(ctx
+            .transaction_by_hash(Bytes32::new(&[99; 32]), tx_fields)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(ctx
+            .block_by_hash(Bytes32::new(&[99; 32]), block_fields)
+            .await
+            .unwrap()
+            .is_none());
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    /// Contract creations are selected with their created contract address on both paths.
+    #[tokio::test]
+    async fn test_contract_creation() {
+        let (ctx, path) = temp_ctx("contract-creation").await;
+
+        write_parquet_folder(
+            &ctx,
+            BlockRange { from: 0, to: 2 },
+            vec![block(0, 10, 20), contract_creation_block(1, 11, 21, 31)],
+        )
+        .await;
+        write_block(&ctx, block(2, 12, 22)).await;
+        write_block(&ctx, contract_creation_block(3, 13, 23, 33)).await;
+
+        let data = query_data(
+            &ctx,
+            json!({
+                "fromBlock": 0,
+                "transactions": [{
+                    "contractCreation": true,
+                    "fieldSelection": {
+                        "transaction": {"hash": true, "to": true, "contractAddress": true},
+                    },
+                }],
+            }),
+        )
+        .await;
+
+        let txs = data
+            .iter()
+            .flat_map(|entry| entry["transactions"].as_array().unwrap().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            txs,
+            vec![
+                json!({"hash": hex(21, 32), "contractAddress": hex(31, 20)}),
+                json!({"hash": hex(23, 32), "contractAddress": hex(33, 20)}),
+            ]
+        );
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    /// Receipt fields are stored and returned the same way in the hot database and in parquet.
+    #[tokio::test]
+    async fn test_receipt_fields() {
+        let (ctx, path) = temp_ctx("receipt-fields").await;
+
+        let with_receipt = |mut block: Block| {
+            let tx = &mut block.transactions[0];
+            tx.status = Some(Index(1));
+            tx.gas_used = Some(Bytes::from_quantity(21_000));
+            tx.cumulative_gas_used = Some(Bytes::from_quantity(42_000));
+            tx.effective_gas_price = Some(Bytes::from_quantity(1_000_000_000));
+            tx.logs_bloom = Some(BloomFilterBytes::new(&[1; 256]));
+            block
+        };
+
+        write_parquet_folder(
+            &ctx,
+            BlockRange { from: 0, to: 1 },
+            vec![with_receipt(block(0, 10, 20))],
+        )
+        .await;
+        write_block(&ctx, with_receipt(block(1, 11, 21))).await;
+
+        let fields = serde_json::from_value(json!({
+            "status": true,
+            "gasUsed": true,
+            "cumulativeGasUsed": true,
+            "effectiveGasPrice": true,
+            "logsBloom": true,
+        }))
+        .unwrap();
+
+        for tx_hash in [20, 21] {
+            let tx = ctx
+                .transaction_by_hash(Bytes32::new(&[tx_hash; 32]), fields)
+                .await
+                .unwrap()
+                .unwrap();
+
+            assert_eq!(
+                serde_json::to_value(tx).unwrap(),
+                json!({
+                    "status": 1,
+                    "gasUsed": "0x5208",
+                    "cumulativeGasUsed": "0xa410",
+                    "effectiveGasPrice": "0x3b9aca00",
+                    "logsBloom": hex(1, 256),
+                }),
+                "transaction {}",
+                tx_hash
+            );
+        }
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
@@ -1,23 +1,26 @@
-use crate::bloom::Bloom;
-use crate::parquet_metadata::ParquetMetadata;
-use crate::types::{LogQueryResult, MiniQuery, QueryResult};
+use crate::parquet_metadata::{ParquetHashes, ParquetIdx, ParquetMetadata, TimestampRange};
+use crate::types::{
+    timestamp_to_u64, LogQueryResult, MiniQuery, Order, QueryResult, StateDiffQueryResult,
+    TraceQueryResult, WithdrawalQueryResult,
+};
 use crate::{Error, Result};
-use eth_archive_core::deserialize::Address;
+use eth_archive_core::deserialize::Bytes32;
 use eth_archive_core::dir_name::DirName;
 use eth_archive_core::ingest_metrics::IngestMetrics;
 use eth_archive_core::types::{
-    Block, BlockRange, Log, ResponseBlock, ResponseTransaction, Transaction,
+    Block, BlockRange, Log, ResponseBlock, ResponseTransaction, StateDiff, Trace, Transaction,
+    Withdrawal,
 };
 use std::collections::{BTreeMap, BTreeSet};
 use std::convert::TryInto;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
 use std::{cmp, iter};
 use tokio::sync::mpsc;
 
-type ParquetIdxIter<'a> = Box<dyn Iterator<Item = Result<(DirName, Bloom<Address>)>> + Send + 'a>;
+type ParquetIdxIter<'a> = Box<dyn Iterator<Item = Result<(DirName, ParquetIdx)>> + Send + 'a>;
 
 pub struct DbHandle {
     inner: rocksdb::DB,
@@ -26,9 +29,9 @@ pub struct DbHandle {
 }
 
 struct Status {
-    parquet_height: AtomicU32,
-    db_height: AtomicU32,
-    db_tail: AtomicU32,
+    parquet_height: AtomicU64,
+    db_height: AtomicU64,
+    db_tail: AtomicU64,
 }
 
 impl DbHandle {
@@ -60,6 +63,8 @@ impl DbHandle {
         let inner =
             rocksdb::DB::open_cf(&opts, path, cf_name::ALL_CF_NAMES).map_err(Error::OpenDb)?;
 
+        Self::migrate_block_number_keys(&inner, MIGRATION_BATCH_SIZE)?;
+
         let status = Self::get_status(&inner)?;
 
         Ok(Self {
@@ -95,13 +100,18 @@ impl DbHandle {
 
     pub async fn iter_parquet_idxs(
         self: Arc<Self>,
-        from: u32,
-        to: Option<u32>,
-    ) -> mpsc::Receiver<Result<(DirName, Bloom<Address>)>> {
+        from: u64,
+        to: Option<u64>,
+        order: Order,
+    ) -> mpsc::Receiver<Result<(DirName, ParquetIdx)>> {
         let (tx, rx): (_, _) = mpsc::channel(1);
 
         tokio::task::spawn_blocking(move || {
-            let iter = self.iter_parquet_idxs_impl(from, to).unwrap();
+            let iter = match order {
+                Order::Asc => self.iter_parquet_idxs_impl(from, to),
+                Order::Desc => self.iter_parquet_idxs_rev_impl(from, to),
+            }
+            .unwrap();
             for res in iter {
                 if tx.blocking_send(res).is_err() {
                     break;
@@ -112,13 +122,13 @@ impl DbHandle {
         rx
     }
 
-    fn iter_parquet_idxs_impl(&self, from: u32, to: Option<u32>) -> Result<ParquetIdxIter<'_>> {
+    fn iter_parquet_idxs_impl(&self, from: u64, to: Option<u64>) -> Result<ParquetIdxIter<'_>> {
         let parquet_idx_cf = self.inner.cf_handle(cf_name::PARQUET_IDX).unwrap();
 
         let key = key_from_dir_name(DirName {
             range: BlockRange {
                 from,
-                to: std::u32::MAX,
+                to: std::u64::MAX,
             },
             is_temp: false,
         });
@@ -143,7 +153,7 @@ impl DbHandle {
             .map(|idx| {
                 let (dir_name, idx) = idx.map_err(Error::Db)?;
                 let dir_name = dir_name_from_key(&dir_name);
-                let idx = rmp_serde::decode::from_slice(&idx).unwrap();
+                let idx = decode_parquet_idx(&idx);
 
                 Ok((dir_name, idx))
             })
@@ -162,6 +172,46 @@ impl DbHandle {
         Ok(Box::new(iter))
     }
 
+    /// Iterates the parquet folders that overlap with the given block range
+    /// starting from the folder with the highest block numbers.
+    fn iter_parquet_idxs_rev_impl(&self, from: u64, to: Option<u64>) -> Result<ParquetIdxIter<'_>> {
+        let parquet_idx_cf = self.inner.cf_handle(cf_name::PARQUET_IDX).unwrap();
+
+        let key;
+        let mode = match to {
+            Some(0) => return Ok(Box::new(iter::empty())),
+            Some(to) => {
+                key = key_from_dir_name(DirName {
+                    range: BlockRange {
+                        from: to - 1,
+                        to: std::u64::MAX,
+                    },
+                    is_temp: false,
+                });
+
+                rocksdb::IteratorMode::From(&key, rocksdb::Direction::Reverse)
+            }
+            None => rocksdb::IteratorMode::End,
+        };
+
+        let iter = self
+            .inner
+            .iterator_cf(parquet_idx_cf, mode)
+            .map(|idx| {
+                let (dir_name, idx) = idx.map_err(Error::Db)?;
+                let dir_name = dir_name_from_key(&dir_name);
+                let idx = decode_parquet_idx(&idx);
+
+                Ok((dir_name, idx))
+            })
+            .take_while(move |res| match res {
+                Ok((dir_name, _)) => dir_name.range.to > from,
+                Err(_) => true,
+            });
+
+        Ok(Box::new(iter))
+    }
+
     pub async fn query(self: Arc<Self>, query: MiniQuery) -> Result<QueryResult> {
         tokio::task::spawn_blocking(move || self.query_impl(query))
             .await
@@ -179,7 +229,43 @@ impl DbHandle {
             LogQueryResult::default()
         };
 
+        let TraceQueryResult {
+            traces,
+            transactions: trace_transactions,
+            blocks: trace_blocks,
+        } = if !query.traces.is_empty() {
+            self.query_traces(&query)?
+        } else {
+            TraceQueryResult::default()
+        };
+
+        let StateDiffQueryResult {
+            state_diffs,
+            transactions: state_diff_transactions,
+            blocks: state_diff_blocks,
+        } = if !query.state_diffs.is_empty() {
+            self.query_state_diffs(&query)?
+        } else {
+            StateDiffQueryResult::default()
+        };
+
+        let WithdrawalQueryResult {
+            withdrawals,
+            blocks: withdrawal_blocks,
+        } = if !query.withdrawals.is_empty() {
+            self.query_withdrawals(&query)?
+        } else {
+            WithdrawalQueryResult::default()
+        };
+
         let mut blocks = blocks;
+        blocks.extend(trace_blocks);
+        blocks.extend(state_diff_blocks);
+        blocks.extend(withdrawal_blocks);
+
+        let mut transactions = transactions;
+        transactions.extend(trace_transactions);
+        transactions.extend(state_diff_transactions);
 
         let transactions = if query.transactions.is_empty() && transactions.is_empty() {
             BTreeMap::new()
@@ -198,6 +284,9 @@ impl DbHandle {
         Ok(QueryResult {
             logs,
             transactions,
+            traces,
+            state_diffs,
+            withdrawals,
             blocks,
         })
     }
@@ -243,12 +332,122 @@ impl DbHandle {
         Ok(query_result)
     }
 
+    fn query_traces(&self, query: &MiniQuery) -> Result<TraceQueryResult> {
+        let trace_cf = self.inner.cf_handle(cf_name::TRACE).unwrap();
+
+        let mut query_result = TraceQueryResult::default();
+
+        for res in self.inner.iterator_cf(
+            trace_cf,
+            rocksdb::IteratorMode::From(
+                &query.from_block.to_be_bytes(),
+                rocksdb::Direction::Forward,
+            ),
+        ) {
+            let (trace_key, trace) = res.map_err(Error::Db)?;
+
+            if trace_key.as_ref() >= query.to_block.to_be_bytes().as_slice() {
+                break;
+            }
+
+            let trace: Trace = rmp_serde::decode::from_slice(&trace).unwrap();
+
+            if !query.matches_trace(&trace) {
+                continue;
+            }
+
+            if let Some(transaction_index) = trace.transaction_index {
+                query_result
+                    .transactions
+                    .insert((trace.block_number.0, transaction_index.0));
+            }
+            query_result.blocks.insert(trace.block_number.0);
+            query_result.traces.insert(
+                (trace.block_number.0, trace.trace_index.0),
+                query.field_selection.trace.prune(trace),
+            );
+        }
+
+        Ok(query_result)
+    }
+
+    fn query_state_diffs(&self, query: &MiniQuery) -> Result<StateDiffQueryResult> {
+        let state_diff_cf = self.inner.cf_handle(cf_name::STATE_DIFF).unwrap();
+
+        let mut query_result = StateDiffQueryResult::default();
+
+        for res in self.inner.iterator_cf(
+            state_diff_cf,
+            rocksdb::IteratorMode::From(
+                &query.from_block.to_be_bytes(),
+                rocksdb::Direction::Forward,
+            ),
+        ) {
+            let (state_diff_key, state_diff) = res.map_err(Error::Db)?;
+
+            if state_diff_key.as_ref() >= query.to_block.to_be_bytes().as_slice() {
+                break;
+            }
+
+            let state_diff: StateDiff = rmp_serde::decode::from_slice(&state_diff).unwrap();
+
+            if !query.matches_state_diff(&state_diff) {
+                continue;
+            }
+
+            query_result
+                .transactions
+                .insert((state_diff.block_number.0, state_diff.transaction_index.0));
+            query_result.blocks.insert(state_diff.block_number.0);
+            query_result.state_diffs.insert(
+                (state_diff.block_number.0, state_diff.diff_index.0),
+                query.field_selection.state_diff.prune(state_diff),
+            );
+        }
+
+        Ok(query_result)
+    }
+
+    fn query_withdrawals(&self, query: &MiniQuery) -> Result<WithdrawalQueryResult> {
+        let withdrawal_cf = self.inner.cf_handle(cf_name::WITHDRAWAL).unwrap();
+
+        let mut query_result = WithdrawalQueryResult::default();
+
+        for res in self.inner.iterator_cf(
+            withdrawal_cf,
+            rocksdb::IteratorMode::From(
+                &query.from_block.to_be_bytes(),
+                rocksdb::Direction::Forward,
+            ),
+        ) {
+            let (withdrawal_key, withdrawal) = res.map_err(Error::Db)?;
+
+            if withdrawal_key.as_ref() >= query.to_block.to_be_bytes().as_slice() {
+                break;
+            }
+
+            let withdrawal: Withdrawal = rmp_serde::decode::from_slice(&withdrawal).unwrap();
+
+            if !query.matches_withdrawal(&withdrawal) {
+                continue;
+            }
+
+            query_result.blocks.insert(withdrawal.block_number.0);
+            query_result.withdrawals.insert(
+                (withdrawal.block_number.0, withdrawal.index.0),
+                query.field_selection.withdrawal.prune(withdrawal),
+            );
+        }
+
+        Ok(query_result)
+    }
+
     fn query_transactions(
         &self,
         query: &MiniQuery,
-        transactions: &BTreeSet<(u32, u32)>,
-        blocks: &mut BTreeSet<u32>,
-    ) -> Result<BTreeMap<(u32, u32), ResponseTransaction>> {
+        transactions: &BTreeSet<(u64, u32)>,
+        blocks: &mut BTreeSet<u64>,
+    ) -> Result<BTreeMap<(u64, u32), ResponseTransaction>> {
         let tx_cf = self.inner.cf_handle(cf_name::TX).unwrap();
 
         let mut res_transactions = BTreeMap::new();
@@ -270,7 +469,7 @@ impl DbHandle {
 
             let tx_id = (tx.block_number.0, tx.transaction_index.0);
 
-            if transactions.contains(&tx_id) || !query.matches_tx(&tx) {
+            if !transactions.contains(&tx_id) && !query.matches_tx(&tx) {
                 continue;
             }
 
@@ -284,8 +483,8 @@ impl DbHandle {
     fn query_blocks(
         &self,
         query: &MiniQuery,
-        blocks: Option<&BTreeSet<u32>>,
-    ) -> Result<BTreeMap<u32, ResponseBlock>> {
+        blocks: Option<&BTreeSet<u64>>,
+    ) -> Result<BTreeMap<u64, ResponseBlock>> {
         let block_cf = self.inner.cf_handle(cf_name::BLOCK).unwrap();
 
         let mut res_blocks = BTreeMap::new();
@@ -305,11 +504,12 @@ impl DbHandle {
 
             let block: Block = rmp_serde::decode::from_slice(&block).unwrap();
 
-            if let Some(blocks) = blocks {
-                if blocks.contains(&block.number.0) {
-                    res_blocks.insert(block.number.0, query.field_selection.block.prune(block));
-                }
-            } else {
+            let is_joined = match blocks {
+                Some(blocks) => blocks.contains(&block.number.0),
+                None => true,
+            };
+
+            if is_joined || query.matches_block(&block.miner, &block.timestamp) {
                 res_blocks.insert(block.number.0, query.field_selection.block.prune(block));
             }
         }
@@ -320,8 +520,9 @@ impl DbHandle {
     pub fn register_parquet_folder(
         &self,
         dir_name: DirName,
-        idx: &Bloom<Address>,
+        idx: &ParquetIdx,
         metadata: &ParquetMetadata,
+        hashes: &ParquetHashes,
     ) -> Result<()> {
         let parquet_idx_cf = self.inner.cf_handle(cf_name::PARQUET_IDX).unwrap();
         let parquet_metadata_cf = self.inner.cf_handle(cf_name::PARQUET_METADATA).unwrap();
@@ -336,14 +537,29 @@ impl DbHandle {
         batch.put_cf(parquet_idx_cf, key, idx_val);
         batch.put_cf(parquet_metadata_cf, key, metadata_val);
 
+        if let Some(timestamp_range) = metadata.timestamp_range() {
+            let parquet_timestamp_cf = self.inner.cf_handle(cf_name::PARQUET_TIMESTAMP).unwrap();
+            let timestamp_val = rmp_serde::encode::to_vec(&timestamp_range).unwrap();
+            batch.put_cf(parquet_timestamp_cf, key, timestamp_val);
+        }
+
+        self.put_hashes(&mut batch, dir_name, hashes);
+
         let mut db_tail = self.status.db_tail.load(Ordering::Relaxed);
 
-        for cf in [cf_name::BLOCK, cf_name::TX, cf_name::LOG] {
+        for cf in [
+            cf_name::BLOCK,
+            cf_name::TX,
+            cf_name::LOG,
+            cf_name::TRACE,
+            cf_name::STATE_DIFF,
+            cf_name::WITHDRAWAL,
+        ] {
             let cf = self.inner.cf_handle(cf).unwrap();
 
             for res in self.inner.iterator_cf(
                 cf,
-                rocksdb::IteratorMode::From(&0u32.to_be_bytes(), rocksdb::Direction::Forward),
+                rocksdb::IteratorMode::From(&0u64.to_be_bytes(), rocksdb::Direction::Forward),
             ) {
                 let (key, _) = res.map_err(Error::Db)?;
 
@@ -371,6 +587,281 @@ impl DbHandle {
         Ok(())
     }
 
+    /// Adds a folder that was registered before timestamps were indexed to the timestamp index.
+    pub fn register_timestamp_range(
+        &self,
+        dir_name: DirName,
+        timestamp_range: TimestampRange,
+    ) -> Result<()> {
+        let parquet_timestamp_cf = self.inner.cf_handle(cf_name::PARQUET_TIMESTAMP).unwrap();
+
+        let val = rmp_serde::encode::to_vec(&timestamp_range).unwrap();
+
+        self.inner
+            .put_cf(parquet_timestamp_cf, key_from_dir_name(dir_name), val)
+            .map_err(Error::Db)
+    }
+
+    /// Adds the hashes of a folder that was registered before hashes were indexed.
+    pub fn register_hashes(&self, dir_name: DirName, hashes: &ParquetHashes) -> Result<()> {
+        let mut batch = rocksdb::WriteBatch::default();
+
+        self.put_hashes(&mut batch, dir_name, hashes);
+
+        self.inner.write(batch).map_err(Error::Db)
+    }
+
+    fn put_hashes(
+        &self,
+        batch: &mut rocksdb::WriteBatch,
+        dir_name: DirName,
+        hashes: &ParquetHashes,
+    ) {
+        let block_hash_cf = self.inner.cf_handle(cf_name::BLOCK_HASH).unwrap();
+        let tx_hash_cf = self.inner.cf_handle(cf_name::TX_HASH).unwrap();
+        let parquet_hash_indexed_cf = self.inner.cf_handle(cf_name::PARQUET_HASH_INDEXED).unwrap();
+
+        for (hash, block_number) in hashes.blocks.iter() {
+            batch.put_cf(block_hash_cf, hash.as_slice(), block_number.to_be_bytes());
+        }
+
+        for (hash, block_number, transaction_index) in hashes.transactions.iter() {
+            batch.put_cf(
+                tx_hash_cf,
+                hash.as_slice(),
+                tx_id_key(*block_number, *transaction_index),
+            );
+        }
+
+        batch.put_cf(parquet_hash_indexed_cf, key_from_dir_name(dir_name), []);
+    }
+
+    /// Returns the registered folders that aren't in the timestamp index.
+    pub async fn parquet_folders_without_timestamp_range(self: Arc<Self>) -> Result<Vec<DirName>> {
+        self.parquet_folders_not_in(cf_name::PARQUET_TIMESTAMP)
+            .await
+    }
+
+    /// Returns the registered folders that don't have their hashes indexed.
+    pub async fn parquet_folders_without_hashes(self: Arc<Self>) -> Result<Vec<DirName>> {
+        self.parquet_folders_not_in(cf_name::PARQUET_HASH_INDEXED)
+            .await
+    }
+
+    async fn parquet_folders_not_in(self: Arc<Self>, cf: &'static str) -> Result<Vec<DirName>> {
+        tokio::task::spawn_blocking(move || {
+            let parquet_idx_cf = self.inner.cf_handle(cf_name::PARQUET_IDX).unwrap();
+            let marker_cf = self.inner.cf_handle(cf).unwrap();
+
+            let mut dir_names = Vec::new();
+
+            for res in self
+                .inner
+                .iterator_cf(parquet_idx_cf, rocksdb::IteratorMode::Start)
+            {
+                let (key, _) = res.map_err(Error::Db)?;
+
+                if self
+                    .inner
+                    .get_pinned_cf(marker_cf, &key)
+                    .map_err(Error::Db)?
+                    .is_none()
+                {
+                    dir_names.push(dir_name_from_key(&key));
+                }
+            }
+
+            Ok(dir_names)
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Finds the first parquet folder that has a block with a timestamp
+    /// greater than or equal to the given timestamp.
+    pub async fn parquet_folder_by_timestamp(
+        self: Arc<Self>,
+        timestamp: u64,
+    ) -> Result<Option<DirName>> {
+        tokio::task::spawn_blocking(move || {
+            let parquet_idx_cf = self.inner.cf_handle(cf_name::PARQUET_IDX).unwrap();
+            let parquet_timestamp_cf = self.inner.cf_handle(cf_name::PARQUET_TIMESTAMP).unwrap();
+
+            let first_folder = self
+                .inner
+                .iterator_cf(parquet_idx_cf, rocksdb::IteratorMode::Start)
+                .next()
+                .transpose()
+                .map_err(Error::Db)?;
+
+            let mut next_from = match first_folder {
+                Some((key, _)) => dir_name_from_key(&key).range.from,
+                None => return Ok(None),
+            };
+
+            for res in self
+                .inner
+                .iterator_cf(parquet_timestamp_cf, rocksdb::IteratorMode::Start)
+            {
+                let (key, val) = res.map_err(Error::Db)?;
+                let dir_name = dir_name_from_key(&key);
+
+                // a missing folder might have the block we are looking for
+                if next_from != dir_name.range.from {
+                    return Err(Error::TimestampIndexIncomplete);
+                }
+                next_from = dir_name.range.to;
+
+                let timestamp_range: TimestampRange = rmp_serde::decode::from_slice(&val).unwrap();
+
+                if timestamp_range.max >= timestamp {
+                    return Ok(Some(dir_name));
+                }
+            }
+
+            if next_from < self.parquet_height() {
+                return Err(Error::TimestampIndexIncomplete);
+            }
+
+            Ok(None)
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Binary searches the hot blocks for the first block that has a timestamp
+    /// greater than or equal to the given timestamp.
+    ///
+    /// Missing block numbers, e.g. after a rollback, are skipped over.
+    pub async fn hot_block_by_timestamp(self: Arc<Self>, timestamp: u64) -> Result<Option<u64>> {
+        tokio::task::spawn_blocking(move || {
+            let block_cf = self.inner.cf_handle(cf_name::BLOCK).unwrap();
+
+            // first block in the database starting from the given block number
+            let next_block = |from: u64| -> Result<Option<(u64, Block)>> {
+                let block = self
+                    .inner
+                    .iterator_cf(
+                        block_cf,
+                        rocksdb::IteratorMode::From(
+                            &from.to_be_bytes(),
+                            rocksdb::Direction::Forward,
+                        ),
+                    )
+                    .next()
+                    .transpose()
+                    .map_err(Error::Db)?;
+
+                Ok(block.map(|(key, block)| {
+                    (
+                        block_num_from_key(&key),
+                        rmp_serde::decode::from_slice(&block).unwrap(),
+                    )
+                }))
+            };
+
+            let mut low = match next_block(self.parquet_height())? {
+                Some((block_num, _)) => block_num,
+                None => return Ok(None),
+            };
+            let mut high = self.db_height();
+
+            while low < high {
+                let mid = low + (high - low) / 2;
+
+                match next_block(mid)? {
+                    Some((block_num, block))
+                        if block_num < high && timestamp_to_u64(&block.timestamp) < timestamp =>
+                    {
+                        low = block_num + 1;
+                    }
+                    // blocks between mid and the found block are missing so the result
+                    // is either before mid or the found block itself
+                    _ => high = mid,
+                }
+            }
+
+            match next_block(low)? {
+                Some((block_num, _)) if block_num < self.db_height() => Ok(Some(block_num)),
+                _ => Ok(None),
+            }
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Returns the block number and transaction index of the transaction with the given hash.
+    pub async fn tx_id_by_hash(self: Arc<Self>, hash: Bytes32) -> Result<Option<(u64, u32)>> {
+        tokio::task::spawn_blocking(move || {
+            let tx_hash_cf = self.inner.cf_handle(cf_name::TX_HASH).unwrap();
+
+            let tx_id = self
+                .inner
+                .get_pinned_cf(tx_hash_cf, hash.as_slice())
+                .map_err(Error::Db)?
+                .map(|key| tx_id_from_key(&key));
+
+            Ok(tx_id)
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Returns the number of the block with the given hash.
+    pub async fn block_number_by_hash(self: Arc<Self>, hash: Bytes32) -> Result<Option<u64>> {
+        tokio::task::spawn_blocking(move || {
+            let block_hash_cf = self.inner.cf_handle(cf_name::BLOCK_HASH).unwrap();
+
+            let block_number = self
+                .inner
+                .get_pinned_cf(block_hash_cf, hash.as_slice())
+                .map_err(Error::Db)?
+                .map(|key| block_num_from_key(&key));
+
+            Ok(block_number)
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Returns the transaction from the hot data if it is there.
+    pub async fn get_transaction(
+        self: Arc<Self>,
+        block_number: u64,
+        transaction_index: u32,
+    ) -> Result<Option<Transaction>> {
+        tokio::task::spawn_blocking(move || {
+            let tx_cf = self.inner.cf_handle(cf_name::TX).unwrap();
+
+            let tx = self
+                .inner
+                .get_pinned_cf(tx_cf, tx_id_key(block_number, transaction_index))
+                .map_err(Error::Db)?
+                .map(|tx| rmp_serde::decode::from_slice(&tx).unwrap());
+
+            Ok(tx)
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Returns the block from the hot data if it is there.
+    pub async fn get_block(self: Arc<Self>, block_number: u64) -> Result<Option<Block>> {
+        tokio::task::spawn_blocking(move || {
+            let block_cf = self.inner.cf_handle(cf_name::BLOCK).unwrap();
+
+            let block = self
+                .inner
+                .get_pinned_cf(block_cf, block_number.to_be_bytes())
+                .map_err(Error::Db)?
+                .map(|block| rmp_serde::decode::from_slice(&block).unwrap());
+
+            Ok(block)
+        })
+        .await
+        .unwrap()
+    }
+
     pub fn insert_batches(
         &self,
         (block_ranges, block_batches, log_batches): (
@@ -382,6 +873,11 @@ impl DbHandle {
         let block_cf = self.inner.cf_handle(cf_name::BLOCK).unwrap();
         let tx_cf = self.inner.cf_handle(cf_name::TX).unwrap();
         let log_cf = self.inner.cf_handle(cf_name::LOG).unwrap();
+        let trace_cf = self.inner.cf_handle(cf_name::TRACE).unwrap();
+        let state_diff_cf = self.inner.cf_handle(cf_name::STATE_DIFF).unwrap();
+        let withdrawal_cf = self.inner.cf_handle(cf_name::WITHDRAWAL).unwrap();
+        let block_hash_cf = self.inner.cf_handle(cf_name::BLOCK_HASH).unwrap();
+        let tx_hash_cf = self.inner.cf_handle(cf_name::TX_HASH).unwrap();
 
         let mut batch = rocksdb::WriteBatch::default();
 
@@ -395,11 +891,37 @@ impl DbHandle {
                 let val = rmp_serde::encode::to_vec(block).unwrap();
                 batch.put_cf(block_cf, block.number.to_be_bytes(), &val);
 
+                if let Some(hash) = &block.hash {
+                    batch.put_cf(block_hash_cf, hash.as_slice(), block.number.to_be_bytes());
+                }
+
                 for tx in block.transactions.iter() {
                     let val = rmp_serde::encode::to_vec(tx).unwrap();
                     let tx_key = tx_key(tx);
 
                     batch.put_cf(tx_cf, tx_key, &val);
+                    batch.put_cf(tx_hash_cf, tx.hash.as_slice(), tx_key);
+                }
+
+                for trace in block.traces.iter() {
+                    let val = rmp_serde::encode::to_vec(trace).unwrap();
+                    let trace_key = trace_key(trace);
+
+                    batch.put_cf(trace_cf, trace_key, &val);
+                }
+
+                for state_diff in block.state_diffs.iter() {
+                    let val = rmp_serde::encode::to_vec(state_diff).unwrap();
+                    let state_diff_key = state_diff_key(state_diff);
+
+                    batch.put_cf(state_diff_cf, state_diff_key, &val);
+                }
+
+                for withdrawal in block.withdrawals.iter() {
+                    let val = rmp_serde::encode::to_vec(withdrawal).unwrap();
+                    let withdrawal_key = withdrawal_key(withdrawal);
+
+                    batch.put_cf(withdrawal_cf, withdrawal_key, &val);
                 }
 
                 db_height = cmp::max(db_height, block.number.0 + 1);
@@ -433,7 +955,107 @@ impl DbHandle {
         Ok(())
     }
 
-    pub fn height(&self) -> u32 {
+    pub async fn recent_block_hashes(
+        self: Arc<Self>,
+        depth: usize,
+    ) -> Result<BTreeMap<u64, Bytes32>> {
+        tokio::task::spawn_blocking(move || self.recent_block_hashes_impl(depth))
+            .await
+            .unwrap()
+    }
+
+    fn recent_block_hashes_impl(&self, depth: usize) -> Result<BTreeMap<u64, Bytes32>> {
+        let block_cf = self.inner.cf_handle(cf_name::BLOCK).unwrap();
+
+        let mut hashes = BTreeMap::new();
+
+        for res in self
+            .inner
+            .iterator_cf(block_cf, rocksdb::IteratorMode::End)
+            .take(depth)
+        {
+            let (_, block) = res.map_err(Error::Db)?;
+
+            let block: Block = rmp_serde::decode::from_slice(&block).unwrap();
+
+            if let Some(hash) = block.hash {
+                hashes.insert(block.number.0, hash);
+            }
+        }
+
+        Ok(hashes)
+    }
+
+    /// Deletes all blocks, transactions, logs, traces and state diffs
+    /// starting from the given block number.
+    pub fn rollback(&self, from: u64) -> Result<()> {
+        let mut batch = rocksdb::WriteBatch::default();
+
+        let block_cf = self.inner.cf_handle(cf_name::BLOCK).unwrap();
+        let block_hash_cf = self.inner.cf_handle(cf_name::BLOCK_HASH).unwrap();
+        let tx_hash_cf = self.inner.cf_handle(cf_name::TX_HASH).unwrap();
+
+        for res in self.inner.iterator_cf(
+            block_cf,
+            rocksdb::IteratorMode::From(&from.to_be_bytes(), rocksdb::Direction::Forward),
+        ) {
+            let (_, block) = res.map_err(Error::Db)?;
+
+            let block: Block = rmp_serde::decode::from_slice(&block).unwrap();
+
+            if let Some(hash) = &block.hash {
+                batch.delete_cf(block_hash_cf, hash.as_slice());
+            }
+
+            for tx in block.transactions.iter() {
+                batch.delete_cf(tx_hash_cf, tx.hash.as_slice());
+            }
+        }
+
+        for cf in [
+            cf_name::BLOCK,
+            cf_name::TX,
+            cf_name::LOG,
+            cf_name::TRACE,
+            cf_name::STATE_DIFF,
+            cf_name::WITHDRAWAL,
+        ] {
+            let cf = self.inner.cf_handle(cf).unwrap();
+
+            for res in self.inner.iterator_cf(
+                cf,
+                rocksdb::IteratorMode::From(&from.to_be_bytes(), rocksdb::Direction::Forward),
+            ) {
+                let (key, _) = res.map_err(Error::Db)?;
+
+                batch.delete_cf(cf, &key);
+            }
+        }
+
+        self.inner.write(batch).map_err(Error::Db)?;
+
+        let db_tail = self
+            .inner
+            .iterator_cf(block_cf, rocksdb::IteratorMode::Start)
+            .next()
+            .transpose()
+            .map_err(Error::Db)?
+            .map(|(key, _)| block_num_from_key(&key))
+            .unwrap_or(0);
+
+        let db_height = cmp::min(self.status.db_height.load(Ordering::Relaxed), from);
+
+        self.status.db_tail.store(db_tail, Ordering::Relaxed);
+        self.status.db_height.store(db_height, Ordering::Relaxed);
+        let height = self.height();
+        if height > 0 {
+            self.metrics.record_write_height(height - 1);
+        }
+
+        Ok(())
+    }
+
+    pub fn height(&self) -> u64 {
         let parquet_height = self.status.parquet_height.load(Ordering::Relaxed);
         let db_height = self.status.db_height.load(Ordering::Relaxed);
         let db_tail = self.status.db_tail.load(Ordering::Relaxed);
@@ -445,14 +1067,137 @@ impl DbHandle {
         }
     }
 
-    pub fn parquet_height(&self) -> u32 {
+    pub fn parquet_height(&self) -> u64 {
         self.status.parquet_height.load(Ordering::Relaxed)
     }
 
-    pub fn db_height(&self) -> u32 {
+    pub fn db_height(&self) -> u64 {
         self.status.db_height.load(Ordering::Relaxed)
     }
 
+    /// Rewrites the keys and values of databases that were created when block numbers
+    /// were stored as u32. Entries are rewritten in batches of `batch_size` and the last
+    /// migrated key of each column family is saved with every batch so the migration
+    /// continues where it left off if the process is stopped.
+    fn migrate_block_number_keys(inner: &rocksdb::DB, batch_size: usize) -> Result<()> {
+        // (column family, old length, number of block numbers, is the block number in the value)
+        let layouts = [
+            (cf_name::BLOCK, 4, 1, false),
+            (cf_name::TX, 8, 1, false),
+            (cf_name::LOG, 8, 1, false),
+            (cf_name::TRACE, 8, 1, false),
+            (cf_name::STATE_DIFF, 8, 1, false),
+            (cf_name::WITHDRAWAL, 8, 1, false),
+            (cf_name::PARQUET_IDX, 8, 2, false),
+            (cf_name::PARQUET_METADATA, 8, 2, false),
+            (cf_name::PARQUET_TIMESTAMP, 8, 2, false),
+            (cf_name::PARQUET_HASH_INDEXED, 8, 2, false),
+            (cf_name::BLOCK_HASH, 4, 1, true),
+            (cf_name::TX_HASH, 8, 1, true),
+        ];
+
+        let meta_cf = inner.cf_handle(cf_name::META).unwrap();
+
+        let version = inner
+            .get_cf(meta_cf, SCHEMA_VERSION_KEY)
+            .map_err(Error::Db)?
+            .map(|val| u32::from_be_bytes(val.as_slice().try_into().unwrap()));
+
+        match version {
+            Some(SCHEMA_VERSION) => return Ok(()),
+            Some(version) => return Err(Error::UnknownDbSchemaVersion(version)),
+            None => (),
+        }
+
+        // databases created before the version key was added are in the u32 layout
+        let mut is_empty = true;
+        for (name, _, _, _) in layouts {
+            let cf = inner.cf_handle(name).unwrap();
+            let first = inner
+                .iterator_cf(cf, rocksdb::IteratorMode::Start)
+                .next()
+                .transpose()
+                .map_err(Error::Db)?;
+            is_empty &= first.is_none();
+        }
+
+        if !is_empty {
+            for (name, old_len, count, in_value) in layouts {
+                Self::migrate_cf(inner, name, old_len, count, in_value, batch_size)?;
+            }
+        }
+
+        let mut batch = rocksdb::WriteBatch::default();
+        for (name, _, _, _) in layouts {
+            batch.delete_cf(meta_cf, migration_progress_key(name));
+        }
+        batch.put_cf(meta_cf, SCHEMA_VERSION_KEY, SCHEMA_VERSION.to_be_bytes());
+        inner.write(batch).map_err(Error::Db)?;
+
+        Ok(())
+    }
+
+    fn migrate_cf(
+        inner: &rocksdb::DB,
+        name: &str,
+        old_len: usize,
+        count: usize,
+        in_value: bool,
+        batch_size: usize,
+    ) -> Result<()> {
+        let start = Instant::now();
+
+        let cf = inner.cf_handle(name).unwrap();
+        let meta_cf = inner.cf_handle(cf_name::META).unwrap();
+        let progress_key = migration_progress_key(name);
+
+        let progress = inner.get_cf(meta_cf, &progress_key).map_err(Error::Db)?;
+        let mode = match &progress {
+            Some(key) => rocksdb::IteratorMode::From(key.as_slice(), rocksdb::Direction::Forward),
+            None => rocksdb::IteratorMode::Start,
+        };
+
+        let mut batch = rocksdb::WriteBatch::default();
+        let mut num_migrated = 0;
+
+        for res in inner.iterator_cf(cf, mode) {
+            let (key, val) = res.map_err(Error::Db)?;
+
+            // entries migrated before the process was stopped are already in the new layout
+            let old = if in_value { &val } else { &key };
+            if old.len() != old_len {
+                continue;
+            }
+
+            if in_value {
+                batch.put_cf(cf, &key, widen_block_numbers(&val, count));
+            } else {
+                batch.delete_cf(cf, &key);
+                batch.put_cf(cf, widen_block_numbers(&key, count), &val);
+            }
+
+            num_migrated += 1;
+
+            if num_migrated % batch_size == 0 {
+                batch.put_cf(meta_cf, &progress_key, &key);
+                inner.write(std::mem::take(&mut batch)).map_err(Error::Db)?;
+            }
+        }
+
+        inner.write(batch).map_err(Error::Db)?;
+
+        if num_migrated > 0 {
+            log::info!(
+                "migrated {} entries of {} to u64 block numbers in {}ms",
+                num_migrated,
+                name,
+                start.elapsed().as_millis()
+            );
+        }
+
+        Ok(())
+    }
+
     fn get_status(inner: &rocksdb::DB) -> Result<Status> {
         let parquet_idx_cf = inner.cf_handle(cf_name::PARQUET_IDX).unwrap();
 
@@ -483,9 +1228,9 @@ impl DbHandle {
             .unwrap_or(0);
 
         Ok(Status {
-            parquet_height: AtomicU32::new(parquet_height),
-            db_tail: AtomicU32::new(db_tail),
-            db_height: AtomicU32::new(db_height),
+            parquet_height: AtomicU64::new(parquet_height),
+            db_tail: AtomicU64::new(db_tail),
+            db_height: AtomicU64::new(db_height),
         })
     }
 
@@ -515,36 +1260,120 @@ mod cf_name {
     pub const BLOCK: &str = "BLOCK";
     pub const TX: &str = "TX";
     pub const LOG: &str = "LOG";
+    pub const TRACE: &str = "TRACE";
+    pub const STATE_DIFF: &str = "STATE_DIFF";
+    pub const WITHDRAWAL: &str = "WITHDRAWAL";
     pub const PARQUET_IDX: &str = "PARQUET_IDX";
     pub const PARQUET_METADATA: &str = "PARQUET_METADATA";
+    pub const PARQUET_TIMESTAMP: &str = "PARQUET_TIMESTAMP";
+    pub const PARQUET_HASH_INDEXED: &str = "PARQUET_HASH_INDEXED";
+    pub const BLOCK_HASH: &str = "BLOCK_HASH";
+    pub const TX_HASH: &str = "TX_HASH";
+    pub const META: &str = "META";
+
+    pub const ALL_CF_NAMES: [&str; 13] = [
+        BLOCK,
+        TX,
+        LOG,
+        TRACE,
+        STATE_DIFF,
+        WITHDRAWAL,
+        PARQUET_IDX,
+        PARQUET_METADATA,
+        PARQUET_TIMESTAMP,
+        PARQUET_HASH_INDEXED,
+        BLOCK_HASH,
+        TX_HASH,
+        META,
+    ];
+}
+
+/// Key of the layout version of the database in the META column family
+const SCHEMA_VERSION_KEY: &[u8] = b"schema_version";
+/// Version of the layout that stores block numbers as u64
+const SCHEMA_VERSION: u32 = 1;
+/// Number of entries that are rewritten in one write when migrating the database
+const MIGRATION_BATCH_SIZE: usize = 100_000;
+
+fn migration_progress_key(cf_name: &str) -> Vec<u8> {
+    format!("migrate_{}", cf_name).into_bytes()
+}
+
+fn tx_key(tx: &Transaction) -> [u8; 12] {
+    tx_id_key(tx.block_number.0, tx.transaction_index.0)
+}
+
+fn tx_id_key(block_number: u64, transaction_index: u32) -> [u8; 12] {
+    let mut key = [0; 12];
+
+    key[..8].copy_from_slice(&block_number.to_be_bytes());
+    key[8..].copy_from_slice(&transaction_index.to_be_bytes());
+
+    key
+}
+
+fn tx_id_from_key(key: &[u8]) -> (u64, u32) {
+    let block_number = block_num_from_key(key);
 
-    pub const ALL_CF_NAMES: [&str; 5] = [BLOCK, TX, LOG, PARQUET_IDX, PARQUET_METADATA];
+    let transaction_index = (&key[8..12]).try_into().unwrap();
+    let transaction_index = u32::from_be_bytes(transaction_index);
+
+    (block_number, transaction_index)
 }
 
-fn tx_key(tx: &Transaction) -> [u8; 8] {
-    let mut key = [0; 8];
+fn log_key(log: &Log) -> [u8; 12] {
+    let mut key = [0; 12];
 
-    key[..4].copy_from_slice(&tx.block_number.0.to_be_bytes());
-    key[4..].copy_from_slice(&tx.transaction_index.0.to_be_bytes());
+    key[..8].copy_from_slice(&log.block_number.to_be_bytes());
+    key[8..].copy_from_slice(&log.log_index.to_be_bytes());
 
     key
 }
 
-fn log_key(log: &Log) -> [u8; 8] {
-    let mut key = [0; 8];
+fn trace_key(trace: &Trace) -> [u8; 12] {
+    let mut key = [0; 12];
 
-    key[..4].copy_from_slice(&log.block_number.to_be_bytes());
-    key[4..].copy_from_slice(&log.log_index.to_be_bytes());
+    key[..8].copy_from_slice(&trace.block_number.to_be_bytes());
+    key[8..].copy_from_slice(&trace.trace_index.to_be_bytes());
 
     key
 }
 
+fn state_diff_key(state_diff: &StateDiff) -> [u8; 12] {
+    let mut key = [0; 12];
+
+    key[..8].copy_from_slice(&state_diff.block_number.to_be_bytes());
+    key[8..].copy_from_slice(&state_diff.diff_index.to_be_bytes());
+
+    key
+}
+
+fn withdrawal_key(withdrawal: &Withdrawal) -> [u8; 12] {
+    let mut key = [0; 12];
+
+    key[..8].copy_from_slice(&withdrawal.block_number.to_be_bytes());
+    key[8..].copy_from_slice(&withdrawal.index.to_be_bytes());
+
+    key
+}
+
+fn decode_parquet_idx(val: &[u8]) -> ParquetIdx {
+    match rmp_serde::decode::from_slice(val) {
+        Ok(idx) => idx,
+        // folders registered before the topic index was added only have the address filter
+        Err(_) => ParquetIdx {
+            address: rmp_serde::decode::from_slice(val).unwrap(),
+            topic: None,
+        },
+    }
+}
+
 fn dir_name_from_key(key: &[u8]) -> DirName {
-    let from = (&key[..4]).try_into().unwrap();
-    let from = u32::from_be_bytes(from);
+    let from = (&key[..8]).try_into().unwrap();
+    let from = u64::from_be_bytes(from);
 
-    let to = (&key[4..]).try_into().unwrap();
-    let to = u32::from_be_bytes(to);
+    let to = (&key[8..]).try_into().unwrap();
+    let to = u64::from_be_bytes(to);
 
     DirName {
         range: BlockRange { from, to },
@@ -552,26 +1381,41 @@ fn dir_name_from_key(key: &[u8]) -> DirName {
     }
 }
 
-fn key_from_dir_name(dir_name: DirName) -> [u8; 8] {
+fn key_from_dir_name(dir_name: DirName) -> [u8; 16] {
     assert!(!dir_name.is_temp);
 
-    let mut key = [0; 8];
+    let mut key = [0; 16];
 
-    key[..4].copy_from_slice(&dir_name.range.from.to_be_bytes());
-    key[4..].copy_from_slice(&dir_name.range.to.to_be_bytes());
+    key[..8].copy_from_slice(&dir_name.range.from.to_be_bytes());
+    key[8..].copy_from_slice(&dir_name.range.to.to_be_bytes());
 
     key
 }
 
-fn block_num_from_key(key: &[u8]) -> u32 {
-    let arr: [u8; 4] = key[..4].try_into().unwrap();
+fn block_num_from_key(key: &[u8]) -> u64 {
+    let arr: [u8; 8] = key[..8].try_into().unwrap();
 
-    u32::from_be_bytes(arr)
+    u64::from_be_bytes(arr)
+}
+
+/// Converts a key or value that starts with the given number of u32 block numbers
+/// to the layout that stores them as u64.
+fn widen_block_numbers(old: &[u8], count: usize) -> Vec<u8> {
+    let mut new = Vec::with_capacity(old.len() + count * 4);
+
+    for num in old[..count * 4].chunks(4) {
+        let num = u32::from_be_bytes(num.try_into().unwrap());
+        new.extend_from_slice(&u64::from(num).to_be_bytes());
+    }
+    new.extend_from_slice(&old[count * 4..]);
+
+    new
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json::json;
 
     #[test]
     fn test_dir_name_key_roundtrip() {
@@ -610,4 +1454,360 @@ mod tests {
 
         assert!(key0 < key1);
     }
+
+    #[test]
+    fn test_widen_block_numbers() {
+        let tx_key = widen_block_numbers(&[0, 0, 1, 2, 0, 0, 0, 3], 1);
+        assert_eq!(tx_id_from_key(&tx_key), (258, 3));
+        assert_eq!(tx_key, tx_id_key(258, 3));
+
+        let dir_key = widen_block_numbers(&[0, 0, 0, 1, 0, 0, 0, 2], 2);
+        assert_eq!(
+            dir_name_from_key(&dir_key).range,
+            BlockRange { from: 1, to: 2 }
+        );
+    }
+
+    #[test]
+    fn test_decode_legacy_parquet_idx() {
+        use crate::bloom::Bloom;
+        use eth_archive_core::deserialize::Address;
+        use eth_archive_core::hash::HashSet;
+
+        let addr = Address::new(&[7; 20]);
+        let mut addrs = HashSet::new();
+        addrs.insert(addr.clone());
+
+        let legacy = Bloom::new(&addrs, 0.001, 1024);
+        let val = rmp_serde::encode::to_vec(&legacy).unwrap();
+
+        let idx = decode_parquet_idx(&val);
+
+        assert!(idx.address.contains(&addr));
+        assert!(idx.topic.is_none());
+    }
+
+    fn hex(byte: u8, len: usize) -> String {
+        prefix_hex::encode(vec![byte; len])
+    }
+
+    /// Block with the given timestamp and one transaction per given source address byte
+    fn block(number: u64, timestamp: u64, tx_sources: &[u8]) -> Block {
+        let transactions = tx_sources
+            .iter()
+            .enumerate()
+            .map(|(idx, &source)| {
+                json!({
+                    "blockHash": hex(number as u8, 32),
+                    "blockNumber": format!("0x{:x}", number),
+                    "from": hex(source, 20),
+                    "gas": "0x5208",
+                    "hash": hex(number as u8 * 16 + idx as u8, 32),
+                    "input": "0x",
+                    "nonce": "0x0",
+                    "transactionIndex": format!("0x{:x}", idx),
+                    "value": "0x0",
+                })
+            })
+            .collect::<Vec<_>>();
+
+        serde_json::from_value(json!({
+            "parentHash": hex(number as u8 - 1, 32),
+            "sha3Uncles": hex(0, 32),
+            "miner": hex(0, 20),
+            "stateRoot": hex(0, 32),
+            "transactionsRoot": hex(0, 32),
+            "receiptsRoot": hex(0, 32),
+            "logsBloom": hex(0, 256),
+            "number": format!("0x{:x}", number),
+            "gasLimit": "0x1c9c380",
+            "gasUsed": "0x5208",
+            "timestamp": format!("0x{:x}", timestamp),
+            "extraData": "0x",
+            "size": "0x220",
+            "hash": hex(number as u8, 32),
+            "transactions": transactions,
+        }))
+        .unwrap()
+    }
+
+    /// Opens an empty database in a temporary directory
+    fn temp_db(name: &str) -> (DbHandle, PathBuf) {
+        let mut path = std::env::temp_dir();
+        path.push(format!("eth-archive-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+
+        let db = DbHandle::new_impl(path.clone(), Arc::new(IngestMetrics::new())).unwrap();
+
+        (db, path)
+    }
+
+    /// Transactions of matched logs are included along with the transactions matched by
+    /// the transaction selections.
+    #[test]
+    fn test_query_transactions_of_logs() {
+        use crate::field_selection::FieldSelection;
+        use crate::types::{MiniLogSelection, MiniTransactionSelection, Range};
+        use eth_archive_core::deserialize::Address;
+
+        let block = block(1, 100, &[1, 2, 3]);
+        let log: Log = serde_json::from_value(json!({
+            "address": hex(9, 20),
+            "blockHash": hex(1, 32),
+            "blockNumber": "0x1",
+            "data": "0x",
+            "logIndex": "0x0",
+            "removed": false,
+            "topics": [],
+            "transactionHash": hex(16, 32),
+            "transactionIndex": "0x0",
+        }))
+        .unwrap();
+
+        let (db, path) = temp_db("query-transactions");
+        db.insert_batches((
+            vec![BlockRange { from: 1, to: 2 }],
+            vec![vec![block]],
+            vec![vec![log]],
+        ))
+        .unwrap();
+
+        let log_selection = MiniLogSelection {
+            address: [Address::new(&[9; 20])].into_iter().collect(),
+            topics: Default::default(),
+            exclude_address: Default::default(),
+            exclude_topics: Default::default(),
+        };
+        let tx_selection = MiniTransactionSelection {
+            source: [Address::new(&[2; 20])].into_iter().collect(),
+            dest: Default::default(),
+            address: Default::default(),
+            sighash: Vec::new(),
+            status: None,
+            contract_creation: false,
+            kind: Vec::new(),
+            access_list_address: Default::default(),
+            authorization_address: Default::default(),
+            value: Range::default(),
+            gas_price: Range::default(),
+            gas: Range::default(),
+            max_fee_per_gas: Range::default(),
+            kind_range: Range::default(),
+            exclude_source: Default::default(),
+            exclude_dest: Default::default(),
+            exclude_address: Default::default(),
+        };
+
+        let query = |logs: Vec<MiniLogSelection>, transactions: Vec<MiniTransactionSelection>| {
+            let res = db
+                .query_impl(MiniQuery {
+                    from_block: 0,
+                    to_block: 2,
+                    logs,
+                    transactions,
+                    traces: Vec::new(),
+                    state_diffs: Vec::new(),
+                    withdrawals: Vec::new(),
+                    blocks: Vec::new(),
+                    field_selection: FieldSelection::default(),
+                    include_all_blocks: false,
+                    abi: Default::default(),
+                })
+                .unwrap();
+            res.transactions.into_keys().collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            query(vec![log_selection.clone()], vec![tx_selection.clone()]),
+            vec![(1, 0), (1, 1)]
+        );
+        assert_eq!(query(vec![log_selection], Vec::new()), vec![(1, 0)]);
+        assert_eq!(query(Vec::new(), vec![tx_selection]), vec![(1, 1)]);
+
+        drop(db);
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    /// Block selections match blocks without any other selections and don't read
+    /// the transactions of the matched blocks.
+    #[test]
+    fn test_query_block_selection() {
+        use crate::field_selection::FieldSelection;
+        use crate::types::MiniBlockSelection;
+        use eth_archive_core::deserialize::Address;
+
+        let (db, path) = temp_db("query-block-selection");
+        db.insert_batches((
+            vec![BlockRange { from: 1, to: 4 }],
+            vec![vec![
+                block(1, 100, &[1]),
+                block(2, 200, &[1]),
+                block(3, 300, &[1]),
+            ]],
+            vec![Vec::new()],
+        ))
+        .unwrap();
+
+        let query = |miner: &[u8], from_timestamp, to_timestamp| {
+            let mut field_selection = FieldSelection::default();
+            field_selection.block.number = true;
+
+            let res = db
+                .query_impl(MiniQuery {
+                    from_block: 0,
+                    to_block: 4,
+                    logs: Vec::new(),
+                    transactions: Vec::new(),
+                    traces: Vec::new(),
+                    state_diffs: Vec::new(),
+                    withdrawals: Vec::new(),
+                    blocks: vec![MiniBlockSelection {
+                        miner: miner.iter().map(|&n| Address::new(&[n; 20])).collect(),
+                        from_timestamp,
+                        to_timestamp,
+                    }],
+                    field_selection,
+                    include_all_blocks: false,
+                    abi: Default::default(),
+                })
+                .unwrap();
+            assert!(res.transactions.is_empty());
+            res.blocks.into_keys().collect::<Vec<_>>()
+        };
+
+        assert_eq!(query(&[], None, None), vec![1, 2, 3]);
+        assert_eq!(query(&[], Some(150), Some(300)), vec![2]);
+        assert_eq!(query(&[], Some(200), None), vec![2, 3]);
+        assert_eq!(query(&[0], None, Some(200)), vec![1]);
+        assert_eq!(query(&[9], None, None), Vec::<u64>::new());
+
+        drop(db);
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_hot_block_by_timestamp_with_gaps() {
+        let (db, path) = temp_db("block-by-timestamp");
+
+        // block 3 is missing
+        let blocks = vec![
+            block(1, 10, &[]),
+            block(2, 20, &[]),
+            block(4, 40, &[]),
+            block(5, 50, &[]),
+        ];
+        db.insert_batches((
+            vec![BlockRange { from: 1, to: 6 }],
+            vec![blocks],
+            vec![Vec::new()],
+        ))
+        .unwrap();
+
+        let db = Arc::new(db);
+        for (timestamp, expected) in [
+            (0, Some(1)),
+            (10, Some(1)),
+            (15, Some(2)),
+            (25, Some(4)),
+            (30, Some(4)),
+            (40, Some(4)),
+            (50, Some(5)),
+            (55, None),
+        ] {
+            assert_eq!(
+                db.clone().hot_block_by_timestamp(timestamp).await.unwrap(),
+                expected,
+                "timestamp {}",
+                timestamp
+            );
+        }
+
+        drop(db);
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_new_db_schema_version() {
+        let (db, path) = temp_db("schema-version");
+
+        let meta_cf = db.inner.cf_handle(cf_name::META).unwrap();
+        let version = db.inner.get_cf(meta_cf, SCHEMA_VERSION_KEY).unwrap();
+        assert_eq!(version, Some(SCHEMA_VERSION.to_be_bytes().to_vec()));
+
+        drop(db);
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    /// Migration continues from the saved progress of a database that was partially migrated
+    #[test]
+    fn test_migrate_block_number_keys_resume() {
+        let mut path = std::env::temp_dir();
+        path.push(format!("eth-archive-migrate-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+
+        let mut opts = rocksdb::Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        let inner = rocksdb::DB::open_cf(&opts, &path, cf_name::ALL_CF_NAMES).unwrap();
+
+        let block_cf = inner.cf_handle(cf_name::BLOCK).unwrap();
+        let tx_hash_cf = inner.cf_handle(cf_name::TX_HASH).unwrap();
+        let meta_cf = inner.cf_handle(cf_name::META).unwrap();
+
+        // blocks 1 and 2 were migrated before the process was stopped
+        for num in 1u32..=2 {
+            inner
+                .put_cf(block_cf, u64::from(num).to_be_bytes(), [num as u8])
+                .unwrap();
+        }
+        for num in 3u32..=7 {
+            inner
+                .put_cf(block_cf, num.to_be_bytes(), [num as u8])
+                .unwrap();
+        }
+        inner
+            .put_cf(
+                meta_cf,
+                migration_progress_key(cf_name::BLOCK),
+                2u32.to_be_bytes(),
+            )
+            .unwrap();
+
+        for num in 1u32..=3 {
+            let mut val = num.to_be_bytes().to_vec();
+            val.extend_from_slice(&0u32.to_be_bytes());
+            inner.put_cf(tx_hash_cf, [num as u8; 32], val).unwrap();
+        }
+
+        DbHandle::migrate_block_number_keys(&inner, 2).unwrap();
+
+        let blocks = inner
+            .iterator_cf(block_cf, rocksdb::IteratorMode::Start)
+            .map(|res| {
+                let (key, val) = res.unwrap();
+                (block_num_from_key(&key), val.to_vec())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            blocks,
+            (1..=7)
+                .map(|num| (num, vec![num as u8]))
+                .collect::<Vec<_>>()
+        );
+
+        for num in 1u64..=3 {
+            let val = inner.get_cf(tx_hash_cf, [num as u8; 32]).unwrap().unwrap();
+            assert_eq!(val, tx_id_key(num, 0));
+        }
+
+        let version = inner.get_cf(meta_cf, SCHEMA_VERSION_KEY).unwrap();
+        assert_eq!(version, Some(SCHEMA_VERSION.to_be_bytes().to_vec()));
+        let progress = inner
+            .get_cf(meta_cf, migration_progress_key(cf_name::BLOCK))
+            .unwrap();
+        assert_eq!(progress, None);
+
+        drop(inner);
+        std::fs::remove_dir_all(&path).unwrap();
+    }
 }
@@ -1,28 +1,42 @@
 use crate::db::DbHandle;
-use crate::parquet_metadata::CollectMetadataAndParquetIdx;
+use crate::parquet_metadata::{CollectMetadataAndParquetIdx, ParquetHashes, TimestampRange};
 use crate::Result;
 use eth_archive_core::dir_name::DirName;
 use eth_archive_core::types::{Block, BlockRange, Log};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc};
 
 pub struct DbWriter {
     tx: mpsc::Sender<Job>,
+    events: broadcast::Sender<DbEvent>,
+}
+
+/// Change to the hot data that was committed to the database.
+#[derive(Clone, Copy, Debug)]
+pub enum DbEvent {
+    /// New blocks were written to the database
+    NewBlocks,
+    /// All blocks starting from the given block number were deleted
+    Rollback(u64),
 }
 
 impl DbWriter {
     pub fn new(db: Arc<DbHandle>, data_path: &Option<PathBuf>) -> Self {
         let (tx, mut rx) = mpsc::channel::<Job>(4);
 
+        let (events, _) = broadcast::channel(64);
+
         let data_path = data_path.as_ref().map(|p| p.to_owned());
 
+        let thread_events = events.clone();
         std::thread::spawn(move || {
             while let Some(job) = rx.blocking_recv() {
                 loop {
                     let res = match job.clone() {
                         Job::WriteBatches(batches) => db.insert_batches(batches),
+                        Job::Rollback(from) => db.rollback(from),
                         Job::RegisterParquetFolders(dir_names) => {
                             Self::handle_register_parquet_folders(
                                 &db,
@@ -30,6 +44,12 @@ impl DbWriter {
                                 dir_names,
                             )
                         }
+                        Job::RegisterTimestampRange((dir_name, timestamp_range)) => {
+                            db.register_timestamp_range(dir_name, timestamp_range)
+                        }
+                        Job::RegisterHashes((dir_name, hashes)) => {
+                            db.register_hashes(dir_name, &hashes)
+                        }
                     };
 
                     match res {
@@ -40,16 +60,36 @@ impl DbWriter {
                         }
                     }
                 }
+
+                let event = match job {
+                    Job::WriteBatches(_) => DbEvent::NewBlocks,
+                    Job::Rollback(from) => DbEvent::Rollback(from),
+                    Job::RegisterParquetFolders(_)
+                    | Job::RegisterTimestampRange(_)
+                    | Job::RegisterHashes(_) => continue,
+                };
+
+                // there might be no subscribers
+                thread_events.send(event).ok();
             }
         });
 
-        Self { tx }
+        Self { tx, events }
+    }
+
+    /// Subscribe to changes to the hot data.
+    pub fn subscribe(&self) -> broadcast::Receiver<DbEvent> {
+        self.events.subscribe()
     }
 
     pub async fn write_batches(&self, batches: (Vec<BlockRange>, Vec<Vec<Block>>, Vec<Vec<Log>>)) {
         self.tx.send(Job::WriteBatches(batches)).await.ok().unwrap();
     }
 
+    pub async fn rollback(&self, from: u64) {
+        self.tx.send(Job::Rollback(from)).await.ok().unwrap();
+    }
+
     pub async fn register_parquet_folders(&self, dir_names: Vec<DirName>) {
         self.tx
             .send(Job::RegisterParquetFolders(dir_names))
@@ -58,6 +98,28 @@ impl DbWriter {
             .unwrap();
     }
 
+    /// Adds the timestamp range of a folder that was registered before timestamps were indexed.
+    pub async fn register_timestamp_range(
+        &self,
+        dir_name: DirName,
+        timestamp_range: TimestampRange,
+    ) {
+        self.tx
+            .send(Job::RegisterTimestampRange((dir_name, timestamp_range)))
+            .await
+            .ok()
+            .unwrap();
+    }
+
+    /// Adds the hashes of a folder that was registered before hashes were indexed.
+    pub async fn register_hashes(&self, dir_name: DirName, hashes: ParquetHashes) {
+        self.tx
+            .send(Job::RegisterHashes((dir_name, hashes)))
+            .await
+            .ok()
+            .unwrap();
+    }
+
     #[allow(clippy::manual_flatten)]
     fn handle_register_parquet_folders(
         db: &DbHandle,
@@ -65,13 +127,14 @@ impl DbWriter {
         dir_names: Vec<DirName>,
     ) -> Result<()> {
         for dir_name in dir_names {
-            let (metadata, idx) = CollectMetadataAndParquetIdx {
+            let collector = CollectMetadataAndParquetIdx {
                 data_path,
                 dir_name,
-            }
-            .collect()?;
+            };
+            let hashes = collector.collect_hashes()?;
+            let (metadata, idx) = collector.collect()?;
 
-            db.register_parquet_folder(dir_name, &idx, &metadata)?;
+            db.register_parquet_folder(dir_name, &idx, &metadata, &hashes)?;
         }
 
         db.compact();
@@ -83,5 +146,8 @@ impl DbWriter {
 #[derive(Clone)]
 enum Job {
     WriteBatches((Vec<BlockRange>, Vec<Vec<Block>>, Vec<Vec<Log>>)),
+    Rollback(u64),
     RegisterParquetFolders(Vec<DirName>),
+    RegisterTimestampRange((DirName, TimestampRange)),
+    RegisterHashes((DirName, ParquetHashes)),
 }
@@ -2,11 +2,22 @@ use crate::config::Config;
 use crate::db::DbHandle;
 use crate::db_writer::DbWriter;
 use crate::{Error, Result};
+use eth_archive_core::deserialize::Bytes32;
 use eth_archive_core::eth_client::EthClient;
 use eth_archive_core::ingest_metrics::IngestMetrics;
 use eth_archive_core::retry::Retry;
+use eth_archive_core::types::{Block, BlockRange, Log};
 use futures::StreamExt;
+use std::cmp;
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+type Batches = (Vec<BlockRange>, Vec<Vec<Block>>, Vec<Vec<Log>>);
+
+/// Time to wait before retrying after the ethereum node fails
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
 
 pub struct Downloader {
     pub config: Config,
@@ -17,7 +28,9 @@ pub struct Downloader {
 }
 
 impl Downloader {
-    pub async fn spawn(self) -> Result<()> {
+    /// Spawns the download task. The task only returns if the hot database can't
+    /// follow the chain anymore, the worker should be stopped in that case.
+    pub async fn spawn(self) -> Result<JoinHandle<Result<()>>> {
         let eth_client =
             EthClient::new(self.config.ingest.clone(), self.retry, self.ingest_metrics)
                 .map_err(Error::CreateEthClient)?;
@@ -39,10 +52,13 @@ impl Downloader {
             .map_err(Error::GetBestBlock)?;
 
         let initial_hot_block_range = self.config.initial_hot_block_range;
+        let max_reorg_depth = self.config.max_reorg_depth;
+
+        let mut block_hashes = self.db.clone().recent_block_hashes(max_reorg_depth).await?;
 
         let db_writer = self.db_writer;
 
-        tokio::spawn(async move {
+        let handle = tokio::spawn(async move {
             let mut start = match initial_hot_block_range {
                 Some(range) if best_block > range => best_block - range,
                 _ => 0,
@@ -52,15 +68,241 @@ impl Downloader {
                 start = start_height;
             }
 
-            let batches = eth_client.stream_batches(Some(start), None);
-            futures::pin_mut!(batches);
+            loop {
+                // parent hashes are checked here instead of in the eth client
+                // so reorgs can be rolled back instead of halting the stream.
+                // The parent hash policy of the eth client is for the ingester, which
+                // can't roll back parquet folders that were already written.
+                let batches = eth_client.clone().stream_batches(Some(start), None, None);
+                futures::pin_mut!(batches);
+
+                let mut fork_block = None;
+                let mut stream_failed = false;
+
+                while let Some(res) = batches.next().await {
+                    let mut data = match res {
+                        Ok(data) => data,
+                        Err(e) => {
+                            log::error!("failed to get batches from ethereum node:\n{}", e);
+                            stream_failed = true;
+                            break;
+                        }
+                    };
+
+                    fork_block = check_parent_hashes(&mut block_hashes, &data.1, max_reorg_depth);
+
+                    if let Some(fork_block) = fork_block {
+                        truncate_batches(&mut data, fork_block);
+                    }
+
+                    // the stream is restarted from here if it fails
+                    if let Some(to) = data.0.iter().map(|range| range.to).max() {
+                        start = cmp::max(start, to);
+                    }
+
+                    db_writer.write_batches(data).await;
+
+                    if fork_block.is_some() {
+                        break;
+                    }
+                }
+
+                let fork_block = match fork_block {
+                    Some(fork_block) => fork_block,
+                    None if stream_failed => {
+                        tokio::time::sleep(RETRY_INTERVAL).await;
+                        continue;
+                    }
+                    None => break,
+                };
+
+                let ancestor = loop {
+                    match find_common_ancestor(&eth_client, &block_hashes, fork_block).await {
+                        Ok(ancestor) => break ancestor,
+                        Err(Error::ReorgTooDeep(fork_block)) => {
+                            // rolling back isn't possible without the hashes of the older
+                            // blocks so stop the worker instead of serving orphaned data.
+                            log::error!(
+                                "chain reorganization at block {} is deeper than the {} recent block hashes in the hot database. stopping the worker, the hot database has to be rebuilt",
+                                fork_block,
+                                max_reorg_depth,
+                            );
+                            return Err(Error::ReorgTooDeep(fork_block));
+                        }
+                        Err(e) => {
+                            log::error!(
+                                "failed to find common ancestor of reorganized block {}, retrying:\n{}",
+                                fork_block,
+                                e
+                            );
+                            tokio::time::sleep(RETRY_INTERVAL).await;
+                        }
+                    }
+                };
+
+                log::warn!(
+                    "detected chain reorganization at block {}. rolling back to block {}",
+                    fork_block,
+                    ancestor + 1
+                );
 
-            while let Some(res) = batches.next().await {
-                let data = res.unwrap();
-                db_writer.write_batches(data).await;
+                block_hashes.retain(|&number, _| number <= ancestor);
+                db_writer.rollback(ancestor + 1).await;
+
+                start = ancestor + 1;
             }
+
+            Ok(())
         });
 
-        Ok(())
+        Ok(handle)
+    }
+}
+
+/// Records hashes of the given blocks and returns the number of the first block
+/// that doesn't build on top of the previously recorded block.
+fn check_parent_hashes(
+    block_hashes: &mut BTreeMap<u64, Bytes32>,
+    block_batches: &[Vec<Block>],
+    max_reorg_depth: usize,
+) -> Option<u64> {
+    for block in block_batches.iter().flatten() {
+        let number = block.number.0;
+
+        if let Some(parent_hash) = number.checked_sub(1).and_then(|n| block_hashes.get(&n)) {
+            if parent_hash != &block.parent_hash {
+                return Some(number);
+            }
+        }
+
+        if let Some(hash) = &block.hash {
+            block_hashes.insert(number, hash.clone());
+        }
+
+        while block_hashes.len() > max_reorg_depth {
+            block_hashes.pop_first();
+        }
+    }
+
+    None
+}
+
+/// Removes all data starting from (and including) the given block number.
+fn truncate_batches((block_ranges, block_batches, log_batches): &mut Batches, to: u64) {
+    for range in block_ranges.iter_mut() {
+        range.from = cmp::min(range.from, to);
+        range.to = cmp::min(range.to, to);
+    }
+
+    for blocks in block_batches.iter_mut() {
+        blocks.retain(|block| block.number.0 < to);
+    }
+
+    for logs in log_batches.iter_mut() {
+        logs.retain(|log| log.block_number.0 < to);
+    }
+}
+
+/// Walks back from the forked block and returns the number of the newest block
+/// that is both in the hot database and on the canonical chain.
+async fn find_common_ancestor(
+    eth_client: &Arc<EthClient>,
+    block_hashes: &BTreeMap<u64, Bytes32>,
+    fork_block: u64,
+) -> Result<u64> {
+    for (&number, hash) in block_hashes.range(..fork_block).rev() {
+        let block = eth_client
+            .clone()
+            .get_block(number)
+            .await
+            .map_err(Error::GetBlock)?;
+
+        if block.hash.as_ref() == Some(hash) {
+            return Ok(number);
+        }
+    }
+
+    Err(Error::ReorgTooDeep(fork_block))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eth_archive_core::deserialize::{Address, BlockNumber, BloomFilterBytes, Bytes};
+
+    fn block(number: u64, hash: u8, parent_hash: u8) -> Block {
+        Block {
+            parent_hash: Bytes32::new(&[parent_hash; 32]),
+            sha3_uncles: Bytes32::new(&[0; 32]),
+            miner: Address::new(&[0; 20]),
+            state_root: Bytes32::new(&[0; 32]),
+            transactions_root: Bytes32::new(&[0; 32]),
+            receipts_root: Bytes32::new(&[0; 32]),
+            logs_bloom: BloomFilterBytes::new(&[0; 256]),
+            difficulty: None,
+            number: BlockNumber(number),
+            gas_limit: Bytes::new(&[]),
+            gas_used: Bytes::new(&[]),
+            timestamp: Bytes::new(&[]),
+            extra_data: Bytes::new(&[]),
+            mix_hash: None,
+            nonce: None,
+            total_difficulty: None,
+            base_fee_per_gas: None,
+            size: Bytes::new(&[]),
+            hash: Some(Bytes32::new(&[hash; 32])),
+            transactions: Vec::new(),
+            traces: Vec::new(),
+            state_diffs: Vec::new(),
+            blob_gas_used: None,
+            excess_blob_gas: None,
+            parent_beacon_block_root: None,
+            withdrawals_root: None,
+            withdrawals: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_check_parent_hashes() {
+        let mut block_hashes = BTreeMap::new();
+        block_hashes.insert(9, Bytes32::new(&[9; 32]));
+
+        let batches = vec![vec![block(10, 10, 9), block(11, 11, 10)]];
+        assert_eq!(check_parent_hashes(&mut block_hashes, &batches, 2), None);
+        assert_eq!(
+            block_hashes.keys().copied().collect::<Vec<_>>(),
+            vec![10, 11]
+        );
+
+        let batches = vec![vec![block(12, 12, 11)], vec![block(13, 13, 42)]];
+        assert_eq!(
+            check_parent_hashes(&mut block_hashes, &batches, 2),
+            Some(13)
+        );
+        assert_eq!(
+            block_hashes.keys().copied().collect::<Vec<_>>(),
+            vec![11, 12]
+        );
+    }
+
+    #[test]
+    fn test_truncate_batches() {
+        let mut batches = (
+            vec![
+                BlockRange { from: 10, to: 12 },
+                BlockRange { from: 12, to: 14 },
+            ],
+            vec![
+                vec![block(10, 10, 9), block(11, 11, 10)],
+                vec![block(12, 12, 11), block(13, 13, 12)],
+            ],
+            vec![Vec::new(), Vec::new()],
+        );
+
+        truncate_batches(&mut batches, 13);
+
+        assert_eq!(batches.0[1], BlockRange { from: 12, to: 13 });
+        assert_eq!(batches.1[0].len(), 2);
+        assert_eq!(batches.1[1].len(), 1);
     }
 }
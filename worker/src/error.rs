@@ -1,4 +1,5 @@
 use arrow2::error::Error as ArrowError;
+use eth_archive_core::dir_name::DirName;
 use std::io;
 use std::result::Result as StdResult;
 use thiserror::Error as ThisError;
@@ -28,7 +29,7 @@ pub enum Error {
     #[error("invalid topic in query")]
     InvalidTopic,
     #[error("block range {0:?} not found in {1} parquet files")]
-    RangeNotFoundInParquetFiles((u32, u32), &'static str),
+    RangeNotFoundInParquetFiles((u64, u64), &'static str),
     #[error("too many toics in query. maximum is 4 but got {0}")]
     TooManyTopics(usize),
     #[error("invalid parquet file name \"{0}\"")]
@@ -51,6 +52,10 @@ pub enum Error {
     EmptyQuery,
     #[error("max number of queries are running.")]
     MaxNumberOfQueriesReached,
+    #[error("max number of subscriptions are running.")]
+    MaxNumberOfSubscriptionsReached,
+    #[error("subscription fell behind database updates")]
+    SubscriptionLagged,
     #[error("invalid request body:\n{0:?}")]
     InvalidRequestBody(Option<serde_json::Error>),
     #[error("failed to create missing directories:\n{0}")]
@@ -61,6 +66,30 @@ pub enum Error {
     ReadParquet(ArrowError),
     #[error("failed to open file:\n{0}")]
     OpenParquetFile(io::Error),
+    #[error("failed to get block from ethereum node:\n{0}")]
+    GetBlock(eth_archive_core::Error),
+    #[error("failed to encode response:\n{0}")]
+    EncodeResponse(ArrowError),
+    #[error("{0} doesn't fit into a u64")]
+    QuantityTooWide(&'static str),
+    #[error("{0} is wider than 256 bits")]
+    QuantityWiderThanU256(&'static str),
+    #[error("metadata of parquet folder {0} not found in the database")]
+    ParquetMetadataNotFound(DirName),
+    #[error("timestamp index is incomplete, it is being built in the background")]
+    TimestampIndexIncomplete,
+    #[error("invalid timestamp in request")]
+    InvalidTimestamp,
+    #[error("invalid hash in request")]
+    InvalidHash,
+    #[error("invalid field selection in request:\n{0}")]
+    InvalidFieldSelection(serde_json::Error),
+    #[error("unknown database schema version {0}")]
+    UnknownDbSchemaVersion(u32),
+    #[error("couldn't find common ancestor of reorganized block {0} in the hot database")]
+    ReorgTooDeep(u64),
+    #[error("invalid aggregation: {0}")]
+    InvalidAggregation(&'static str),
 }
 
 pub type Result<T> = StdResult<T, Error>;
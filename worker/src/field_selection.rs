@@ -1,6 +1,7 @@
 use eth_archive_core::hash::HashSet;
 use eth_archive_core::types::{
-    Block, Log, ResponseBlock, ResponseLog, ResponseTransaction, Transaction,
+    Block, Log, ResponseBlock, ResponseLog, ResponseStateDiff, ResponseTrace, ResponseTransaction,
+    ResponseWithdrawal, StateDiff, Trace, Transaction, Withdrawal,
 };
 use serde::{Deserialize, Serialize};
 
@@ -50,6 +51,9 @@ pub struct FieldSelection {
     pub block: BlockFieldSelection,
     pub transaction: TransactionFieldSelection,
     pub log: LogFieldSelection,
+    pub trace: TraceFieldSelection,
+    pub state_diff: StateDiffFieldSelection,
+    pub withdrawal: WithdrawalFieldSelection,
 }
 
 impl FieldSelection {
@@ -61,11 +65,27 @@ impl FieldSelection {
         self.transaction.dest = true;
         self.transaction.source = true;
         self.transaction.status = true;
+        self.transaction.kind = true;
         self.log.block_number = true;
         self.log.log_index = true;
         self.log.transaction_index = true;
         self.log.address = true;
         self.log.topics = true;
+        self.trace.block_number = true;
+        self.trace.trace_index = true;
+        self.trace.transaction_index = true;
+        self.trace.source = true;
+        self.trace.dest = true;
+        self.trace.call_type = true;
+        self.state_diff.block_number = true;
+        self.state_diff.diff_index = true;
+        self.state_diff.transaction_index = true;
+        self.state_diff.address = true;
+        self.state_diff.key = true;
+        self.withdrawal.block_number = true;
+        self.withdrawal.index = true;
+        self.withdrawal.validator_index = true;
+        self.withdrawal.address = true;
 
         self
     }
@@ -105,6 +125,10 @@ pub struct BlockFieldSelection {
     pub base_fee_per_gas: bool,
     pub size: bool,
     pub hash: bool,
+    pub blob_gas_used: bool,
+    pub excess_blob_gas: bool,
+    pub parent_beacon_block_root: bool,
+    pub withdrawals_root: bool,
 }
 
 impl BlockFieldSelection {
@@ -130,6 +154,10 @@ impl BlockFieldSelection {
         to_fields!(self, fields, base_fee_per_gas);
         to_fields!(self, fields, size);
         to_fields!(self, fields, hash);
+        to_fields!(self, fields, blob_gas_used);
+        to_fields!(self, fields, excess_blob_gas);
+        to_fields!(self, fields, parent_beacon_block_root);
+        to_fields!(self, fields, withdrawals_root);
 
         fields
     }
@@ -155,6 +183,10 @@ impl BlockFieldSelection {
             base_fee_per_gas: prune_col!(block, self, base_fee_per_gas).flatten(),
             size: prune_col!(block, self, size),
             hash: prune_col!(block, self, hash).flatten(),
+            blob_gas_used: prune_col!(block, self, blob_gas_used).flatten(),
+            excess_blob_gas: prune_col!(block, self, excess_blob_gas).flatten(),
+            parent_beacon_block_root: prune_col!(block, self, parent_beacon_block_root).flatten(),
+            withdrawals_root: prune_col!(block, self, withdrawals_root).flatten(),
         }
     }
 
@@ -179,6 +211,10 @@ impl BlockFieldSelection {
             base_fee_per_gas: prune_col_opt!(block, self, base_fee_per_gas),
             size: prune_col_opt!(block, self, size),
             hash: prune_col_opt!(block, self, hash),
+            blob_gas_used: prune_col_opt!(block, self, blob_gas_used),
+            excess_blob_gas: prune_col_opt!(block, self, excess_blob_gas),
+            parent_beacon_block_root: prune_col_opt!(block, self, parent_beacon_block_root),
+            withdrawals_root: prune_col_opt!(block, self, withdrawals_root),
         }
     }
 }
@@ -222,6 +258,15 @@ pub struct TransactionFieldSelection {
     pub gas_price: bool,
     pub hash: bool,
     pub status: bool,
+    pub contract_address: bool,
+    pub gas_used: bool,
+    pub cumulative_gas_used: bool,
+    pub effective_gas_price: bool,
+    pub logs_bloom: bool,
+    pub max_fee_per_blob_gas: bool,
+    pub blob_versioned_hashes: bool,
+    pub access_list: bool,
+    pub authorization_list: bool,
 }
 
 impl TransactionFieldSelection {
@@ -248,6 +293,15 @@ impl TransactionFieldSelection {
         to_fields!(self, fields, gas_price);
         to_fields!(self, fields, hash);
         to_fields!(self, fields, status);
+        to_fields!(self, fields, contract_address);
+        to_fields!(self, fields, gas_used);
+        to_fields!(self, fields, cumulative_gas_used);
+        to_fields!(self, fields, effective_gas_price);
+        to_fields!(self, fields, logs_bloom);
+        to_fields!(self, fields, max_fee_per_blob_gas);
+        to_fields!(self, fields, blob_versioned_hashes);
+        to_fields!(self, fields, access_list);
+        to_fields!(self, fields, authorization_list);
 
         fields
     }
@@ -274,6 +328,15 @@ impl TransactionFieldSelection {
             gas_price: prune_col!(tx, self, gas_price).flatten(),
             hash: prune_col!(tx, self, hash),
             status: prune_col!(tx, self, status).flatten(),
+            contract_address: prune_col!(tx, self, contract_address).flatten(),
+            gas_used: prune_col!(tx, self, gas_used).flatten(),
+            cumulative_gas_used: prune_col!(tx, self, cumulative_gas_used).flatten(),
+            effective_gas_price: prune_col!(tx, self, effective_gas_price).flatten(),
+            logs_bloom: prune_col!(tx, self, logs_bloom).flatten(),
+            max_fee_per_blob_gas: prune_col!(tx, self, max_fee_per_blob_gas).flatten(),
+            blob_versioned_hashes: prune_col!(tx, self, blob_versioned_hashes).flatten(),
+            access_list: prune_col!(tx, self, access_list).flatten(),
+            authorization_list: prune_col!(tx, self, authorization_list).flatten(),
         }
     }
 
@@ -299,6 +362,15 @@ impl TransactionFieldSelection {
             gas_price: prune_col_opt!(tx, self, gas_price),
             hash: prune_col_opt!(tx, self, hash),
             status: prune_col_opt!(tx, self, status),
+            contract_address: prune_col_opt!(tx, self, contract_address),
+            gas_used: prune_col_opt!(tx, self, gas_used),
+            cumulative_gas_used: prune_col_opt!(tx, self, cumulative_gas_used),
+            effective_gas_price: prune_col_opt!(tx, self, effective_gas_price),
+            logs_bloom: prune_col_opt!(tx, self, logs_bloom),
+            max_fee_per_blob_gas: prune_col_opt!(tx, self, max_fee_per_blob_gas),
+            blob_versioned_hashes: prune_col_opt!(tx, self, blob_versioned_hashes),
+            access_list: prune_col_opt!(tx, self, access_list),
+            authorization_list: prune_col_opt!(tx, self, authorization_list),
         }
     }
 }
@@ -340,7 +412,9 @@ impl LogFieldSelection {
         to_fields!(self, fields, data);
         to_fields!(self, fields, log_index);
         to_fields!(self, fields, removed);
-        to_fields!(self, fields, topics);
+        if self.topics {
+            fields.extend(["topic0", "topic1", "topic2", "topic3"]);
+        }
         to_fields!(self, fields, transaction_hash);
         to_fields!(self, fields, transaction_index);
 
@@ -375,3 +449,231 @@ impl LogFieldSelection {
         }
     }
 }
+
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    derive_more::BitOr,
+    derive_more::Not,
+    PartialEq,
+    Eq,
+)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct TraceFieldSelection {
+    pub block_number: bool,
+    pub transaction_index: bool,
+    pub transaction_hash: bool,
+    #[serde(rename = "index")]
+    pub trace_index: bool,
+    pub trace_address: bool,
+    pub subtraces: bool,
+    pub call_type: bool,
+    #[serde(rename = "from")]
+    pub source: bool,
+    #[serde(rename = "to")]
+    pub dest: bool,
+    pub value: bool,
+    pub gas: bool,
+    pub gas_used: bool,
+    pub input: bool,
+    pub output: bool,
+    pub error: bool,
+}
+
+impl TraceFieldSelection {
+    pub fn as_fields(&self) -> HashSet<&'static str> {
+        let mut fields = HashSet::new();
+
+        to_fields!(self, fields, block_number);
+        to_fields!(self, fields, transaction_index);
+        to_fields!(self, fields, transaction_hash);
+        to_fields!(self, fields, trace_index);
+        to_fields!(self, fields, trace_address);
+        to_fields!(self, fields, subtraces);
+        to_fields!(self, fields, call_type);
+        to_fields!(self, fields, source);
+        to_fields!(self, fields, dest);
+        to_fields!(self, fields, value);
+        to_fields!(self, fields, gas);
+        to_fields!(self, fields, gas_used);
+        to_fields!(self, fields, input);
+        to_fields!(self, fields, output);
+        to_fields!(self, fields, error);
+
+        fields
+    }
+
+    pub fn prune(&self, trace: Trace) -> ResponseTrace {
+        ResponseTrace {
+            block_number: prune_col!(trace, self, block_number),
+            transaction_index: prune_col!(trace, self, transaction_index).flatten(),
+            transaction_hash: prune_col!(trace, self, transaction_hash).flatten(),
+            trace_index: prune_col!(trace, self, trace_index),
+            trace_address: prune_col!(trace, self, trace_address),
+            subtraces: prune_col!(trace, self, subtraces),
+            call_type: prune_col!(trace, self, call_type),
+            source: prune_col!(trace, self, source).flatten(),
+            dest: prune_col!(trace, self, dest).flatten(),
+            value: prune_col!(trace, self, value).flatten(),
+            gas: prune_col!(trace, self, gas).flatten(),
+            gas_used: prune_col!(trace, self, gas_used).flatten(),
+            input: prune_col!(trace, self, input).flatten(),
+            output: prune_col!(trace, self, output).flatten(),
+            error: prune_col!(trace, self, error).flatten(),
+        }
+    }
+
+    pub fn prune_opt(&self, trace: ResponseTrace) -> ResponseTrace {
+        ResponseTrace {
+            block_number: prune_col_opt!(trace, self, block_number),
+            transaction_index: prune_col_opt!(trace, self, transaction_index),
+            transaction_hash: prune_col_opt!(trace, self, transaction_hash),
+            trace_index: prune_col_opt!(trace, self, trace_index),
+            trace_address: prune_col_opt!(trace, self, trace_address),
+            subtraces: prune_col_opt!(trace, self, subtraces),
+            call_type: prune_col_opt!(trace, self, call_type),
+            source: prune_col_opt!(trace, self, source),
+            dest: prune_col_opt!(trace, self, dest),
+            value: prune_col_opt!(trace, self, value),
+            gas: prune_col_opt!(trace, self, gas),
+            gas_used: prune_col_opt!(trace, self, gas_used),
+            input: prune_col_opt!(trace, self, input),
+            output: prune_col_opt!(trace, self, output),
+            error: prune_col_opt!(trace, self, error),
+        }
+    }
+}
+
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    derive_more::BitOr,
+    derive_more::Not,
+    PartialEq,
+    Eq,
+)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct StateDiffFieldSelection {
+    pub block_number: bool,
+    pub transaction_index: bool,
+    pub transaction_hash: bool,
+    #[serde(rename = "index")]
+    pub diff_index: bool,
+    pub address: bool,
+    pub kind: bool,
+    pub key: bool,
+    pub prev: bool,
+    pub next: bool,
+}
+
+impl StateDiffFieldSelection {
+    pub fn as_fields(&self) -> HashSet<&'static str> {
+        let mut fields = HashSet::new();
+
+        to_fields!(self, fields, block_number);
+        to_fields!(self, fields, transaction_index);
+        to_fields!(self, fields, transaction_hash);
+        to_fields!(self, fields, diff_index);
+        to_fields!(self, fields, address);
+        to_fields!(self, fields, kind);
+        to_fields!(self, fields, key);
+        to_fields!(self, fields, prev);
+        to_fields!(self, fields, next);
+
+        fields
+    }
+
+    pub fn prune(&self, state_diff: StateDiff) -> ResponseStateDiff {
+        ResponseStateDiff {
+            block_number: prune_col!(state_diff, self, block_number),
+            transaction_index: prune_col!(state_diff, self, transaction_index),
+            transaction_hash: prune_col!(state_diff, self, transaction_hash),
+            diff_index: prune_col!(state_diff, self, diff_index),
+            address: prune_col!(state_diff, self, address),
+            kind: prune_col!(state_diff, self, kind),
+            key: prune_col!(state_diff, self, key).flatten(),
+            prev: prune_col!(state_diff, self, prev).flatten(),
+            next: prune_col!(state_diff, self, next).flatten(),
+        }
+    }
+
+    pub fn prune_opt(&self, state_diff: ResponseStateDiff) -> ResponseStateDiff {
+        ResponseStateDiff {
+            block_number: prune_col_opt!(state_diff, self, block_number),
+            transaction_index: prune_col_opt!(state_diff, self, transaction_index),
+            transaction_hash: prune_col_opt!(state_diff, self, transaction_hash),
+            diff_index: prune_col_opt!(state_diff, self, diff_index),
+            address: prune_col_opt!(state_diff, self, address),
+            kind: prune_col_opt!(state_diff, self, kind),
+            key: prune_col_opt!(state_diff, self, key),
+            prev: prune_col_opt!(state_diff, self, prev),
+            next: prune_col_opt!(state_diff, self, next),
+        }
+    }
+}
+
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    derive_more::BitOr,
+    derive_more::Not,
+    PartialEq,
+    Eq,
+)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct WithdrawalFieldSelection {
+    pub block_number: bool,
+    pub index: bool,
+    pub validator_index: bool,
+    pub address: bool,
+    pub amount: bool,
+}
+
+impl WithdrawalFieldSelection {
+    pub fn as_fields(&self) -> HashSet<&'static str> {
+        let mut fields = HashSet::new();
+
+        to_fields!(self, fields, block_number);
+        to_fields!(self, fields, index);
+        to_fields!(self, fields, validator_index);
+        to_fields!(self, fields, address);
+        to_fields!(self, fields, amount);
+
+        fields
+    }
+
+    pub fn prune(&self, withdrawal: Withdrawal) -> ResponseWithdrawal {
+        ResponseWithdrawal {
+            block_number: prune_col!(withdrawal, self, block_number),
+            index: prune_col!(withdrawal, self, index),
+            validator_index: prune_col!(withdrawal, self, validator_index),
+            address: prune_col!(withdrawal, self, address),
+            amount: prune_col!(withdrawal, self, amount),
+        }
+    }
+
+    pub fn prune_opt(&self, withdrawal: ResponseWithdrawal) -> ResponseWithdrawal {
+        ResponseWithdrawal {
+            block_number: prune_col_opt!(withdrawal, self, block_number),
+            index: prune_col_opt!(withdrawal, self, index),
+            validator_index: prune_col_opt!(withdrawal, self, validator_index),
+            address: prune_col_opt!(withdrawal, self, address),
+            amount: prune_col_opt!(withdrawal, self, amount),
+        }
+    }
+}
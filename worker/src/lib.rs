@@ -1,3 +1,5 @@
+mod abi;
+mod aggregate;
 mod bloom;
 mod config;
 mod data_ctx;
@@ -9,6 +11,7 @@ mod field_selection;
 mod parquet_metadata;
 mod parquet_query;
 mod parquet_watcher;
+mod response_format;
 mod serialize_task;
 mod server;
 mod types;
@@ -1,17 +1,23 @@
 use eth_archive_worker::{Config, Server};
 
 use mimalloc::MiMalloc;
+use std::process::ExitCode;
 
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     env_logger::init();
 
     let config = Config::parse();
 
-    if let Err(e) = Server::run(config).await {
-        log::error!("failed to run server:\n{}", e);
+    // the runtime is dropped before exiting so the database is closed cleanly
+    match Server::run(config).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            log::error!("failed to run server:\n{}", e);
+            ExitCode::FAILURE
+        }
     }
 }
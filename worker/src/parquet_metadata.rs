@@ -1,13 +1,15 @@
 use crate::bloom::Bloom;
+use crate::types::{MiniTransactionSelection, Range};
 use crate::{Error, Result};
-use arrow2::array::{self, UInt32Array};
+use arrow2::array::{self, FixedSizeBinaryArray, UInt32Array, UInt64Array};
 use arrow2::compute::concatenate::concatenate;
 use arrow2::datatypes::{DataType, Field};
 use arrow2::io::parquet;
 use eth_archive_core::define_cols;
-use eth_archive_core::deserialize::{Address, Bytes32};
+use eth_archive_core::deserialize::{Address, Bytes32, U256};
 use eth_archive_core::dir_name::DirName;
 use eth_archive_core::hash::HashSet;
+use eth_archive_core::parquet_source::util::{cast_columns, file_fields};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::{cmp, fs, io};
@@ -22,12 +24,49 @@ pub struct ParquetMetadata {
     pub log: Vec<LogRowGroupMetadata>,
     pub tx: Vec<TransactionRowGroupMetadata>,
     pub block: Vec<BlockRowGroupMetadata>,
+    /// Empty for folders that don't have a trace file
+    #[serde(default)]
+    pub trace: Vec<TraceRowGroupMetadata>,
+    /// Empty for folders that don't have a state diff file
+    #[serde(default)]
+    pub state_diff: Vec<StateDiffRowGroupMetadata>,
+    /// Empty for folders that don't have a withdrawal file
+    #[serde(default)]
+    pub withdrawal: Vec<WithdrawalRowGroupMetadata>,
+}
+
+/// Folder level index that is checked before loading the metadata of a folder
+#[derive(Serialize, Deserialize)]
+pub struct ParquetIdx {
+    /// Addresses of all logs, transactions, traces, state diffs and withdrawals in the folder
+    pub address: Bloom<Address>,
+    /// Topics of all logs in the folder. None for folders indexed before this was added.
+    pub topic: Option<Bloom<Bytes32>>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct LogRowGroupMetadata {
     pub address_filter: Bloom<Address>,
     pub topic0_filter: Bloom<Bytes32>,
+    /// None for folders indexed before topic1-3 filters were added
+    #[serde(default)]
+    pub topic1_filter: Option<Bloom<Bytes32>>,
+    #[serde(default)]
+    pub topic2_filter: Option<Bloom<Bytes32>>,
+    #[serde(default)]
+    pub topic3_filter: Option<Bloom<Bytes32>>,
+}
+
+impl LogRowGroupMetadata {
+    pub fn topic_filter(&self, i: usize) -> Option<&Bloom<Bytes32>> {
+        match i {
+            0 => Some(&self.topic0_filter),
+            1 => self.topic1_filter.as_ref(),
+            2 => self.topic2_filter.as_ref(),
+            3 => self.topic3_filter.as_ref(),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -36,12 +75,133 @@ pub struct TransactionRowGroupMetadata {
     pub dest_filter: Bloom<Address>,
     pub max_blk_num_tx_idx: u64,
     pub min_blk_num_tx_idx: u64,
+    /// None for folders indexed before value ranges were added
+    #[serde(default)]
+    pub value_ranges: Option<TransactionValueRanges>,
+}
+
+/// Ranges of the columns that transaction selections can filter by range.
+/// A range is None if the column only has nulls in the row group.
+#[derive(Serialize, Deserialize, Default)]
+pub struct TransactionValueRanges {
+    pub value: Option<ValueRange<U256>>,
+    pub gas: Option<ValueRange<u64>>,
+    pub gas_price: Option<ValueRange<U256>>,
+    pub max_fee_per_gas: Option<ValueRange<U256>>,
+    pub kind: Option<ValueRange<u32>>,
+}
+
+impl TransactionValueRanges {
+    /// Returns false if no transaction in the row group can match the selection
+    pub fn can_match(&self, selection: &MiniTransactionSelection) -> bool {
+        ValueRange::overlaps(&self.value, &selection.value)
+            && ValueRange::overlaps(&self.gas, &selection.gas)
+            && ValueRange::overlaps(&self.gas_price, &selection.gas_price)
+            && ValueRange::overlaps(&self.max_fee_per_gas, &selection.max_fee_per_gas)
+            && ValueRange::overlaps(&self.kind, &selection.kind_range)
+    }
+}
+
+/// Inclusive range of the non-null values of a column
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ValueRange<T> {
+    pub min: T,
+    pub max: T,
+}
+
+impl<T: Ord + Copy> ValueRange<T> {
+    fn insert(range: &mut Option<Self>, val: T) {
+        *range = Some(match *range {
+            Some(range) => ValueRange {
+                min: cmp::min(range.min, val),
+                max: cmp::max(range.max, val),
+            },
+            None => ValueRange { min: val, max: val },
+        });
+    }
+
+    /// Returns false if no value of the column can be in the given range
+    fn overlaps(range: &Option<Self>, filter: &Range<T>) -> bool {
+        if filter.is_unbounded() {
+            return true;
+        }
+
+        match range {
+            Some(range) => {
+                filter.min.map_or(true, |min| range.max >= min)
+                    && filter.max.map_or(true, |max| range.min <= max)
+            }
+            None => false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TraceRowGroupMetadata {
+    pub source_filter: Bloom<Address>,
+    pub dest_filter: Bloom<Address>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StateDiffRowGroupMetadata {
+    pub address_filter: Bloom<Address>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WithdrawalRowGroupMetadata {
+    pub address_filter: Bloom<Address>,
+    /// Big endian validator indices
+    pub validator_index_filter: Bloom<[u8; 4]>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct BlockRowGroupMetadata {
-    pub max_block_number: u32,
-    pub min_block_number: u32,
+    pub max_block_number: u64,
+    pub min_block_number: u64,
+    /// None for folders indexed before timestamps were added
+    #[serde(default)]
+    pub timestamp_range: Option<TimestampRange>,
+}
+
+/// Inclusive range of block timestamps
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimestampRange {
+    pub min: u64,
+    pub max: u64,
+}
+
+impl TimestampRange {
+    fn merge(self, other: TimestampRange) -> TimestampRange {
+        TimestampRange {
+            min: cmp::min(self.min, other.min),
+            max: cmp::max(self.max, other.max),
+        }
+    }
+}
+
+impl ParquetMetadata {
+    /// Returns the timestamp range of all blocks in the folder.
+    /// None if the folder has no blocks or was indexed before timestamps were added.
+    pub fn timestamp_range(&self) -> Option<TimestampRange> {
+        timestamp_range_of(&self.block)
+    }
+}
+
+fn timestamp_range_of(block_rg_meta: &[BlockRowGroupMetadata]) -> Option<TimestampRange> {
+    block_rg_meta
+        .iter()
+        .map(|rg_meta| rg_meta.timestamp_range)
+        .reduce(|a, b| Some(a?.merge(b?)))
+        .flatten()
+}
+
+/// Hashes of the blocks and transactions in a folder, used for lookups by hash
+#[derive(Default, Clone)]
+pub struct ParquetHashes {
+    /// (hash, block_number)
+    pub blocks: Vec<(Bytes32, u64)>,
+    /// (hash, block_number, transaction_index)
+    pub transactions: Vec<(Bytes32, u64, u32)>,
 }
 
 pub struct CollectMetadataAndParquetIdx<'a> {
@@ -50,23 +210,38 @@ pub struct CollectMetadataAndParquetIdx<'a> {
 }
 
 impl<'a> CollectMetadataAndParquetIdx<'a> {
-    pub fn collect(self) -> Result<(ParquetMetadata, Bloom<Address>)> {
+    pub fn collect(self) -> Result<(ParquetMetadata, ParquetIdx)> {
         let mut addrs = HashSet::new();
+        let mut topics = HashSet::new();
 
-        let log = self.collect_log_meta(&mut addrs)?;
+        let log = self.collect_log_meta(&mut addrs, &mut topics)?;
         let tx = self.collect_tx_meta(&mut addrs)?;
         let block = self.collect_block_meta()?;
-
-        let filter = Bloom::new(&addrs, BLOOM_FP_RATE, BLOOM_MAX_BITS);
-
-        let metadata = ParquetMetadata { log, tx, block };
-
-        Ok((metadata, filter))
+        let trace = self.collect_trace_meta(&mut addrs)?;
+        let state_diff = self.collect_state_diff_meta(&mut addrs)?;
+        let withdrawal = self.collect_withdrawal_meta(&mut addrs)?;
+
+        let idx = ParquetIdx {
+            address: Bloom::new(&addrs, BLOOM_FP_RATE, BLOOM_MAX_BITS),
+            topic: Some(Bloom::new(&topics, BLOOM_FP_RATE, BLOOM_MAX_BITS)),
+        };
+
+        let metadata = ParquetMetadata {
+            log,
+            tx,
+            block,
+            trace,
+            state_diff,
+            withdrawal,
+        };
+
+        Ok((metadata, idx))
     }
 
     fn collect_log_meta(
         &self,
         addrs_global: &mut HashSet<Address>,
+        topics_global: &mut HashSet<Bytes32>,
     ) -> Result<Vec<LogRowGroupMetadata>> {
         let mut path = self.data_path.to_owned();
         path.push(self.dir_name.to_string());
@@ -84,6 +259,9 @@ impl<'a> CollectMetadataAndParquetIdx<'a> {
                 vec![
                     Field::new("address", DataType::Binary, false),
                     Field::new("topic0", DataType::Binary, true),
+                    Field::new("topic1", DataType::Binary, true),
+                    Field::new("topic2", DataType::Binary, true),
+                    Field::new("topic3", DataType::Binary, true),
                 ],
                 None,
                 None,
@@ -92,13 +270,16 @@ impl<'a> CollectMetadataAndParquetIdx<'a> {
             .map_err(Error::ReadParquet)?;
 
             let mut addrs = HashSet::new();
-            let mut topic0_set = HashSet::new();
+            let mut topic_sets: [HashSet<Bytes32>; 4] = Default::default();
 
             #[rustfmt::skip]
             define_cols!(
                 columns,
                 address, BinaryArray,
-                topic0, BinaryArray
+                topic0, BinaryArray,
+                topic1, BinaryArray,
+                topic2, BinaryArray,
+                topic3, BinaryArray
             );
 
             let len = address.len();
@@ -108,14 +289,26 @@ impl<'a> CollectMetadataAndParquetIdx<'a> {
                 addrs.insert(Address::new(address));
                 addrs_global.insert(Address::new(address));
 
-                if let Some(topic) = topic0.get(i) {
-                    topic0_set.insert(Bytes32::new(topic));
+                for (topic_set, topic) in topic_sets
+                    .iter_mut()
+                    .zip([&topic0, &topic1, &topic2, &topic3])
+                {
+                    if let Some(topic) = topic.get(i) {
+                        topic_set.insert(Bytes32::new(topic));
+                        topics_global.insert(Bytes32::new(topic));
+                    }
                 }
             }
 
+            let [topic0_filter, topic1_filter, topic2_filter, topic3_filter] =
+                topic_sets.map(|topics| Bloom::new(&topics, BLOOM_FP_RATE, BLOOM_MAX_BITS));
+
             log_rg_meta.push(LogRowGroupMetadata {
                 address_filter: Bloom::new(&addrs, BLOOM_FP_RATE, BLOOM_MAX_BITS),
-                topic0_filter: Bloom::new(&topic0_set, BLOOM_FP_RATE, BLOOM_MAX_BITS),
+                topic0_filter,
+                topic1_filter: Some(topic1_filter),
+                topic2_filter: Some(topic2_filter),
+                topic3_filter: Some(topic3_filter),
             });
         }
 
@@ -132,37 +325,51 @@ impl<'a> CollectMetadataAndParquetIdx<'a> {
         let mut file = io::BufReader::new(fs::File::open(&path).map_err(Error::OpenParquetFile)?);
 
         let metadata = parquet::read::read_metadata(&mut file).map_err(Error::ReadParquet)?;
+        let schema = parquet::read::infer_schema(&metadata).map_err(Error::ReadParquet)?;
 
         let mut tx_rg_meta = Vec::new();
 
         for row_group_meta in metadata.row_groups.iter() {
+            let fields = vec![
+                Field::new("source", DataType::Binary, true),
+                Field::new("dest", DataType::Binary, true),
+                Field::new("block_number", DataType::UInt64, false),
+                Field::new("transaction_index", DataType::UInt32, false),
+                Field::new("value", DataType::FixedSizeBinary(32), false),
+                Field::new("gas", DataType::UInt64, false),
+                Field::new("gas_price", DataType::FixedSizeBinary(32), true),
+                Field::new("max_fee_per_gas", DataType::FixedSizeBinary(32), true),
+                Field::new("kind", DataType::UInt32, true),
+            ];
             let columns = parquet::read::read_columns_many(
                 &mut file,
                 row_group_meta,
-                vec![
-                    Field::new("source", DataType::Binary, true),
-                    Field::new("dest", DataType::Binary, true),
-                    Field::new("block_number", DataType::UInt32, false),
-                    Field::new("transaction_index", DataType::UInt32, false),
-                ],
+                file_fields(&schema, &fields),
                 None,
                 None,
                 None,
             )
             .map_err(Error::ReadParquet)?;
+            let columns = cast_columns(columns, &fields);
 
             let mut max_blk_num_tx_idx = 0;
             let mut min_blk_num_tx_idx = 0;
             let mut source_addrs = HashSet::new();
             let mut dest_addrs = HashSet::new();
+            let mut value_ranges = TransactionValueRanges::default();
 
             #[rustfmt::skip]
             define_cols!(
                 columns,
                 source, BinaryArray,
                 dest, BinaryArray,
-                block_number, UInt32Array,
-                transaction_index, UInt32Array
+                block_number, UInt64Array,
+                transaction_index, UInt32Array,
+                value, FixedSizeBinaryArray,
+                gas, UInt64Array,
+                gas_price, FixedSizeBinaryArray,
+                max_fee_per_gas, FixedSizeBinaryArray,
+                kind, UInt32Array
             );
 
             let len = block_number.len();
@@ -183,6 +390,25 @@ impl<'a> CollectMetadataAndParquetIdx<'a> {
 
                 max_blk_num_tx_idx = cmp::max(max_blk_num_tx_idx, blk_num_tx_idx);
                 min_blk_num_tx_idx = cmp::min(min_blk_num_tx_idx, blk_num_tx_idx);
+
+                if let Some(value) = value.get(i) {
+                    ValueRange::insert(&mut value_ranges.value, U256::new(value));
+                }
+                if let Some(gas) = gas.get(i) {
+                    ValueRange::insert(&mut value_ranges.gas, gas);
+                }
+                if let Some(gas_price) = gas_price.get(i) {
+                    ValueRange::insert(&mut value_ranges.gas_price, U256::new(gas_price));
+                }
+                if let Some(max_fee_per_gas) = max_fee_per_gas.get(i) {
+                    ValueRange::insert(
+                        &mut value_ranges.max_fee_per_gas,
+                        U256::new(max_fee_per_gas),
+                    );
+                }
+                if let Some(kind) = kind.get(i) {
+                    ValueRange::insert(&mut value_ranges.kind, kind);
+                }
             }
 
             tx_rg_meta.push(TransactionRowGroupMetadata {
@@ -190,12 +416,288 @@ impl<'a> CollectMetadataAndParquetIdx<'a> {
                 dest_filter: Bloom::new(&dest_addrs, BLOOM_FP_RATE, BLOOM_MAX_BITS),
                 max_blk_num_tx_idx,
                 min_blk_num_tx_idx,
+                value_ranges: Some(value_ranges),
             });
         }
 
         Ok(tx_rg_meta)
     }
 
+    fn collect_trace_meta(
+        &self,
+        addrs_global: &mut HashSet<Address>,
+    ) -> Result<Vec<TraceRowGroupMetadata>> {
+        let mut path = self.data_path.to_owned();
+        path.push(self.dir_name.to_string());
+        path.push("trace.parquet");
+        let file = match fs::File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(Error::OpenParquetFile(e)),
+        };
+        let mut file = io::BufReader::new(file);
+
+        let metadata = parquet::read::read_metadata(&mut file).map_err(Error::ReadParquet)?;
+
+        let mut trace_rg_meta = Vec::new();
+
+        for row_group_meta in metadata.row_groups.iter() {
+            let columns = parquet::read::read_columns_many(
+                &mut file,
+                row_group_meta,
+                vec![
+                    Field::new("source", DataType::Binary, true),
+                    Field::new("dest", DataType::Binary, true),
+                ],
+                None,
+                None,
+                None,
+            )
+            .map_err(Error::ReadParquet)?;
+
+            let mut source_addrs = HashSet::new();
+            let mut dest_addrs = HashSet::new();
+
+            #[rustfmt::skip]
+            define_cols!(
+                columns,
+                source, BinaryArray,
+                dest, BinaryArray
+            );
+
+            let len = source.len();
+
+            for i in 0..len {
+                if let Some(source) = source.get(i) {
+                    source_addrs.insert(Address::new(source));
+                    addrs_global.insert(Address::new(source));
+                }
+                if let Some(dest) = dest.get(i) {
+                    dest_addrs.insert(Address::new(dest));
+                    addrs_global.insert(Address::new(dest));
+                }
+            }
+
+            trace_rg_meta.push(TraceRowGroupMetadata {
+                source_filter: Bloom::new(&source_addrs, BLOOM_FP_RATE, BLOOM_MAX_BITS),
+                dest_filter: Bloom::new(&dest_addrs, BLOOM_FP_RATE, BLOOM_MAX_BITS),
+            });
+        }
+
+        Ok(trace_rg_meta)
+    }
+
+    fn collect_state_diff_meta(
+        &self,
+        addrs_global: &mut HashSet<Address>,
+    ) -> Result<Vec<StateDiffRowGroupMetadata>> {
+        let mut path = self.data_path.to_owned();
+        path.push(self.dir_name.to_string());
+        path.push("state_diff.parquet");
+        let file = match fs::File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(Error::OpenParquetFile(e)),
+        };
+        let mut file = io::BufReader::new(file);
+
+        let metadata = parquet::read::read_metadata(&mut file).map_err(Error::ReadParquet)?;
+
+        let mut state_diff_rg_meta = Vec::new();
+
+        for row_group_meta in metadata.row_groups.iter() {
+            let columns = parquet::read::read_columns_many(
+                &mut file,
+                row_group_meta,
+                vec![Field::new("address", DataType::Binary, false)],
+                None,
+                None,
+                None,
+            )
+            .map_err(Error::ReadParquet)?;
+
+            let mut addrs = HashSet::new();
+
+            #[rustfmt::skip]
+            define_cols!(
+                columns,
+                address, BinaryArray
+            );
+
+            let len = address.len();
+
+            for i in 0..len {
+                let address = address.get(i).unwrap();
+                addrs.insert(Address::new(address));
+                addrs_global.insert(Address::new(address));
+            }
+
+            state_diff_rg_meta.push(StateDiffRowGroupMetadata {
+                address_filter: Bloom::new(&addrs, BLOOM_FP_RATE, BLOOM_MAX_BITS),
+            });
+        }
+
+        Ok(state_diff_rg_meta)
+    }
+
+    fn collect_withdrawal_meta(
+        &self,
+        addrs_global: &mut HashSet<Address>,
+    ) -> Result<Vec<WithdrawalRowGroupMetadata>> {
+        let mut path = self.data_path.to_owned();
+        path.push(self.dir_name.to_string());
+        path.push("withdrawal.parquet");
+        let file = match fs::File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(Error::OpenParquetFile(e)),
+        };
+        let mut file = io::BufReader::new(file);
+
+        let metadata = parquet::read::read_metadata(&mut file).map_err(Error::ReadParquet)?;
+
+        let mut withdrawal_rg_meta = Vec::new();
+
+        for row_group_meta in metadata.row_groups.iter() {
+            let columns = parquet::read::read_columns_many(
+                &mut file,
+                row_group_meta,
+                vec![
+                    Field::new("address", DataType::Binary, false),
+                    Field::new("validator_index", DataType::UInt32, false),
+                ],
+                None,
+                None,
+                None,
+            )
+            .map_err(Error::ReadParquet)?;
+
+            let mut addrs = HashSet::new();
+            let mut validator_indices = HashSet::new();
+
+            #[rustfmt::skip]
+            define_cols!(
+                columns,
+                address, BinaryArray,
+                validator_index, UInt32Array
+            );
+
+            let len = address.len();
+
+            for i in 0..len {
+                let address = address.get(i).unwrap();
+                addrs.insert(Address::new(address));
+                addrs_global.insert(Address::new(address));
+                validator_indices.insert(validator_index.get(i).unwrap().to_be_bytes());
+            }
+
+            withdrawal_rg_meta.push(WithdrawalRowGroupMetadata {
+                address_filter: Bloom::new(&addrs, BLOOM_FP_RATE, BLOOM_MAX_BITS),
+                validator_index_filter: Bloom::new(
+                    &validator_indices,
+                    BLOOM_FP_RATE,
+                    BLOOM_MAX_BITS,
+                ),
+            });
+        }
+
+        Ok(withdrawal_rg_meta)
+    }
+
+    /// Reads the hashes of all blocks and transactions in the folder.
+    pub fn collect_hashes(&self) -> Result<ParquetHashes> {
+        let mut hashes = ParquetHashes::default();
+
+        let mut path = self.data_path.to_owned();
+        path.push(self.dir_name.to_string());
+        path.push("block.parquet");
+        let mut file = io::BufReader::new(fs::File::open(&path).map_err(Error::OpenParquetFile)?);
+
+        let metadata = parquet::read::read_metadata(&mut file).map_err(Error::ReadParquet)?;
+        let schema = parquet::read::infer_schema(&metadata).map_err(Error::ReadParquet)?;
+
+        for row_group_meta in metadata.row_groups.iter() {
+            let fields = vec![
+                Field::new("number", DataType::UInt64, false),
+                Field::new("hash", DataType::Binary, true),
+            ];
+            let columns = parquet::read::read_columns_many(
+                &mut file,
+                row_group_meta,
+                file_fields(&schema, &fields),
+                None,
+                None,
+                None,
+            )
+            .map_err(Error::ReadParquet)?;
+            let columns = cast_columns(columns, &fields);
+
+            #[rustfmt::skip]
+            define_cols!(
+                columns,
+                number, UInt64Array,
+                hash, BinaryArray
+            );
+
+            for (number, hash) in number.values_iter().zip(hash.iter()) {
+                if let Some(hash) = hash {
+                    hashes.blocks.push((Bytes32::new(hash), *number));
+                }
+            }
+        }
+
+        let mut path = self.data_path.to_owned();
+        path.push(self.dir_name.to_string());
+        path.push("tx.parquet");
+        let mut file = io::BufReader::new(fs::File::open(&path).map_err(Error::OpenParquetFile)?);
+
+        let metadata = parquet::read::read_metadata(&mut file).map_err(Error::ReadParquet)?;
+        let schema = parquet::read::infer_schema(&metadata).map_err(Error::ReadParquet)?;
+
+        for row_group_meta in metadata.row_groups.iter() {
+            let fields = vec![
+                Field::new("hash", DataType::Binary, false),
+                Field::new("block_number", DataType::UInt64, false),
+                Field::new("transaction_index", DataType::UInt32, false),
+            ];
+            let columns = parquet::read::read_columns_many(
+                &mut file,
+                row_group_meta,
+                file_fields(&schema, &fields),
+                None,
+                None,
+                None,
+            )
+            .map_err(Error::ReadParquet)?;
+            let columns = cast_columns(columns, &fields);
+
+            #[rustfmt::skip]
+            define_cols!(
+                columns,
+                hash, BinaryArray,
+                block_number, UInt64Array,
+                transaction_index, UInt32Array
+            );
+
+            for i in 0..hash.len() {
+                hashes.transactions.push((
+                    Bytes32::new(hash.get(i).unwrap()),
+                    block_number.get(i).unwrap(),
+                    transaction_index.get(i).unwrap(),
+                ));
+            }
+        }
+
+        Ok(hashes)
+    }
+
+    /// Reads the timestamp range of the folder from its block file.
+    pub fn collect_timestamp_range(self) -> Result<Option<TimestampRange>> {
+        let block = self.collect_block_meta()?;
+
+        Ok(timestamp_range_of(&block))
+    }
+
     fn collect_block_meta(&self) -> Result<Vec<BlockRowGroupMetadata>> {
         let mut path = self.data_path.to_owned();
         path.push(self.dir_name.to_string());
@@ -203,27 +705,35 @@ impl<'a> CollectMetadataAndParquetIdx<'a> {
         let mut file = io::BufReader::new(fs::File::open(&path).map_err(Error::OpenParquetFile)?);
 
         let metadata = parquet::read::read_metadata(&mut file).map_err(Error::ReadParquet)?;
+        let schema = parquet::read::infer_schema(&metadata).map_err(Error::ReadParquet)?;
 
         let mut block_rg_meta = Vec::new();
 
         for row_group_meta in metadata.row_groups.iter() {
+            let fields = vec![
+                Field::new("number", DataType::UInt64, false),
+                Field::new("timestamp", DataType::UInt64, false),
+            ];
             let columns = parquet::read::read_columns_many(
                 &mut file,
                 row_group_meta,
-                vec![Field::new("number", DataType::UInt32, false)],
+                file_fields(&schema, &fields),
                 None,
                 None,
                 None,
             )
             .map_err(Error::ReadParquet)?;
+            let columns = cast_columns(columns, &fields);
 
             let mut max_block_number = 0;
-            let mut min_block_number = 0;
+            let mut min_block_number = u64::MAX;
+            let mut timestamp_range: Option<TimestampRange> = None;
 
             #[rustfmt::skip]
             define_cols!(
                 columns,
-                number, UInt32Array
+                number, UInt64Array,
+                timestamp, UInt64Array
             );
 
             let len = number.len();
@@ -232,11 +742,22 @@ impl<'a> CollectMetadataAndParquetIdx<'a> {
                 let blk_num = number.get(i).unwrap();
                 max_block_number = cmp::max(max_block_number, blk_num);
                 min_block_number = cmp::min(min_block_number, blk_num);
+
+                let timestamp = timestamp.get(i).unwrap();
+                let range = TimestampRange {
+                    min: timestamp,
+                    max: timestamp,
+                };
+                timestamp_range = Some(match timestamp_range {
+                    Some(timestamp_range) => timestamp_range.merge(range),
+                    None => range,
+                });
             }
 
             block_rg_meta.push(BlockRowGroupMetadata {
                 max_block_number,
                 min_block_number,
+                timestamp_range,
             });
         }
 
@@ -244,6 +765,6 @@ impl<'a> CollectMetadataAndParquetIdx<'a> {
     }
 }
 
-pub fn combine_block_num_tx_idx(block_num: u32, tx_idx: u32) -> u64 {
-    (u64::from(block_num) << 4) | u64::from(tx_idx)
+pub fn combine_block_num_tx_idx(block_num: u64, tx_idx: u32) -> u64 {
+    (block_num << 4) | u64::from(tx_idx)
 }
@@ -1,25 +1,28 @@
 use super::read::ReadParquet;
 use super::util::{define_cols, map_from_arrow, map_from_arrow_opt};
 use super::ParquetQuery;
-use crate::parquet_metadata::BlockRowGroupMetadata;
+use crate::parquet_metadata::{BlockRowGroupMetadata, ParquetMetadata};
 use crate::types::MiniQuery;
 use crate::Result;
-use arrow2::array::{self, Array, UInt32Array, UInt64Array};
+use arrow2::array::{self, Array, FixedSizeBinaryArray, UInt64Array};
 use eth_archive_core::deserialize::{
-    Address, BigUnsigned, BloomFilterBytes, Bytes, Bytes32, Index,
+    Address, BigUnsigned, BlockNumber, BloomFilterBytes, Bytes, Bytes32,
 };
+use eth_archive_core::dir_name::DirName;
 use eth_archive_core::hash::HashMap;
 use eth_archive_core::types::ResponseBlock;
 use eth_archive_ingester::schema::block_schema;
+use std::cmp;
 use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
 use std::sync::Arc;
 
 type BinaryArray = array::BinaryArray<i32>;
 
 pub fn prune_blocks_per_rg(
     rg_meta: &BlockRowGroupMetadata,
-    blocks: &Option<BTreeSet<u32>>,
-) -> Option<BTreeSet<u32>> {
+    blocks: &Option<BTreeSet<u64>>,
+) -> Option<BTreeSet<u64>> {
     blocks.as_ref().map(|blocks| {
         blocks
             .iter()
@@ -36,13 +39,19 @@ pub fn prune_blocks_per_rg(
 
 pub async fn query_blocks(
     query: Arc<ParquetQuery>,
-    pruned_blocks_per_rg: Vec<Option<BTreeSet<u32>>>,
-) -> Result<BTreeMap<u32, ResponseBlock>> {
+    pruned_blocks_per_rg: Vec<Option<BTreeSet<u64>>>,
+) -> Result<BTreeMap<u64, ResponseBlock>> {
     let mut path = query.data_path.clone();
     path.push(query.dir_name.to_string());
     path.push("block.parquet");
 
-    let selected_fields = query.mini_query.field_selection.block.as_fields();
+    let mut selected_fields = query.mini_query.field_selection.block.as_fields();
+
+    // columns needed to match block selections
+    if !query.mini_query.blocks.is_empty() {
+        selected_fields.insert("miner");
+        selected_fields.insert("timestamp");
+    }
 
     let fields: Vec<_> = block_schema()
         .fields
@@ -50,9 +59,13 @@ pub async fn query_blocks(
         .filter(|field| selected_fields.contains(field.name.as_str()))
         .collect();
 
+    let has_block_selection = !query.mini_query.blocks.is_empty();
+
     let rg_filter = |i| {
-        let val: &Option<BTreeSet<u32>> = &pruned_blocks_per_rg[i];
-        if let Some(block_nums) = val {
+        let val: &Option<BTreeSet<u64>> = &pruned_blocks_per_rg[i];
+        if has_block_selection {
+            true
+        } else if let Some(block_nums) = val {
             !block_nums.is_empty()
         } else {
             true
@@ -77,16 +90,73 @@ pub async fn query_blocks(
     Ok(blocks)
 }
 
+/// Finds the first block in the folder that has a timestamp greater than or equal to
+/// the given timestamp. Only reads the row groups that can have such a block.
+pub async fn find_block_by_timestamp(
+    data_path: &Path,
+    dir_name: DirName,
+    metadata: &ParquetMetadata,
+    target: u64,
+) -> Result<Option<u64>> {
+    let mut path = data_path.to_owned();
+    path.push(dir_name.to_string());
+    path.push("block.parquet");
+
+    let fields: Vec<_> = block_schema()
+        .fields
+        .into_iter()
+        .filter(|field| field.name == "number" || field.name == "timestamp")
+        .collect();
+
+    let rg_filter = |i: usize| match metadata.block.get(i).and_then(|rg| rg.timestamp_range) {
+        Some(timestamp_range) => timestamp_range.max >= target,
+        None => true,
+    };
+
+    let mut chunk_rx = ReadParquet {
+        path,
+        rg_filter,
+        fields,
+    }
+    .read()
+    .await?;
+
+    let mut block_number: Option<u64> = None;
+    while let Some(res) = chunk_rx.recv().await {
+        let (_, mut columns) = res?;
+
+        #[rustfmt::skip]
+        define_cols!(
+            columns,
+            number, UInt64Array,
+            timestamp, UInt64Array
+        );
+
+        let (number, timestamp) = match (number, timestamp) {
+            (Some(number), Some(timestamp)) => (number, timestamp),
+            _ => continue,
+        };
+
+        for (num, ts) in number.values_iter().zip(timestamp.values_iter()) {
+            if *ts >= target {
+                block_number = Some(block_number.map_or(*num, |b| cmp::min(b, *num)));
+            }
+        }
+    }
+
+    Ok(block_number)
+}
+
 fn process_cols(
     query: &MiniQuery,
-    block_nums: &Option<BTreeSet<u32>>,
+    block_nums: &Option<BTreeSet<u64>>,
     mut columns: HashMap<String, Box<dyn Array>>,
-    blocks: &mut BTreeMap<u32, ResponseBlock>,
+    blocks: &mut BTreeMap<u64, ResponseBlock>,
 ) {
     #[rustfmt::skip]
 	define_cols!(
     	columns,
-        number, UInt32Array,
+        number, UInt64Array,
         parent_hash, BinaryArray,
         sha3_uncles, BinaryArray,
         miner, BinaryArray,
@@ -95,16 +165,20 @@ fn process_cols(
         receipts_root, BinaryArray,
         logs_bloom, BinaryArray,
         difficulty, BinaryArray,
-        gas_limit, BinaryArray,
-        gas_used, BinaryArray,
-        timestamp, BinaryArray,
+        gas_limit, UInt64Array,
+        gas_used, UInt64Array,
+        timestamp, UInt64Array,
         extra_data, BinaryArray,
         mix_hash, BinaryArray,
         nonce, UInt64Array,
         total_difficulty, BinaryArray,
-        base_fee_per_gas, BinaryArray,
+        base_fee_per_gas, FixedSizeBinaryArray,
         size, BinaryArray,
-        hash, BinaryArray
+        hash, BinaryArray,
+        blob_gas_used, UInt64Array,
+        excess_blob_gas, UInt64Array,
+        parent_beacon_block_root, BinaryArray,
+        withdrawals_root, BinaryArray
 	);
 
     let len = number.as_ref().unwrap().len();
@@ -119,17 +193,25 @@ fn process_cols(
             receipts_root: map_from_arrow!(receipts_root, Bytes32::new, i),
             logs_bloom: map_from_arrow!(logs_bloom, BloomFilterBytes::new, i),
             difficulty: map_from_arrow_opt!(difficulty, Bytes::new, i),
-            number: map_from_arrow!(number, Index, i),
-            gas_limit: map_from_arrow!(gas_limit, Bytes::new, i),
-            gas_used: map_from_arrow!(gas_used, Bytes::new, i),
-            timestamp: map_from_arrow!(timestamp, Bytes::new, i),
+            number: map_from_arrow!(number, BlockNumber, i),
+            gas_limit: map_from_arrow!(gas_limit, Bytes::from_quantity, i),
+            gas_used: map_from_arrow!(gas_used, Bytes::from_quantity, i),
+            timestamp: map_from_arrow!(timestamp, Bytes::from_quantity, i),
             extra_data: map_from_arrow!(extra_data, Bytes::new, i),
             mix_hash: map_from_arrow_opt!(mix_hash, Bytes32::new, i),
             nonce: map_from_arrow_opt!(nonce, BigUnsigned, i),
             total_difficulty: map_from_arrow_opt!(total_difficulty, Bytes::new, i),
-            base_fee_per_gas: map_from_arrow_opt!(base_fee_per_gas, Bytes::new, i),
+            base_fee_per_gas: map_from_arrow_opt!(base_fee_per_gas, Bytes::from_be_quantity, i),
             size: map_from_arrow!(size, Bytes::new, i),
             hash: map_from_arrow_opt!(hash, Bytes32::new, i),
+            blob_gas_used: map_from_arrow_opt!(blob_gas_used, Bytes::from_quantity, i),
+            excess_blob_gas: map_from_arrow_opt!(excess_blob_gas, Bytes::from_quantity, i),
+            parent_beacon_block_root: map_from_arrow_opt!(
+                parent_beacon_block_root,
+                Bytes32::new,
+                i
+            ),
+            withdrawals_root: map_from_arrow_opt!(withdrawals_root, Bytes32::new, i),
         };
 
         let block_number = block.number.unwrap().0;
@@ -138,12 +220,79 @@ fn process_cols(
             continue;
         }
 
-        if let Some(block_nums) = block_nums {
-            if !block_nums.contains(&block_number) {
-                continue;
-            }
+        let is_joined = match block_nums {
+            Some(block_nums) => block_nums.contains(&block_number),
+            None => true,
+        };
+
+        let is_selected = || match (&block.miner, &block.timestamp) {
+            (Some(miner), Some(timestamp)) => query.matches_block(miner, timestamp),
+            _ => false,
+        };
+
+        if !is_joined && !is_selected() {
+            continue;
         }
 
         blocks.insert(block_number, block);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field_selection::FieldSelection;
+    use crate::types::MiniBlockSelection;
+
+    /// Rows of block.parquet are returned if they match a block selection or their blocks
+    /// were joined from the other selections.
+    #[test]
+    fn test_process_cols_block_selection() {
+        let mut columns: HashMap<String, Box<dyn Array>> = HashMap::default();
+        columns.insert(
+            "number".to_owned(),
+            Box::new(UInt64Array::from_vec(vec![1, 2, 3])),
+        );
+        columns.insert(
+            "miner".to_owned(),
+            Box::new(BinaryArray::from_slice([[1; 20], [2; 20], [1; 20]])),
+        );
+        columns.insert(
+            "timestamp".to_owned(),
+            Box::new(UInt64Array::from_vec(vec![100, 200, 300])),
+        );
+
+        let query = |miner: &[u8], from_timestamp, block_nums: Option<BTreeSet<u64>>| {
+            let mini_query = MiniQuery {
+                from_block: 0,
+                to_block: 4,
+                logs: Vec::new(),
+                transactions: Vec::new(),
+                traces: Vec::new(),
+                state_diffs: Vec::new(),
+                withdrawals: Vec::new(),
+                blocks: vec![MiniBlockSelection {
+                    miner: miner.iter().map(|&n| Address::new(&[n; 20])).collect(),
+                    from_timestamp,
+                    to_timestamp: None,
+                }],
+                field_selection: FieldSelection::default(),
+                include_all_blocks: false,
+                abi: Default::default(),
+            };
+
+            let mut blocks = BTreeMap::new();
+            process_cols(&mini_query, &block_nums, columns.clone(), &mut blocks);
+            blocks.into_keys().collect::<Vec<_>>()
+        };
+
+        assert_eq!(query(&[], None, None), vec![1, 2, 3]);
+        assert_eq!(query(&[1], None, None), vec![1, 3]);
+        assert_eq!(query(&[1], Some(200), None), vec![3]);
+        // blocks of matched items are returned even if the block selection doesn't match them
+        assert_eq!(
+            query(&[2], Some(300), Some([1].into_iter().collect())),
+            vec![1]
+        );
+    }
+}
@@ -5,8 +5,8 @@ use crate::parquet_metadata::LogRowGroupMetadata;
 use crate::types::{LogQueryResult, MiniLogSelection, MiniQuery};
 use crate::Result;
 use arrayvec::ArrayVec;
-use arrow2::array::{self, Array, BooleanArray, UInt32Array};
-use eth_archive_core::deserialize::{Address, Bytes, Bytes32, Index};
+use arrow2::array::{self, Array, BooleanArray, UInt32Array, UInt64Array};
+use eth_archive_core::deserialize::{Address, BlockNumber, Bytes, Bytes32, Index};
 use eth_archive_core::hash::{HashMap, HashSet};
 use eth_archive_core::types::ResponseLog;
 use eth_archive_ingester::schema::log_schema;
@@ -33,28 +33,35 @@ pub fn prune_log_queries_per_rg(
                 return None;
             }
 
-            match log_selection.topics.get(0) {
-                Some(topic0) if !topic0.is_empty() => {
-                    let pruned_topic0 = topic0
-                        .iter()
-                        .filter(|topic| rg_meta.topic0_filter.contains(topic))
-                        .cloned()
-                        .collect::<HashSet<_>>();
+            let mut topics = log_selection.topics.clone();
 
-                    if pruned_topic0.is_empty() {
-                        return None;
-                    }
+            for (i, topic) in topics.iter_mut().enumerate() {
+                if topic.is_empty() {
+                    continue;
+                }
+
+                let filter = match rg_meta.topic_filter(i) {
+                    Some(filter) => filter,
+                    None => continue,
+                };
 
-                    let mut topics = log_selection.topics.clone();
-                    topics[0] = pruned_topic0;
+                *topic = topic
+                    .iter()
+                    .filter(|topic| filter.contains(topic))
+                    .cloned()
+                    .collect::<HashSet<_>>();
 
-                    Some(MiniLogSelection { address, topics })
+                if topic.is_empty() {
+                    return None;
                 }
-                _ => Some(MiniLogSelection {
-                    address,
-                    topics: log_selection.topics.clone(),
-                }),
             }
+
+            Some(MiniLogSelection {
+                address,
+                topics,
+                exclude_address: log_selection.exclude_address.clone(),
+                exclude_topics: log_selection.exclude_topics.clone(),
+            })
         })
         .collect()
 }
@@ -113,7 +120,7 @@ fn process_cols(
     	columns,
     	address, BinaryArray,
     	block_hash, BinaryArray,
-    	block_number, UInt32Array,
+    	block_number, UInt64Array,
     	data, BinaryArray,
     	log_index, UInt32Array,
     	removed, BooleanArray,
@@ -131,7 +138,7 @@ fn process_cols(
         let log = ResponseLog {
             address: map_from_arrow!(address, Address::new, i),
             block_hash: map_from_arrow!(block_hash, Bytes32::new, i),
-            block_number: map_from_arrow!(block_number, Index, i),
+            block_number: map_from_arrow!(block_number, BlockNumber, i),
             data: map_from_arrow!(data, Bytes::new, i),
             log_index: map_from_arrow!(log_index, Index, i),
             removed: removed.as_ref().and_then(|arr| arr.get(i)),
@@ -183,3 +190,133 @@ fn process_cols(
         query_result.blocks.insert(block_number);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::util::{check_hot_and_parquet_agree, empty_query};
+    use super::*;
+    use crate::bloom::Bloom;
+    use crate::parquet_metadata::ParquetIdx;
+
+    fn addr(n: u8) -> Address {
+        Address::new(&[n; 20])
+    }
+
+    fn topic(n: u8) -> Bytes32 {
+        Bytes32::new(&[n; 32])
+    }
+
+    fn topics(topics: &[&[u8]]) -> ArrayVec<HashSet<Bytes32>, 4> {
+        topics
+            .iter()
+            .map(|set| set.iter().map(|&n| topic(n)).collect())
+            .collect()
+    }
+
+    fn selection(address: &[u8], exclude_address: &[u8]) -> MiniLogSelection {
+        MiniLogSelection {
+            address: address.iter().map(|&n| addr(n)).collect(),
+            topics: ArrayVec::new(),
+            exclude_address: exclude_address.iter().map(|&n| addr(n)).collect(),
+            exclude_topics: ArrayVec::new(),
+        }
+    }
+
+    /// Address and topic selections and their exclusions match the same logs on both paths.
+    #[test]
+    fn test_log_selection_hot_and_parquet_agree() {
+        // (address, topics) of the logs in a single row group
+        let logs = [
+            (addr(1), vec![topic(1), topic(2)]),
+            (addr(1), vec![topic(1), topic(3)]),
+            (addr(2), vec![topic(1), topic(2)]),
+            (addr(3), vec![topic(4)]),
+        ];
+
+        let addresses = logs.iter().map(|log| log.0.clone()).collect::<HashSet<_>>();
+        let topic_filter = |i: usize| {
+            let topics = logs
+                .iter()
+                .filter_map(|log| log.1.get(i).cloned())
+                .collect::<HashSet<_>>();
+            Bloom::new(&topics, 0.001, 128_000)
+        };
+        let all_topics = logs
+            .iter()
+            .flat_map(|log| log.1.iter().cloned())
+            .collect::<HashSet<_>>();
+
+        let parquet_idx = ParquetIdx {
+            address: Bloom::new(&addresses, 0.001, 128_000),
+            topic: Some(Bloom::new(&all_topics, 0.001, 128_000)),
+        };
+        let rg_meta = LogRowGroupMetadata {
+            address_filter: Bloom::new(&addresses, 0.001, 128_000),
+            topic0_filter: topic_filter(0),
+            topic1_filter: Some(topic_filter(1)),
+            topic2_filter: Some(topic_filter(2)),
+            topic3_filter: Some(topic_filter(3)),
+        };
+
+        let cases = [
+            (selection(&[], &[]), [true, true, true, true]),
+            (selection(&[1], &[]), [true, true, false, false]),
+            (selection(&[], &[1]), [false, false, true, true]),
+            (selection(&[1, 2], &[2]), [true, true, false, false]),
+            // excluded addresses that aren't in the row group don't prune the selection
+            (selection(&[], &[9]), [true, true, true, true]),
+            (
+                MiniLogSelection {
+                    exclude_topics: topics(&[&[], &[2]]),
+                    ..selection(&[], &[])
+                },
+                [false, true, false, true],
+            ),
+            (
+                MiniLogSelection {
+                    topics: topics(&[&[1]]),
+                    exclude_topics: topics(&[&[], &[3]]),
+                    ..selection(&[1], &[])
+                },
+                [true, false, false, false],
+            ),
+            (
+                MiniLogSelection {
+                    exclude_topics: topics(&[&[1, 4]]),
+                    ..selection(&[], &[])
+                },
+                [false, false, false, false],
+            ),
+            (
+                MiniLogSelection {
+                    exclude_topics: topics(&[&[9], &[9]]),
+                    ..selection(&[], &[])
+                },
+                [true, true, true, true],
+            ),
+            (
+                MiniLogSelection {
+                    exclude_topics: topics(&[&[2]]),
+                    ..selection(&[2], &[3])
+                },
+                [false, false, true, false],
+            ),
+        ];
+
+        check_hot_and_parquet_agree(
+            cases,
+            |selections, i| {
+                let (address, topics) = &logs[i];
+                MiniLogSelection::matches_log_impl(selections, address, topics)
+            },
+            |selection| {
+                let mini_query = MiniQuery {
+                    logs: vec![selection],
+                    ..empty_query()
+                };
+                let pruned = mini_query.pruned_log_selection(&parquet_idx);
+                prune_log_queries_per_rg(&rg_meta, &pruned)
+            },
+        );
+    }
+}
@@ -1,5 +1,8 @@
 use crate::parquet_metadata::ParquetMetadata;
-use crate::types::{LogQueryResult, MiniQuery, QueryResult};
+use crate::types::{
+    LogQueryResult, MiniQuery, QueryResult, StateDiffQueryResult, TraceQueryResult,
+    WithdrawalQueryResult,
+};
 use crate::Result;
 use eth_archive_core::dir_name::DirName;
 use eth_archive_core::rayon_async;
@@ -11,8 +14,13 @@ use std::sync::Arc;
 mod block;
 mod log;
 mod read;
+mod state_diff;
+mod trace;
 mod transaction;
 mod util;
+mod withdrawal;
+
+pub use block::find_block_by_timestamp;
 
 pub struct ParquetQuery {
     pub data_path: PathBuf,
@@ -35,6 +43,44 @@ impl ParquetQuery {
             LogQueryResult::default()
         };
 
+        let TraceQueryResult {
+            traces,
+            transactions: trace_transactions,
+            blocks: trace_blocks,
+        } = if !query.mini_query.traces.is_empty() {
+            query.clone().query_traces().await?
+        } else {
+            TraceQueryResult::default()
+        };
+
+        let StateDiffQueryResult {
+            state_diffs,
+            transactions: state_diff_transactions,
+            blocks: state_diff_blocks,
+        } = if !query.mini_query.state_diffs.is_empty() {
+            query.clone().query_state_diffs().await?
+        } else {
+            StateDiffQueryResult::default()
+        };
+
+        let WithdrawalQueryResult {
+            withdrawals,
+            blocks: withdrawal_blocks,
+        } = if !query.mini_query.withdrawals.is_empty() {
+            query.clone().query_withdrawals().await?
+        } else {
+            WithdrawalQueryResult::default()
+        };
+
+        let mut transactions = transactions;
+        transactions.extend(trace_transactions);
+        transactions.extend(state_diff_transactions);
+
+        let mut blocks = blocks;
+        blocks.extend(trace_blocks);
+        blocks.extend(state_diff_blocks);
+        blocks.extend(withdrawal_blocks);
+
         let (transactions, blocks) =
             if query.mini_query.transactions.is_empty() && transactions.is_empty() {
                 (BTreeMap::new(), blocks)
@@ -56,10 +102,37 @@ impl ParquetQuery {
         Ok(QueryResult {
             logs,
             transactions,
+            traces,
+            state_diffs,
+            withdrawals,
             blocks,
         })
     }
 
+    /// Reads a single transaction from the folder.
+    pub async fn get_transaction(
+        self,
+        block_number: u64,
+        transaction_index: u32,
+    ) -> Result<Option<ResponseTransaction>> {
+        let tx_id = (block_number, transaction_index);
+
+        let (mut transactions, _) = Arc::new(self)
+            .query_transactions(BTreeSet::from([tx_id]), BTreeSet::new())
+            .await?;
+
+        Ok(transactions.remove(&tx_id))
+    }
+
+    /// Reads a single block from the folder.
+    pub async fn get_block(self, block_number: u64) -> Result<Option<ResponseBlock>> {
+        let mut blocks = Arc::new(self)
+            .query_blocks(Some(BTreeSet::from([block_number])))
+            .await?;
+
+        Ok(blocks.remove(&block_number))
+    }
+
     async fn query_logs(self: Arc<Self>) -> Result<LogQueryResult> {
         let pruned_queries_per_rg: Vec<_> = rayon_async::spawn({
             let query = self.clone();
@@ -81,11 +154,86 @@ impl ParquetQuery {
         log::query_logs(self, pruned_queries_per_rg).await
     }
 
+    async fn query_traces(self: Arc<Self>) -> Result<TraceQueryResult> {
+        let pruned_queries_per_rg: Vec<_> = rayon_async::spawn({
+            let query = self.clone();
+            move || {
+                query
+                    .metadata
+                    .trace
+                    .iter()
+                    .map(|rg_meta| {
+                        trace::prune_trace_queries_per_rg(rg_meta, &query.mini_query.traces)
+                    })
+                    .collect()
+            }
+        })
+        .await;
+
+        if pruned_queries_per_rg.iter().all(Vec::is_empty) {
+            return Ok(TraceQueryResult::default());
+        }
+
+        trace::query_traces(self, pruned_queries_per_rg).await
+    }
+
+    async fn query_state_diffs(self: Arc<Self>) -> Result<StateDiffQueryResult> {
+        let pruned_queries_per_rg: Vec<_> = rayon_async::spawn({
+            let query = self.clone();
+            move || {
+                query
+                    .metadata
+                    .state_diff
+                    .iter()
+                    .map(|rg_meta| {
+                        state_diff::prune_state_diff_queries_per_rg(
+                            rg_meta,
+                            &query.mini_query.state_diffs,
+                        )
+                    })
+                    .collect()
+            }
+        })
+        .await;
+
+        if pruned_queries_per_rg.iter().all(Vec::is_empty) {
+            return Ok(StateDiffQueryResult::default());
+        }
+
+        state_diff::query_state_diffs(self, pruned_queries_per_rg).await
+    }
+
+    async fn query_withdrawals(self: Arc<Self>) -> Result<WithdrawalQueryResult> {
+        let pruned_queries_per_rg: Vec<_> = rayon_async::spawn({
+            let query = self.clone();
+            move || {
+                query
+                    .metadata
+                    .withdrawal
+                    .iter()
+                    .map(|rg_meta| {
+                        withdrawal::prune_withdrawal_queries_per_rg(
+                            rg_meta,
+                            &query.mini_query.withdrawals,
+                        )
+                    })
+                    .collect()
+            }
+        })
+        .await;
+
+        if pruned_queries_per_rg.iter().all(Vec::is_empty) {
+            return Ok(WithdrawalQueryResult::default());
+        }
+
+        withdrawal::query_withdrawals(self, pruned_queries_per_rg).await
+    }
+
     async fn query_transactions(
         self: Arc<Self>,
-        transactions: BTreeSet<(u32, u32)>,
-        blocks: BTreeSet<u32>,
-    ) -> Result<(BTreeMap<(u32, u32), ResponseTransaction>, BTreeSet<u32>)> {
+        transactions: BTreeSet<(u64, u32)>,
+        blocks: BTreeSet<u64>,
+    ) -> Result<(BTreeMap<(u64, u32), ResponseTransaction>, BTreeSet<u64>)> {
         let pruned_tx_queries_per_rg: Vec<_> = rayon_async::spawn({
             let query = self.clone();
             move || {
@@ -117,8 +265,8 @@ impl ParquetQuery {
 
     async fn query_blocks(
         self: Arc<Self>,
-        blocks: Option<BTreeSet<u32>>,
-    ) -> Result<BTreeMap<u32, ResponseBlock>> {
+        blocks: Option<BTreeSet<u64>>,
+    ) -> Result<BTreeMap<u64, ResponseBlock>> {
         let pruned_blocks_per_rg: Vec<_> = rayon_async::spawn({
             let query = self.clone();
             move || {
@@ -132,13 +280,16 @@ impl ParquetQuery {
         })
         .await;
 
-        if pruned_blocks_per_rg.iter().all(|blocks| {
-            if let Some(blocks) = blocks {
-                blocks.is_empty()
-            } else {
-                false
-            }
-        }) {
+        // block selections have to be checked against every row group
+        if self.mini_query.blocks.is_empty()
+            && pruned_blocks_per_rg.iter().all(|blocks| {
+                if let Some(blocks) = blocks {
+                    blocks.is_empty()
+                } else {
+                    false
+                }
+            })
+        {
             return Ok(BTreeMap::new());
         }
 
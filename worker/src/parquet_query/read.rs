@@ -5,6 +5,7 @@ use arrow2::datatypes::Field;
 use arrow2::io::parquet;
 use arrow2::io::parquet::read::ArrayIter;
 use eth_archive_core::hash::HashMap;
+use eth_archive_core::parquet_source::util::{cast_columns, file_fields};
 use eth_archive_core::rayon_async;
 use futures::future::BoxFuture;
 use rayon::prelude::*;
@@ -47,10 +48,22 @@ impl<F: Fn(usize) -> bool> ReadParquet<F> {
                 .map_err(Error::ReadParquet)?
         };
 
+        // files written by older versions might not have all of the columns
+        let schema = parquet::read::infer_schema(&metadata).map_err(Error::ReadParquet)?;
+        let fields: Vec<_> = self
+            .fields
+            .into_iter()
+            .filter(|field| schema.fields.iter().any(|f| f.name == field.name))
+            .collect();
+        // older files store block numbers as UInt32 so the columns are
+        // read with the types in the file and then cast
+        let file_fields = file_fields(&schema, &fields);
+
         let (tx, rx) = mpsc::channel(metadata.row_groups.len());
         for (i, rg_meta) in metadata.row_groups.into_iter().enumerate() {
             if (self.rg_filter)(i) {
-                let fields = self.fields.clone();
+                let fields = fields.clone();
+                let file_fields = file_fields.clone();
                 let path = self.path.clone();
                 let tx = tx.clone();
                 tokio::task::spawn(async move {
@@ -62,10 +75,10 @@ impl<F: Fn(usize) -> bool> ReadParquet<F> {
                         }) as BoxFuture<_>
                     };
 
-                    let mut columns = match parquet::read::read_columns_many_async(
+                    let columns = match parquet::read::read_columns_many_async(
                         open_reader,
                         &rg_meta,
-                        fields.clone(),
+                        file_fields,
                         Some(CHUNK_SIZE),
                         None,
                         None,
@@ -79,6 +92,7 @@ impl<F: Fn(usize) -> bool> ReadParquet<F> {
                             return;
                         }
                     };
+                    let mut columns = cast_columns(columns, &fields);
 
                     let mut num_rows = rg_meta.num_rows();
                     let chunks = rayon_async::spawn(move || {
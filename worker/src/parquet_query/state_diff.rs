@@ -0,0 +1,168 @@
+use super::read::ReadParquet;
+use super::util::{define_cols, map_from_arrow, map_from_arrow_opt};
+use super::ParquetQuery;
+use crate::parquet_metadata::StateDiffRowGroupMetadata;
+use crate::types::{MiniQuery, MiniStateDiffSelection, StateDiffQueryResult};
+use crate::Result;
+use arrow2::array::{self, Array, UInt32Array, UInt64Array};
+use eth_archive_core::deserialize::{Address, BlockNumber, Bytes, Bytes32, Index};
+use eth_archive_core::hash::HashMap;
+use eth_archive_core::types::ResponseStateDiff;
+use eth_archive_ingester::schema::state_diff_schema;
+use std::sync::Arc;
+
+type BinaryArray = array::BinaryArray<i32>;
+type Utf8Array = array::Utf8Array<i32>;
+
+pub fn prune_state_diff_queries_per_rg(
+    rg_meta: &StateDiffRowGroupMetadata,
+    state_diff_selections: &[MiniStateDiffSelection],
+) -> Vec<MiniStateDiffSelection> {
+    state_diff_selections
+        .iter()
+        .filter_map(|state_diff_selection| {
+            state_diff_selection.pruned(|addr| rg_meta.address_filter.contains(addr))
+        })
+        .collect()
+}
+
+pub async fn query_state_diffs(
+    query: Arc<ParquetQuery>,
+    pruned_queries_per_rg: Vec<Vec<MiniStateDiffSelection>>,
+) -> Result<StateDiffQueryResult> {
+    let mut path = query.data_path.clone();
+    path.push(query.dir_name.to_string());
+    path.push("state_diff.parquet");
+
+    let selected_fields = query.mini_query.field_selection.state_diff.as_fields();
+
+    let fields: Vec<_> = state_diff_schema()
+        .fields
+        .into_iter()
+        .filter(|field| selected_fields.contains(field.name.as_str()))
+        .collect();
+
+    let rg_filter = |i| {
+        let val: &Vec<MiniStateDiffSelection> = &pruned_queries_per_rg[i];
+        !val.is_empty()
+    };
+
+    let mut chunk_rx = ReadParquet {
+        path,
+        rg_filter,
+        fields,
+    }
+    .read()
+    .await?;
+
+    let mut query_result = StateDiffQueryResult::default();
+    while let Some(res) = chunk_rx.recv().await {
+        let (i, columns) = res?;
+        let state_diff_queries = &pruned_queries_per_rg[i];
+        process_cols(
+            &query.mini_query,
+            state_diff_queries,
+            columns,
+            &mut query_result,
+        );
+    }
+
+    Ok(query_result)
+}
+
+fn process_cols(
+    query: &MiniQuery,
+    state_diff_queries: &[MiniStateDiffSelection],
+    mut columns: HashMap<String, Box<dyn Array>>,
+    query_result: &mut StateDiffQueryResult,
+) {
+    #[rustfmt::skip]
+	define_cols!(
+    	columns,
+    	block_number, UInt64Array,
+    	transaction_index, UInt32Array,
+    	transaction_hash, BinaryArray,
+    	diff_index, UInt32Array,
+    	address, BinaryArray,
+    	kind, Utf8Array,
+    	key, BinaryArray,
+    	prev, BinaryArray,
+    	next, BinaryArray
+	);
+
+    let len = block_number.as_ref().unwrap().len();
+
+    for i in 0..len {
+        let state_diff = ResponseStateDiff {
+            block_number: map_from_arrow!(block_number, BlockNumber, i),
+            transaction_index: map_from_arrow!(transaction_index, Index, i),
+            transaction_hash: map_from_arrow!(transaction_hash, Bytes32::new, i),
+            diff_index: map_from_arrow!(diff_index, Index, i),
+            address: map_from_arrow!(address, Address::new, i),
+            kind: map_from_arrow!(kind, str::to_owned, i),
+            key: map_from_arrow_opt!(key, Bytes32::new, i),
+            prev: map_from_arrow_opt!(prev, Bytes::new, i),
+            next: map_from_arrow_opt!(next, Bytes::new, i),
+        };
+
+        let block_number = state_diff.block_number.unwrap().0;
+        let transaction_index = state_diff.transaction_index.unwrap().0;
+        let diff_index = state_diff.diff_index.unwrap().0;
+
+        if query.from_block > block_number || query.to_block <= block_number {
+            continue;
+        }
+
+        if !MiniStateDiffSelection::matches_state_diff_impl(
+            state_diff_queries,
+            state_diff.address.as_ref().unwrap(),
+            &state_diff.key,
+        ) {
+            continue;
+        }
+
+        query_result
+            .transactions
+            .insert((block_number, transaction_index));
+        query_result.blocks.insert(block_number);
+        query_result
+            .state_diffs
+            .insert((block_number, diff_index), state_diff);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bloom::Bloom;
+    use eth_archive_core::hash::HashSet;
+
+    fn addr(n: u8) -> Address {
+        Address::new(&[n; 20])
+    }
+
+    fn selection(address: &[u8]) -> MiniStateDiffSelection {
+        MiniStateDiffSelection {
+            address: address.iter().map(|&n| addr(n)).collect(),
+            key: [Bytes32::new(&[7; 32])].into_iter().collect(),
+        }
+    }
+
+    #[test]
+    fn test_prune_state_diff_queries_per_rg() {
+        let addresses = [addr(1), addr(2)].into_iter().collect::<HashSet<_>>();
+        let rg_meta = StateDiffRowGroupMetadata {
+            address_filter: Bloom::new(&addresses, 0.001, 128_000),
+        };
+
+        let pruned = prune_state_diff_queries_per_rg(
+            &rg_meta,
+            &[selection(&[]), selection(&[1, 9]), selection(&[9])],
+        );
+
+        assert_eq!(pruned.len(), 2);
+        assert!(pruned[0].address.is_empty());
+        assert_eq!(pruned[1].address, selection(&[1]).address);
+        assert_eq!(pruned[1].key, selection(&[]).key);
+    }
+}
@@ -0,0 +1,235 @@
+use super::read::ReadParquet;
+use super::util::{define_cols, map_from_arrow, map_from_arrow_opt};
+use super::ParquetQuery;
+use crate::parquet_metadata::TraceRowGroupMetadata;
+use crate::types::{MiniQuery, MiniTraceSelection, TraceQueryResult};
+use crate::Result;
+use arrow2::array::{self, Array, UInt32Array, UInt64Array};
+use eth_archive_core::deserialize::{Address, BlockNumber, Bytes, Bytes32, Index, Sighash};
+use eth_archive_core::hash::HashMap;
+use eth_archive_core::types::ResponseTrace;
+use eth_archive_ingester::schema::trace_schema;
+use std::sync::Arc;
+
+type BinaryArray = array::BinaryArray<i32>;
+type Utf8Array = array::Utf8Array<i32>;
+type ListArray = array::ListArray<i32>;
+
+pub fn prune_trace_queries_per_rg(
+    rg_meta: &TraceRowGroupMetadata,
+    trace_selections: &[MiniTraceSelection],
+) -> Vec<MiniTraceSelection> {
+    trace_selections
+        .iter()
+        .filter_map(|trace_selection| {
+            trace_selection.pruned(
+                |addr| rg_meta.source_filter.contains(addr),
+                |addr| rg_meta.dest_filter.contains(addr),
+            )
+        })
+        .collect()
+}
+
+pub async fn query_traces(
+    query: Arc<ParquetQuery>,
+    pruned_queries_per_rg: Vec<Vec<MiniTraceSelection>>,
+) -> Result<TraceQueryResult> {
+    let mut path = query.data_path.clone();
+    path.push(query.dir_name.to_string());
+    path.push("trace.parquet");
+
+    let selected_fields = query.mini_query.field_selection.trace.as_fields();
+
+    // sighash isn't a response field but it is needed for filtering
+    let fields: Vec<_> = trace_schema()
+        .fields
+        .into_iter()
+        .filter(|field| selected_fields.contains(field.name.as_str()) || field.name == "sighash")
+        .collect();
+
+    let rg_filter = |i| {
+        let val: &Vec<MiniTraceSelection> = &pruned_queries_per_rg[i];
+        !val.is_empty()
+    };
+
+    let mut chunk_rx = ReadParquet {
+        path,
+        rg_filter,
+        fields,
+    }
+    .read()
+    .await?;
+
+    let mut query_result = TraceQueryResult::default();
+    while let Some(res) = chunk_rx.recv().await {
+        let (i, columns) = res?;
+        let trace_queries = &pruned_queries_per_rg[i];
+        process_cols(&query.mini_query, trace_queries, columns, &mut query_result);
+    }
+
+    Ok(query_result)
+}
+
+fn process_cols(
+    query: &MiniQuery,
+    trace_queries: &[MiniTraceSelection],
+    mut columns: HashMap<String, Box<dyn Array>>,
+    query_result: &mut TraceQueryResult,
+) {
+    #[rustfmt::skip]
+	define_cols!(
+    	columns,
+    	block_number, UInt64Array,
+    	transaction_index, UInt32Array,
+    	transaction_hash, BinaryArray,
+    	trace_index, UInt32Array,
+    	trace_address, ListArray,
+    	subtraces, UInt32Array,
+    	call_type, Utf8Array,
+    	source, BinaryArray,
+    	dest, BinaryArray,
+    	value, BinaryArray,
+    	gas, BinaryArray,
+    	gas_used, BinaryArray,
+    	input, BinaryArray,
+    	output, BinaryArray,
+    	error, Utf8Array,
+    	sighash, BinaryArray
+	);
+
+    let len = block_number.as_ref().unwrap().len();
+
+    for i in 0..len {
+        let trace = ResponseTrace {
+            block_number: map_from_arrow!(block_number, BlockNumber, i),
+            transaction_index: map_from_arrow_opt!(transaction_index, Index, i),
+            transaction_hash: map_from_arrow_opt!(transaction_hash, Bytes32::new, i),
+            trace_index: map_from_arrow!(trace_index, Index, i),
+            trace_address: trace_address.as_ref().map(|arr| {
+                let trace_address = arr.value(i);
+                let trace_address = trace_address
+                    .as_any()
+                    .downcast_ref::<UInt32Array>()
+                    .unwrap();
+                trace_address.values().iter().copied().collect()
+            }),
+            subtraces: map_from_arrow!(subtraces, Index, i),
+            call_type: map_from_arrow!(call_type, str::to_owned, i),
+            source: map_from_arrow_opt!(source, Address::new, i),
+            dest: map_from_arrow_opt!(dest, Address::new, i),
+            value: map_from_arrow_opt!(value, Bytes::new, i),
+            gas: map_from_arrow_opt!(gas, Bytes::new, i),
+            gas_used: map_from_arrow_opt!(gas_used, Bytes::new, i),
+            input: map_from_arrow_opt!(input, Bytes::new, i),
+            output: map_from_arrow_opt!(output, Bytes::new, i),
+            error: map_from_arrow_opt!(error, str::to_owned, i),
+        };
+
+        let sighash = map_from_arrow_opt!(sighash, Sighash::new, i);
+
+        let block_number = trace.block_number.unwrap().0;
+        let trace_index = trace.trace_index.unwrap().0;
+
+        if query.from_block > block_number || query.to_block <= block_number {
+            continue;
+        }
+
+        if !MiniTraceSelection::matches_trace_impl(
+            trace_queries,
+            &trace.source,
+            &trace.dest,
+            trace.call_type.as_ref().unwrap(),
+            &sighash,
+        ) {
+            continue;
+        }
+
+        if let Some(transaction_index) = trace.transaction_index {
+            query_result
+                .transactions
+                .insert((block_number, transaction_index.0));
+        }
+        query_result.blocks.insert(block_number);
+        query_result
+            .traces
+            .insert((block_number, trace_index), trace);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::util::{check_hot_and_parquet_agree, empty_query};
+    use super::*;
+    use crate::bloom::Bloom;
+    use eth_archive_core::hash::HashSet;
+
+    fn addr(n: u8) -> Address {
+        Address::new(&[n; 20])
+    }
+
+    fn selection(source: &[u8], dest: &[u8]) -> MiniTraceSelection {
+        MiniTraceSelection {
+            source: source.iter().map(|&n| addr(n)).collect(),
+            dest: dest.iter().map(|&n| addr(n)).collect(),
+            call_type: Vec::new(),
+            sighash: Vec::new(),
+        }
+    }
+
+    /// Checks that `from` and `to` only selections aren't pruned by the filter of the other side.
+    #[test]
+    fn test_trace_selection_hot_and_parquet_agree() {
+        // (source, dest) of the traces in a single row group
+        let traces = [
+            (Some(addr(1)), Some(addr(2))),
+            (Some(addr(2)), Some(addr(3))),
+            (Some(addr(3)), None),
+            (Some(addr(4)), Some(addr(1))),
+        ];
+
+        let sources = traces
+            .iter()
+            .filter_map(|trace| trace.0.clone())
+            .collect::<HashSet<_>>();
+        let dests = traces
+            .iter()
+            .filter_map(|trace| trace.1.clone())
+            .collect::<HashSet<_>>();
+        let all = sources.union(&dests).cloned().collect::<HashSet<_>>();
+
+        let parquet_idx = Bloom::new(&all, 0.001, 128_000);
+        let rg_meta = TraceRowGroupMetadata {
+            source_filter: Bloom::new(&sources, 0.001, 128_000),
+            dest_filter: Bloom::new(&dests, 0.001, 128_000),
+        };
+
+        let cases = [
+            (selection(&[], &[]), [true, true, true, true]),
+            (selection(&[1], &[]), [true, false, false, false]),
+            (selection(&[4], &[]), [false, false, false, true]),
+            (selection(&[], &[3]), [false, true, false, false]),
+            (selection(&[], &[1]), [false, false, false, true]),
+            (selection(&[2], &[3]), [false, true, false, false]),
+            (selection(&[1], &[3]), [false, false, false, false]),
+            (selection(&[9], &[]), [false, false, false, false]),
+            // 4 is only a source
+            (selection(&[], &[4]), [false, false, false, false]),
+        ];
+
+        check_hot_and_parquet_agree(
+            cases,
+            |selections, i| {
+                let (source, dest) = &traces[i];
+                MiniTraceSelection::matches_trace_impl(selections, source, dest, "call", &None)
+            },
+            |selection| {
+                let mini_query = MiniQuery {
+                    traces: vec![selection],
+                    ..empty_query()
+                };
+                let pruned = mini_query.pruned_trace_selection(&parquet_idx);
+                prune_trace_queries_per_rg(&rg_meta, &pruned)
+            },
+        );
+    }
+}
@@ -4,21 +4,27 @@ use super::ParquetQuery;
 use crate::parquet_metadata::{combine_block_num_tx_idx, TransactionRowGroupMetadata};
 use crate::types::{MiniQuery, MiniTransactionSelection};
 use crate::Result;
-use arrow2::array::{self, Array, UInt32Array, UInt64Array};
-use eth_archive_core::deserialize::{Address, BigUnsigned, Bytes, Bytes32, Index, Sighash};
-use eth_archive_core::hash::{HashMap, HashSet};
+use arrow2::array::{self, Array, FixedSizeBinaryArray, UInt32Array, UInt64Array};
+use eth_archive_core::deserialize::{
+    Address, BigUnsigned, BlockNumber, BloomFilterBytes, Bytes, Bytes32, ChainId, Index, Sighash,
+};
+use eth_archive_core::hash::HashMap;
+use eth_archive_core::parquet_source::util::{
+    access_list_from_arrow, authorization_list_from_arrow,
+};
 use eth_archive_core::types::ResponseTransaction;
 use eth_archive_ingester::schema::tx_schema;
 use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
 
 type BinaryArray = array::BinaryArray<i32>;
+type ListArray = array::ListArray<i32>;
 
 pub fn prune_tx_queries_per_rg(
     rg_meta: &TransactionRowGroupMetadata,
     tx_selections: &[MiniTransactionSelection],
-    transactions: BTreeSet<(u32, u32)>,
-) -> (Vec<MiniTransactionSelection>, BTreeSet<(u32, u32)>) {
+    transactions: BTreeSet<(u64, u32)>,
+) -> (Vec<MiniTransactionSelection>, BTreeSet<(u64, u32)>) {
     let transactions = transactions
         .into_iter()
         .filter(|(block_num, tx_idx)| {
@@ -30,53 +36,29 @@ pub fn prune_tx_queries_per_rg(
 
     let tx_selections = tx_selections
         .iter()
+        .filter(|tx_selection| match rg_meta.value_ranges.as_ref() {
+            Some(value_ranges) => value_ranges.can_match(tx_selection),
+            None => true,
+        })
         .filter_map(|tx_selection| {
-            if tx_selection.source.is_empty() && tx_selection.dest.is_empty() {
-                return Some(tx_selection.clone());
-            }
-
-            let source = tx_selection
-                .source
-                .iter()
-                .filter(|addr| rg_meta.source_filter.contains(addr))
-                .cloned()
-                .collect::<HashSet<_>>();
-
-            if source.is_empty() {
-                return None;
-            }
-
-            let dest = tx_selection
-                .dest
-                .iter()
-                .filter(|addr| rg_meta.dest_filter.contains(addr))
-                .cloned()
-                .collect::<HashSet<_>>();
-
-            if dest.is_empty() {
-                return None;
-            }
-
-            Some(MiniTransactionSelection {
-                source,
-                dest,
-                sighash: tx_selection.sighash.clone(),
-                status: tx_selection.status,
-            })
+            tx_selection.pruned(
+                |addr| rg_meta.source_filter.contains(addr),
+                |addr| rg_meta.dest_filter.contains(addr),
+            )
         })
         .collect();
 
     (tx_selections, transactions)
 }
 
-type TxIds = BTreeSet<(u32, u32)>;
-type Txs = BTreeMap<(u32, u32), ResponseTransaction>;
+type TxIds = BTreeSet<(u64, u32)>;
+type Txs = BTreeMap<(u64, u32), ResponseTransaction>;
 
 pub async fn query_transactions(
     query: Arc<ParquetQuery>,
     pruned_queries_per_rg: Vec<(Vec<MiniTransactionSelection>, TxIds)>,
-    blocks: BTreeSet<u32>,
-) -> Result<(Txs, BTreeSet<u32>)> {
+    blocks: BTreeSet<u64>,
+) -> Result<(Txs, BTreeSet<u64>)> {
     let mut path = query.data_path.clone();
     path.push(query.dir_name.to_string());
     path.push("tx.parquet");
@@ -124,10 +106,10 @@ pub async fn query_transactions(
 fn process_cols(
     query: &MiniQuery,
     tx_queries: &[MiniTransactionSelection],
-    tx_ids: &BTreeSet<(u32, u32)>,
+    tx_ids: &BTreeSet<(u64, u32)>,
     mut columns: HashMap<String, Box<dyn Array>>,
-    blocks: &mut BTreeSet<u32>,
-    transactions: &mut BTreeMap<(u32, u32), ResponseTransaction>,
+    blocks: &mut BTreeSet<u64>,
+    transactions: &mut BTreeMap<(u64, u32), ResponseTransaction>,
 ) {
     #[rustfmt::skip]
 	define_cols!(
@@ -135,24 +117,33 @@ fn process_cols(
     	kind, UInt32Array,
         nonce, UInt64Array,
         dest, BinaryArray,
-        gas, BinaryArray,
-        value, BinaryArray,
+        gas, UInt64Array,
+        value, FixedSizeBinaryArray,
         input, BinaryArray,
-        max_priority_fee_per_gas, BinaryArray,
-        max_fee_per_gas, BinaryArray,
+        max_priority_fee_per_gas, FixedSizeBinaryArray,
+        max_fee_per_gas, FixedSizeBinaryArray,
         y_parity, UInt32Array,
-        chain_id, UInt32Array,
-        v, UInt64Array,
+        chain_id, UInt64Array,
+        v, BinaryArray,
         r, BinaryArray,
         s, BinaryArray,
         source, BinaryArray,
         block_hash, BinaryArray,
-        block_number, UInt32Array,
+        block_number, UInt64Array,
         transaction_index, UInt32Array,
-        gas_price, BinaryArray,
+        gas_price, FixedSizeBinaryArray,
         hash, BinaryArray,
         status, UInt32Array,
-        sighash, BinaryArray
+        sighash, BinaryArray,
+        contract_address, BinaryArray,
+        gas_used, UInt64Array,
+        cumulative_gas_used, UInt64Array,
+        effective_gas_price, FixedSizeBinaryArray,
+        logs_bloom, BinaryArray,
+        max_fee_per_blob_gas, FixedSizeBinaryArray,
+        blob_versioned_hashes, ListArray,
+        access_list, ListArray,
+        authorization_list, ListArray
 	);
 
     let len = block_number.as_ref().unwrap().len();
@@ -162,23 +153,54 @@ fn process_cols(
             kind: map_from_arrow_opt!(kind, Index, i),
             nonce: map_from_arrow!(nonce, BigUnsigned, i),
             dest: map_from_arrow_opt!(dest, Address::new, i),
-            gas: map_from_arrow!(gas, Bytes::new, i),
-            value: map_from_arrow!(value, Bytes::new, i),
+            gas: map_from_arrow!(gas, Bytes::from_quantity, i),
+            value: map_from_arrow!(value, Bytes::from_be_quantity, i),
             input: map_from_arrow!(input, Bytes::new, i),
-            max_priority_fee_per_gas: map_from_arrow_opt!(max_priority_fee_per_gas, Bytes::new, i),
-            max_fee_per_gas: map_from_arrow_opt!(max_fee_per_gas, Bytes::new, i),
+            max_priority_fee_per_gas: map_from_arrow_opt!(
+                max_priority_fee_per_gas,
+                Bytes::from_be_quantity,
+                i
+            ),
+            max_fee_per_gas: map_from_arrow_opt!(max_fee_per_gas, Bytes::from_be_quantity, i),
             y_parity: map_from_arrow_opt!(y_parity, Index, i),
-            chain_id: map_from_arrow_opt!(chain_id, Index, i),
-            v: map_from_arrow_opt!(v, BigUnsigned, i),
+            chain_id: map_from_arrow_opt!(chain_id, ChainId, i),
+            v: map_from_arrow_opt!(v, Bytes::new, i),
             r: map_from_arrow_opt!(r, Bytes::new, i),
             s: map_from_arrow_opt!(s, Bytes::new, i),
             source: map_from_arrow_opt!(source, Address::new, i),
             block_hash: map_from_arrow!(block_hash, Bytes32::new, i),
-            block_number: map_from_arrow!(block_number, Index, i),
+            block_number: map_from_arrow!(block_number, BlockNumber, i),
             transaction_index: map_from_arrow!(transaction_index, Index, i),
-            gas_price: map_from_arrow!(gas_price, Bytes::new, i),
+            gas_price: map_from_arrow_opt!(gas_price, Bytes::from_be_quantity, i),
             hash: map_from_arrow!(hash, Bytes32::new, i),
             status: map_from_arrow_opt!(status, Index, i),
+            contract_address: map_from_arrow_opt!(contract_address, Address::new, i),
+            gas_used: map_from_arrow_opt!(gas_used, Bytes::from_quantity, i),
+            cumulative_gas_used: map_from_arrow_opt!(cumulative_gas_used, Bytes::from_quantity, i),
+            effective_gas_price: map_from_arrow_opt!(
+                effective_gas_price,
+                Bytes::from_be_quantity,
+                i
+            ),
+            logs_bloom: map_from_arrow_opt!(logs_bloom, BloomFilterBytes::new, i),
+            max_fee_per_blob_gas: map_from_arrow_opt!(
+                max_fee_per_blob_gas,
+                Bytes::from_be_quantity,
+                i
+            ),
+            blob_versioned_hashes: blob_versioned_hashes.as_ref().and_then(|arr| {
+                arr.is_valid(i).then(|| {
+                    let hashes = arr.value(i);
+                    let hashes = hashes.as_any().downcast_ref::<BinaryArray>().unwrap();
+                    hashes.values_iter().map(Bytes32::new).collect()
+                })
+            }),
+            access_list: access_list
+                .as_ref()
+                .and_then(|arr| access_list_from_arrow(arr, i)),
+            authorization_list: authorization_list
+                .as_ref()
+                .and_then(|arr| authorization_list_from_arrow(arr, i)),
         };
 
         let sighash = map_from_arrow!(sighash, Sighash::new, i);
@@ -193,7 +215,18 @@ fn process_cols(
         let tx_id = (block_number, transaction_index);
         if !tx_ids.contains(&tx_id)
             && !MiniTransactionSelection::matches_tx_impl(
-                tx_queries, &tx.source, &tx.dest, &sighash, tx.status,
+                tx_queries,
+                &tx.source,
+                &tx.dest,
+                &sighash,
+                tx.status,
+                tx.kind,
+                tx.access_list.as_deref(),
+                tx.authorization_list.as_deref(),
+                tx.value.as_ref(),
+                tx.gas_price.as_ref(),
+                tx.gas.as_ref(),
+                tx.max_fee_per_gas.as_ref(),
             )
         {
             continue;
@@ -203,3 +236,276 @@ fn process_cols(
         transactions.insert(tx_id, tx);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::util::{check_hot_and_parquet_agree, empty_query};
+    use super::*;
+    use crate::bloom::Bloom;
+    use crate::parquet_metadata::{TransactionValueRanges, ValueRange};
+    use crate::types::Range;
+    use eth_archive_core::deserialize::U256;
+    use eth_archive_core::hash::HashSet;
+
+    fn addr(n: u8) -> Address {
+        Address::new(&[n; 20])
+    }
+
+    fn selection(source: &[u8], dest: &[u8], address: &[u8]) -> MiniTransactionSelection {
+        MiniTransactionSelection {
+            source: source.iter().map(|&n| addr(n)).collect(),
+            dest: dest.iter().map(|&n| addr(n)).collect(),
+            address: address.iter().map(|&n| addr(n)).collect(),
+            sighash: Vec::new(),
+            status: None,
+            contract_creation: false,
+            kind: Vec::new(),
+            access_list_address: HashSet::new(),
+            authorization_address: HashSet::new(),
+            value: Range::default(),
+            gas_price: Range::default(),
+            gas: Range::default(),
+            max_fee_per_gas: Range::default(),
+            kind_range: Range::default(),
+            exclude_source: HashSet::new(),
+            exclude_dest: HashSet::new(),
+            exclude_address: HashSet::new(),
+        }
+    }
+
+    /// Either-side `address`, `from`, `to` and exclusion selections agree on both paths.
+    #[test]
+    fn test_tx_selection_hot_and_parquet_agree() {
+        // (source, dest) of the transactions in a single row group
+        let txs = [
+            (Some(addr(1)), Some(addr(2))),
+            (Some(addr(2)), Some(addr(3))),
+            (Some(addr(3)), None),
+            (Some(addr(4)), Some(addr(1))),
+        ];
+
+        let sources = txs
+            .iter()
+            .filter_map(|tx| tx.0.clone())
+            .collect::<HashSet<_>>();
+        let dests = txs
+            .iter()
+            .filter_map(|tx| tx.1.clone())
+            .collect::<HashSet<_>>();
+        let all = sources.union(&dests).cloned().collect::<HashSet<_>>();
+
+        let parquet_idx = Bloom::new(&all, 0.001, 128_000);
+        let rg_meta = TransactionRowGroupMetadata {
+            source_filter: Bloom::new(&sources, 0.001, 128_000),
+            dest_filter: Bloom::new(&dests, 0.001, 128_000),
+            max_blk_num_tx_idx: u64::MAX,
+            min_blk_num_tx_idx: 0,
+            value_ranges: None,
+        };
+
+        let cases = [
+            (selection(&[], &[], &[]), [true, true, true, true]),
+            (selection(&[1], &[], &[]), [true, false, false, false]),
+            (selection(&[], &[3], &[]), [false, true, false, false]),
+            (selection(&[], &[], &[2]), [true, true, false, false]),
+            (selection(&[], &[], &[1, 3]), [true, true, true, true]),
+            (selection(&[4], &[], &[1]), [false, false, false, true]),
+            (selection(&[2], &[3], &[]), [false, true, false, false]),
+            (selection(&[], &[], &[9]), [false, false, false, false]),
+            (selection(&[], &[4], &[]), [false, false, false, false]),
+            (
+                MiniTransactionSelection {
+                    contract_creation: true,
+                    ..selection(&[], &[], &[])
+                },
+                [false, false, true, false],
+            ),
+            (
+                MiniTransactionSelection {
+                    contract_creation: true,
+                    ..selection(&[1], &[], &[])
+                },
+                [false, false, false, false],
+            ),
+            (
+                MiniTransactionSelection {
+                    exclude_source: [addr(1)].into_iter().collect(),
+                    ..selection(&[], &[], &[])
+                },
+                [false, true, true, true],
+            ),
+            (
+                MiniTransactionSelection {
+                    exclude_address: [addr(1)].into_iter().collect(),
+                    ..selection(&[], &[], &[2])
+                },
+                [false, true, false, false],
+            ),
+            // excluded addresses that aren't in the row group don't prune the selection
+            (
+                MiniTransactionSelection {
+                    exclude_dest: [addr(9)].into_iter().collect(),
+                    ..selection(&[], &[], &[])
+                },
+                [true, true, true, true],
+            ),
+        ];
+
+        check_hot_and_parquet_agree(
+            cases,
+            |selections, i| {
+                let (source, dest) = &txs[i];
+                MiniTransactionSelection::matches_tx_impl(
+                    selections, source, dest, &None, None, None, None, None, None, None, None, None,
+                )
+            },
+            |selection| {
+                let mini_query = MiniQuery {
+                    transactions: vec![selection],
+                    ..empty_query()
+                };
+                let pruned = mini_query.pruned_tx_selection(&parquet_idx);
+                prune_tx_queries_per_rg(&rg_meta, &pruned, BTreeSet::new()).0
+            },
+        );
+    }
+
+    /// Value and gas price ranges are inclusive and don't match transactions without the value.
+    #[test]
+    fn test_tx_value_range() {
+        let quantity = |n: u64| U256::from_quantity(&n.to_be_bytes()).unwrap();
+        let selection = MiniTransactionSelection {
+            value: Range {
+                min: Some(quantity(100)),
+                max: None,
+            },
+            gas_price: Range {
+                min: None,
+                max: Some(quantity(10)),
+            },
+            ..selection(&[], &[], &[])
+        };
+
+        let matches = |value: u64, gas_price: Option<u64>| {
+            MiniTransactionSelection::matches_tx_impl(
+                &[selection.clone()],
+                &None,
+                &None,
+                &None,
+                None,
+                None,
+                None,
+                None,
+                Some(&Bytes::from_quantity(value)),
+                gas_price.map(Bytes::from_quantity).as_ref(),
+                None,
+                None,
+            )
+        };
+
+        assert!(matches(100, Some(10)));
+        assert!(matches(u64::MAX, Some(0)));
+        assert!(!matches(99, Some(10)));
+        assert!(!matches(100, Some(11)));
+        // transactions without a gas price don't match a gas price range
+        assert!(!matches(100, None));
+    }
+
+    /// Selections are pruned from row groups whose value ranges can't overlap their ranges.
+    #[test]
+    fn test_prune_tx_by_value_ranges() {
+        let quantity = |n: u64| U256::from_quantity(&n.to_be_bytes()).unwrap();
+        let rg_meta = TransactionRowGroupMetadata {
+            source_filter: Bloom::new(&HashSet::new(), 0.001, 128_000),
+            dest_filter: Bloom::new(&HashSet::new(), 0.001, 128_000),
+            max_blk_num_tx_idx: u64::MAX,
+            min_blk_num_tx_idx: 0,
+            value_ranges: Some(TransactionValueRanges {
+                value: Some(ValueRange {
+                    min: quantity(0),
+                    max: quantity(1000),
+                }),
+                gas: Some(ValueRange {
+                    min: 21_000,
+                    max: 500_000,
+                }),
+                gas_price: Some(ValueRange {
+                    min: quantity(10),
+                    max: quantity(20),
+                }),
+                // legacy transactions only
+                max_fee_per_gas: None,
+                kind: Some(ValueRange { min: 0, max: 0 }),
+            }),
+        };
+
+        let cases = [
+            (selection(&[], &[], &[]), true),
+            (
+                MiniTransactionSelection {
+                    value: Range {
+                        min: Some(quantity(1000)),
+                        max: None,
+                    },
+                    ..selection(&[], &[], &[])
+                },
+                true,
+            ),
+            (
+                MiniTransactionSelection {
+                    value: Range {
+                        min: Some(quantity(1001)),
+                        max: None,
+                    },
+                    ..selection(&[], &[], &[])
+                },
+                false,
+            ),
+            (
+                MiniTransactionSelection {
+                    gas: Range {
+                        min: None,
+                        max: Some(20_999),
+                    },
+                    ..selection(&[], &[], &[])
+                },
+                false,
+            ),
+            (
+                MiniTransactionSelection {
+                    gas_price: Range {
+                        min: Some(quantity(15)),
+                        max: Some(quantity(100)),
+                    },
+                    ..selection(&[], &[], &[])
+                },
+                true,
+            ),
+            (
+                MiniTransactionSelection {
+                    max_fee_per_gas: Range {
+                        min: Some(quantity(1)),
+                        max: None,
+                    },
+                    ..selection(&[], &[], &[])
+                },
+                false,
+            ),
+            (
+                MiniTransactionSelection {
+                    kind_range: Range {
+                        min: Some(2),
+                        max: None,
+                    },
+                    ..selection(&[], &[], &[])
+                },
+                false,
+            ),
+        ];
+
+        for (i, (selection, expected)) in cases.into_iter().enumerate() {
+            let (pruned, _) = prune_tx_queries_per_rg(&rg_meta, &[selection], BTreeSet::new());
+            assert_eq!(!pruned.is_empty(), expected, "case {i}");
+        }
+    }
+}
@@ -1,3 +1,6 @@
+#[cfg(test)]
+use crate::types::MiniQuery;
+
 // defines columns using the result frame and a list of column names
 macro_rules! define_cols {
     ($columns:expr, $($name:ident, $arrow_type:ident),*) => {
@@ -29,3 +32,45 @@ macro_rules! map_from_arrow_opt {
 pub(crate) use define_cols;
 pub(crate) use map_from_arrow;
 pub(crate) use map_from_arrow_opt;
+
+/// Query over the first block without any selections
+#[cfg(test)]
+pub fn empty_query() -> MiniQuery {
+    MiniQuery {
+        from_block: 0,
+        to_block: 1,
+        logs: Vec::new(),
+        transactions: Vec::new(),
+        traces: Vec::new(),
+        state_diffs: Vec::new(),
+        withdrawals: Vec::new(),
+        blocks: Vec::new(),
+        field_selection: Default::default(),
+        include_all_blocks: false,
+        abi: Default::default(),
+    }
+}
+
+/// Checks that each selection matches the expected items of a row group when it is applied
+/// directly, like the hot data query does, and after it is pruned with the parquet indexes.
+///
+/// `matches` checks the item at the given index against the selections and `prune` prunes
+/// a selection the same way the parquet query does.
+#[cfg(test)]
+pub fn check_hot_and_parquet_agree<S: Clone, const N: usize>(
+    cases: impl IntoIterator<Item = (S, [bool; N])>,
+    matches: impl Fn(&[S], usize) -> bool,
+    prune: impl Fn(S) -> Vec<S>,
+) {
+    for (i, (selection, expected)) in cases.into_iter().enumerate() {
+        let hot = (0..N)
+            .map(|idx| matches(&[selection.clone()], idx))
+            .collect::<Vec<_>>();
+
+        let pruned = prune(selection);
+        let parquet = (0..N).map(|idx| matches(&pruned, idx)).collect::<Vec<_>>();
+
+        assert_eq!(hot, expected, "hot data query, case {i}");
+        assert_eq!(parquet, expected, "parquet query, case {i}");
+    }
+}
@@ -0,0 +1,168 @@
+use super::read::ReadParquet;
+use super::util::{define_cols, map_from_arrow};
+use super::ParquetQuery;
+use crate::parquet_metadata::WithdrawalRowGroupMetadata;
+use crate::types::{MiniQuery, MiniWithdrawalSelection, WithdrawalQueryResult};
+use crate::Result;
+use arrow2::array::{self, Array, UInt32Array, UInt64Array};
+use eth_archive_core::deserialize::{Address, BlockNumber, Bytes, Index};
+use eth_archive_core::hash::HashMap;
+use eth_archive_core::types::ResponseWithdrawal;
+use eth_archive_ingester::schema::withdrawal_schema;
+use std::sync::Arc;
+
+type BinaryArray = array::BinaryArray<i32>;
+
+pub fn prune_withdrawal_queries_per_rg(
+    rg_meta: &WithdrawalRowGroupMetadata,
+    withdrawal_selections: &[MiniWithdrawalSelection],
+) -> Vec<MiniWithdrawalSelection> {
+    withdrawal_selections
+        .iter()
+        .filter_map(|withdrawal_selection| {
+            withdrawal_selection.pruned(
+                |addr| rg_meta.address_filter.contains(addr),
+                |idx| rg_meta.validator_index_filter.contains(&idx.to_be_bytes()),
+            )
+        })
+        .collect()
+}
+
+pub async fn query_withdrawals(
+    query: Arc<ParquetQuery>,
+    pruned_queries_per_rg: Vec<Vec<MiniWithdrawalSelection>>,
+) -> Result<WithdrawalQueryResult> {
+    let mut path = query.data_path.clone();
+    path.push(query.dir_name.to_string());
+    path.push("withdrawal.parquet");
+
+    let selected_fields = query.mini_query.field_selection.withdrawal.as_fields();
+
+    let fields: Vec<_> = withdrawal_schema()
+        .fields
+        .into_iter()
+        .filter(|field| selected_fields.contains(field.name.as_str()))
+        .collect();
+
+    let rg_filter = |i| {
+        let val: &Vec<MiniWithdrawalSelection> = &pruned_queries_per_rg[i];
+        !val.is_empty()
+    };
+
+    let mut chunk_rx = ReadParquet {
+        path,
+        rg_filter,
+        fields,
+    }
+    .read()
+    .await?;
+
+    let mut query_result = WithdrawalQueryResult::default();
+    while let Some(res) = chunk_rx.recv().await {
+        let (i, columns) = res?;
+        let withdrawal_queries = &pruned_queries_per_rg[i];
+        process_cols(
+            &query.mini_query,
+            withdrawal_queries,
+            columns,
+            &mut query_result,
+        );
+    }
+
+    Ok(query_result)
+}
+
+fn process_cols(
+    query: &MiniQuery,
+    withdrawal_queries: &[MiniWithdrawalSelection],
+    mut columns: HashMap<String, Box<dyn Array>>,
+    query_result: &mut WithdrawalQueryResult,
+) {
+    #[rustfmt::skip]
+	define_cols!(
+    	columns,
+    	block_number, UInt64Array,
+    	index, UInt32Array,
+    	validator_index, UInt32Array,
+    	address, BinaryArray,
+    	amount, UInt64Array
+	);
+
+    let len = block_number.as_ref().unwrap().len();
+
+    for i in 0..len {
+        let withdrawal = ResponseWithdrawal {
+            block_number: map_from_arrow!(block_number, BlockNumber, i),
+            index: map_from_arrow!(index, Index, i),
+            validator_index: map_from_arrow!(validator_index, Index, i),
+            address: map_from_arrow!(address, Address::new, i),
+            amount: map_from_arrow!(amount, Bytes::from_quantity, i),
+        };
+
+        let block_number = withdrawal.block_number.unwrap().0;
+        let index = withdrawal.index.unwrap().0;
+
+        if query.from_block > block_number || query.to_block <= block_number {
+            continue;
+        }
+
+        if !MiniWithdrawalSelection::matches_withdrawal_impl(
+            withdrawal_queries,
+            withdrawal.address.as_ref().unwrap(),
+            withdrawal.validator_index.unwrap().0,
+        ) {
+            continue;
+        }
+
+        query_result.blocks.insert(block_number);
+        query_result
+            .withdrawals
+            .insert((block_number, index), withdrawal);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bloom::Bloom;
+    use eth_archive_core::hash::HashSet;
+
+    fn addr(n: u8) -> Address {
+        Address::new(&[n; 20])
+    }
+
+    fn selection(address: &[u8], validator_index: &[u32]) -> MiniWithdrawalSelection {
+        MiniWithdrawalSelection {
+            address: address.iter().map(|&n| addr(n)).collect(),
+            validator_index: validator_index.iter().copied().collect(),
+        }
+    }
+
+    #[test]
+    fn test_prune_withdrawal_queries_per_rg() {
+        let addresses = [addr(1)].into_iter().collect::<HashSet<_>>();
+        let validator_indices = [5u32.to_be_bytes()].into_iter().collect::<HashSet<_>>();
+        let rg_meta = WithdrawalRowGroupMetadata {
+            address_filter: Bloom::new(&addresses, 0.001, 128_000),
+            validator_index_filter: Bloom::new(&validator_indices, 0.001, 128_000),
+        };
+
+        let pruned = prune_withdrawal_queries_per_rg(
+            &rg_meta,
+            &[
+                selection(&[], &[]),
+                selection(&[1], &[5, 6]),
+                selection(&[], &[6]),
+                selection(&[9], &[5]),
+            ],
+        );
+
+        assert_eq!(pruned.len(), 2);
+        assert!(pruned[0].address.is_empty() && pruned[0].validator_index.is_empty());
+        assert_eq!(pruned[1].address, selection(&[1], &[]).address);
+        assert_eq!(
+            pruned[1].validator_index,
+            selection(&[], &[5]).validator_index
+        );
+    }
+}
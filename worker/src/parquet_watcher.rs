@@ -1,5 +1,6 @@
 use crate::db::DbHandle;
 use crate::db_writer::DbWriter;
+use crate::parquet_metadata::CollectMetadataAndParquetIdx;
 use crate::{Error, Result};
 use eth_archive_core::dir_name::DirName;
 use std::io;
@@ -24,6 +25,11 @@ impl ParquetWatcher {
             .await
             .map_err(Error::CreateMissingDirectories)?;
 
+        Self::spawn_timestamp_indexer(self.db.clone(), data_path.clone(), self.db_writer.clone())
+            .await?;
+        Self::spawn_hash_indexer(self.db.clone(), data_path.clone(), self.db_writer.clone())
+            .await?;
+
         let db_writer = self.db_writer;
 
         tokio::spawn(async move {
@@ -56,6 +62,105 @@ impl ParquetWatcher {
         Ok(())
     }
 
+    /// Adds the folders that were registered before timestamps were indexed to the timestamp index.
+    /// The folders are read outside of the db writer so it doesn't block writes of new blocks.
+    async fn spawn_timestamp_indexer(
+        db: Arc<DbHandle>,
+        data_path: PathBuf,
+        db_writer: Arc<DbWriter>,
+    ) -> Result<()> {
+        let dir_names = db.parquet_folders_without_timestamp_range().await?;
+
+        if dir_names.is_empty() {
+            return Ok(());
+        }
+
+        log::info!(
+            "adding {} parquet folders to the timestamp index",
+            dir_names.len()
+        );
+
+        tokio::spawn(async move {
+            for dir_name in dir_names {
+                let data_path = data_path.clone();
+                let res = tokio::task::spawn_blocking(move || {
+                    CollectMetadataAndParquetIdx {
+                        data_path: &data_path,
+                        dir_name,
+                    }
+                    .collect_timestamp_range()
+                })
+                .await
+                .unwrap();
+
+                match res {
+                    Ok(Some(timestamp_range)) => {
+                        db_writer
+                            .register_timestamp_range(dir_name, timestamp_range)
+                            .await
+                    }
+                    Ok(None) => (),
+                    Err(e) => log::error!(
+                        "failed to add folder {} to the timestamp index:\n{}",
+                        dir_name,
+                        e
+                    ),
+                }
+            }
+
+            log::info!("finished building the timestamp index");
+        });
+
+        Ok(())
+    }
+
+    /// Adds the hashes of the folders that were registered before hashes were indexed.
+    /// The folders are read outside of the db writer so it doesn't block writes of new blocks.
+    async fn spawn_hash_indexer(
+        db: Arc<DbHandle>,
+        data_path: PathBuf,
+        db_writer: Arc<DbWriter>,
+    ) -> Result<()> {
+        let dir_names = db.parquet_folders_without_hashes().await?;
+
+        if dir_names.is_empty() {
+            return Ok(());
+        }
+
+        log::info!(
+            "adding {} parquet folders to the hash index",
+            dir_names.len()
+        );
+
+        tokio::spawn(async move {
+            for dir_name in dir_names {
+                let data_path = data_path.clone();
+                let res = tokio::task::spawn_blocking(move || {
+                    CollectMetadataAndParquetIdx {
+                        data_path: &data_path,
+                        dir_name,
+                    }
+                    .collect_hashes()
+                })
+                .await
+                .unwrap();
+
+                match res {
+                    Ok(hashes) => db_writer.register_hashes(dir_name, hashes).await,
+                    Err(e) => log::error!(
+                        "failed to add folder {} to the hash index:\n{}",
+                        dir_name,
+                        e
+                    ),
+                }
+            }
+
+            log::info!("finished building the hash index");
+        });
+
+        Ok(())
+    }
+
     async fn parquet_folder_is_valid(data_path: &Path, dir_name: DirName) -> Result<bool> {
         let mut path = data_path.to_owned();
         path.push(dir_name.to_string());
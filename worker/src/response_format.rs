@@ -0,0 +1,774 @@
+use crate::abi::Abi;
+use crate::field_selection::FieldSelection;
+use crate::types::{Order, QueryResult};
+use crate::{Error, Result};
+use arrow2::array::{
+    Array, BinaryArray, BooleanArray, MutableArray, MutableBinaryArray, MutableListArray, TryPush,
+    UInt32Array, UInt32Vec, UInt64Array, Utf8Array,
+};
+use arrow2::chunk::Chunk;
+use arrow2::datatypes::{Field, Metadata, Schema};
+use arrow2::io::ipc::write::{StreamWriter, WriteOptions as IpcWriteOptions};
+use arrow2::io::parquet::write::{transverse, Encoding, FileWriter, RowGroupIterator};
+use eth_archive_core::deserialize::U256;
+use eth_archive_core::hash::HashSet;
+use eth_archive_core::types::{
+    ResponseBlock, ResponseLog, ResponseStateDiff, ResponseTrace, ResponseTransaction,
+    ResponseWithdrawal,
+};
+use eth_archive_ingester::schema::{
+    block_schema, log_schema, parquet_write_options, state_diff_schema, trace_schema, tx_schema,
+    withdrawal_schema, MutableAccessListArray, MutableAuthorizationListArray, MutableU256Array,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// The body is a sequence of length prefixed segments, not a single arrow ipc stream or
+/// parquet file, so the standard media types aren't used.
+pub const ARROW_IPC_CONTENT_TYPE: &str = "application/x-eth-archive-segments+arrow";
+pub const PARQUET_CONTENT_TYPE: &str = "application/x-eth-archive-segments+parquet";
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+
+macro_rules! binary_col {
+    ($items:expr, $field:ident) => {
+        Box::new(
+            $items
+                .iter()
+                .map(|item| item.$field.as_ref().map(|v| v.as_slice()))
+                .collect::<BinaryArray<i32>>(),
+        )
+    };
+}
+
+macro_rules! u32_col {
+    ($items:expr, $field:ident) => {
+        Box::new(UInt32Array::from(
+            $items
+                .iter()
+                .map(|item| item.$field.map(|v| v.0))
+                .collect::<Vec<_>>(),
+        ))
+    };
+}
+
+macro_rules! u64_col {
+    ($items:expr, $field:ident) => {
+        Box::new(UInt64Array::from(
+            $items
+                .iter()
+                .map(|item| item.$field.map(|v| v.0))
+                .collect::<Vec<_>>(),
+        ))
+    };
+}
+
+macro_rules! quantity_col {
+    ($items:expr, $field:ident) => {
+        Box::new(UInt64Array::from(
+            $items
+                .iter()
+                .map(|item| {
+                    item.$field
+                        .as_ref()
+                        .map(|v| v.to_u64().ok_or(Error::QuantityTooWide(stringify!($field))))
+                        .transpose()
+                })
+                .collect::<Result<Vec<_>>>()?,
+        ))
+    };
+}
+
+macro_rules! u256_col {
+    ($items:expr, $field:ident) => {{
+        let mut arr = MutableU256Array::default();
+        for item in $items.iter() {
+            let quantity = item.$field.as_ref().map(|v| {
+                U256::from_quantity(v).ok_or(Error::QuantityWiderThanU256(stringify!($field)))
+            });
+            arr.push(quantity.transpose()?);
+        }
+        arr.as_box()
+    }};
+}
+
+macro_rules! utf8_col {
+    ($items:expr, $field:ident) => {
+        Box::new(
+            $items
+                .iter()
+                .map(|item| item.$field.as_deref())
+                .collect::<Utf8Array<i32>>(),
+        )
+    };
+}
+
+/// Encoding of the /query response body
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ResponseFormat {
+    #[default]
+    Json,
+    ArrowIpc,
+    Parquet,
+}
+
+impl ResponseFormat {
+    /// Picks the first supported media type in the Accept header, defaults to json.
+    pub fn from_accept_header(accept: &str) -> Self {
+        accept
+            .split(',')
+            .filter_map(|media_type| media_type.split(';').next())
+            .find_map(|media_type| match media_type.trim() {
+                ARROW_IPC_CONTENT_TYPE => Some(Self::ArrowIpc),
+                PARQUET_CONTENT_TYPE => Some(Self::Parquet),
+                JSON_CONTENT_TYPE => Some(Self::Json),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Json => JSON_CONTENT_TYPE,
+            Self::ArrowIpc => ARROW_IPC_CONTENT_TYPE,
+            Self::Parquet => PARQUET_CONTENT_TYPE,
+        }
+    }
+}
+
+/// Encodes each non-empty table in the query result as a separate segment.
+///
+/// A segment is a little endian u64 byte length followed by an arrow ipc stream
+/// or a parquet file. The name of the table is in the "table" key of the schema metadata.
+///
+/// Transactions and logs get a "decoded" column if the abi has functions or events.
+pub fn encode_query_result(
+    res: QueryResult,
+    field_selection: FieldSelection,
+    abi: &Abi,
+    format: ResponseFormat,
+    order: Order,
+) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+
+    let blocks = ordered(res.blocks, order);
+    let columns = select_columns(block_schema(), &field_selection.block.as_fields(), |name| {
+        block_column(&blocks, name)
+    })?;
+    write_segment(&mut buf, "block", blocks.len(), columns, format)?;
+
+    let txs = ordered(res.transactions, order);
+    let mut columns = select_columns(
+        tx_schema(),
+        &field_selection.transaction.as_fields(),
+        |name| tx_column(&txs, name),
+    )?;
+    if abi.has_functions() && !columns.is_empty() {
+        columns.push(decoded_column(&txs, |tx| abi.decode_tx(tx)));
+    }
+    write_segment(&mut buf, "transaction", txs.len(), columns, format)?;
+
+    let logs = ordered(res.logs, order);
+    let mut columns = select_columns(log_schema(), &field_selection.log.as_fields(), |name| {
+        log_column(&logs, name)
+    })?;
+    if abi.has_events() && !columns.is_empty() {
+        columns.push(decoded_column(&logs, |log| abi.decode_log(log)));
+    }
+    write_segment(&mut buf, "log", logs.len(), columns, format)?;
+
+    let traces = ordered(res.traces, order);
+    let columns = select_columns(trace_schema(), &field_selection.trace.as_fields(), |name| {
+        trace_column(&traces, name)
+    })?;
+    write_segment(&mut buf, "trace", traces.len(), columns, format)?;
+
+    let state_diffs = ordered(res.state_diffs, order);
+    let columns = select_columns(
+        state_diff_schema(),
+        &field_selection.state_diff.as_fields(),
+        |name| state_diff_column(&state_diffs, name),
+    )?;
+    write_segment(&mut buf, "state_diff", state_diffs.len(), columns, format)?;
+
+    let withdrawals = ordered(res.withdrawals, order);
+    let columns = select_columns(
+        withdrawal_schema(),
+        &field_selection.withdrawal.as_fields(),
+        |name| withdrawal_column(&withdrawals, name),
+    )?;
+    write_segment(&mut buf, "withdrawal", withdrawals.len(), columns, format)?;
+
+    Ok(buf)
+}
+
+/// Encodes the trailing "status" segment that has the same fields as the json trailer.
+pub fn encode_status(
+    archive_height: Option<u64>,
+    next_block: u64,
+    next_block_offset: usize,
+    total_time: u64,
+    format: ResponseFormat,
+) -> Result<Vec<u8>> {
+    let columns: Vec<(&str, Box<dyn Array>)> = vec![
+        (
+            "archive_height",
+            Box::new(UInt64Array::from([archive_height])),
+        ),
+        (
+            "next_block",
+            Box::new(UInt64Array::from([Some(next_block)])),
+        ),
+        (
+            "next_block_offset",
+            Box::new(UInt64Array::from([Some(next_block_offset as u64)])),
+        ),
+        (
+            "total_time",
+            Box::new(UInt64Array::from([Some(total_time)])),
+        ),
+    ];
+
+    let columns = columns
+        .into_iter()
+        .map(|(name, arr)| (Field::new(name, arr.data_type().clone(), true), arr))
+        .collect();
+
+    let mut buf = Vec::new();
+    write_segment(&mut buf, "status", 1, columns, format)?;
+
+    Ok(buf)
+}
+
+/// Builds the selected columns in the order of the parquet schema.
+/// All fields are nullable since selected fields can be missing in the response types.
+fn select_columns<F: Fn(&str) -> Result<Option<Box<dyn Array>>>>(
+    schema: Schema,
+    selected: &HashSet<&'static str>,
+    column: F,
+) -> Result<Vec<(Field, Box<dyn Array>)>> {
+    let mut columns = Vec::new();
+
+    for field in schema
+        .fields
+        .into_iter()
+        .filter(|field| selected.contains(field.name.as_str()))
+    {
+        if let Some(arr) = column(&field.name)? {
+            columns.push((Field::new(field.name, arr.data_type().clone(), true), arr));
+        }
+    }
+
+    Ok(columns)
+}
+
+/// The decoded objects as json strings, null for items that don't belong to any
+/// event or function of the query.
+fn decoded_column<T, F: Fn(&T) -> Option<Value>>(
+    items: &[T],
+    decode: F,
+) -> (Field, Box<dyn Array>) {
+    let arr = items
+        .iter()
+        .map(|item| decode(item).map(|decoded| decoded.to_string()))
+        .collect::<Utf8Array<i32>>();
+
+    (
+        Field::new("decoded", arr.data_type().clone(), true),
+        Box::new(arr),
+    )
+}
+
+/// Collects the values of the map in the given block order.
+fn ordered<K, V>(map: BTreeMap<K, V>, order: Order) -> Vec<V> {
+    match order {
+        Order::Asc => map.into_values().collect(),
+        Order::Desc => map.into_values().rev().collect(),
+    }
+}
+
+fn write_segment(
+    buf: &mut Vec<u8>,
+    table: &str,
+    len: usize,
+    columns: Vec<(Field, Box<dyn Array>)>,
+    format: ResponseFormat,
+) -> Result<()> {
+    if len == 0 || columns.is_empty() {
+        return Ok(());
+    }
+
+    let (fields, arrays): (Vec<_>, Vec<_>) = columns.into_iter().unzip();
+
+    let mut metadata = Metadata::new();
+    metadata.insert("table".to_owned(), table.to_owned());
+    let schema = Schema::from(fields).with_metadata(metadata);
+
+    let chunk = Chunk::new(arrays);
+
+    let bytes = match format {
+        ResponseFormat::ArrowIpc => write_ipc(schema, chunk)?,
+        ResponseFormat::Parquet => write_parquet(schema, chunk)?,
+        ResponseFormat::Json => unreachable!("json responses are written by the serialize task"),
+    };
+
+    buf.extend_from_slice(&u64::try_from(bytes.len()).unwrap().to_le_bytes());
+    buf.extend_from_slice(&bytes);
+
+    Ok(())
+}
+
+fn write_ipc(schema: Schema, chunk: Chunk<Box<dyn Array>>) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+
+    let mut writer = StreamWriter::new(&mut buf, IpcWriteOptions { compression: None });
+    writer.start(&schema, None).map_err(Error::EncodeResponse)?;
+    writer.write(&chunk, None).map_err(Error::EncodeResponse)?;
+    writer.finish().map_err(Error::EncodeResponse)?;
+
+    Ok(buf)
+}
+
+fn write_parquet(schema: Schema, chunk: Chunk<Box<dyn Array>>) -> Result<Vec<u8>> {
+    let encodings = schema
+        .fields
+        .iter()
+        .map(|f| transverse(&f.data_type, |_| Encoding::Plain))
+        .collect();
+
+    let row_groups = RowGroupIterator::try_new(
+        std::iter::once(Ok(chunk)),
+        &schema,
+        parquet_write_options(None),
+        encodings,
+    )
+    .map_err(Error::EncodeResponse)?;
+
+    let mut buf = Vec::new();
+    let mut writer = FileWriter::try_new(&mut buf, schema, parquet_write_options(None))
+        .map_err(Error::EncodeResponse)?;
+
+    for group in row_groups {
+        writer
+            .write(group.map_err(Error::EncodeResponse)?)
+            .map_err(Error::EncodeResponse)?;
+    }
+
+    writer.end(None).map_err(Error::EncodeResponse)?;
+
+    Ok(buf)
+}
+
+fn block_column(blocks: &[ResponseBlock], name: &str) -> Result<Option<Box<dyn Array>>> {
+    let arr: Box<dyn Array> = match name {
+        "parent_hash" => binary_col!(blocks, parent_hash),
+        "sha3_uncles" => binary_col!(blocks, sha3_uncles),
+        "miner" => binary_col!(blocks, miner),
+        "state_root" => binary_col!(blocks, state_root),
+        "transactions_root" => binary_col!(blocks, transactions_root),
+        "receipts_root" => binary_col!(blocks, receipts_root),
+        "logs_bloom" => binary_col!(blocks, logs_bloom),
+        "difficulty" => binary_col!(blocks, difficulty),
+        "number" => u64_col!(blocks, number),
+        "gas_limit" => quantity_col!(blocks, gas_limit),
+        "gas_used" => quantity_col!(blocks, gas_used),
+        "timestamp" => quantity_col!(blocks, timestamp),
+        "extra_data" => binary_col!(blocks, extra_data),
+        "mix_hash" => binary_col!(blocks, mix_hash),
+        "nonce" => u64_col!(blocks, nonce),
+        "total_difficulty" => binary_col!(blocks, total_difficulty),
+        "base_fee_per_gas" => u256_col!(blocks, base_fee_per_gas),
+        "size" => binary_col!(blocks, size),
+        "hash" => binary_col!(blocks, hash),
+        "blob_gas_used" => quantity_col!(blocks, blob_gas_used),
+        "excess_blob_gas" => quantity_col!(blocks, excess_blob_gas),
+        "parent_beacon_block_root" => binary_col!(blocks, parent_beacon_block_root),
+        "withdrawals_root" => binary_col!(blocks, withdrawals_root),
+        _ => return Ok(None),
+    };
+
+    Ok(Some(arr))
+}
+
+fn tx_column(txs: &[ResponseTransaction], name: &str) -> Result<Option<Box<dyn Array>>> {
+    let arr: Box<dyn Array> = match name {
+        "kind" => u32_col!(txs, kind),
+        "nonce" => u64_col!(txs, nonce),
+        "dest" => binary_col!(txs, dest),
+        "gas" => quantity_col!(txs, gas),
+        "value" => u256_col!(txs, value),
+        "input" => binary_col!(txs, input),
+        "max_priority_fee_per_gas" => u256_col!(txs, max_priority_fee_per_gas),
+        "max_fee_per_gas" => u256_col!(txs, max_fee_per_gas),
+        "y_parity" => u32_col!(txs, y_parity),
+        "chain_id" => u64_col!(txs, chain_id),
+        "v" => binary_col!(txs, v),
+        "r" => binary_col!(txs, r),
+        "s" => binary_col!(txs, s),
+        "source" => binary_col!(txs, source),
+        "block_hash" => binary_col!(txs, block_hash),
+        "block_number" => u64_col!(txs, block_number),
+        "transaction_index" => u32_col!(txs, transaction_index),
+        "gas_price" => u256_col!(txs, gas_price),
+        "hash" => binary_col!(txs, hash),
+        "status" => u32_col!(txs, status),
+        "contract_address" => binary_col!(txs, contract_address),
+        "gas_used" => quantity_col!(txs, gas_used),
+        "cumulative_gas_used" => quantity_col!(txs, cumulative_gas_used),
+        "effective_gas_price" => u256_col!(txs, effective_gas_price),
+        "logs_bloom" => binary_col!(txs, logs_bloom),
+        "max_fee_per_blob_gas" => u256_col!(txs, max_fee_per_blob_gas),
+        "blob_versioned_hashes" => {
+            let mut arr = MutableListArray::<i32, MutableBinaryArray<i32>>::new();
+            for tx in txs.iter() {
+                arr.try_push(
+                    tx.blob_versioned_hashes
+                        .as_ref()
+                        .map(|hashes| hashes.iter().map(|hash| Some(hash.as_slice()))),
+                )
+                .unwrap();
+            }
+            arr.as_box()
+        }
+        "access_list" => {
+            let mut arr = MutableAccessListArray::default();
+            for tx in txs.iter() {
+                arr.push(tx.access_list.as_deref());
+            }
+            arr.as_box()
+        }
+        "authorization_list" => {
+            let mut arr = MutableAuthorizationListArray::default();
+            for tx in txs.iter() {
+                arr.push(tx.authorization_list.as_deref());
+            }
+            arr.as_box()
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(arr))
+}
+
+fn log_column(logs: &[ResponseLog], name: &str) -> Result<Option<Box<dyn Array>>> {
+    let topic_col = |i: usize| -> Box<dyn Array> {
+        Box::new(
+            logs.iter()
+                .map(|log| {
+                    log.topics
+                        .as_ref()
+                        .and_then(|topics| topics.get(i))
+                        .map(|v| v.as_slice())
+                })
+                .collect::<BinaryArray<i32>>(),
+        )
+    };
+
+    let arr: Box<dyn Array> = match name {
+        "address" => binary_col!(logs, address),
+        "block_hash" => binary_col!(logs, block_hash),
+        "block_number" => u64_col!(logs, block_number),
+        "data" => binary_col!(logs, data),
+        "log_index" => u32_col!(logs, log_index),
+        "removed" => Box::new(logs.iter().map(|log| log.removed).collect::<BooleanArray>()),
+        "topic0" => topic_col(0),
+        "topic1" => topic_col(1),
+        "topic2" => topic_col(2),
+        "topic3" => topic_col(3),
+        "transaction_hash" => binary_col!(logs, transaction_hash),
+        "transaction_index" => u32_col!(logs, transaction_index),
+        _ => return Ok(None),
+    };
+
+    Ok(Some(arr))
+}
+
+fn trace_column(traces: &[ResponseTrace], name: &str) -> Result<Option<Box<dyn Array>>> {
+    let arr: Box<dyn Array> = match name {
+        "block_number" => u64_col!(traces, block_number),
+        "transaction_index" => u32_col!(traces, transaction_index),
+        "transaction_hash" => binary_col!(traces, transaction_hash),
+        "trace_index" => u32_col!(traces, trace_index),
+        "trace_address" => {
+            let mut arr = MutableListArray::<i32, UInt32Vec>::new();
+            for trace in traces.iter() {
+                arr.try_push(
+                    trace
+                        .trace_address
+                        .as_ref()
+                        .map(|addr| addr.iter().map(|&i| Some(i))),
+                )
+                .unwrap();
+            }
+            arr.as_box()
+        }
+        "subtraces" => u32_col!(traces, subtraces),
+        "call_type" => utf8_col!(traces, call_type),
+        "source" => binary_col!(traces, source),
+        "dest" => binary_col!(traces, dest),
+        "value" => binary_col!(traces, value),
+        "gas" => binary_col!(traces, gas),
+        "gas_used" => binary_col!(traces, gas_used),
+        "input" => binary_col!(traces, input),
+        "output" => binary_col!(traces, output),
+        "error" => utf8_col!(traces, error),
+        _ => return Ok(None),
+    };
+
+    Ok(Some(arr))
+}
+
+fn state_diff_column(
+    state_diffs: &[ResponseStateDiff],
+    name: &str,
+) -> Result<Option<Box<dyn Array>>> {
+    let arr: Box<dyn Array> = match name {
+        "block_number" => u64_col!(state_diffs, block_number),
+        "transaction_index" => u32_col!(state_diffs, transaction_index),
+        "transaction_hash" => binary_col!(state_diffs, transaction_hash),
+        "diff_index" => u32_col!(state_diffs, diff_index),
+        "address" => binary_col!(state_diffs, address),
+        "kind" => utf8_col!(state_diffs, kind),
+        "key" => binary_col!(state_diffs, key),
+        "prev" => binary_col!(state_diffs, prev),
+        "next" => binary_col!(state_diffs, next),
+        _ => return Ok(None),
+    };
+
+    Ok(Some(arr))
+}
+
+fn withdrawal_column(
+    withdrawals: &[ResponseWithdrawal],
+    name: &str,
+) -> Result<Option<Box<dyn Array>>> {
+    let arr: Box<dyn Array> = match name {
+        "block_number" => u64_col!(withdrawals, block_number),
+        "index" => u32_col!(withdrawals, index),
+        "validator_index" => u32_col!(withdrawals, validator_index),
+        "address" => binary_col!(withdrawals, address),
+        "amount" => quantity_col!(withdrawals, amount),
+        _ => return Ok(None),
+    };
+
+    Ok(Some(arr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abi::Function;
+    use arrow2::array::FixedSizeBinaryArray;
+    use arrow2::io::ipc::read::{read_stream_metadata, StreamReader, StreamState};
+    use arrow2::io::parquet::read::{infer_schema, read_metadata, FileReader};
+    use eth_archive_core::deserialize::{BlockNumber, Bytes, Bytes32, Index, U256};
+    use std::io::Cursor;
+    use std::iter;
+
+    fn query_result() -> QueryResult {
+        let mut res = QueryResult::default();
+
+        for num in [1, 2] {
+            res.blocks.insert(
+                num,
+                ResponseBlock {
+                    number: Some(BlockNumber(num)),
+                    hash: Some(Bytes32::new(&[num as u8; 32])),
+                    ..Default::default()
+                },
+            );
+            res.transactions.insert(
+                (num, 0),
+                ResponseTransaction {
+                    block_number: Some(BlockNumber(num)),
+                    transaction_index: Some(Index(0)),
+                    value: Some(Bytes::from_quantity(num * 1000)),
+                    input: (num == 1).then(|| transfer_input().into()),
+                    ..Default::default()
+                },
+            );
+        }
+
+        res
+    }
+
+    fn transfer() -> Function {
+        Function::parse("transfer(address to,uint256 amount)").unwrap()
+    }
+
+    fn transfer_input() -> Vec<u8> {
+        let mut input = transfer().sighash().to_vec();
+        input.extend_from_slice(&[0; 12]);
+        input.extend_from_slice(&[3; 20]);
+        input.extend_from_slice(&[0; 31]);
+        input.push(7);
+        input
+    }
+
+    fn field_selection() -> FieldSelection {
+        let mut field_selection = FieldSelection::default();
+        field_selection.block.number = true;
+        field_selection.block.hash = true;
+        field_selection.transaction.block_number = true;
+        field_selection.transaction.value = true;
+        field_selection
+    }
+
+    fn split_segments(mut buf: &[u8]) -> Vec<&[u8]> {
+        let mut segments = Vec::new();
+
+        while !buf.is_empty() {
+            let len = u64::from_le_bytes(buf[..8].try_into().unwrap()) as usize;
+            segments.push(&buf[8..8 + len]);
+            buf = &buf[8 + len..];
+        }
+
+        segments
+    }
+
+    fn read_ipc(segment: &[u8]) -> (Schema, Vec<Chunk<Box<dyn Array>>>) {
+        let mut reader = Cursor::new(segment);
+        let metadata = read_stream_metadata(&mut reader).unwrap();
+        let schema = metadata.schema.clone();
+
+        let chunks = StreamReader::new(reader, metadata, None)
+            .map(|state| match state.unwrap() {
+                StreamState::Some(chunk) => chunk,
+                StreamState::Waiting => panic!("stream is incomplete"),
+            })
+            .collect();
+
+        (schema, chunks)
+    }
+
+    fn read_parquet(segment: &[u8]) -> (Schema, Vec<Chunk<Box<dyn Array>>>) {
+        let mut reader = Cursor::new(segment);
+        let metadata = read_metadata(&mut reader).unwrap();
+        let schema = infer_schema(&metadata).unwrap();
+
+        let chunks = FileReader::new(
+            reader,
+            metadata.row_groups,
+            schema.clone(),
+            None,
+            None,
+            None,
+        )
+        .map(|chunk| chunk.unwrap())
+        .collect();
+
+        (schema, chunks)
+    }
+
+    fn column<'a, T: 'static>(
+        schema: &Schema,
+        chunk: &'a Chunk<Box<dyn Array>>,
+        name: &str,
+    ) -> &'a T {
+        let idx = schema.fields.iter().position(|f| f.name == name).unwrap();
+        chunk.arrays()[idx].as_any().downcast_ref::<T>().unwrap()
+    }
+
+    fn check_round_trip(format: ResponseFormat) {
+        let abi = Abi::new(iter::empty(), [&transfer()].into_iter());
+        let mut buf =
+            encode_query_result(query_result(), field_selection(), &abi, format, Order::Desc)
+                .unwrap();
+        buf.extend(encode_status(Some(10), 3, 0, 5, format).unwrap());
+
+        let read = match format {
+            ResponseFormat::ArrowIpc => read_ipc,
+            ResponseFormat::Parquet => read_parquet,
+            ResponseFormat::Json => unreachable!(),
+        };
+
+        let tables = split_segments(&buf)
+            .into_iter()
+            .map(read)
+            .collect::<Vec<_>>();
+
+        let names = tables
+            .iter()
+            .map(|(schema, _)| schema.metadata.get("table").unwrap().as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["block", "transaction", "status"]);
+
+        let (schema, chunks) = &tables[0];
+        assert_eq!(chunks.len(), 1);
+        let numbers = column::<UInt64Array>(schema, &chunks[0], "number");
+        assert_eq!(
+            numbers.values_iter().copied().collect::<Vec<_>>(),
+            vec![2, 1]
+        );
+        let hashes = column::<BinaryArray<i32>>(schema, &chunks[0], "hash");
+        assert_eq!(hashes.value(0), &[2; 32]);
+
+        let (schema, chunks) = &tables[1];
+        let values = column::<FixedSizeBinaryArray>(schema, &chunks[0], "value");
+        assert_eq!(
+            values.value(0),
+            U256::from_quantity(&Bytes::from_quantity(2000)).unwrap().0
+        );
+        assert_eq!(
+            values.value(1),
+            U256::from_quantity(&Bytes::from_quantity(1000)).unwrap().0
+        );
+        let decoded = column::<Utf8Array<i32>>(schema, &chunks[0], "decoded");
+        assert!(decoded.is_null(0));
+        let decoded: Value = serde_json::from_str(decoded.value(1)).unwrap();
+        assert_eq!(decoded["name"], "transfer");
+        assert_eq!(decoded["params"][1]["value"], "7");
+
+        let (schema, chunks) = &tables[2];
+        let next_block = column::<UInt64Array>(schema, &chunks[0], "next_block");
+        assert_eq!(next_block.value(0), 3);
+        let archive_height = column::<UInt64Array>(schema, &chunks[0], "archive_height");
+        assert_eq!(archive_height.value(0), 10);
+    }
+
+    #[test]
+    fn test_arrow_ipc_round_trip() {
+        check_round_trip(ResponseFormat::ArrowIpc);
+    }
+
+    #[test]
+    fn test_parquet_round_trip() {
+        check_round_trip(ResponseFormat::Parquet);
+    }
+
+    #[test]
+    fn test_quantity_wider_than_u64() {
+        let mut res = query_result();
+        res.transactions.get_mut(&(1, 0)).unwrap().gas_used =
+            Some(Bytes::from_be_quantity(&[1; 9]));
+
+        let mut field_selection = field_selection();
+        field_selection.transaction.gas_used = true;
+
+        let abi = Abi::default();
+        let err = encode_query_result(
+            res,
+            field_selection,
+            &abi,
+            ResponseFormat::ArrowIpc,
+            Order::Asc,
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::QuantityTooWide("gas_used")));
+    }
+
+    #[test]
+    fn test_from_accept_header() {
+        assert_eq!(
+            ResponseFormat::from_accept_header(
+                "text/html, application/x-eth-archive-segments+parquet;q=0.9"
+            ),
+            ResponseFormat::Parquet
+        );
+        assert_eq!(
+            ResponseFormat::from_accept_header("application/vnd.apache.arrow.stream"),
+            ResponseFormat::Json
+        );
+    }
+}
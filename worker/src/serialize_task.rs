@@ -1,110 +1,386 @@
+use crate::abi::Abi;
+use crate::aggregate::Aggregator;
 use crate::field_selection::FieldSelection;
-use crate::types::QueryResult;
+use crate::response_format::{encode_query_result, encode_status, ResponseFormat};
+use crate::types::{Order, QueryResult};
 use crate::{Error, Result};
-use eth_archive_core::types::{BlockRange, ResponseBlock, ResponseLog, ResponseTransaction};
+use eth_archive_core::types::{
+    BlockRange, ResponseBlock, ResponseLog, ResponseStateDiff, ResponseTrace, ResponseTransaction,
+    ResponseWithdrawal,
+};
+use hyper::body::{Body, Sender as BodySender};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
-use std::io::Write;
 use std::mem;
-use std::time::Instant;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 
-type Sender = mpsc::Sender<(QueryResult, BlockRange)>;
+type Sender = mpsc::Sender<Result<Msg>>;
+
+enum Msg {
+    Data((QueryResult, BlockRange)),
+    Rollback(u64),
+}
 
 pub struct SerializeTask {
     tx: Sender,
-    join_handle: tokio::task::JoinHandle<Vec<u8>>,
+    join_handle: tokio::task::JoinHandle<()>,
 }
 
 impl SerializeTask {
+    /// Spawns the serialization task and returns it along with the response body
+    /// that the serialized chunks are streamed into.
+    ///
+    /// `next_block` is reported if no data is sent, it is `from_block` for ascending
+    /// and `to_block` for descending queries.
+    ///
+    /// The first `block_offset` items of the first block of the query are skipped since they
+    /// were returned by the previous page.
+    ///
+    /// Logs and transactions are decoded with `abi`.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        from_block: u32,
+        mut next_block: u64,
         size_limit: usize,
         time_limit: u128,
-        archive_height: Option<u32>,
+        archive_height: Option<u64>,
         field_selection: FieldSelection,
-    ) -> Self {
+        abi: Arc<Abi>,
+        format: ResponseFormat,
+        limit: Option<NonZeroUsize>,
+        block_offset: usize,
+        order: Order,
+    ) -> (Self, Body) {
         let (tx, mut rx): (Sender, _) = mpsc::channel(1);
+        let (mut body_tx, body) = Body::channel();
 
         // convert size limit to bytes from megabytes
         let size_limit = size_limit * 1_000_000;
 
+        let skip = match (block_offset, order) {
+            (0, _) => None,
+            (_, Order::Asc) => Some((next_block, block_offset)),
+            (_, Order::Desc) => next_block.checked_sub(1).map(|b| (b, block_offset)),
+        };
+
         let join_handle = tokio::spawn(async move {
             let query_start = Instant::now();
 
-            let mut bytes = br#"{"data":["#.to_vec();
+            if format == ResponseFormat::Json
+                && !send_bytes(&mut body_tx, br#"{"data":["#.to_vec()).await
+            {
+                return;
+            }
 
             let mut is_first = true;
 
-            let mut next_block = from_block;
+            let mut num_bytes = 0;
 
-            while let Some((res, range)) = rx.recv().await {
-                next_block = range.to;
+            let mut num_items = 0;
+
+            let mut next_block_offset = 0;
+
+            while let Some(msg) = rx.recv().await {
+                let (mut res, range) = match msg {
+                    Ok(Msg::Data(msg)) => msg,
+                    // rollbacks are only sent to subscriptions
+                    Ok(Msg::Rollback(_)) => continue,
+                    Err(e) => {
+                        log::error!("failed to run query:\n{}", e);
+                        body_tx.abort();
+                        return;
+                    }
+                };
+
+                next_block = match order {
+                    Order::Asc => range.to,
+                    Order::Desc => range.from,
+                };
 
                 if query_start.elapsed().as_millis() >= time_limit {
                     break;
                 }
 
+                let mut limit_reached = false;
+
+                if limit.is_some() || skip.is_some() {
+                    let limit = limit.map_or(usize::MAX, NonZeroUsize::get);
+
+                    let (kept, cut_at) =
+                        res.truncate(limit - num_items, skip, order, field_selection);
+
+                    num_items += kept;
+
+                    // continue from the first item that was cut off
+                    if let Some((block_num, offset)) = cut_at {
+                        next_block = match order {
+                            Order::Asc => block_num,
+                            Order::Desc => block_num + 1,
+                        };
+                        next_block_offset = offset;
+                    }
+
+                    limit_reached = cut_at.is_some() || num_items >= limit;
+                }
+
                 if res.is_empty() {
+                    if limit_reached {
+                        break;
+                    }
+
                     continue;
                 }
 
-                bytes = tokio::task::spawn_blocking(move || {
-                    process_query_result(bytes, res, is_first, field_selection)
+                let abi = abi.clone();
+                let bytes = tokio::task::spawn_blocking(move || match format {
+                    ResponseFormat::Json => Ok(process_query_result(
+                        res,
+                        is_first,
+                        field_selection,
+                        &abi,
+                        order,
+                    )),
+                    _ => encode_query_result(res, field_selection, &abi, format, order),
                 })
                 .await
                 .unwrap();
 
+                let bytes = match bytes {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        log::error!("failed to encode query result:\n{}", e);
+                        body_tx.abort();
+                        return;
+                    }
+                };
+
                 is_first = false;
 
-                if bytes.len() >= size_limit {
+                num_bytes += bytes.len();
+
+                if !send_bytes(&mut body_tx, bytes).await {
+                    return;
+                }
+
+                if num_bytes >= size_limit || limit_reached {
                     break;
                 }
             }
 
-            let archive_height = match archive_height {
-                Some(archive_height) => archive_height.to_string(),
-                None => "null".to_owned(),
+            let total_time = query_start.elapsed().as_millis();
+
+            let trailer = match format {
+                ResponseFormat::Json => {
+                    let archive_height = match archive_height {
+                        Some(archive_height) => archive_height.to_string(),
+                        None => "null".to_owned(),
+                    };
+
+                    format!(
+                        r#"],"archiveHeight":{},"nextBlock":{},"nextBlockOffset":{},"totalTime":{}}}"#,
+                        archive_height, next_block, next_block_offset, total_time,
+                    )
+                    .into_bytes()
+                }
+                _ => match encode_status(
+                    archive_height,
+                    next_block,
+                    next_block_offset,
+                    total_time as u64,
+                    format,
+                ) {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        log::error!("failed to encode query status:\n{}", e);
+                        body_tx.abort();
+                        return;
+                    }
+                },
             };
 
-            write!(
-                &mut bytes,
-                r#"],"archiveHeight":{},"nextBlock":{},"totalTime":{}}}"#,
-                archive_height,
-                next_block,
-                query_start.elapsed().as_millis(),
-            )
-            .unwrap();
+            send_bytes(&mut body_tx, trailer).await;
+        });
 
-            bytes
+        (Self { tx, join_handle }, body)
+    }
+
+    /// Spawns a serialization task that streams each query result as a server-sent event.
+    ///
+    /// Matched data is sent as `data` events and rollbacks of the hot data are sent as
+    /// `rollback` events. There are no size or time limits since the subscription
+    /// is supposed to run until the client disconnects.
+    ///
+    /// A comment is sent every `keep_alive` so a disconnected client is noticed
+    /// even if no new blocks arrive.
+    pub fn new_subscription(
+        field_selection: FieldSelection,
+        abi: Arc<Abi>,
+        keep_alive: Duration,
+    ) -> (Self, Body) {
+        let (tx, mut rx): (Sender, _) = mpsc::channel(1);
+        let (mut body_tx, body) = Body::channel();
+
+        let join_handle = tokio::spawn(async move {
+            let mut keep_alive =
+                tokio::time::interval_at(tokio::time::Instant::now() + keep_alive, keep_alive);
+            keep_alive.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            loop {
+                let msg = tokio::select! {
+                    msg = rx.recv() => match msg {
+                        Some(msg) => msg,
+                        None => return,
+                    },
+                    _ = keep_alive.tick() => {
+                        if !send_bytes(&mut body_tx, b":\n\n".to_vec()).await {
+                            return;
+                        }
+
+                        continue;
+                    }
+                };
+
+                let event = match msg {
+                    Ok(Msg::Data((res, range))) => {
+                        if res.is_empty() {
+                            continue;
+                        }
+
+                        let abi = abi.clone();
+                        let data = tokio::task::spawn_blocking(move || {
+                            process_query_result(res, true, field_selection, &abi, Order::Asc)
+                        })
+                        .await
+                        .unwrap();
+
+                        let mut event = b"event: data\ndata: {\"data\":".to_vec();
+                        event.extend_from_slice(&data);
+                        event.extend_from_slice(
+                            format!(",\"nextBlock\":{}}}\n\n", range.to).as_bytes(),
+                        );
+
+                        event
+                    }
+                    Ok(Msg::Rollback(from)) => {
+                        format!("event: rollback\ndata: {{\"fromBlock\":{}}}\n\n", from)
+                            .into_bytes()
+                    }
+                    Err(e) => {
+                        log::error!("failed to run subscription:\n{}", e);
+                        body_tx.abort();
+                        return;
+                    }
+                };
+
+                if !send_bytes(&mut body_tx, event).await {
+                    return;
+                }
+            }
         });
 
-        Self { tx, join_handle }
+        (Self { tx, join_handle }, body)
     }
 
-    pub async fn join(self) -> Result<Vec<u8>> {
+    /// Spawns a task that folds the query results into the aggregator and sends the groups
+    /// as a single JSON response when the query is done or the time limit is reached.
+    pub fn new_aggregation(
+        mut next_block: u64,
+        time_limit: u128,
+        archive_height: Option<u64>,
+        mut aggregator: Aggregator,
+    ) -> (Self, Body) {
+        let (tx, mut rx): (Sender, _) = mpsc::channel(1);
+        let (mut body_tx, body) = Body::channel();
+
+        let join_handle = tokio::spawn(async move {
+            let query_start = Instant::now();
+
+            while let Some(msg) = rx.recv().await {
+                let (res, range) = match msg {
+                    Ok(Msg::Data(msg)) => msg,
+                    Ok(Msg::Rollback(_)) => continue,
+                    Err(e) => {
+                        log::error!("failed to run aggregation:\n{}", e);
+                        body_tx.abort();
+                        return;
+                    }
+                };
+
+                // the range isn't aggregated so the client continues from its start
+                if query_start.elapsed().as_millis() >= time_limit {
+                    break;
+                }
+
+                next_block = range.to;
+
+                aggregator = tokio::task::spawn_blocking(move || {
+                    aggregator.push(res);
+                    aggregator
+                })
+                .await
+                .unwrap();
+            }
+
+            let total_time = query_start.elapsed().as_millis();
+
+            let bytes = aggregator.finish(archive_height, next_block, total_time);
+
+            send_bytes(&mut body_tx, bytes).await;
+        });
+
+        (Self { tx, join_handle }, body)
+    }
+
+    pub async fn join(self) -> Result<()> {
         mem::drop(self.tx);
 
-        let res = self.join_handle.await.map_err(Error::TaskJoinError);
-        res
+        self.join_handle.await.map_err(Error::TaskJoinError)
+    }
+
+    /// Aborts the response body so the client doesn't mistake
+    /// the partial response for a successful one.
+    pub async fn fail(self, e: Error) -> Result<()> {
+        self.tx.send(Err(e)).await.ok();
+
+        self.join().await
     }
 
     pub async fn send(&self, msg: (QueryResult, BlockRange)) -> bool {
-        self.tx.send(msg).await.is_ok()
+        self.tx.send(Ok(Msg::Data(msg))).await.is_ok()
+    }
+
+    /// Notifies the client that the data starting from the given block number
+    /// was rolled back because of a chain reorganization.
+    pub async fn send_rollback(&self, from: u64) -> bool {
+        self.tx.send(Ok(Msg::Rollback(from))).await.is_ok()
     }
 
     pub fn is_closed(&self) -> bool {
         self.tx.is_closed()
     }
+
+    /// Completes when the serialization task is gone, which happens when the client
+    /// disconnects.
+    pub async fn closed(&self) {
+        self.tx.closed().await
+    }
+}
+
+/// Returns false if the client is gone.
+async fn send_bytes(body_tx: &mut BodySender, bytes: Vec<u8>) -> bool {
+    body_tx.send_data(bytes.into()).await.is_ok()
 }
 
 fn process_query_result(
-    mut bytes: Vec<u8>,
     res: QueryResult,
     is_first: bool,
     field_selection: FieldSelection,
+    abi: &Abi,
+    order: Order,
 ) -> Vec<u8> {
-    let mut data: BTreeMap<u32, BlockEntry> = BTreeMap::new();
+    let mut data: BTreeMap<u64, BlockEntry> = BTreeMap::new();
 
     for (block_num, block) in res.blocks.into_iter() {
         let block = if field_selection.block == Default::default() {
@@ -119,6 +395,9 @@ fn process_query_result(
                 block,
                 transactions: BTreeMap::new(),
                 logs: BTreeMap::new(),
+                traces: BTreeMap::new(),
+                state_diffs: BTreeMap::new(),
+                withdrawals: BTreeMap::new(),
             },
         );
     }
@@ -128,10 +407,17 @@ fn process_query_result(
             continue;
         }
 
+        let decoded = abi.decode_tx(&transaction);
         let transaction = field_selection.transaction.prune_opt(transaction);
 
         let entry = data.get_mut(&block_num).unwrap();
-        entry.transactions.insert(transaction_index, transaction);
+        entry.transactions.insert(
+            transaction_index,
+            WithDecoded {
+                item: transaction,
+                decoded,
+            },
+        );
     }
 
     for ((block_num, log_index), log) in res.logs.into_iter() {
@@ -139,17 +425,66 @@ fn process_query_result(
             continue;
         }
 
+        let decoded = abi.decode_log(&log);
         let log = field_selection.log.prune_opt(log);
 
         let entry = data.get_mut(&block_num).unwrap();
-        entry.logs.insert(log_index, log);
+        entry
+            .logs
+            .insert(log_index, WithDecoded { item: log, decoded });
     }
 
-    let data = data
+    for ((block_num, trace_index), trace) in res.traces.into_iter() {
+        if field_selection.trace == Default::default() {
+            continue;
+        }
+
+        let trace = field_selection.trace.prune_opt(trace);
+
+        let entry = data.get_mut(&block_num).unwrap();
+        entry.traces.insert(trace_index, trace);
+    }
+
+    for ((block_num, diff_index), state_diff) in res.state_diffs.into_iter() {
+        if field_selection.state_diff == Default::default() {
+            continue;
+        }
+
+        let state_diff = field_selection.state_diff.prune_opt(state_diff);
+
+        let entry = data.get_mut(&block_num).unwrap();
+        entry.state_diffs.insert(diff_index, state_diff);
+    }
+
+    for ((block_num, index), withdrawal) in res.withdrawals.into_iter() {
+        if field_selection.withdrawal == Default::default() {
+            continue;
+        }
+
+        let withdrawal = field_selection.withdrawal.prune_opt(withdrawal);
+
+        let entry = data.get_mut(&block_num).unwrap();
+        entry.withdrawals.insert(index, withdrawal);
+    }
+
+    let mut data = data
         .into_values()
         .map(BlockEntryVec::from)
         .collect::<Vec<BlockEntryVec>>();
 
+    if order == Order::Desc {
+        data.reverse();
+        for entry in data.iter_mut() {
+            entry.transactions.reverse();
+            entry.logs.reverse();
+            entry.traces.reverse();
+            entry.state_diffs.reverse();
+            entry.withdrawals.reverse();
+        }
+    }
+
+    let mut bytes = Vec::new();
+
     if !is_first {
         bytes.push(b',');
     }
@@ -161,8 +496,11 @@ fn process_query_result(
 
 pub struct BlockEntry {
     pub block: Option<ResponseBlock>,
-    pub transactions: BTreeMap<u32, ResponseTransaction>,
-    pub logs: BTreeMap<u32, ResponseLog>,
+    pub transactions: BTreeMap<u32, WithDecoded<ResponseTransaction>>,
+    pub logs: BTreeMap<u32, WithDecoded<ResponseLog>>,
+    pub traces: BTreeMap<u32, ResponseTrace>,
+    pub state_diffs: BTreeMap<u32, ResponseStateDiff>,
+    pub withdrawals: BTreeMap<u32, ResponseWithdrawal>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -170,8 +508,11 @@ pub struct BlockEntry {
 pub struct BlockEntryVec {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub block: Option<ResponseBlock>,
-    pub transactions: Vec<ResponseTransaction>,
-    pub logs: Vec<ResponseLog>,
+    pub transactions: Vec<WithDecoded<ResponseTransaction>>,
+    pub logs: Vec<WithDecoded<ResponseLog>>,
+    pub traces: Vec<ResponseTrace>,
+    pub state_diffs: Vec<ResponseStateDiff>,
+    pub withdrawals: Vec<ResponseWithdrawal>,
 }
 
 impl From<BlockEntry> for BlockEntryVec {
@@ -180,6 +521,164 @@ impl From<BlockEntry> for BlockEntryVec {
             block: entry.block,
             transactions: entry.transactions.into_values().collect(),
             logs: entry.logs.into_values().collect(),
+            traces: entry.traces.into_values().collect(),
+            state_diffs: entry.state_diffs.into_values().collect(),
+            withdrawals: entry.withdrawals.into_values().collect(),
         }
     }
 }
+
+/// Item along with the result of decoding it with the abi of the query.
+/// `decoded` is left out if the item doesn't belong to any event or function in the query
+/// and it is an object with an `error` field if decoding failed.
+#[derive(Serialize, Deserialize)]
+pub struct WithDecoded<T> {
+    #[serde(flatten)]
+    pub item: T,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoded: Option<serde_json::Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eth_archive_core::deserialize::BlockNumber;
+    use hyper::body::HttpBody;
+    use serde_json::Value;
+
+    fn block_result(num: u64) -> (QueryResult, BlockRange) {
+        let mut res = QueryResult::default();
+        res.blocks.insert(
+            num,
+            ResponseBlock {
+                number: Some(BlockNumber(num)),
+                ..Default::default()
+            },
+        );
+
+        (
+            res,
+            BlockRange {
+                from: num,
+                to: num + 1,
+            },
+        )
+    }
+
+    fn json_task() -> (SerializeTask, Body) {
+        let mut field_selection = FieldSelection::default();
+        field_selection.block.number = true;
+
+        SerializeTask::new(
+            1,
+            30,
+            u128::MAX,
+            Some(10),
+            field_selection,
+            Arc::default(),
+            ResponseFormat::Json,
+            None,
+            0,
+            Order::Asc,
+        )
+    }
+
+    async fn next_chunk(body: &mut Body) -> Vec<u8> {
+        body.data().await.unwrap().unwrap().to_vec()
+    }
+
+    #[tokio::test]
+    async fn test_stream_json_chunks() {
+        let (task, mut body) = json_task();
+
+        assert_eq!(next_chunk(&mut body).await, br#"{"data":["#);
+
+        // each result is on the wire before the query is done
+        assert!(task.send(block_result(1)).await);
+        let chunk = next_chunk(&mut body).await;
+        let data: Value = serde_json::from_slice(&chunk).unwrap();
+        assert_eq!(data[0]["block"]["number"], 1);
+
+        assert!(task.send(block_result(2)).await);
+        let chunk = next_chunk(&mut body).await;
+        assert_eq!(chunk[0], b',');
+        let data: Value = serde_json::from_slice(&chunk[1..]).unwrap();
+        assert_eq!(data[0]["block"]["number"], 2);
+
+        let join = tokio::spawn(task.join());
+
+        let mut trailer = br#"{"data":[[],[]"#.to_vec();
+        trailer.extend(next_chunk(&mut body).await);
+        let trailer: Value = serde_json::from_slice(&trailer).unwrap();
+        assert_eq!(trailer["archiveHeight"], 10);
+        assert_eq!(trailer["nextBlock"], 3);
+        assert_eq!(trailer["nextBlockOffset"], 0);
+        assert!(trailer["totalTime"].is_u64());
+
+        join.await.unwrap().unwrap();
+        assert!(body.data().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_failed_query_aborts_body() {
+        let (task, mut body) = json_task();
+
+        assert_eq!(next_chunk(&mut body).await, br#"{"data":["#);
+
+        assert!(task.send(block_result(1)).await);
+        next_chunk(&mut body).await;
+
+        let join = tokio::spawn(task.fail(Error::NoBlocks));
+
+        // the trailer isn't written so the partial response can't pass as a complete one
+        assert!(body.data().await.unwrap().is_err());
+
+        join.await.unwrap().unwrap();
+    }
+
+    fn subscription_task(keep_alive: Duration) -> (SerializeTask, Body) {
+        let mut field_selection = FieldSelection::default();
+        field_selection.block.number = true;
+
+        SerializeTask::new_subscription(field_selection, Arc::default(), keep_alive)
+    }
+
+    #[tokio::test]
+    async fn test_subscription_events() {
+        let (task, mut body) = subscription_task(Duration::from_secs(3600));
+
+        assert!(task.send(block_result(1)).await);
+        let chunk = String::from_utf8(next_chunk(&mut body).await).unwrap();
+        let data = chunk
+            .strip_prefix("event: data\ndata: ")
+            .unwrap()
+            .strip_suffix("\n\n")
+            .unwrap();
+        let data: Value = serde_json::from_str(data).unwrap();
+        assert_eq!(data["data"][0]["block"]["number"], 1);
+        assert_eq!(data["nextBlock"], 2);
+
+        assert!(task.send_rollback(1).await);
+        assert_eq!(
+            next_chunk(&mut body).await,
+            b"event: rollback\ndata: {\"fromBlock\":1}\n\n"
+        );
+
+        task.join().await.unwrap();
+        assert!(body.data().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_subscription_keep_alive() {
+        let (task, mut body) = subscription_task(Duration::from_millis(10));
+
+        assert_eq!(next_chunk(&mut body).await, b":\n\n");
+
+        // the failed keep-alive tells the subscription that the client is gone
+        drop(body);
+        tokio::time::timeout(Duration::from_secs(5), task.closed())
+            .await
+            .unwrap();
+        assert!(!task.send_rollback(1).await);
+    }
+}
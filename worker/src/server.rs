@@ -1,12 +1,21 @@
+use crate::aggregate::AggregateQuery;
 use crate::config::Config;
 use crate::data_ctx::DataCtx;
 use crate::error::{Error, Result};
+use crate::field_selection::{BlockFieldSelection, TransactionFieldSelection};
+use crate::response_format::ResponseFormat;
 use crate::types::Query;
+use eth_archive_core::deserialize::Bytes32;
 use eth_archive_core::ingest_metrics::IngestMetrics;
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{header, Body, Method, Request, Response, Server as HttpServer, StatusCode};
 use std::convert::Infallible;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Time the running requests get to finish when the worker is stopped
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
 
 pub struct Server {}
 
@@ -23,7 +32,7 @@ impl Server {
 
         let server_addr = config.server_addr;
 
-        let data_ctx = DataCtx::new(config, ingest_metrics.clone()).await?;
+        let (data_ctx, downloader) = DataCtx::new(config, ingest_metrics.clone()).await?;
         let data_ctx = Arc::new(data_ctx);
 
         let app_data = AppData {
@@ -36,12 +45,38 @@ impl Server {
             async move { Ok::<_, Infallible>(service_fn(move |req| handler(app_data.clone(), req))) }
         });
 
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+
         let server = HttpServer::bind(&server_addr)
             .http1_preserve_header_case(true)
             .http1_title_case_headers(true)
-            .serve(make_service);
+            .serve(make_service)
+            .with_graceful_shutdown(async move {
+                shutdown_rx.await.ok();
+            });
+        tokio::pin!(server);
 
-        server.await.map_err(Error::RunHttpServer)
+        let downloader_res = tokio::select! {
+            res = &mut server => return res.map_err(Error::RunHttpServer),
+            res = downloader => res,
+        };
+
+        let err = match downloader_res {
+            Ok(Ok(())) => return server.await.map_err(Error::RunHttpServer),
+            Ok(Err(e)) => e,
+            Err(e) => Error::TaskJoinError(e),
+        };
+
+        // let the running requests finish, with a timeout since subscriptions don't end on their own
+        shutdown_tx.send(()).ok();
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, server)
+            .await
+            .is_err()
+        {
+            log::warn!("closing the connections that didn't finish before shutdown");
+        }
+
+        Err(err)
     }
 }
 
@@ -49,7 +84,14 @@ async fn handler(app_data: AppData, req: Request<Body>) -> hyper::Result<Respons
     let res = match (req.method(), req.uri().path()) {
         (&Method::GET, "/ingest-metrics") => metrics_handler(app_data).await,
         (&Method::POST, "/query") => query_handler(app_data, req).await,
+        (&Method::POST, "/subscribe") => subscribe_handler(app_data, req).await,
+        (&Method::POST, "/aggregate") => aggregate_handler(app_data, req).await,
         (&Method::GET, "/height") => height_handler(app_data).await,
+        (&Method::GET, "/block-by-timestamp") => block_by_timestamp_handler(app_data, req).await,
+        (&Method::GET, path) if path.starts_with("/tx/") => tx_by_hash_handler(app_data, req).await,
+        (&Method::GET, path) if path.starts_with("/block/") => {
+            block_by_hash_handler(app_data, req).await
+        }
         _ => Ok(Response::builder()
             .status(StatusCode::NOT_FOUND)
             .body(Body::empty())
@@ -95,7 +137,141 @@ async fn height_handler(app_data: AppData) -> Result<Response<Body>> {
         .unwrap())
 }
 
+async fn block_by_timestamp_handler(
+    app_data: AppData,
+    req: Request<Body>,
+) -> Result<Response<Body>> {
+    let timestamp = req
+        .uri()
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|param| param.strip_prefix("timestamp="))
+        .and_then(|timestamp| timestamp.parse::<u64>().ok())
+        .ok_or(Error::InvalidTimestamp)?;
+
+    let number = app_data.data_ctx.block_by_timestamp(timestamp).await?;
+
+    let json = serde_json::json!({ "number": number });
+
+    let json = serde_json::to_string(&json).unwrap();
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(json))
+        .unwrap())
+}
+
+async fn tx_by_hash_handler(app_data: AppData, req: Request<Body>) -> Result<Response<Body>> {
+    let hash = hash_from_path(req.uri().path(), "/tx/")?;
+    let field_selection: TransactionFieldSelection = field_selection_from_query(req.uri().query())?;
+
+    let transaction = app_data
+        .data_ctx
+        .transaction_by_hash(hash, field_selection)
+        .await?;
+
+    match transaction {
+        Some(transaction) => json_response(&serde_json::json!({ "transaction": transaction })),
+        None => Ok(not_found()),
+    }
+}
+
+async fn block_by_hash_handler(app_data: AppData, req: Request<Body>) -> Result<Response<Body>> {
+    let hash = hash_from_path(req.uri().path(), "/block/")?;
+    let field_selection: BlockFieldSelection = field_selection_from_query(req.uri().query())?;
+
+    let block = app_data
+        .data_ctx
+        .block_by_hash(hash, field_selection)
+        .await?;
+
+    match block {
+        Some(block) => json_response(&serde_json::json!({ "block": block })),
+        None => Ok(not_found()),
+    }
+}
+
+fn hash_from_path(path: &str, prefix: &str) -> Result<Bytes32> {
+    let hash = path.strip_prefix(prefix).ok_or(Error::InvalidHash)?;
+
+    serde_json::from_value(serde_json::Value::String(hash.to_owned()))
+        .map_err(|_| Error::InvalidHash)
+}
+
+/// Parses a comma separated list of camelCase field names from the `fields` parameter.
+/// All fields are selected if the parameter is missing.
+fn field_selection_from_query<T>(query: Option<&str>) -> Result<T>
+where
+    T: serde::de::DeserializeOwned + Default + std::ops::Not<Output = T>,
+{
+    let fields = match query
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|param| param.strip_prefix("fields="))
+    {
+        Some(fields) => fields,
+        None => return Ok(!T::default()),
+    };
+
+    let fields = fields
+        .split(',')
+        .filter(|field| !field.is_empty())
+        .map(|field| (field.to_owned(), serde_json::Value::Bool(true)))
+        .collect::<serde_json::Map<_, _>>();
+
+    serde_json::from_value(serde_json::Value::Object(fields)).map_err(Error::InvalidFieldSelection)
+}
+
+fn json_response(json: &serde_json::Value) -> Result<Response<Body>> {
+    let json = serde_json::to_string(json).unwrap();
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(json))
+        .unwrap())
+}
+
+fn not_found() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body(Body::empty())
+        .unwrap()
+}
+
 async fn query_handler(app_data: AppData, req: Request<Body>) -> Result<Response<Body>> {
+    let (parts, body) = req.into_parts();
+
+    let req = hyper::body::to_bytes(body)
+        .await
+        .map_err(|_| Error::InvalidRequestBody(None))?;
+
+    let mut query: Query =
+        serde_json::from_slice(req.as_ref()).map_err(|e| Error::InvalidRequestBody(Some(e)))?;
+
+    // format field in the query takes precedence over the accept header
+    let format = query.format.unwrap_or_else(|| {
+        parts
+            .headers
+            .get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .map(ResponseFormat::from_accept_header)
+            .unwrap_or_default()
+    });
+    query.format = Some(format);
+
+    let body = app_data.data_ctx.clone().query(query).await?;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, format.content_type())
+        .body(body)
+        .unwrap())
+}
+
+async fn subscribe_handler(app_data: AppData, req: Request<Body>) -> Result<Response<Body>> {
     let req = hyper::body::to_bytes(req.into_body())
         .await
         .map_err(|_| Error::InvalidRequestBody(None))?;
@@ -103,11 +279,29 @@ async fn query_handler(app_data: AppData, req: Request<Body>) -> Result<Response
     let query: Query =
         serde_json::from_slice(req.as_ref()).map_err(|e| Error::InvalidRequestBody(Some(e)))?;
 
-    let res = app_data.data_ctx.clone().query(query).await?;
+    let body = app_data.data_ctx.clone().subscribe(query).await?;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(body)
+        .unwrap())
+}
+
+async fn aggregate_handler(app_data: AppData, req: Request<Body>) -> Result<Response<Body>> {
+    let req = hyper::body::to_bytes(req.into_body())
+        .await
+        .map_err(|_| Error::InvalidRequestBody(None))?;
+
+    let query: AggregateQuery =
+        serde_json::from_slice(req.as_ref()).map_err(|e| Error::InvalidRequestBody(Some(e)))?;
+
+    let body = app_data.data_ctx.clone().aggregate(query).await?;
 
     Ok(Response::builder()
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, "application/json")
-        .body(Body::from(res))
+        .body(body)
         .unwrap())
 }
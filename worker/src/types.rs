@@ -1,45 +1,167 @@
+use crate::abi::{Abi, Event, Function};
 use crate::bloom::Bloom;
 use crate::field_selection::FieldSelection;
+use crate::parquet_metadata::ParquetIdx;
+use crate::response_format::ResponseFormat;
 use arrayvec::ArrayVec;
-use eth_archive_core::deserialize::{Address, Bytes32, Index, Sighash};
+use eth_archive_core::deserialize::{Address, Bytes, Bytes32, Index, Sighash, U256};
 use eth_archive_core::hash::HashSet;
-use eth_archive_core::types::{ResponseBlock, ResponseLog, ResponseTransaction, Transaction};
+use eth_archive_core::types::{
+    AccessListItem, Authorization, ResponseBlock, ResponseLog, ResponseStateDiff, ResponseTrace,
+    ResponseTransaction, ResponseWithdrawal, StateDiff, Trace, Transaction, Withdrawal,
+};
 use serde::{Deserialize, Serialize};
 use std::cmp;
 use std::collections::{BTreeMap, BTreeSet};
+use std::num::NonZeroUsize;
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct MiniQuery {
-    pub from_block: u32,
-    pub to_block: u32,
+    pub from_block: u64,
+    pub to_block: u64,
     pub logs: Vec<MiniLogSelection>,
     pub transactions: Vec<MiniTransactionSelection>,
+    pub traces: Vec<MiniTraceSelection>,
+    pub state_diffs: Vec<MiniStateDiffSelection>,
+    pub withdrawals: Vec<MiniWithdrawalSelection>,
+    pub blocks: Vec<MiniBlockSelection>,
     pub field_selection: FieldSelection,
     pub include_all_blocks: bool,
+    /// Events and functions that the matched logs and transactions are decoded with
+    pub abi: Arc<Abi>,
 }
 
 #[derive(Clone)]
 pub struct MiniLogSelection {
     pub address: HashSet<Address>,
     pub topics: ArrayVec<HashSet<Bytes32>, 4>,
+    /// Logs of these addresses don't match even if they match the rest of the selection
+    pub exclude_address: HashSet<Address>,
+    /// Logs that have any of the topics in the set at that position don't match
+    pub exclude_topics: ArrayVec<HashSet<Bytes32>, 4>,
 }
 
 #[derive(Clone)]
 pub struct MiniTransactionSelection {
     pub source: HashSet<Address>,
     pub dest: HashSet<Address>,
+    /// Matches if either the source or the dest is in the set
+    pub address: HashSet<Address>,
     pub sighash: Vec<Sighash>,
     pub status: Option<u32>,
+    /// Only matches transactions that don't have a dest
+    pub contract_creation: bool,
+    /// Transaction types to match, matches any type if empty
+    pub kind: Vec<u32>,
+    /// Matches if any address in the access list is in the set
+    pub access_list_address: HashSet<Address>,
+    /// Matches if the authority or the delegate of any authorization is in the set
+    pub authorization_address: HashSet<Address>,
+    pub value: Range<U256>,
+    pub gas_price: Range<U256>,
+    pub gas: Range<u64>,
+    pub max_fee_per_gas: Range<U256>,
+    /// Range of transaction types, checked in addition to `kind`
+    pub kind_range: Range<u32>,
+    pub exclude_source: HashSet<Address>,
+    pub exclude_dest: HashSet<Address>,
+    /// Doesn't match if either the source or the dest is in the set
+    pub exclude_address: HashSet<Address>,
+}
+
+#[derive(Clone)]
+pub struct MiniTraceSelection {
+    pub source: HashSet<Address>,
+    pub dest: HashSet<Address>,
+    pub call_type: Vec<String>,
+    pub sighash: Vec<Sighash>,
+}
+
+#[derive(Clone)]
+pub struct MiniStateDiffSelection {
+    pub address: HashSet<Address>,
+    pub key: HashSet<Bytes32>,
+}
+
+#[derive(Clone)]
+pub struct MiniWithdrawalSelection {
+    pub address: HashSet<Address>,
+    pub validator_index: HashSet<u32>,
+}
+
+#[derive(Clone)]
+pub struct MiniBlockSelection {
+    pub miner: HashSet<Address>,
+    pub from_timestamp: Option<u64>,
+    pub to_timestamp: Option<u64>,
 }
 
 #[derive(Default)]
 pub struct LogQueryResult {
-    pub logs: BTreeMap<(u32, u32), ResponseLog>,
-    pub transactions: BTreeSet<(u32, u32)>,
-    pub blocks: BTreeSet<u32>,
+    pub logs: BTreeMap<(u64, u32), ResponseLog>,
+    pub transactions: BTreeSet<(u64, u32)>,
+    pub blocks: BTreeSet<u64>,
+}
+
+#[derive(Default)]
+pub struct TraceQueryResult {
+    pub traces: BTreeMap<(u64, u32), ResponseTrace>,
+    pub transactions: BTreeSet<(u64, u32)>,
+    pub blocks: BTreeSet<u64>,
+}
+
+#[derive(Default)]
+pub struct StateDiffQueryResult {
+    pub state_diffs: BTreeMap<(u64, u32), ResponseStateDiff>,
+    pub transactions: BTreeSet<(u64, u32)>,
+    pub blocks: BTreeSet<u64>,
+}
+
+#[derive(Default)]
+pub struct WithdrawalQueryResult {
+    pub withdrawals: BTreeMap<(u64, u32), ResponseWithdrawal>,
+    pub blocks: BTreeSet<u64>,
 }
 
 impl MiniQuery {
+    /// Field selection with the columns needed to join and filter the query.
+    /// The list columns are only added if a transaction selection filters on them
+    /// since they are expensive to read.
+    pub fn join_field_selection(&self) -> FieldSelection {
+        let mut field_selection = self.field_selection.with_join_columns();
+
+        if self.abi.has_events() {
+            field_selection.log.data = true;
+        }
+        if self.abi.has_functions() {
+            field_selection.transaction.input = true;
+        }
+
+        for tx in self.transactions.iter() {
+            if !tx.access_list_address.is_empty() {
+                field_selection.transaction.access_list = true;
+            }
+            if !tx.authorization_address.is_empty() {
+                field_selection.transaction.authorization_list = true;
+            }
+            if !tx.value.is_unbounded() {
+                field_selection.transaction.value = true;
+            }
+            if !tx.gas_price.is_unbounded() {
+                field_selection.transaction.gas_price = true;
+            }
+            if !tx.gas.is_unbounded() {
+                field_selection.transaction.gas = true;
+            }
+            if !tx.max_fee_per_gas.is_unbounded() {
+                field_selection.transaction.max_fee_per_gas = true;
+            }
+        }
+
+        field_selection
+    }
+
     pub fn matches_log(&self, address: &Address, topics: &[Bytes32]) -> bool {
         MiniLogSelection::matches_log_impl(&self.logs, address, topics)
     }
@@ -52,36 +174,90 @@ impl MiniQuery {
             &tx.dest,
             &tx.input.get(..4).map(Sighash::new),
             tx.status,
+            tx.kind,
+            tx.access_list.as_deref(),
+            tx.authorization_list.as_deref(),
+            Some(&tx.value),
+            tx.gas_price.as_ref(),
+            Some(&tx.gas),
+            tx.max_fee_per_gas.as_ref(),
         )
     }
 
-    pub fn pruned_log_selection(&self, parquet_idx: &Bloom<Address>) -> Vec<MiniLogSelection> {
+    pub fn matches_trace(&self, trace: &Trace) -> bool {
+        MiniTraceSelection::matches_trace_impl(
+            &self.traces,
+            &trace.source,
+            &trace.dest,
+            &trace.call_type,
+            &trace
+                .input
+                .as_ref()
+                .and_then(|input| input.get(..4))
+                .map(Sighash::new),
+        )
+    }
+
+    pub fn matches_state_diff(&self, state_diff: &StateDiff) -> bool {
+        MiniStateDiffSelection::matches_state_diff_impl(
+            &self.state_diffs,
+            &state_diff.address,
+            &state_diff.key,
+        )
+    }
+
+    pub fn matches_withdrawal(&self, withdrawal: &Withdrawal) -> bool {
+        MiniWithdrawalSelection::matches_withdrawal_impl(
+            &self.withdrawals,
+            &withdrawal.address,
+            withdrawal.validator_index.0,
+        )
+    }
+
+    pub fn matches_block(&self, miner: &Address, timestamp: &[u8]) -> bool {
+        MiniBlockSelection::matches_block_impl(&self.blocks, miner, timestamp)
+    }
+
+    pub fn pruned_log_selection(&self, parquet_idx: &ParquetIdx) -> Vec<MiniLogSelection> {
         self.logs
             .iter()
             .filter_map(|log_selection| {
                 let address = &log_selection.address;
 
-                if address.is_empty() {
-                    return Some(MiniLogSelection {
-                        address: HashSet::default(),
-                        topics: log_selection.topics.clone(),
-                    });
-                }
-
-                let address = address
-                    .iter()
-                    .filter(|addr| parquet_idx.contains(addr))
-                    .cloned()
-                    .collect::<HashSet<_>>();
-
-                if !address.is_empty() {
-                    Some(MiniLogSelection {
-                        address,
-                        topics: log_selection.topics.clone(),
-                    })
+                let address = if address.is_empty() {
+                    HashSet::default()
                 } else {
-                    None
+                    let address = address
+                        .iter()
+                        .filter(|addr| parquet_idx.address.contains(addr))
+                        .cloned()
+                        .collect::<HashSet<_>>();
+
+                    if address.is_empty() {
+                        return None;
+                    }
+
+                    address
+                };
+
+                let mut topics = log_selection.topics.clone();
+
+                if let Some(topic_idx) = parquet_idx.topic.as_ref() {
+                    for topic in topics.iter_mut().filter(|topic| !topic.is_empty()) {
+                        topic.retain(|topic| topic_idx.contains(topic));
+
+                        if topic.is_empty() {
+                            return None;
+                        }
+                    }
                 }
+
+                Some(MiniLogSelection {
+                    address,
+                    topics,
+                    exclude_address: log_selection.exclude_address.clone(),
+                    exclude_topics: log_selection.exclude_topics.clone(),
+                })
             })
             .collect::<Vec<_>>()
     }
@@ -93,42 +269,48 @@ impl MiniQuery {
         self.transactions
             .iter()
             .filter_map(|tx_selection| {
-                let source = &tx_selection.source;
-                let dest = &tx_selection.dest;
-
-                if source.is_empty() && dest.is_empty() {
-                    return Some(MiniTransactionSelection {
-                        source: HashSet::default(),
-                        dest: HashSet::default(),
-                        sighash: tx_selection.sighash.clone(),
-                        status: tx_selection.status,
-                    });
-                }
+                tx_selection.pruned(
+                    |addr| parquet_idx.contains(addr),
+                    |addr| parquet_idx.contains(addr),
+                )
+            })
+            .collect::<Vec<_>>()
+    }
 
-                let source = source
-                    .iter()
-                    .filter(|addr| parquet_idx.contains(addr))
-                    .cloned()
-                    .collect::<HashSet<_>>();
+    pub fn pruned_trace_selection(&self, parquet_idx: &Bloom<Address>) -> Vec<MiniTraceSelection> {
+        self.traces
+            .iter()
+            .filter_map(|trace_selection| {
+                trace_selection.pruned(
+                    |addr| parquet_idx.contains(addr),
+                    |addr| parquet_idx.contains(addr),
+                )
+            })
+            .collect()
+    }
 
-                let dest = dest
-                    .iter()
-                    .filter(|addr| parquet_idx.contains(addr))
-                    .cloned()
-                    .collect::<HashSet<_>>();
+    pub fn pruned_state_diff_selection(
+        &self,
+        parquet_idx: &Bloom<Address>,
+    ) -> Vec<MiniStateDiffSelection> {
+        self.state_diffs
+            .iter()
+            .filter_map(|state_diff_selection| {
+                state_diff_selection.pruned(|addr| parquet_idx.contains(addr))
+            })
+            .collect()
+    }
 
-                if source.is_empty() && dest.is_empty() {
-                    None
-                } else {
-                    Some(MiniTransactionSelection {
-                        source,
-                        dest,
-                        sighash: tx_selection.sighash.clone(),
-                        status: tx_selection.status,
-                    })
-                }
+    pub fn pruned_withdrawal_selection(
+        &self,
+        parquet_idx: &Bloom<Address>,
+    ) -> Vec<MiniWithdrawalSelection> {
+        self.withdrawals
+            .iter()
+            .filter_map(|withdrawal_selection| {
+                withdrawal_selection.pruned(|addr| parquet_idx.contains(addr), |_| true)
             })
-            .collect::<Vec<_>>()
+            .collect()
     }
 }
 
@@ -138,9 +320,12 @@ impl MiniLogSelection {
         address: &Address,
         topics: &[Bytes32],
     ) -> bool {
-        filters
-            .iter()
-            .any(|selection| selection.matches_addr(address) && selection.matches_topics(topics))
+        filters.iter().any(|selection| {
+            selection.matches_addr(address)
+                && selection.matches_topics(topics)
+                && !selection.exclude_address.contains(address)
+                && !selection.excludes_topics(topics)
+        })
     }
 
     fn matches_addr(&self, filter_addr: &Address) -> bool {
@@ -156,50 +341,153 @@ impl MiniLogSelection {
 
         true
     }
+
+    fn excludes_topics(&self, topics: &[Bytes32]) -> bool {
+        self.exclude_topics
+            .iter()
+            .zip(topics.iter())
+            .any(|(exclude, log_topic)| exclude.contains(log_topic))
+    }
 }
 
 impl MiniTransactionSelection {
+    #[allow(clippy::too_many_arguments)]
     pub fn matches_tx_impl(
         filters: &[MiniTransactionSelection],
         source: &Option<Address>,
         dest: &Option<Address>,
         sighash: &Option<Sighash>,
         status: Option<Index>,
+        kind: Option<Index>,
+        access_list: Option<&[AccessListItem]>,
+        authorization_list: Option<&[Authorization]>,
+        value: Option<&Bytes>,
+        gas_price: Option<&Bytes>,
+        gas: Option<&Bytes>,
+        max_fee_per_gas: Option<&Bytes>,
     ) -> bool {
         filters.iter().any(|selection| {
-            let match_all_addr = selection.source.is_empty() && selection.dest.is_empty();
-            let matches_addr =
-                match_all_addr || selection.matches_dest(dest) && selection.matches_source(source);
+            Self::matches_addr(&selection.source, source)
+                && Self::matches_addr(&selection.dest, dest)
+                && selection.matches_either_addr(source, dest)
+                && selection.matches_sighash(sighash)
+                && selection.matches_status(&status)
+                && (!selection.contract_creation || dest.is_none())
+                && selection.matches_kind(&kind)
+                && selection.matches_access_list(access_list)
+                && selection.matches_authorization_list(authorization_list)
+                && Self::matches_quantity(&selection.value, value)
+                && Self::matches_quantity(&selection.gas_price, gas_price)
+                && Self::matches_gas(&selection.gas, gas)
+                && Self::matches_quantity(&selection.max_fee_per_gas, max_fee_per_gas)
+                && !selection.excludes_addr(source, dest)
+        })
+    }
+
+    /// Removes the addresses that aren't kept by the given functions.
+    /// Addresses in `address` are kept if either function keeps them.
+    /// Returns None if the selection can't match anything after pruning.
+    pub fn pruned<S, D>(&self, keep_source: S, keep_dest: D) -> Option<MiniTransactionSelection>
+    where
+        S: Fn(&Address) -> bool,
+        D: Fn(&Address) -> bool,
+    {
+        let source = self
+            .source
+            .iter()
+            .filter(|&addr| keep_source(addr))
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        if !self.source.is_empty() && source.is_empty() {
+            return None;
+        }
 
-            matches_addr && selection.matches_sighash(sighash) && selection.matches_status(&status)
+        let dest = self
+            .dest
+            .iter()
+            .filter(|&addr| keep_dest(addr))
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        if !self.dest.is_empty() && dest.is_empty() {
+            return None;
+        }
+
+        let address = self
+            .address
+            .iter()
+            .filter(|&addr| keep_source(addr) || keep_dest(addr))
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        if !self.address.is_empty() && address.is_empty() {
+            return None;
+        }
+
+        Some(MiniTransactionSelection {
+            source,
+            dest,
+            address,
+            sighash: self.sighash.clone(),
+            status: self.status,
+            contract_creation: self.contract_creation,
+            kind: self.kind.clone(),
+            access_list_address: self.access_list_address.clone(),
+            authorization_address: self.authorization_address.clone(),
+            value: self.value,
+            gas_price: self.gas_price,
+            gas: self.gas,
+            max_fee_per_gas: self.max_fee_per_gas,
+            kind_range: self.kind_range,
+            exclude_source: self.exclude_source.clone(),
+            exclude_dest: self.exclude_dest.clone(),
+            exclude_address: self.exclude_address.clone(),
         })
     }
 
-    fn matches_dest(&self, dest: &Option<Address>) -> bool {
-        let tx_addr = match dest.as_ref() {
-            Some(addr) => addr,
-            None => return false,
-        };
+    fn matches_addr(filter: &HashSet<Address>, addr: &Option<Address>) -> bool {
+        if filter.is_empty() {
+            return true;
+        }
 
-        self.dest.contains(tx_addr)
+        match addr {
+            Some(addr) => filter.contains(addr),
+            None => false,
+        }
     }
 
-    fn matches_source(&self, source: &Option<Address>) -> bool {
-        let tx_addr = match source.as_ref() {
-            Some(addr) => addr,
-            None => return false,
+    fn excludes_addr(&self, source: &Option<Address>, dest: &Option<Address>) -> bool {
+        let excluded = |set: &HashSet<Address>, addr: &Option<Address>| {
+            addr.as_ref().map_or(false, |addr| set.contains(addr))
         };
 
-        self.source.contains(tx_addr)
+        excluded(&self.exclude_source, source)
+            || excluded(&self.exclude_dest, dest)
+            || excluded(&self.exclude_address, source)
+            || excluded(&self.exclude_address, dest)
+    }
+
+    fn matches_either_addr(&self, source: &Option<Address>, dest: &Option<Address>) -> bool {
+        if self.address.is_empty() {
+            return true;
+        }
+
+        [source, dest]
+            .into_iter()
+            .flatten()
+            .any(|addr| self.address.contains(addr))
     }
 
     fn matches_sighash(&self, sighash: &Option<Sighash>) -> bool {
-        let sighash = match sighash {
-            Some(sig) => sig,
-            None => return false,
-        };
+        if self.sighash.is_empty() {
+            return true;
+        }
 
-        self.sighash.is_empty() || self.sighash.iter().any(|sig| sig == sighash)
+        match sighash {
+            Some(sighash) => self.sighash.iter().any(|sig| sig == sighash),
+            None => false,
+        }
     }
 
     fn matches_status(&self, tx_status: &Option<Index>) -> bool {
@@ -208,19 +496,386 @@ impl MiniTransactionSelection {
             _ => true,
         }
     }
+
+    fn matches_kind(&self, tx_kind: &Option<Index>) -> bool {
+        if self.kind.is_empty() && self.kind_range.is_unbounded() {
+            return true;
+        }
+
+        match tx_kind {
+            Some(tx_kind) => {
+                (self.kind.is_empty() || self.kind.contains(&tx_kind.0))
+                    && self.kind_range.contains(&tx_kind.0)
+            }
+            None => false,
+        }
+    }
+
+    fn matches_access_list(&self, access_list: Option<&[AccessListItem]>) -> bool {
+        if self.access_list_address.is_empty() {
+            return true;
+        }
+
+        access_list
+            .unwrap_or_default()
+            .iter()
+            .any(|item| self.access_list_address.contains(&item.address))
+    }
+
+    fn matches_authorization_list(&self, authorization_list: Option<&[Authorization]>) -> bool {
+        if self.authorization_address.is_empty() {
+            return true;
+        }
+
+        authorization_list.unwrap_or_default().iter().any(|auth| {
+            self.authorization_address.contains(&auth.address)
+                || auth
+                    .authority
+                    .as_ref()
+                    .map(|authority| self.authorization_address.contains(authority))
+                    .unwrap_or(false)
+        })
+    }
+
+    fn matches_quantity(range: &Range<U256>, quantity: Option<&Bytes>) -> bool {
+        if range.is_unbounded() {
+            return true;
+        }
+
+        match quantity.and_then(|quantity| U256::from_quantity(quantity)) {
+            Some(quantity) => range.contains(&quantity),
+            None => false,
+        }
+    }
+
+    fn matches_gas(range: &Range<u64>, gas: Option<&Bytes>) -> bool {
+        if range.is_unbounded() {
+            return true;
+        }
+
+        match gas.and_then(|gas| gas.to_u64()) {
+            Some(gas) => range.contains(&gas),
+            None => false,
+        }
+    }
+}
+
+impl MiniTraceSelection {
+    pub fn matches_trace_impl(
+        filters: &[MiniTraceSelection],
+        source: &Option<Address>,
+        dest: &Option<Address>,
+        call_type: &str,
+        sighash: &Option<Sighash>,
+    ) -> bool {
+        filters.iter().any(|selection| {
+            Self::matches_addr(&selection.source, source)
+                && Self::matches_addr(&selection.dest, dest)
+                && selection.matches_call_type(call_type)
+                && selection.matches_sighash(sighash)
+        })
+    }
+
+    /// Removes the addresses that aren't kept by the given functions.
+    /// Returns None if the selection can't match anything after pruning.
+    pub fn pruned<S, D>(&self, keep_source: S, keep_dest: D) -> Option<MiniTraceSelection>
+    where
+        S: Fn(&Address) -> bool,
+        D: Fn(&Address) -> bool,
+    {
+        let source = self
+            .source
+            .iter()
+            .filter(|&addr| keep_source(addr))
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        if !self.source.is_empty() && source.is_empty() {
+            return None;
+        }
+
+        let dest = self
+            .dest
+            .iter()
+            .filter(|&addr| keep_dest(addr))
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        if !self.dest.is_empty() && dest.is_empty() {
+            return None;
+        }
+
+        Some(MiniTraceSelection {
+            source,
+            dest,
+            call_type: self.call_type.clone(),
+            sighash: self.sighash.clone(),
+        })
+    }
+
+    fn matches_addr(filter: &HashSet<Address>, addr: &Option<Address>) -> bool {
+        if filter.is_empty() {
+            return true;
+        }
+
+        match addr {
+            Some(addr) => filter.contains(addr),
+            None => false,
+        }
+    }
+
+    fn matches_call_type(&self, call_type: &str) -> bool {
+        self.call_type.is_empty() || self.call_type.iter().any(|c| c == call_type)
+    }
+
+    fn matches_sighash(&self, sighash: &Option<Sighash>) -> bool {
+        if self.sighash.is_empty() {
+            return true;
+        }
+
+        match sighash {
+            Some(sighash) => self.sighash.iter().any(|sig| sig == sighash),
+            None => false,
+        }
+    }
+}
+
+impl MiniStateDiffSelection {
+    pub fn matches_state_diff_impl(
+        filters: &[MiniStateDiffSelection],
+        address: &Address,
+        key: &Option<Bytes32>,
+    ) -> bool {
+        filters
+            .iter()
+            .any(|selection| selection.matches_addr(address) && selection.matches_key(key))
+    }
+
+    /// Removes the addresses that aren't kept by the given function.
+    /// Returns None if the selection can't match anything after pruning.
+    pub fn pruned<F: Fn(&Address) -> bool>(&self, keep: F) -> Option<MiniStateDiffSelection> {
+        let address = self
+            .address
+            .iter()
+            .filter(|&addr| keep(addr))
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        if !self.address.is_empty() && address.is_empty() {
+            return None;
+        }
+
+        Some(MiniStateDiffSelection {
+            address,
+            key: self.key.clone(),
+        })
+    }
+
+    fn matches_addr(&self, address: &Address) -> bool {
+        self.address.is_empty() || self.address.contains(address)
+    }
+
+    fn matches_key(&self, key: &Option<Bytes32>) -> bool {
+        if self.key.is_empty() {
+            return true;
+        }
+
+        match key {
+            Some(key) => self.key.contains(key),
+            None => false,
+        }
+    }
+}
+
+impl MiniWithdrawalSelection {
+    pub fn matches_withdrawal_impl(
+        filters: &[MiniWithdrawalSelection],
+        address: &Address,
+        validator_index: u32,
+    ) -> bool {
+        filters.iter().any(|selection| {
+            selection.matches_addr(address) && selection.matches_validator_index(validator_index)
+        })
+    }
+
+    /// Removes the addresses and validator indices that aren't kept by the given functions.
+    /// Returns None if the selection can't match anything after pruning.
+    pub fn pruned<A, V>(&self, keep_addr: A, keep_validator: V) -> Option<MiniWithdrawalSelection>
+    where
+        A: Fn(&Address) -> bool,
+        V: Fn(u32) -> bool,
+    {
+        let address = self
+            .address
+            .iter()
+            .filter(|&addr| keep_addr(addr))
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        if !self.address.is_empty() && address.is_empty() {
+            return None;
+        }
+
+        let validator_index = self
+            .validator_index
+            .iter()
+            .filter(|&&idx| keep_validator(idx))
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        if !self.validator_index.is_empty() && validator_index.is_empty() {
+            return None;
+        }
+
+        Some(MiniWithdrawalSelection {
+            address,
+            validator_index,
+        })
+    }
+
+    fn matches_addr(&self, address: &Address) -> bool {
+        self.address.is_empty() || self.address.contains(address)
+    }
+
+    fn matches_validator_index(&self, validator_index: u32) -> bool {
+        self.validator_index.is_empty() || self.validator_index.contains(&validator_index)
+    }
+}
+
+impl MiniBlockSelection {
+    pub fn matches_block_impl(
+        filters: &[MiniBlockSelection],
+        miner: &Address,
+        timestamp: &[u8],
+    ) -> bool {
+        let timestamp = timestamp_to_u64(timestamp);
+
+        filters.iter().any(|selection| {
+            selection.matches_miner(miner) && selection.matches_timestamp(timestamp)
+        })
+    }
+
+    fn matches_miner(&self, miner: &Address) -> bool {
+        self.miner.is_empty() || self.miner.contains(miner)
+    }
+
+    fn matches_timestamp(&self, timestamp: u64) -> bool {
+        if let Some(from_timestamp) = self.from_timestamp {
+            if timestamp < from_timestamp {
+                return false;
+            }
+        }
+
+        if let Some(to_timestamp) = self.to_timestamp {
+            if timestamp >= to_timestamp {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Converts a big endian timestamp to u64, saturating on overflow.
+pub fn timestamp_to_u64(timestamp: &[u8]) -> u64 {
+    timestamp.iter().fold(0u64, |acc, &byte| {
+        acc.saturating_mul(256).saturating_add(u64::from(byte))
+    })
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Query {
-    pub from_block: u32,
-    pub to_block: Option<u32>,
+    #[serde(default)]
+    pub from_block: u64,
+    pub to_block: Option<u64>,
+    pub from_timestamp: Option<u64>,
+    pub to_timestamp: Option<u64>,
     #[serde(default)]
     logs: Vec<LogSelection>,
     #[serde(default)]
     transactions: Vec<TransactionSelection>,
     #[serde(default)]
+    traces: Vec<TraceSelection>,
+    #[serde(default)]
+    state_diffs: Vec<StateDiffSelection>,
+    #[serde(default)]
+    withdrawals: Vec<WithdrawalSelection>,
+    #[serde(default)]
+    blocks: Vec<BlockSelection>,
+    #[serde(default)]
     include_all_blocks: bool,
+    #[serde(default)]
+    pub format: Option<ResponseFormat>,
+    #[serde(default)]
+    pub limit: Option<NonZeroUsize>,
+    /// Number of items of the first block that were already returned by the previous page
+    #[serde(default)]
+    pub block_offset: usize,
+    #[serde(default)]
+    pub order: Order,
+    #[serde(default)]
+    api_version: ApiVersion,
+}
+
+/// Version of the query semantics. Changes that would silently change the results
+/// of existing queries only apply to newer versions.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(try_from = "u32", into = "u32")]
+pub enum ApiVersion {
+    /// `address` in transaction selections is an alias of `to`
+    #[default]
+    V1,
+    /// `address` in transaction selections matches either `from` or `to`
+    V2,
+}
+
+impl TryFrom<u32> for ApiVersion {
+    type Error = String;
+
+    fn try_from(version: u32) -> std::result::Result<Self, Self::Error> {
+        match version {
+            1 => Ok(Self::V1),
+            2 => Ok(Self::V2),
+            _ => Err(format!("unsupported api version {}", version)),
+        }
+    }
+}
+
+impl From<ApiVersion> for u32 {
+    fn from(version: ApiVersion) -> u32 {
+        match version {
+            ApiVersion::V1 => 1,
+            ApiVersion::V2 => 2,
+        }
+    }
+}
+
+/// Inclusive range, either bound can be left out
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct Range<T> {
+    pub min: Option<T>,
+    pub max: Option<T>,
+}
+
+impl<T: Ord> Range<T> {
+    pub fn is_unbounded(&self) -> bool {
+        self.min.is_none() && self.max.is_none()
+    }
+
+    pub fn contains(&self, val: &T) -> bool {
+        self.min.as_ref().map_or(true, |min| val >= min)
+            && self.max.as_ref().map_or(true, |max| val <= max)
+    }
+}
+
+/// Order of the blocks in the response
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Order {
+    #[default]
+    Asc,
+    Desc,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -229,6 +884,12 @@ pub struct LogSelection {
     #[serde(default)]
     address: Vec<Address>,
     topics: ArrayVec<Vec<Bytes32>, 4>,
+    #[serde(default)]
+    exclude_address: Vec<Address>,
+    #[serde(default)]
+    exclude_topics: ArrayVec<Vec<Bytes32>, 4>,
+    #[serde(default)]
+    event: Option<Event>,
     field_selection: FieldSelection,
 }
 
@@ -238,17 +899,95 @@ pub struct TransactionSelection {
     #[serde(rename = "from")]
     #[serde(default)]
     source: Vec<Address>,
-    #[serde(rename = "to", alias = "address")]
+    #[serde(rename = "to")]
     #[serde(default)]
     dest: Vec<Address>,
     #[serde(default)]
+    address: Vec<Address>,
+    #[serde(default)]
     sighash: Vec<Sighash>,
     status: Option<u32>,
+    #[serde(default)]
+    contract_creation: bool,
+    #[serde(rename = "type")]
+    #[serde(default)]
+    kind: Vec<u32>,
+    #[serde(default)]
+    access_list_address: Vec<Address>,
+    #[serde(default)]
+    authorization_address: Vec<Address>,
+    #[serde(default)]
+    value: Range<U256>,
+    #[serde(default)]
+    gas_price: Range<U256>,
+    #[serde(default)]
+    gas: Range<u64>,
+    #[serde(default)]
+    max_fee_per_gas: Range<U256>,
+    #[serde(rename = "typeRange")]
+    #[serde(default)]
+    kind_range: Range<u32>,
+    #[serde(rename = "excludeFrom")]
+    #[serde(default)]
+    exclude_source: Vec<Address>,
+    #[serde(rename = "excludeTo")]
+    #[serde(default)]
+    exclude_dest: Vec<Address>,
+    #[serde(default)]
+    exclude_address: Vec<Address>,
+    #[serde(default)]
+    function: Option<Function>,
+    field_selection: FieldSelection,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceSelection {
+    #[serde(rename = "from")]
+    #[serde(default)]
+    source: Vec<Address>,
+    #[serde(rename = "to")]
+    #[serde(default)]
+    dest: Vec<Address>,
+    #[serde(default)]
+    call_type: Vec<String>,
+    #[serde(default)]
+    sighash: Vec<Sighash>,
+    field_selection: FieldSelection,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StateDiffSelection {
+    #[serde(default)]
+    address: Vec<Address>,
+    #[serde(default)]
+    key: Vec<Bytes32>,
+    field_selection: FieldSelection,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WithdrawalSelection {
+    #[serde(default)]
+    address: Vec<Address>,
+    #[serde(default)]
+    validator_index: Vec<u32>,
+    field_selection: FieldSelection,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockSelection {
+    #[serde(default)]
+    miner: Vec<Address>,
+    from_timestamp: Option<u64>,
+    to_timestamp: Option<u64>,
     field_selection: FieldSelection,
 }
 
 impl Query {
-    pub fn optimize(&self, archive_height: u32) -> MiniQuery {
+    pub fn optimize(&self, archive_height: u64) -> MiniQuery {
         let to_block = match self.to_block {
             Some(to_block) => cmp::min(archive_height, to_block),
             None => archive_height,
@@ -259,41 +998,188 @@ impl Query {
             to_block,
             logs: self.log_selection(),
             transactions: self.tx_selection(),
+            traces: self.trace_selection(),
+            state_diffs: self.state_diff_selection(),
+            withdrawals: self.withdrawal_selection(),
+            blocks: self.block_selection(),
             field_selection: self.field_selection(),
             include_all_blocks: self.include_all_blocks,
+            abi: Arc::new(self.abi()),
         }
     }
 
+    fn abi(&self) -> Abi {
+        Abi::new(
+            self.logs.iter().filter_map(|log| log.event.as_ref()),
+            self.transactions
+                .iter()
+                .filter_map(|transaction| transaction.function.as_ref()),
+        )
+    }
+
     fn field_selection(&self) -> FieldSelection {
         self.logs
             .iter()
             .map(|log| log.field_selection)
             .chain(self.transactions.iter().map(|tx| tx.field_selection))
+            .chain(self.traces.iter().map(|trace| trace.field_selection))
+            .chain(
+                self.state_diffs
+                    .iter()
+                    .map(|state_diff| state_diff.field_selection),
+            )
+            .chain(
+                self.withdrawals
+                    .iter()
+                    .map(|withdrawal| withdrawal.field_selection),
+            )
+            .chain(self.blocks.iter().map(|block| block.field_selection))
             .fold(Default::default(), |a, b| a | b)
     }
 
+    /// Selections with an event only match logs with the topic0 of the event.
+    /// They are left out if they also have a topic0 filter that doesn't include it.
     fn log_selection(&self) -> Vec<MiniLogSelection> {
         self.logs
             .iter()
-            .map(|log| MiniLogSelection {
-                address: log.address.iter().cloned().collect(),
-                topics: log
+            .filter_map(|log| {
+                let mut topics: ArrayVec<HashSet<Bytes32>, 4> = log
                     .topics
                     .iter()
                     .map(|topic| topic.iter().cloned().collect())
-                    .collect(),
+                    .collect();
+
+                if let Some(event) = &log.event {
+                    let topic0 = event.topic0();
+
+                    if topics.is_empty() {
+                        topics.push(HashSet::new());
+                    }
+
+                    if !topics[0].is_empty() && !topics[0].contains(&topic0) {
+                        return None;
+                    }
+
+                    topics[0] = HashSet::from([topic0]);
+                }
+
+                Some(MiniLogSelection {
+                    address: log.address.iter().cloned().collect(),
+                    topics,
+                    exclude_address: log.exclude_address.iter().cloned().collect(),
+                    exclude_topics: log
+                        .exclude_topics
+                        .iter()
+                        .map(|topic| topic.iter().cloned().collect())
+                        .collect(),
+                })
             })
             .collect()
     }
 
+    /// Selections with a function only match transactions with the sighash of the function.
+    /// They are left out if they also have a sighash filter that doesn't include it.
     fn tx_selection(&self) -> Vec<MiniTransactionSelection> {
         self.transactions
             .iter()
-            .map(|transaction| MiniTransactionSelection {
-                source: transaction.source.iter().cloned().collect(),
-                dest: transaction.dest.iter().cloned().collect(),
-                sighash: transaction.sighash.clone(),
-                status: transaction.status,
+            .filter_map(|transaction| {
+                let sighash = match &transaction.function {
+                    Some(function) => {
+                        let sighash = function.sighash();
+
+                        if !transaction.sighash.is_empty()
+                            && !transaction.sighash.contains(&sighash)
+                        {
+                            return None;
+                        }
+
+                        vec![sighash]
+                    }
+                    None => transaction.sighash.clone(),
+                };
+
+                let (dest, address) = match self.api_version {
+                    ApiVersion::V1 => (
+                        transaction
+                            .dest
+                            .iter()
+                            .chain(transaction.address.iter())
+                            .cloned()
+                            .collect(),
+                        HashSet::new(),
+                    ),
+                    ApiVersion::V2 => (
+                        transaction.dest.iter().cloned().collect(),
+                        transaction.address.iter().cloned().collect(),
+                    ),
+                };
+
+                Some(MiniTransactionSelection {
+                    source: transaction.source.iter().cloned().collect(),
+                    dest,
+                    address,
+                    sighash,
+                    status: transaction.status,
+                    contract_creation: transaction.contract_creation,
+                    kind: transaction.kind.clone(),
+                    access_list_address: transaction.access_list_address.iter().cloned().collect(),
+                    authorization_address: transaction
+                        .authorization_address
+                        .iter()
+                        .cloned()
+                        .collect(),
+                    value: transaction.value,
+                    gas_price: transaction.gas_price,
+                    gas: transaction.gas,
+                    max_fee_per_gas: transaction.max_fee_per_gas,
+                    kind_range: transaction.kind_range,
+                    exclude_source: transaction.exclude_source.iter().cloned().collect(),
+                    exclude_dest: transaction.exclude_dest.iter().cloned().collect(),
+                    exclude_address: transaction.exclude_address.iter().cloned().collect(),
+                })
+            })
+            .collect()
+    }
+
+    fn trace_selection(&self) -> Vec<MiniTraceSelection> {
+        self.traces
+            .iter()
+            .map(|trace| MiniTraceSelection {
+                source: trace.source.iter().cloned().collect(),
+                dest: trace.dest.iter().cloned().collect(),
+                call_type: trace.call_type.iter().map(|c| c.to_lowercase()).collect(),
+                sighash: trace.sighash.clone(),
+            })
+            .collect()
+    }
+
+    fn state_diff_selection(&self) -> Vec<MiniStateDiffSelection> {
+        self.state_diffs
+            .iter()
+            .map(|state_diff| MiniStateDiffSelection {
+                address: state_diff.address.iter().cloned().collect(),
+                key: state_diff.key.iter().cloned().collect(),
+            })
+            .collect()
+    }
+
+    fn withdrawal_selection(&self) -> Vec<MiniWithdrawalSelection> {
+        self.withdrawals
+            .iter()
+            .map(|withdrawal| MiniWithdrawalSelection {
+                address: withdrawal.address.iter().cloned().collect(),
+                validator_index: withdrawal.validator_index.iter().cloned().collect(),
+            })
+            .collect()
+    }
+
+    fn block_selection(&self) -> Vec<MiniBlockSelection> {
+        self.blocks
+            .iter()
+            .map(|block| MiniBlockSelection {
+                miner: block.miner.iter().cloned().collect(),
+                from_timestamp: block.from_timestamp,
+                to_timestamp: block.to_timestamp,
             })
             .collect()
     }
@@ -301,13 +1187,330 @@ impl Query {
 
 #[derive(Default)]
 pub struct QueryResult {
-    pub logs: BTreeMap<(u32, u32), ResponseLog>,
-    pub transactions: BTreeMap<(u32, u32), ResponseTransaction>,
-    pub blocks: BTreeMap<u32, ResponseBlock>,
+    pub logs: BTreeMap<(u64, u32), ResponseLog>,
+    pub transactions: BTreeMap<(u64, u32), ResponseTransaction>,
+    pub traces: BTreeMap<(u64, u32), ResponseTrace>,
+    pub state_diffs: BTreeMap<(u64, u32), ResponseStateDiff>,
+    pub withdrawals: BTreeMap<(u64, u32), ResponseWithdrawal>,
+    pub blocks: BTreeMap<u64, ResponseBlock>,
 }
 
 impl QueryResult {
     pub fn is_empty(&self) -> bool {
-        self.logs.is_empty() && self.transactions.is_empty() && self.blocks.is_empty()
+        self.logs.is_empty()
+            && self.transactions.is_empty()
+            && self.traces.is_empty()
+            && self.state_diffs.is_empty()
+            && self.withdrawals.is_empty()
+            && self.blocks.is_empty()
+    }
+    /// Cuts the result so it has at most `limit` items, counting only the kinds of items
+    /// that have a field selection.
+    ///
+    /// Items of a block are ordered by kind (transactions, logs, traces, state diffs,
+    /// withdrawals) and by index in the given order. The first `skip.1` items of block
+    /// `skip.0` are removed since they were already returned.
+    /// Returns the number of kept items and the block and the number of its items that were
+    /// returned up to the cut if the result was cut.
+    pub fn truncate(
+        &mut self,
+        limit: usize,
+        skip: Option<(u64, usize)>,
+        order: Order,
+        field_selection: FieldSelection,
+    ) -> (usize, Option<(u64, usize)>) {
+        let mut items_per_block: BTreeMap<u64, [Vec<u32>; 5]> = BTreeMap::new();
+
+        let selected = [
+            field_selection.transaction != Default::default(),
+            field_selection.log != Default::default(),
+            field_selection.trace != Default::default(),
+            field_selection.state_diff != Default::default(),
+            field_selection.withdrawal != Default::default(),
+        ];
+        let keys: [Box<dyn Iterator<Item = &(u64, u32)>>; 5] = [
+            Box::new(self.transactions.keys()),
+            Box::new(self.logs.keys()),
+            Box::new(self.traces.keys()),
+            Box::new(self.state_diffs.keys()),
+            Box::new(self.withdrawals.keys()),
+        ];
+
+        for (kind, keys) in keys.into_iter().enumerate() {
+            if !selected[kind] {
+                continue;
+            }
+
+            for &(block_num, idx) in keys {
+                items_per_block.entry(block_num).or_default()[kind].push(idx);
+            }
+        }
+
+        let blocks: Vec<_> = match order {
+            Order::Asc => items_per_block.into_iter().collect(),
+            Order::Desc => items_per_block.into_iter().rev().collect(),
+        };
+
+        let mut num_items = 0;
+
+        for (block_num, mut kinds) in blocks {
+            if order == Order::Desc {
+                for idxs in kinds.iter_mut() {
+                    idxs.reverse();
+                }
+            }
+
+            let items = kinds
+                .iter()
+                .enumerate()
+                .flat_map(|(kind, idxs)| idxs.iter().map(move |&idx| (kind, idx)))
+                .collect::<Vec<_>>();
+
+            let skipped = match skip {
+                Some((skip_block, num_skipped)) if skip_block == block_num => {
+                    cmp::min(num_skipped, items.len())
+                }
+                _ => 0,
+            };
+            let taken = cmp::min(items.len() - skipped, limit - num_items);
+
+            for &(kind, idx) in items[..skipped].iter().chain(&items[skipped + taken..]) {
+                self.remove_item(kind, (block_num, idx));
+            }
+
+            num_items += taken;
+
+            if skipped + taken < items.len() {
+                // the header of the cut block is only kept if some of its items are kept
+                match (order, taken > 0) {
+                    (Order::Asc, true) => self.retain_blocks(|num| num <= block_num),
+                    (Order::Asc, false) => self.retain_blocks(|num| num < block_num),
+                    (Order::Desc, true) => self.retain_blocks(|num| num >= block_num),
+                    (Order::Desc, false) => self.retain_blocks(|num| num > block_num),
+                }
+
+                return (num_items, Some((block_num, skipped + taken)));
+            }
+        }
+
+        (num_items, None)
+    }
+
+    fn remove_item(&mut self, kind: usize, key: (u64, u32)) {
+        match kind {
+            0 => {
+                self.transactions.remove(&key);
+            }
+            1 => {
+                self.logs.remove(&key);
+            }
+            2 => {
+                self.traces.remove(&key);
+            }
+            3 => {
+                self.state_diffs.remove(&key);
+            }
+            _ => {
+                self.withdrawals.remove(&key);
+            }
+        }
+    }
+
+    fn retain_blocks<F: Fn(u64) -> bool>(&mut self, f: F) {
+        self.logs.retain(|&(block_num, _), _| f(block_num));
+        self.transactions.retain(|&(block_num, _), _| f(block_num));
+        self.traces.retain(|&(block_num, _), _| f(block_num));
+        self.state_diffs.retain(|&(block_num, _), _| f(block_num));
+        self.withdrawals.retain(|&(block_num, _), _| f(block_num));
+        self.blocks.retain(|&block_num, _| f(block_num));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Block 1 has 2 transactions, block 2 has 3 transactions and 2 logs,
+    /// block 3 has 1 transaction and block 4 only has a header.
+    fn query_result() -> QueryResult {
+        let mut res = QueryResult::default();
+
+        for block_num in 1..=4 {
+            res.blocks.insert(block_num, ResponseBlock::default());
+        }
+        for key in [(1, 0), (1, 1), (2, 0), (2, 1), (2, 2), (3, 0)] {
+            res.transactions.insert(key, ResponseTransaction::default());
+        }
+        for key in [(2, 0), (2, 1)] {
+            res.logs.insert(key, ResponseLog::default());
+        }
+
+        res
+    }
+
+    fn field_selection() -> FieldSelection {
+        let mut field_selection = FieldSelection::default();
+        field_selection.transaction.hash = true;
+        field_selection.log.address = true;
+        field_selection
+    }
+
+    fn items(res: &QueryResult) -> Vec<(&'static str, u64, u32)> {
+        let txs = res.transactions.keys().map(|&(b, i)| ("tx", b, i));
+        let logs = res.logs.keys().map(|&(b, i)| ("log", b, i));
+        txs.chain(logs).collect()
+    }
+
+    #[test]
+    fn test_truncate_asc_mid_block() {
+        let mut res = query_result();
+        let (kept, cut_at) = res.truncate(4, None, Order::Asc, field_selection());
+
+        assert_eq!(kept, 4);
+        assert_eq!(cut_at, Some((2, 2)));
+        assert_eq!(
+            items(&res),
+            vec![("tx", 1, 0), ("tx", 1, 1), ("tx", 2, 0), ("tx", 2, 1)]
+        );
+        assert_eq!(res.blocks.keys().copied().collect::<Vec<_>>(), vec![1, 2]);
+
+        let mut res = query_result();
+        res.retain_blocks(|num| num >= 2);
+        let (kept, cut_at) = res.truncate(4, Some((2, 2)), Order::Asc, field_selection());
+
+        assert_eq!(kept, 4);
+        assert_eq!(cut_at, None);
+        assert_eq!(
+            items(&res),
+            vec![("tx", 2, 2), ("tx", 3, 0), ("log", 2, 0), ("log", 2, 1)]
+        );
+        assert_eq!(
+            res.blocks.keys().copied().collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn test_truncate_desc_mid_block() {
+        let mut res = query_result();
+        let (kept, cut_at) = res.truncate(3, None, Order::Desc, field_selection());
+
+        // block 2 is ordered tx 2, tx 1, tx 0, log 1, log 0
+        assert_eq!(kept, 3);
+        assert_eq!(cut_at, Some((2, 2)));
+        assert_eq!(items(&res), vec![("tx", 2, 1), ("tx", 2, 2), ("tx", 3, 0)]);
+        assert_eq!(
+            res.blocks.keys().copied().collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+
+        let mut res = query_result();
+        res.retain_blocks(|num| num <= 2);
+        let (kept, cut_at) = res.truncate(3, Some((2, 2)), Order::Desc, field_selection());
+
+        assert_eq!(kept, 3);
+        assert_eq!(cut_at, Some((1, 0)));
+        assert_eq!(
+            items(&res),
+            vec![("tx", 2, 0), ("log", 2, 0), ("log", 2, 1)]
+        );
+        assert_eq!(res.blocks.keys().copied().collect::<Vec<_>>(), vec![2]);
+    }
+
+    #[test]
+    fn test_truncate_first_block_over_limit() {
+        let mut res = query_result();
+        let (kept, cut_at) = res.truncate(1, None, Order::Asc, field_selection());
+
+        assert_eq!(kept, 1);
+        assert_eq!(cut_at, Some((1, 1)));
+        assert_eq!(items(&res), vec![("tx", 1, 0)]);
+        assert_eq!(res.blocks.keys().copied().collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn test_truncate_at_block_boundary() {
+        let mut res = query_result();
+        let (kept, cut_at) = res.truncate(2, None, Order::Asc, field_selection());
+
+        assert_eq!(kept, 2);
+        assert_eq!(cut_at, Some((2, 0)));
+        assert_eq!(items(&res), vec![("tx", 1, 0), ("tx", 1, 1)]);
+        assert_eq!(res.blocks.keys().copied().collect::<Vec<_>>(), vec![1]);
+    }
+
+    /// Paging through the result with every limit returns each item exactly once
+    #[test]
+    fn test_truncate_paging() {
+        let all = items(&query_result());
+
+        for order in [Order::Asc, Order::Desc] {
+            for limit in 1..=all.len() + 1 {
+                let mut next_block = match order {
+                    Order::Asc => 0,
+                    Order::Desc => 5,
+                };
+                let mut skip = None;
+                let mut seen = Vec::new();
+
+                loop {
+                    let mut res = query_result();
+                    match order {
+                        Order::Asc => res.retain_blocks(|num| num >= next_block),
+                        Order::Desc => res.retain_blocks(|num| num < next_block),
+                    }
+
+                    let (kept, cut_at) = res.truncate(limit, skip, order, field_selection());
+                    let page = items(&res);
+                    assert_eq!(kept, page.len());
+                    seen.extend(page);
+
+                    match cut_at {
+                        Some((block_num, offset)) => {
+                            assert_eq!(kept, limit);
+                            next_block = match order {
+                                Order::Asc => block_num,
+                                Order::Desc => block_num + 1,
+                            };
+                            skip = Some((block_num, offset));
+                        }
+                        None => break,
+                    }
+                }
+
+                seen.sort();
+                let mut expected = all.clone();
+                expected.sort();
+                assert_eq!(seen, expected, "order {:?}, limit {}", order, limit);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tx_address_api_version() {
+        let query = |api_version: &str| -> Query {
+            serde_json::from_str(&format!(
+                r#"{{
+                    {}
+                    "transactions": [{{
+                        "to": ["0x0101010101010101010101010101010101010101"],
+                        "address": ["0x0202020202020202020202020202020202020202"],
+                        "fieldSelection": {{}}
+                    }}]
+                }}"#,
+                api_version
+            ))
+            .unwrap()
+        };
+
+        let selection = query("").tx_selection().pop().unwrap();
+        assert_eq!(selection.dest.len(), 2);
+        assert!(selection.address.is_empty());
+
+        let selection = query(r#""apiVersion": 2,"#).tx_selection().pop().unwrap();
+        assert_eq!(selection.dest.len(), 1);
+        assert_eq!(selection.address.len(), 1);
+
+        assert!(serde_json::from_str::<Query>(r#"{"apiVersion": 3}"#).is_err());
     }
 }